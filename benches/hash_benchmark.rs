@@ -4,12 +4,15 @@
 //! - File hashing with different algorithms and file sizes
 //! - Database operations (insert, query, update)
 //! - Filesystem scanning performance
+//! - Exclude pattern matching while collecting files
 //! - Real-time event processing throughput
+//! - Report rendering for large change sets
 
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use rusty_fim::hasher::{FileHasher, HashConfig};
-use rusty_fim::database::{FimDb, FimEntryData};
-use rusty_fim::fim::{FimEngine, FimConfig};
+use rusty_fim::database::{FimDb, FimEntry, FimEntryData, FullSnapshot, SnapshotFormat};
+use rusty_fim::fim::{ChangeType, FileChange, FimEngine, FimConfig};
+use rusty_fim::reporting::{AlertSeverity, OutputFormat, ReportConfig, ReportGenerator};
 
 use std::fs;
 use std::io::Write;
@@ -84,7 +87,7 @@ fn bench_hashing(c: &mut Criterion) {
         );
         
         // Benchmark all algorithms together
-        let all_hasher = FileHasher::all_algorithms();
+        let all_hasher = FileHasher::_all_algorithms();
         group.bench_with_input(
             BenchmarkId::new("all_algorithms", size_name),
             &temp_file,
@@ -161,14 +164,14 @@ fn bench_database(c: &mut Criterion) {
         b.iter(|| {
             let inode = black_box(12345);
             let dev = black_box(2049);
-            query_db.get_inode(inode, dev).unwrap()
+            query_db._get_inode(inode, dev).unwrap()
         });
     });
     
     // Benchmark range count
     group.bench_function("count_range", |b| {
         b.iter(|| {
-            query_db.get_count_range(
+            query_db._get_count_range(
                 black_box("/test/bulk_100"),
                 black_box("/test/bulk_200")
             ).unwrap()
@@ -201,26 +204,29 @@ fn bench_scanning(c: &mut Criterion) {
             writeln!(file, "Test content for file {}", i).unwrap();
         }
         
-        // Benchmark baseline scan
-        group.bench_with_input(
-            BenchmarkId::new("baseline_scan", count),
-            &count,
-            |b, _| {
-                b.iter(|| {
-                    let config = FimConfig {
-                        monitor_paths: vec![temp_dir.path().to_path_buf()],
-                        memory_database: true,
-                        enable_realtime: false,
-                        ..Default::default()
-                    };
-                    
-                    let mut engine = FimEngine::new(config).unwrap();
-                    engine.start().unwrap();
-                    let results = engine.baseline_scan().unwrap();
-                    black_box(results);
-                });
-            },
-        );
+        // Benchmark baseline scan, hashing on one thread and on every core
+        for (name, scan_threads) in [("baseline_scan_1_thread", Some(1)), ("baseline_scan", None)] {
+            group.bench_with_input(
+                BenchmarkId::new(name, count),
+                &count,
+                |b, _| {
+                    b.iter(|| {
+                        let config = FimConfig {
+                            monitor_paths: vec![temp_dir.path().to_path_buf()],
+                            memory_database: true,
+                            enable_realtime: false,
+                            scan_threads,
+                            ..Default::default()
+                        };
+
+                        let mut engine = FimEngine::new(config).unwrap();
+                        engine.start().unwrap();
+                        let results = engine.baseline_scan().unwrap();
+                        black_box(results);
+                    });
+                },
+            );
+        }
         
         // Benchmark incremental scan
         let config = FimConfig {
@@ -256,6 +262,45 @@ fn bench_scanning(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark collecting files through many exclude patterns
+fn bench_exclude_patterns(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exclude_patterns");
+    group.sample_size(10);
+
+    // 10k files in 100 directories; every tenth file is excluded
+    let temp_dir = tempdir().unwrap();
+    for dir in 0..100 {
+        let dir_path = temp_dir.path().join(format!("dir_{:03}", dir));
+        fs::create_dir(&dir_path).unwrap();
+        for i in 0..100 {
+            let ext = if i % 10 == 0 { "bak" } else { "txt" };
+            fs::write(dir_path.join(format!("file_{:03}.{}", i, ext)), b"x").unwrap();
+        }
+    }
+
+    // 50 patterns, nearly all of which match nothing, so each is tried per file
+    let mut patterns: Vec<String> = (0..48).map(|i| format!("**/cache_{}/**", i)).collect();
+    patterns.push("**/*.bak".to_string());
+    patterns.push("!**/dir_000/*.bak".to_string());
+
+    let engine = FimEngine::new(FimConfig {
+        monitor_paths: vec![temp_dir.path().to_path_buf()],
+        exclude_patterns: patterns,
+        memory_database: true,
+        enable_realtime: false,
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(engine.list_scannable().unwrap().len(), 9010);
+
+    group.throughput(Throughput::Elements(10_000));
+    group.bench_function("collect_10k_files_50_patterns", |b| {
+        b.iter(|| black_box(engine.list_scannable().unwrap()));
+    });
+
+    group.finish();
+}
+
 /// Benchmark parallel file processing
 fn bench_parallel_processing(c: &mut Criterion) {
     let mut group = c.benchmark_group("parallel_processing");
@@ -286,7 +331,7 @@ fn bench_parallel_processing(c: &mut Criterion) {
     // Parallel processing
     group.bench_function("parallel_1000_files", |b| {
         b.iter(|| {
-            let _hashes = hasher.hash_files_parallel(black_box(&files));
+            let _hashes = hasher._hash_files_parallel(black_box(&files));
         });
     });
     
@@ -333,6 +378,78 @@ fn bench_memory_usage(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark rendering 100k changes to HTML, sequentially and in parallel
+fn bench_report_rendering(c: &mut Criterion) {
+    let mut group = c.benchmark_group("report_rendering");
+    group.sample_size(10);
+
+    let changes: Vec<_> = (0..100_000)
+        .map(|i| FileChange {
+            path: PathBuf::from(format!("/srv/app/data/file_{:06}.dat", i)),
+            change_type: ChangeType::HashChanged,
+            old_entry: None,
+            new_entry: Some(create_test_entry_data(i)),
+            detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
+        })
+        .collect();
+
+    for (name, threshold) in [("sequential_100k_html", None), ("parallel_100k_html", Some(1))] {
+        let generator = ReportGenerator::new(ReportConfig {
+            max_changes_displayed: None,
+            parallel_render_threshold: threshold,
+            ..Default::default()
+        });
+        let report = generator.generate_report(changes.clone(), None, None);
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                black_box(generator.render_report(&report, OutputFormat::Html).unwrap());
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Benchmark loading a large baseline snapshot, JSON vs binary
+fn bench_snapshot_import(c: &mut Criterion) {
+    let mut group = c.benchmark_group("snapshot_import");
+    group.sample_size(10);
+
+    let entries: Vec<_> = (0..100_000)
+        .map(|i| FimEntry {
+            path: PathBuf::from(format!("/srv/app/data/file_{:06}.dat", i)),
+            data: create_test_entry_data(i),
+            created_at: None,
+            updated_at: None,
+        })
+        .collect();
+    let snapshot = FullSnapshot {
+        checksum: String::new(),
+        digest: String::new(),
+        entries,
+    };
+
+    let temp_dir = tempdir().unwrap();
+    for (name, format) in [("json_100k", SnapshotFormat::Json), ("binary_100k", SnapshotFormat::Binary)] {
+        let path = temp_dir.path().join(name);
+        snapshot.save(&path, format).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                black_box(FullSnapshot::from_bytes(black_box(&bytes), format).unwrap());
+            });
+        });
+    }
+
+    group.finish();
+}
+
 /// Helper function to create test files of specified size
 fn create_test_file(size_bytes: usize) -> NamedTempFile {
     let mut temp_file = NamedTempFile::new().unwrap();
@@ -368,6 +485,21 @@ fn create_test_entry_data(index: usize) -> FimEntryData {
         inode: 12345 + (index as u64),
         dev: 2049,
         scanned: true,
+        capabilities: None,
+        content_type: None,
+        decompressed: None,
+        is_dir: false,
+        unstable: false,
+        sha512: None,
+        sha3_256: None,
+        xxh3: None,
+        sampled: false,
+        owner_sid: None,
+        dacl: None,
+        xattr_hash: None,
+        mtime_nsec: None,
+        change_time_ns: None,
+        link_target: None,
     }
 }
 
@@ -376,14 +508,19 @@ criterion_group!(
     bench_hashing,
     bench_database,
     bench_scanning,
+    bench_exclude_patterns,
     bench_parallel_processing,
-    bench_memory_usage
+    bench_memory_usage,
+    bench_report_rendering,
+    bench_snapshot_import
 );
 
 criterion_main!(benches);
 
 #[cfg(test)]
 mod benchmark_tests {
+    // `harness = false` strips the #[test] fns below, leaving this import unused
+    #[allow(unused_imports)]
     use super::*;
     
     #[test]
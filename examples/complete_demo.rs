@@ -8,14 +8,12 @@
 //! - Integrating with alerting systems
 
 use rusty_fim::prelude::*;
-use rusty_fim::reporting::{OutputFormat, ReportGenerator};
+use rusty_fim::reporting::{CompiledSeverityRules, OutputFormat, ReportGenerator};
 
 use anyhow::Result;
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
 use tempfile::tempdir;
 use tokio::time;
@@ -23,7 +21,7 @@ use tokio::time;
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    rusty_fim::init_logging()?;
+    rusty_fim::init_logging(rusty_fim::LogFormat::Text)?;
     
     println!("🛡️  Rusty FIM - Complete Demo");
     println!("============================\n");
@@ -33,10 +31,10 @@ async fn main() -> Result<()> {
     println!("📁 Demo directory: {}", demo_dir.path().display());
 
     // Setup initial files
-    setup_demo_files(&demo_dir.path().to_path_buf()).await?;
+    setup_demo_files(demo_dir.path()).await?;
 
     // Configure FIM
-    let config = create_demo_config(&demo_dir.path().to_path_buf())?;
+    let config = create_demo_config(demo_dir.path())?;
     
     // Run the complete demo
     run_fim_demo(config, demo_dir.path().to_path_buf()).await?;
@@ -46,7 +44,7 @@ async fn main() -> Result<()> {
 }
 
 /// Create demonstration files and directory structure
-async fn setup_demo_files(demo_dir: &PathBuf) -> Result<()> {
+async fn setup_demo_files(demo_dir: &Path) -> Result<()> {
     println!("📝 Setting up demo files...");
 
     // Create subdirectories
@@ -91,25 +89,27 @@ async fn setup_demo_files(demo_dir: &PathBuf) -> Result<()> {
 }
 
 /// Create FIM configuration for the demo
-fn create_demo_config(demo_dir: &PathBuf) -> Result<FimConfig> {
-    let mut config = FimConfig::default();
-    
-    // Monitor the demo directory
-    config.monitor_paths = vec![demo_dir.clone()];
-    
-    // Exclude log files and temporary files
-    config.exclude_patterns = vec![
-        "**/logs/**".to_string(),
-        "**/*.tmp".to_string(),
-        "**/*.temp".to_string(),
-    ];
-    
-    // Use in-memory database for demo
-    config.memory_database = true;
-    
-    // Enable real-time monitoring
-    config.enable_realtime = true;
-    config.scan_interval = 10; // Short interval for demo
+fn create_demo_config(demo_dir: &Path) -> Result<FimConfig> {
+    let mut config = FimConfig {
+        // Monitor the demo directory
+        monitor_paths: vec![demo_dir.to_path_buf()],
+
+        // Exclude log files and temporary files
+        exclude_patterns: vec![
+            "**/logs/**".to_string(),
+            "**/*.tmp".to_string(),
+            "**/*.temp".to_string(),
+        ],
+
+        // Use in-memory database for demo
+        memory_database: true,
+
+        // Enable real-time monitoring
+        enable_realtime: true,
+        scan_interval: 10, // Short interval for demo
+
+        ..Default::default()
+    };
     
     // Configure hashing
     config.hash_config.use_blake3 = true;
@@ -197,8 +197,13 @@ async fn perform_incremental_scan(config: FimConfig) -> Result<ScanResults> {
             ChangeType::PermissionChanged => "🔐",
             ChangeType::SizeChanged => "📏",
             ChangeType::TimestampChanged => "⏰",
+            ChangeType::AttributeChanged => "🛡",
+            ChangeType::ContentClassChanged => "🧬",
+            ChangeType::MountChanged => "💽",
+            ChangeType::AccessLost => "🚫",
+            ChangeType::Moved { .. } => "🔀",
         };
-        println!("  {} {:?}: {}", icon, change.change_type, change.path.display());
+        println!("  {} {}: {}", icon, change.change_type.name(), change.path.display());
     });
     
     engine.start()?;
@@ -208,7 +213,7 @@ async fn perform_incremental_scan(config: FimConfig) -> Result<ScanResults> {
 }
 
 /// Simulate various file changes for demonstration
-async fn simulate_file_changes(demo_dir: &PathBuf) -> Result<()> {
+async fn simulate_file_changes(demo_dir: &Path) -> Result<()> {
     println!("  📝 Modifying important.txt...");
     fs::write(
         demo_dir.join("data").join("important.txt"),
@@ -252,8 +257,8 @@ async fn demonstrate_realtime_monitoring(config: FimConfig, demo_dir: PathBuf) -
     
     // Add change handler that collects changes
     engine.add_change_handler(move |change| {
-        println!("    🚨 Real-time change detected: {:?} - {}", 
-                 change.change_type, change.path.display());
+        println!("    🚨 Real-time change detected: {} - {}", 
+                 change.change_type.name(), change.path.display());
         
         let mut changes = changes_clone.lock().unwrap();
         changes.push(change.clone());
@@ -314,41 +319,37 @@ async fn demonstrate_realtime_monitoring(config: FimConfig, demo_dir: PathBuf) -
 }
 
 /// Generate demonstration reports in various formats
-async fn generate_demo_reports(demo_dir: &PathBuf) -> Result<()> {
+async fn generate_demo_reports(demo_dir: &Path) -> Result<()> {
     // Create some mock changes for the report
     let mock_changes = create_mock_changes();
     
-    // Create report generator
-    let mut report_config = ReportConfig::default();
-    report_config.title = "Rusty FIM Demo Report".to_string();
-    report_config.description = Some("Demonstration of FIM capabilities and change detection".to_string());
-    
-    let generator = ReportGenerator::new(report_config);
+    // Create report generator writing every format at once
+    let report_config = ReportConfig {
+        title: "Rusty FIM Demo Report".to_string(),
+        description: Some("Demonstration of FIM capabilities and change detection".to_string()),
+        ..Default::default()
+    };
+    let outputs = [
+        ("JSON", OutputFormat::Json, demo_dir.join("fim_report.json")),
+        ("HTML", OutputFormat::Html, demo_dir.join("fim_report.html")),
+        ("CSV", OutputFormat::Csv, demo_dir.join("fim_report.csv")),
+        ("Text", OutputFormat::Text, demo_dir.join("fim_report.txt")),
+    ];
+    let generator = outputs
+        .iter()
+        .fold(ReportGenerator::builder().config(report_config), |builder, (_, format, path)| {
+            builder.file(format.clone(), path)
+        })
+        .build();
     
     // Generate report
-    let report = generator.generate_report(mock_changes, None, None);
+    let report = generator.generate_report(mock_changes.clone(), None, None);
     
     println!("  📊 Generating reports in multiple formats...");
-    
-    // Export JSON report
-    let json_path = demo_dir.join("fim_report.json");
-    generator.export_report(&report, &json_path, OutputFormat::Json)?;
-    println!("    ✅ JSON report: {}", json_path.display());
-    
-    // Export HTML report
-    let html_path = demo_dir.join("fim_report.html");
-    generator.export_report(&report, &html_path, OutputFormat::Html)?;
-    println!("    ✅ HTML report: {}", html_path.display());
-    
-    // Export CSV report
-    let csv_path = demo_dir.join("fim_report.csv");
-    generator.export_report(&report, &csv_path, OutputFormat::Csv)?;
-    println!("    ✅ CSV report: {}", csv_path.display());
-    
-    // Export text report
-    let text_path = demo_dir.join("fim_report.txt");
-    generator.export_report(&report, &text_path, OutputFormat::Text)?;
-    println!("    ✅ Text report: {}", text_path.display());
+    generator.export_all(&report)?;
+    for (name, _, path) in &outputs {
+        println!("    ✅ {} report: {}", name, path.display());
+    }
     
     // Display report summary
     println!("\n  📋 Report Summary:");
@@ -367,7 +368,7 @@ fn create_mock_changes() -> Vec<FileChange> {
     use rusty_fim::database::FimEntryData;
     use chrono::Utc;
     
-    vec![
+    let mut changes = vec![
         FileChange {
             path: PathBuf::from("/demo/config/app.conf"),
             change_type: ChangeType::Modified,
@@ -387,8 +388,26 @@ fn create_mock_changes() -> Vec<FileChange> {
                 inode: 12345,
                 dev: 2049,
                 scanned: true,
+                capabilities: None,
+                content_type: None,
+                decompressed: None,
+                is_dir: false,
+                unstable: false,
+                sha512: None,
+                sha3_256: None,
+                xxh3: None,
+                sampled: false,
+                owner_sid: None,
+                dacl: None,
+                xattr_hash: None,
+                mtime_nsec: None,
+                change_time_ns: None,
+                link_target: None,
             }),
             detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
         },
         FileChange {
             path: PathBuf::from("/demo/data/important.txt"),
@@ -409,8 +428,26 @@ fn create_mock_changes() -> Vec<FileChange> {
                 inode: 54321,
                 dev: 2049,
                 scanned: true,
+                capabilities: None,
+                content_type: None,
+                decompressed: None,
+                is_dir: false,
+                unstable: false,
+                sha512: None,
+                sha3_256: None,
+                xxh3: None,
+                sampled: false,
+                owner_sid: None,
+                dacl: None,
+                xattr_hash: None,
+                mtime_nsec: None,
+                change_time_ns: None,
+                link_target: None,
             }),
             detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
         },
         FileChange {
             path: PathBuf::from("/demo/config/database.conf"),
@@ -430,11 +467,36 @@ fn create_mock_changes() -> Vec<FileChange> {
                 inode: 67890,
                 dev: 2049,
                 scanned: true,
+                capabilities: None,
+                content_type: None,
+                decompressed: None,
+                is_dir: false,
+                unstable: false,
+                sha512: None,
+                sha3_256: None,
+                xxh3: None,
+                sampled: false,
+                owner_sid: None,
+                dacl: None,
+                xattr_hash: None,
+                mtime_nsec: None,
+                change_time_ns: None,
+                link_target: None,
             }),
             new_entry: None,
             detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
         },
-    ]
+    ];
+
+    // Classify as the engine would for changes it detects
+    let rules = CompiledSeverityRules::default();
+    for change in &mut changes {
+        rules.classify(change);
+    }
+    changes
 }
 
 /// Demonstrate alert generation and handling
@@ -0,0 +1,60 @@
+//! Serve FIM metrics for Prometheus to scrape
+//!
+//! Scans the given directory every 30 seconds and answers every HTTP request
+//! on 127.0.0.1:9898 with the current metrics:
+//!
+//! ```text
+//! cargo run --example metrics_server -- /etc
+//! curl http://127.0.0.1:9898/metrics
+//! ```
+
+use anyhow::Result;
+use rusty_fim::{FimConfig, FimEngine};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const LISTEN_ADDR: &str = "127.0.0.1:9898";
+const SCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+    let config = FimConfig {
+        monitor_paths: vec![path],
+        memory_database: true,
+        enable_realtime: false,
+        ..Default::default()
+    };
+    let engine = Arc::new(Mutex::new(FimEngine::new(config)?));
+    engine.lock().unwrap().baseline_scan()?;
+
+    let listener = TcpListener::bind(LISTEN_ADDR)?;
+    println!("Serving metrics on http://{}/metrics", LISTEN_ADDR);
+    let server_engine = Arc::clone(&engine);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            // Any request gets the metrics; read its request line first
+            let mut request_line = String::new();
+            if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let body = server_engine.lock().unwrap().metrics_text();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                rusty_fim::metrics::CONTENT_TYPE,
+                body.len(),
+                body
+            );
+        }
+    });
+
+    loop {
+        std::thread::sleep(SCAN_INTERVAL);
+        let results = engine.lock().unwrap().incremental_scan()?;
+        println!("Scanned {} files", results.files_scanned);
+    }
+}
@@ -0,0 +1,103 @@
+//! Windows file ownership and access control
+//!
+//! Windows has no mode bits or numeric owner; access is governed by each
+//! file's owner SID and discretionary ACL (DACL). FIM records both as text,
+//! the SID in `S-1-5-...` form and the DACL in SDDL, so they compare and
+//! report the way Unix permissions and ownership do.
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Owner and DACL of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowsSecurity {
+    /// Owner as a string SID, e.g. `S-1-5-32-544`
+    pub owner_sid: String,
+    /// DACL in SDDL form, e.g. `D:PAI(A;;FA;;;SY)(A;;0x1200a9;;;BU)`
+    pub dacl: String,
+}
+
+/// Read the owner and DACL of a file
+///
+/// Returns `None` when the filesystem keeps no security descriptors (e.g.
+/// FAT volumes).
+#[cfg(windows)]
+pub fn read_security(path: &Path) -> Result<Option<WindowsSecurity>> {
+    use anyhow::Context;
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSecurityDescriptorToStringSecurityDescriptorW, ConvertSidToStringSidW,
+        GetNamedSecurityInfoW, SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        DACL_SECURITY_INFORMATION, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut owner: PSID = std::ptr::null_mut();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    let status = unsafe {
+        GetNamedSecurityInfoW(
+            wide.as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+            &mut owner,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        return Err(anyhow::Error::new(std::io::Error::from_raw_os_error(status as i32))
+            .context(format!("Failed to read security descriptor of {}", path.display())));
+    }
+
+    let security = if owner.is_null() {
+        Ok(None)
+    } else {
+        (|| {
+            let mut sid = std::ptr::null_mut();
+            if unsafe { ConvertSidToStringSidW(owner, &mut sid) } == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let owner_sid = unsafe { take_local_string(sid) };
+
+            let mut sddl = std::ptr::null_mut();
+            let converted = unsafe {
+                ConvertSecurityDescriptorToStringSecurityDescriptorW(
+                    descriptor,
+                    SDDL_REVISION_1,
+                    DACL_SECURITY_INFORMATION,
+                    &mut sddl,
+                    std::ptr::null_mut(),
+                )
+            };
+            if converted == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let dacl = unsafe { take_local_string(sddl) };
+            Ok(Some(WindowsSecurity { owner_sid, dacl }))
+        })()
+    };
+    // The owner SID points into the descriptor, so it is freed last
+    unsafe { LocalFree(descriptor) };
+
+    security.with_context(|| format!("Failed to convert security descriptor of {}", path.display()))
+}
+
+/// Read the owner and DACL of a file
+#[cfg(not(windows))]
+pub fn read_security(_path: &Path) -> Result<Option<WindowsSecurity>> {
+    Ok(None)
+}
+
+/// Copy a NUL-terminated UTF-16 string allocated by the system, then free it
+#[cfg(windows)]
+unsafe fn take_local_string(text: windows_sys::core::PWSTR) -> String {
+    let len = (0..).take_while(|&i| *text.add(i) != 0).count();
+    let value = String::from_utf16_lossy(std::slice::from_raw_parts(text, len));
+    windows_sys::Win32::Foundation::LocalFree(text.cast());
+    value
+}
@@ -0,0 +1,213 @@
+//! Linux file capability handling
+//!
+//! Decodes the `security.capability` extended attribute into the textual form
+//! used by `getcap` (e.g. `cap_net_bind_service+ep`). File capabilities grant
+//! privileges without setuid, so FIM tracks them alongside content hashes.
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Extended attribute holding file capabilities
+pub const CAPABILITY_XATTR: &str = "security.capability";
+
+const VFS_CAP_REVISION_MASK: u32 = 0xFF00_0000;
+const VFS_CAP_REVISION_1: u32 = 0x0100_0000;
+const VFS_CAP_REVISION_2: u32 = 0x0200_0000;
+const VFS_CAP_REVISION_3: u32 = 0x0300_0000;
+const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x0000_0001;
+
+/// Capability names indexed by capability number
+const CAPABILITY_NAMES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// Read and decode the capabilities of a file
+///
+/// Returns `None` when the file carries no capabilities or the platform and
+/// filesystem do not support them.
+#[cfg(target_os = "linux")]
+pub fn read_capabilities(path: &Path) -> Result<Option<String>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let c_name = CString::new(CAPABILITY_XATTR)?;
+    // vfs_cap_data is at most 24 bytes (revision 3)
+    let mut buf = [0u8; 64];
+
+    let len = unsafe {
+        libc::lgetxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+        )
+    };
+
+    if len < 0 {
+        let err = std::io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ENODATA) | Some(libc::ENOTSUP) => Ok(None),
+            _ => Err(anyhow::Error::new(err)
+                .context(format!("Failed to read capabilities of {}", path.display()))),
+        };
+    }
+
+    Ok(Some(decode_capabilities(&buf[..len as usize])))
+}
+
+/// Read and decode the capabilities of a file
+#[cfg(not(target_os = "linux"))]
+pub fn read_capabilities(_path: &Path) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// Decode raw `vfs_cap_data` into `getcap`-style text
+///
+/// Capabilities sharing the same flags are grouped, e.g.
+/// `cap_chown+i cap_net_admin,cap_net_raw+p`. Malformed data is rendered as
+/// `invalid:<hex>` so it still compares and reports meaningfully.
+pub fn decode_capabilities(raw: &[u8]) -> String {
+    let word = |index: usize| -> Option<u32> {
+        raw.get(index * 4..index * 4 + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let Some(magic) = word(0) else {
+        return invalid(raw);
+    };
+    let (words, expected_len) = match magic & VFS_CAP_REVISION_MASK {
+        VFS_CAP_REVISION_1 => (1, 12),
+        VFS_CAP_REVISION_2 => (2, 20),
+        VFS_CAP_REVISION_3 => (2, 24),
+        _ => return invalid(raw),
+    };
+    if raw.len() != expected_len {
+        return invalid(raw);
+    }
+    let effective = magic & VFS_CAP_FLAGS_EFFECTIVE != 0;
+
+    // (flags, capability names) in order of first appearance
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for index in 0..words {
+        let permitted = word(1 + index * 2).unwrap_or(0);
+        let inheritable = word(2 + index * 2).unwrap_or(0);
+
+        for bit in 0..32 {
+            let mask = 1u32 << bit;
+            let p = permitted & mask != 0;
+            let i = inheritable & mask != 0;
+            if !p && !i {
+                continue;
+            }
+
+            let mut flags = String::new();
+            if effective {
+                flags.push('e');
+            }
+            if i {
+                flags.push('i');
+            }
+            if p {
+                flags.push('p');
+            }
+
+            let number = index * 32 + bit;
+            let name = CAPABILITY_NAMES
+                .get(number)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("cap_{}", number));
+
+            match groups.iter_mut().find(|(group_flags, _)| *group_flags == flags) {
+                Some((_, names)) => names.push(name),
+                None => groups.push((flags, vec![name])),
+            }
+        }
+    }
+
+    let mut text = groups
+        .iter()
+        .map(|(flags, names)| format!("{}+{}", names.join(","), flags))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if magic & VFS_CAP_REVISION_MASK == VFS_CAP_REVISION_3 {
+        let root_id = word(5).unwrap_or(0);
+        if root_id != 0 {
+            text.push_str(&format!(" [rootid={}]", root_id));
+        }
+    }
+
+    text
+}
+
+fn invalid(raw: &[u8]) -> String {
+    let hex: String = raw.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("invalid:{}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap_data(magic: u32, words: &[u32]) -> Vec<u8> {
+        std::iter::once(magic)
+            .chain(words.iter().copied())
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_capabilities() {
+        let raw = cap_data(VFS_CAP_REVISION_2 | VFS_CAP_FLAGS_EFFECTIVE, &[1 << 10, 0, 0, 0]);
+        assert_eq!(decode_capabilities(&raw), "cap_net_bind_service+ep");
+
+        let raw = cap_data(VFS_CAP_REVISION_2, &[(1 << 13) | (1 << 12), 1, 1 << 7, 0]);
+        assert_eq!(decode_capabilities(&raw), "cap_chown+i cap_net_admin,cap_net_raw,cap_bpf+p");
+
+        let raw = cap_data(VFS_CAP_REVISION_3 | VFS_CAP_FLAGS_EFFECTIVE, &[1 << 13, 0, 0, 0, 1000]);
+        assert_eq!(decode_capabilities(&raw), "cap_net_raw+ep [rootid=1000]");
+
+        assert_eq!(decode_capabilities(&[0xff, 0x00]), "invalid:ff00");
+    }
+}
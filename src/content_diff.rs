@@ -0,0 +1,245 @@
+//! Line diffs of text file content
+//!
+//! Compares two versions of a text file line by line. A minified file on one
+//! enormous line would diff as a single replaced line, so [`DiffOptions`] can
+//! diff changed lines word by word, or cap their length, to keep the result
+//! readable.
+
+use serde::{Deserialize, Serialize};
+
+/// Most cells of the comparison table for one diff; larger differing regions
+/// are reported as removed and added whole
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+/// How [`diff_text`] compares lines
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiffOptions {
+    /// Diff a changed line word by word, so only the differing words show
+    #[serde(default)]
+    pub inline: bool,
+    /// Cut lines to this many characters before diffing (None = no cap);
+    /// differences past the cap are not reported
+    #[serde(default)]
+    pub max_line_length: Option<usize>,
+}
+
+/// A run of text within a changed line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffSpan {
+    Equal(String),
+    Delete(String),
+    Insert(String),
+}
+
+/// One line of a diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffLine {
+    Equal(String),
+    Delete(String),
+    Insert(String),
+    /// A line replaced by another, diffed word by word (with
+    /// [`DiffOptions::inline`] only)
+    Changed(Vec<DiffSpan>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Diff `old` against `new` line by line
+pub fn diff_text(old: &str, new: &str, options: &DiffOptions) -> Vec<DiffLine> {
+    let cap = |line: &'_ str| -> String {
+        match options.max_line_length.and_then(|max| line.char_indices().nth(max)) {
+            Some((end, _)) => line[..end].to_string(),
+            None => line.to_string(),
+        }
+    };
+    let old_lines: Vec<String> = old.lines().map(cap).collect();
+    let new_lines: Vec<String> = new.lines().map(cap).collect();
+
+    let mut lines = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for (edit, line) in diff_tokens(&old_lines, &new_lines) {
+        match edit {
+            Edit::Delete => deleted.push(line),
+            Edit::Insert => inserted.push(line),
+            Edit::Equal => {
+                flush_hunk(&mut lines, &mut deleted, &mut inserted, options.inline);
+                lines.push(DiffLine::Equal(line.to_string()));
+            }
+        }
+    }
+    flush_hunk(&mut lines, &mut deleted, &mut inserted, options.inline);
+    lines
+}
+
+/// Emit a run of removed and added lines, pairing them up as changed lines
+/// when diffing inline
+fn flush_hunk(lines: &mut Vec<DiffLine>, deleted: &mut Vec<&str>, inserted: &mut Vec<&str>, inline: bool) {
+    let paired = if inline { deleted.len().min(inserted.len()) } else { 0 };
+    for (old, new) in deleted.iter().zip(inserted.iter()).take(paired) {
+        lines.push(DiffLine::Changed(diff_words(old, new)));
+    }
+    lines.extend(deleted.drain(..).skip(paired).map(|line| DiffLine::Delete(line.to_string())));
+    lines.extend(inserted.drain(..).skip(paired).map(|line| DiffLine::Insert(line.to_string())));
+}
+
+/// Diff two lines by word, merging neighbouring words with the same edit
+fn diff_words(old: &str, new: &str) -> Vec<DiffSpan> {
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    for (edit, word) in diff_tokens(&split_words(old), &split_words(new)) {
+        match (spans.last_mut(), edit) {
+            (Some(DiffSpan::Equal(text)), Edit::Equal)
+            | (Some(DiffSpan::Delete(text)), Edit::Delete)
+            | (Some(DiffSpan::Insert(text)), Edit::Insert) => text.push_str(word),
+            (_, Edit::Equal) => spans.push(DiffSpan::Equal(word.to_string())),
+            (_, Edit::Delete) => spans.push(DiffSpan::Delete(word.to_string())),
+            (_, Edit::Insert) => spans.push(DiffSpan::Insert(word.to_string())),
+        }
+    }
+    spans
+}
+
+/// Split a line into words, whitespace runs and single punctuation characters
+fn split_words(line: &str) -> Vec<&str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        let current = class(c);
+        if i > start && (previous != Some(current) || current == 2) {
+            words.push(&line[start..i]);
+            start = i;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+/// Longest-common-subsequence diff of two token lists
+///
+/// The common prefix and suffix are matched first, so a small change in a
+/// long input only compares the region around it.
+fn diff_tokens<'a, T: AsRef<str>>(old: &'a [T], new: &'a [T]) -> Vec<(Edit, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a.as_ref() == b.as_ref()).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a.as_ref() == b.as_ref())
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<(Edit, &str)> = old[..prefix].iter().map(|t| (Edit::Equal, t.as_ref())).collect();
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        edits.extend(old_middle.iter().map(|t| (Edit::Delete, t.as_ref())));
+        edits.extend(new_middle.iter().map(|t| (Edit::Insert, t.as_ref())));
+    } else {
+        // lengths[i][j]: common subsequence length of old_middle[i..] and new_middle[j..]
+        let width = new_middle.len() + 1;
+        let mut lengths = vec![0u32; (old_middle.len() + 1) * width];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                lengths[i * width + j] = if old_middle[i].as_ref() == new_middle[j].as_ref() {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() && j < new_middle.len() {
+            if old_middle[i].as_ref() == new_middle[j].as_ref() {
+                edits.push((Edit::Equal, old_middle[i].as_ref()));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                edits.push((Edit::Delete, old_middle[i].as_ref()));
+                i += 1;
+            } else {
+                edits.push((Edit::Insert, new_middle[j].as_ref()));
+                j += 1;
+            }
+        }
+        edits.extend(old_middle[i..].iter().map(|t| (Edit::Delete, t.as_ref())));
+        edits.extend(new_middle[j..].iter().map(|t| (Edit::Insert, t.as_ref())));
+    }
+    edits.extend(old[old.len() - suffix..].iter().map(|t| (Edit::Equal, t.as_ref())));
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_inline_diff_isolates_token() -> anyhow::Result<()> {
+        let temp_dir = tempdir()?;
+        let old_path = temp_dir.path().join("app.min.js.old");
+        let new_path = temp_dir.path().join("app.min.js");
+        fs::write(&old_path, "var a=1;function f(x){return x*2}var url=\"https://cdn.example.com\";f(a);")?;
+        fs::write(&new_path, "var a=1;function f(x){return x*2}var url=\"https://evil.example.com\";f(a);")?;
+        let old = fs::read_to_string(&old_path)?;
+        let new = fs::read_to_string(&new_path)?;
+
+        // By default the one line is replaced whole
+        assert_eq!(diff_text(&old, &new, &DiffOptions::default()), vec![
+            DiffLine::Delete(old.clone()),
+            DiffLine::Insert(new.clone()),
+        ]);
+
+        // Inline, only the differing token is marked
+        let inline = DiffOptions { inline: true, ..Default::default() };
+        let lines = diff_text(&old, &new, &inline);
+        assert_eq!(lines.len(), 1);
+        let DiffLine::Changed(spans) = &lines[0] else {
+            panic!("expected a changed line, got {:?}", lines[0]);
+        };
+        let changed: Vec<_> = spans.iter().filter(|span| !matches!(span, DiffSpan::Equal(_))).collect();
+        assert_eq!(changed, vec![&DiffSpan::Delete("cdn".to_string()), &DiffSpan::Insert("evil".to_string())]);
+
+        // Equal and deleted spans rebuild the old line, equal and inserted the new one
+        let rebuild = |keep: fn(&DiffSpan) -> Option<&str>| spans.iter().filter_map(keep).collect::<String>();
+        assert_eq!(rebuild(|span| match span {
+            DiffSpan::Equal(text) | DiffSpan::Delete(text) => Some(text),
+            DiffSpan::Insert(_) => None,
+        }), old);
+        assert_eq!(rebuild(|span| match span {
+            DiffSpan::Equal(text) | DiffSpan::Insert(text) => Some(text),
+            DiffSpan::Delete(_) => None,
+        }), new);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_length_cap() {
+        let old = "header\nAAAAAAAAAAold\nfooter\n";
+        let new = "header\nAAAAAAAAAAnew\nfooter\nadded\n";
+        let capped = DiffOptions { max_line_length: Some(10), ..Default::default() };
+        assert_eq!(diff_text(old, new, &capped), vec![
+            DiffLine::Equal("header".to_string()),
+            DiffLine::Equal("AAAAAAAAAA".to_string()),
+            DiffLine::Equal("footer".to_string()),
+            DiffLine::Insert("added".to_string()),
+        ]);
+        assert_eq!(diff_text(old, new, &DiffOptions::default())[1], DiffLine::Delete("AAAAAAAAAAold".to_string()));
+    }
+}
@@ -3,16 +3,96 @@
 //! Implements SQLite-based storage with optimized queries for FIM operations.
 //! Based on the Wazuh FIM PoC but with enhanced Rust patterns and performance.
 
+use crate::fim::FileChange;
+use crate::hasher::{Compression, ContentClass};
+use crate::manifest;
+use crate::reporting::CompiledSeverityRules;
+
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
 pub const FIMDB_OK: i32 = 0;
 pub const _FIMDB_ERR: i32 = -1;
 
+/// `file_data` columns in the order [`FimDb::entry_from_row`] reads them
+const ENTRY_COLUMNS: &str = "path, size, perm, uid, gid, md5, sha1, sha256, blake3, \
+    mtime, ctime, atime, inode, dev, scanned, capabilities, content_type, decompressed, \
+    created_at, updated_at, is_dir, unstable, sha512, sha3_256, link_target, xxh3, sampled, \
+    owner_sid, dacl, xattr_hash, mtime_nsec, change_time_ns";
+
+/// Default on-disk database file name
+pub const DEFAULT_DB_FILE: &str = "fim_integrity.db";
+
+/// Profile of databases that predate profiles, and of configs naming none
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Column definitions of `file_data`; entries are unique per profile and path
+const FILE_DATA_SCHEMA: &str = r#"
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    profile TEXT NOT NULL DEFAULT 'default',
+    path TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    perm TEXT NOT NULL,
+    uid INTEGER NOT NULL,
+    gid INTEGER NOT NULL,
+    md5 TEXT,
+    sha1 TEXT,
+    sha256 TEXT,
+    blake3 TEXT NOT NULL,
+    mtime INTEGER NOT NULL,
+    ctime INTEGER NOT NULL,
+    atime INTEGER NOT NULL,
+    inode INTEGER NOT NULL,
+    dev INTEGER NOT NULL,
+    scanned INTEGER NOT NULL DEFAULT 1,
+    capabilities TEXT,
+    content_type TEXT,
+    decompressed TEXT,
+    is_dir INTEGER NOT NULL DEFAULT 0,
+    unstable INTEGER NOT NULL DEFAULT 0,
+    sha512 TEXT,
+    sha3_256 TEXT,
+    link_target TEXT,
+    xxh3 TEXT,
+    sampled INTEGER NOT NULL DEFAULT 0,
+    owner_sid TEXT,
+    dacl TEXT,
+    xattr_hash TEXT,
+    mtime_nsec INTEGER,
+    change_time_ns INTEGER,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    UNIQUE (profile, path)
+"#;
+
+/// Version of the table layout, kept in `PRAGMA user_version`
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Steps taking a database from version `n` to `n + 1`, in order; new
+/// steps go at the end along with a bump of [`SCHEMA_VERSION`]
+const MIGRATIONS: [fn(&FimDb) -> Result<()>; SCHEMA_VERSION as usize] = [
+    FimDb::migrate_added_columns,
+    FimDb::migrate_to_profiles,
+    FimDb::migrate_xxh3,
+    FimDb::migrate_sampled,
+    FimDb::migrate_windows_security,
+    FimDb::migrate_xattr_hash,
+    FimDb::migrate_change_times,
+    FimDb::migrate_history_chain,
+    FimDb::migrate_change_log_profiles,
+];
+
+/// Previous states kept per profile in `file_history` unless set with
+/// [`FimDb::set_history_limit`]
+pub const DEFAULT_HISTORY_LIMIT: usize = 10_000;
+
 /// File entry data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FimEntryData {
@@ -24,26 +104,270 @@ pub struct FimEntryData {
     pub sha1: Option<String>,
     pub sha256: Option<String>,
     pub blake3: String,  // Primary hash using BLAKE3
+    #[serde(with = "compact_time")]
     pub mtime: DateTime<Utc>,
+    #[serde(with = "compact_time")]
     pub ctime: DateTime<Utc>,
+    #[serde(with = "compact_time")]
     pub atime: DateTime<Utc>,
     pub inode: u64,
     pub dev: u64,
     pub scanned: bool,
+    /// Decoded Linux file capabilities, e.g. `cap_net_bind_service+ep`
+    #[serde(default)]
+    pub capabilities: Option<String>,
+    /// Text/binary classification (None for entries recorded before it existed)
+    #[serde(default)]
+    pub content_type: Option<ContentClass>,
+    /// Compression removed before hashing; the hashes then cover the decompressed content
+    #[serde(default)]
+    pub decompressed: Option<Compression>,
+    /// Directory entry: only metadata is recorded, the hashes are empty
+    #[serde(default)]
+    pub is_dir: bool,
+    /// Size or modification time changed while the file was hashed, so the
+    /// hashes may not match any complete version of it
+    #[serde(default)]
+    pub unstable: bool,
+    #[serde(default)]
+    pub sha512: Option<String>,
+    #[serde(default)]
+    pub sha3_256: Option<String>,
+    /// Target of a symbolic link recorded as a link rather than followed
+    #[serde(default)]
+    pub link_target: Option<PathBuf>,
+    /// Non-cryptographic xxHash3, see [`crate::hasher::FileHashes::xxh3`]
+    #[serde(default)]
+    pub xxh3: Option<String>,
+    /// The hashes fingerprint the file's ends and size rather than all of its
+    /// content, see [`crate::hasher::SamplingConfig`]
+    #[serde(default)]
+    pub sampled: bool,
+    /// Windows owner as a string SID, e.g. `S-1-5-32-544`
+    #[serde(default)]
+    pub owner_sid: Option<String>,
+    /// Windows discretionary ACL in SDDL form, e.g. `D:(A;;FA;;;SY)(A;;FR;;;BU)`
+    #[serde(default)]
+    pub dacl: Option<String>,
+    /// Digest of the extended attributes, see [`crate::xattrs::hash_xattrs`]
+    /// (None when not monitored or unsupported)
+    #[serde(default)]
+    pub xattr_hash: Option<String>,
+    /// Sub-second part of `mtime`, which is kept in whole seconds
+    #[serde(default)]
+    pub mtime_nsec: Option<u32>,
+    /// Inode change time (`st_ctime`) in nanoseconds on Unix, where `ctime`
+    /// is the birth time; like `mtime_nsec`, only the unchanged-metadata
+    /// shortcut reads it
+    #[serde(default)]
+    pub change_time_ns: Option<i64>,
+}
+
+impl FimEntryData {
+    /// Per-field differences from `self` (old) to `other` (new)
+    ///
+    /// Access time and the scan marker are bookkeeping and never reported.
+    pub fn diff(&self, other: &Self) -> Vec<FieldChange> {
+        fn optional(value: &Option<String>) -> String {
+            value.clone().unwrap_or_else(|| "none".to_string())
+        }
+
+        let fields = [
+            ("size", self.size.to_string(), other.size.to_string()),
+            ("perm", self.perm.clone(), other.perm.clone()),
+            ("uid", self.uid.to_string(), other.uid.to_string()),
+            ("gid", self.gid.to_string(), other.gid.to_string()),
+            ("md5", optional(&self.md5), optional(&other.md5)),
+            ("sha1", optional(&self.sha1), optional(&other.sha1)),
+            ("sha256", optional(&self.sha256), optional(&other.sha256)),
+            ("sha512", optional(&self.sha512), optional(&other.sha512)),
+            ("sha3_256", optional(&self.sha3_256), optional(&other.sha3_256)),
+            ("xxh3", optional(&self.xxh3), optional(&other.xxh3)),
+            ("blake3", self.blake3.clone(), other.blake3.clone()),
+            ("mtime", self.mtime.to_rfc3339(), other.mtime.to_rfc3339()),
+            ("ctime", self.ctime.to_rfc3339(), other.ctime.to_rfc3339()),
+            ("inode", self.inode.to_string(), other.inode.to_string()),
+            ("dev", self.dev.to_string(), other.dev.to_string()),
+            ("capabilities", optional(&self.capabilities), optional(&other.capabilities)),
+            ("owner_sid", optional(&self.owner_sid), optional(&other.owner_sid)),
+            ("dacl", optional(&self.dacl), optional(&other.dacl)),
+            ("xattr_hash", optional(&self.xattr_hash), optional(&other.xattr_hash)),
+            (
+                "content_type",
+                self.content_type.map(|c| c.as_str()).unwrap_or("unknown").to_string(),
+                other.content_type.map(|c| c.as_str()).unwrap_or("unknown").to_string(),
+            ),
+            (
+                "decompressed",
+                self.decompressed.map(|c| c.as_str()).unwrap_or("none").to_string(),
+                other.decompressed.map(|c| c.as_str()).unwrap_or("none").to_string(),
+            ),
+            ("is_dir", self.is_dir.to_string(), other.is_dir.to_string()),
+            ("unstable", self.unstable.to_string(), other.unstable.to_string()),
+            ("sampled", self.sampled.to_string(), other.sampled.to_string()),
+            (
+                "link_target",
+                self.link_target.as_ref().map(|t| t.display().to_string()).unwrap_or_else(|| "none".to_string()),
+                other.link_target.as_ref().map(|t| t.display().to_string()).unwrap_or_else(|| "none".to_string()),
+            ),
+        ];
+
+        fields
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(field, old, new)| FieldChange {
+                field: field.to_string(),
+                old,
+                new,
+            })
+            .collect()
+    }
+}
+
+/// A single field that differs between two entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// SQLite `auto_vacuum` mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoVacuum {
+    /// Free pages stay in the file until a manual `VACUUM` (SQLite default)
+    #[default]
+    None,
+    /// Free pages are returned to the filesystem at every commit
+    Full,
+    /// Free pages are tracked and reclaimed by [`FimDb::incremental_vacuum`]
+    Incremental,
+}
+
+impl AutoVacuum {
+    fn pragma_value(self) -> &'static str {
+        match self {
+            AutoVacuum::None => "NONE",
+            AutoVacuum::Full => "FULL",
+            AutoVacuum::Incremental => "INCREMENTAL",
+        }
+    }
+}
+
+/// Options for opening a database
+///
+/// The storage layout only takes effect when a database is created.
+#[derive(Debug, Clone, Default)]
+pub struct DbOptions {
+    /// Page size in bytes: a power of two from 512 to 65536 (None = SQLite default)
+    pub page_size: Option<u32>,
+    /// Free page handling
+    pub auto_vacuum: AutoVacuum,
+    /// Passphrase SQLCipher derives the database key from (needs the
+    /// `encryption` feature; ignored for in-memory databases)
+    pub encryption_key: Option<String>,
+    /// Baseline the handle reads and writes (None = [`DEFAULT_PROFILE`])
+    pub profile: Option<String>,
 }
 
 /// Complete file entry including path
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FimEntry {
     pub path: PathBuf,
     pub data: FimEntryData,
+    /// When the path was first recorded (None if the store doesn't track it)
+    #[serde(default, with = "compact_time::option")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the recorded data last changed, ignoring access time and the scan marker
+    #[serde(default, with = "compact_time::option")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Timestamps as RFC 3339 strings in human-readable formats such as JSON, and
+/// as `(seconds, nanoseconds)` in binary snapshots so loading skips date parsing
+mod compact_time {
+    use chrono::{DateTime, Utc};
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            time.serialize(serializer)
+        } else {
+            (time.timestamp(), time.timestamp_subsec_nanos()).serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        if deserializer.is_human_readable() {
+            DateTime::deserialize(deserializer)
+        } else {
+            let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+            DateTime::from_timestamp(secs, nanos)
+                .ok_or_else(|| D::Error::custom("timestamp out of range"))
+        }
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                time.serialize(serializer)
+            } else {
+                time.map(|time| (time.timestamp(), time.timestamp_subsec_nanos()))
+                    .serialize(serializer)
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            if deserializer.is_human_readable() {
+                Option::deserialize(deserializer)
+            } else {
+                <Option<(i64, u32)>>::deserialize(deserializer)?
+                    .map(|(secs, nanos)| {
+                        DateTime::from_timestamp(secs, nanos)
+                            .ok_or_else(|| D::Error::custom("timestamp out of range"))
+                    })
+                    .transpose()
+            }
+        }
+    }
 }
 
 /// FIM Database handle
 pub struct FimDb {
     conn: Connection,
-    _memory_mode: bool,
+    #[cfg_attr(not(test), allow(dead_code))]
+    memory_mode: bool,
+    path: Option<PathBuf>,
     transaction_count: usize,
+    /// Running checksum changes made inside the open transaction, written to
+    /// `profile_info` once when it commits
+    pending_checksum: Cell<[u8; 32]>,
+    checkpoints: u64,
+    read_only: bool,
+    /// Keep the previous data of entries overwritten by [`FimDb::insert_data`]
+    /// or removed
+    record_history: bool,
+    /// Most `file_history` rows kept per profile; the oldest are pruned
+    history_limit: usize,
+    /// Every entry query and write is limited to this profile
+    profile: String,
+    /// Classify change log rows recorded before changes carried a severity
+    severity_rules: CompiledSeverityRules,
 }
 
 impl FimDb {
@@ -55,16 +379,59 @@ impl FimDb {
     /// # Returns
     /// * `Result<Self>` - Database instance or error
     pub fn init(memory: bool) -> Result<Self> {
-        let conn = if memory {
-            Connection::open_in_memory()
-                .context("Failed to create in-memory database")?
+        Self::init_with(memory, &DbOptions::default())
+    }
+
+    /// Initialize FIM database stored at `db_path`, or in memory when `memory`
+    /// is true
+    pub fn init_at(db_path: &Path, memory: bool) -> Result<Self> {
+        Self::open((!memory).then_some(db_path), &DbOptions::default())
+    }
+
+    /// Initialize FIM database with storage layout options
+    ///
+    /// Page size and auto_vacuum are fixed once tables exist, so they only
+    /// apply to newly created databases.
+    pub fn init_with(memory: bool, options: &DbOptions) -> Result<Self> {
+        if memory {
+            Self::open(None, options)
         } else {
-            // Use a more descriptive filename
-            let db_path = "fim_integrity.db";
-            Connection::open(db_path)
-                .context("Failed to open database file")?
+            Self::open(Some(Path::new(DEFAULT_DB_FILE)), options)
+        }
+    }
+
+    /// Open the database at `db_path`, or in memory when `None`
+    pub(crate) fn open(db_path: Option<&Path>, options: &DbOptions) -> Result<Self> {
+        if let Some(page_size) = options.page_size {
+            if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+                anyhow::bail!("Invalid page size {}: must be a power of two from 512 to 65536", page_size);
+            }
+        }
+
+        let memory = db_path.is_none();
+        let (conn, path) = match db_path {
+            None => {
+                let conn = Connection::open_in_memory()
+                    .context("Failed to create in-memory database")?;
+                (conn, None)
+            }
+            Some(db_path) => {
+                let conn = Connection::open(db_path)
+                    .context("Failed to open database file")?;
+                // The key has to be set before anything reads the database
+                if let Some(key) = &options.encryption_key {
+                    apply_encryption_key(&conn, key)?;
+                }
+                (conn, std::fs::canonicalize(db_path).ok())
+            }
         };
 
+        // Layout pragmas must precede WAL mode and table creation to apply
+        if let Some(page_size) = options.page_size {
+            conn.pragma_update(None, "page_size", page_size)?;
+        }
+        conn.pragma_update(None, "auto_vacuum", options.auto_vacuum.pragma_value())?;
+
         // Configure SQLite for performance
         conn.pragma_update(None, "journal_mode", "WAL")?;
         conn.pragma_update(None, "synchronous", "NORMAL")?;
@@ -73,43 +440,133 @@ impl FimDb {
         
         let db = Self {
             conn,
-            _memory_mode: memory,
+            memory_mode: memory,
+            path,
             transaction_count: 0,
+            pending_checksum: Cell::new([0u8; 32]),
+            checkpoints: 0,
+            read_only: false,
+            record_history: true,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            profile: options.profile.clone().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+            severity_rules: CompiledSeverityRules::default(),
         };
 
+        // A new database starts at the current version; an older one migrates
+        let existing = db.table_exists("file_data")?;
         db.create_tables()?;
+        if existing {
+            db.migrate()?;
+        } else {
+            db.set_schema_version(SCHEMA_VERSION)?;
+        }
         db.create_indices()?;
+
+        // Databases predating the running checksum start from their current contents
+        if db.stored_checksum()?.is_none() {
+            let checksum = db.digest_rows("profile = ?1", [&db.profile])?;
+            db.store_checksum(checksum)?;
+        }
         
         info!("FIM database initialized (memory: {})", memory);
         Ok(db)
     }
 
-    /// Create database tables
+    /// Open an existing database read-only (`SQLITE_OPEN_READ_ONLY`)
+    ///
+    /// Nothing is created or migrated, so the database must come from this
+    /// version and be closed cleanly by its writer; every write fails.
+    pub fn open_read_only(db_path: &Path) -> Result<Self> {
+        Self::open_read_only_with(db_path, &DbOptions::default())
+    }
+
+    /// [`FimDb::open_read_only`] with the encryption key and profile from
+    /// `options`; the layout options do not apply
+    pub fn open_read_only_with(db_path: &Path, options: &DbOptions) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("Failed to open {} read-only", db_path.display()))?;
+        if let Some(key) = &options.encryption_key {
+            apply_encryption_key(&conn, key)?;
+        }
+        conn.pragma_update(None, "query_only", true)?;
+
+        let db = Self {
+            conn,
+            memory_mode: false,
+            path: std::fs::canonicalize(db_path).ok(),
+            transaction_count: 0,
+            pending_checksum: Cell::new([0u8; 32]),
+            checkpoints: 0,
+            read_only: true,
+            record_history: false,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            profile: options.profile.clone().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+            severity_rules: CompiledSeverityRules::default(),
+        };
+        // Fail now rather than on the first scan if the schema is missing or outdated
+        let version = db.schema_version()?;
+        if version != SCHEMA_VERSION {
+            anyhow::bail!(
+                "Database schema version {} does not match this build ({}); \
+                 open it writable once to migrate it",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        db.conn
+            .prepare(&format!("SELECT profile, {} FROM file_data LIMIT 1", ENTRY_COLUMNS))
+            .and_then(|_| db.conn.prepare("SELECT running_checksum FROM profile_info LIMIT 1"))
+            .context("Database schema is missing or outdated")?;
+
+        info!("FIM database opened read-only: {}", db_path.display());
+        Ok(db)
+    }
+
+    /// Whether the database was opened with [`FimDb::open_read_only`]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Profile this handle reads and writes
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Names of the profiles with entries or checksums in the database, sorted
+    pub fn list_profiles(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT profile FROM file_data UNION SELECT profile FROM profile_info ORDER BY 1"
+        )?;
+        let profiles = stmt.query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(profiles)
+    }
+
+    /// Enable or disable recording overwritten and removed entries in
+    /// `file_history` (on by default)
+    pub fn set_record_history(&mut self, enabled: bool) {
+        self.record_history = enabled;
+    }
+
+    /// Keep at most `limit` previous states per profile, pruning the oldest
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+    }
+
+    /// Rules classifying change log rows recorded without a severity
+    /// (the defaults until set)
+    pub fn set_severity_rules(&mut self, rules: CompiledSeverityRules) {
+        self.severity_rules = rules;
+    }
+
+    /// Create the tables of a new database, laid out at [`SCHEMA_VERSION`]
     fn create_tables(&self) -> Result<()> {
         // Main file data table
         self.conn.execute(
-            r#"
-            CREATE TABLE IF NOT EXISTS file_data (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                path TEXT NOT NULL UNIQUE,
-                size INTEGER NOT NULL,
-                perm TEXT NOT NULL,
-                uid INTEGER NOT NULL,
-                gid INTEGER NOT NULL,
-                md5 TEXT,
-                sha1 TEXT,
-                sha256 TEXT,
-                blake3 TEXT NOT NULL,
-                mtime INTEGER NOT NULL,
-                ctime INTEGER NOT NULL,
-                atime INTEGER NOT NULL,
-                inode INTEGER NOT NULL,
-                dev INTEGER NOT NULL,
-                scanned INTEGER NOT NULL DEFAULT 1,
-                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-                updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
-            )
-            "#,
+            &format!("CREATE TABLE IF NOT EXISTS file_data ({})", FILE_DATA_SCHEMA),
             [],
         )?;
 
@@ -120,7 +577,10 @@ impl FimDb {
                 id INTEGER PRIMARY KEY,
                 last_sync_id INTEGER NOT NULL DEFAULT 0,
                 total_files INTEGER NOT NULL DEFAULT 0,
-                last_sync_time INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                last_sync_time INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                running_checksum TEXT,
+                change_log_head TEXT,
+                baseline_signature TEXT
             )
             "#,
             [],
@@ -132,9 +592,284 @@ impl FimDb {
             [],
         )?;
 
+        // Per-profile checksum and signature
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS profile_info (
+                profile TEXT PRIMARY KEY,
+                running_checksum TEXT,
+                baseline_signature TEXT,
+                history_head TEXT,
+                history_base TEXT,
+                change_log_head TEXT
+            )
+            "#,
+            [],
+        )?;
+
+        // Audit log of detected changes
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS changes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile TEXT NOT NULL DEFAULT 'default',
+                path TEXT NOT NULL,
+                change_type TEXT NOT NULL,
+                detected_at INTEGER NOT NULL,
+                data TEXT NOT NULL,
+                prev_hash TEXT,
+                hash TEXT,
+                change_id TEXT
+            )
+            "#,
+            [],
+        )?;
+        // Previous data of overwritten and removed entries, hash chained per profile
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS file_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                profile TEXT NOT NULL DEFAULT 'default',
+                path TEXT NOT NULL,
+                changed_at INTEGER NOT NULL,
+                data TEXT NOT NULL,
+                prev_hash TEXT,
+                hash TEXT
+            )
+            "#,
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// Schema version recorded in the database (`PRAGMA user_version`);
+    /// 0 for databases that predate versioning
+    pub fn schema_version(&self) -> Result<u32> {
+        let version: u32 = self.conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(version)
+    }
+
+    fn set_schema_version(&self, version: u32) -> Result<()> {
+        self.conn.pragma_update(None, "user_version", version)?;
+        Ok(())
+    }
+
+    /// Bring an existing database up to [`SCHEMA_VERSION`]
+    ///
+    /// Each outstanding step of [`MIGRATIONS`] runs in its own transaction
+    /// together with the version bump, so a failed step leaves the database
+    /// at the last version that completed.
+    fn migrate(&self) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            anyhow::bail!(
+                "Database schema version {} is newer than this build supports ({})",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+
+        for (from, step) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let to = from as u32 + 1;
+            info!("Migrating database schema from version {} to {}", from, to);
+            self.conn.execute("BEGIN IMMEDIATE", [])?;
+            if let Err(e) = step(self).and_then(|()| self.set_schema_version(to)) {
+                let _ = self.conn.execute("ROLLBACK", []);
+                return Err(e.context(format!("Failed to migrate database schema to version {}", to)));
+            }
+            self.conn.execute("COMMIT", [])?;
+        }
+
+        Ok(())
+    }
+
+    /// Version 1: columns added to the initial schema before versioning
+    fn migrate_added_columns(&self) -> Result<()> {
+        self.ensure_column("file_data", "capabilities", "TEXT")?;
+        self.ensure_column("file_data", "content_type", "TEXT")?;
+        self.ensure_column("file_data", "decompressed", "TEXT")?;
+        self.ensure_column("file_data", "is_dir", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("file_data", "unstable", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("file_data", "sha512", "TEXT")?;
+        self.ensure_column("file_data", "sha3_256", "TEXT")?;
+        self.ensure_column("file_data", "link_target", "TEXT")?;
+
+        self.ensure_column("sync_info", "running_checksum", "TEXT")?;
+        self.ensure_column("sync_info", "change_log_head", "TEXT")?;
+        self.ensure_column("sync_info", "baseline_signature", "TEXT")?;
+
+        self.ensure_column("changes", "prev_hash", "TEXT")?;
+        self.ensure_column("changes", "change_id", "TEXT")?;
+        if self.ensure_column("changes", "hash", "TEXT")? {
+            // Logs predating the hash chain are chained as they stand
+            self.chain_unhashed_changes()?;
+        }
+
+        Ok(())
+    }
+
+    /// Version 2: profiles
+    ///
+    /// Rebuilds a `file_data` table whose paths were unique on their own,
+    /// moving its entries, history and checksum to [`DEFAULT_PROFILE`].
+    fn migrate_to_profiles(&self) -> Result<()> {
+        if !self.column_exists("file_data", "profile")? {
+            // The columns as of this version; later ones are left to their steps
+            const COLUMNS: &str = "path, size, perm, uid, gid, md5, sha1, sha256, blake3, \
+                mtime, ctime, atime, inode, dev, scanned, capabilities, content_type, \
+                decompressed, created_at, updated_at, is_dir, unstable, sha512, sha3_256, \
+                link_target";
+
+            info!("Moving existing entries to the '{}' profile", DEFAULT_PROFILE);
+            self.conn.execute_batch(&format!(
+                "ALTER TABLE file_data RENAME TO file_data_old;
+                 CREATE TABLE file_data ({schema});
+                 INSERT INTO file_data (id, {columns}) SELECT id, {columns} FROM file_data_old;
+                 DROP TABLE file_data_old;",
+                schema = FILE_DATA_SCHEMA,
+                columns = COLUMNS,
+            ))?;
+        }
+        self.ensure_column("file_history", "profile", "TEXT NOT NULL DEFAULT 'default'")?;
+
+        // Databases predating profiles kept these for their only baseline
+        self.conn.execute(
+            "INSERT OR IGNORE INTO profile_info (profile, running_checksum, baseline_signature) \
+             SELECT ?1, running_checksum, baseline_signature FROM sync_info \
+             WHERE id = 1 AND (running_checksum IS NOT NULL OR baseline_signature IS NOT NULL)",
+            [DEFAULT_PROFILE],
+        )?;
+
+        Ok(())
+    }
+
+    /// Version 3: xxHash3 content hashes
+    fn migrate_xxh3(&self) -> Result<()> {
+        self.ensure_column("file_data", "xxh3", "TEXT")?;
+        Ok(())
+    }
+
+    /// Version 4: sampled fingerprints of large files
+    fn migrate_sampled(&self) -> Result<()> {
+        self.ensure_column("file_data", "sampled", "INTEGER NOT NULL DEFAULT 0")?;
+        Ok(())
+    }
+
+    /// Version 5: Windows owner and access control
+    fn migrate_windows_security(&self) -> Result<()> {
+        self.ensure_column("file_data", "owner_sid", "TEXT")?;
+        self.ensure_column("file_data", "dacl", "TEXT")?;
+        Ok(())
+    }
+
+    /// Version 6: extended attribute digests
+    fn migrate_xattr_hash(&self) -> Result<()> {
+        self.ensure_column("file_data", "xattr_hash", "TEXT")?;
+        Ok(())
+    }
+
+    /// Version 7: sub-second modification and inode change times
+    fn migrate_change_times(&self) -> Result<()> {
+        self.ensure_column("file_data", "mtime_nsec", "INTEGER")?;
+        self.ensure_column("file_data", "change_time_ns", "INTEGER")?;
+        Ok(())
+    }
+
+    /// Version 8: hash chained file history
+    fn migrate_history_chain(&self) -> Result<()> {
+        self.ensure_column("file_history", "prev_hash", "TEXT")?;
+        self.ensure_column("file_history", "hash", "TEXT")?;
+        self.ensure_column("profile_info", "history_head", "TEXT")?;
+        self.ensure_column("profile_info", "history_base", "TEXT")?;
+
+        // History predating the chain is chained as it stands
+        let mut stmt = self.conn.prepare(
+            "SELECT id, profile, path, changed_at, data FROM file_history WHERE hash IS NULL ORDER BY id"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?.collect::<Result<Vec<_>, _>>()?;
+
+        let mut heads: HashMap<String, String> = HashMap::new();
+        for (id, profile, path, changed_at, data) in &rows {
+            let prev_hash = heads.remove(profile).unwrap_or_else(|| CHAIN_GENESIS.to_string());
+            let hash = history_row_hash(&prev_hash, profile, path, *changed_at, data);
+            self.conn.execute(
+                "UPDATE file_history SET prev_hash = ?1, hash = ?2 WHERE id = ?3",
+                params![prev_hash, hash, id],
+            )?;
+            heads.insert(profile.clone(), hash);
+        }
+        for (profile, head) in &heads {
+            self.conn.execute(
+                "INSERT INTO profile_info (profile, history_head) VALUES (?1, ?2) \
+                 ON CONFLICT(profile) DO UPDATE SET history_head = excluded.history_head",
+                params![profile, head],
+            )?;
+        }
+        if !rows.is_empty() {
+            info!("Chained {} existing file history rows", rows.len());
+        }
+
+        Ok(())
+    }
+
+    /// Version 9: a change log per profile
+    ///
+    /// Existing changes, and the head of their chain, move to
+    /// [`DEFAULT_PROFILE`].
+    fn migrate_change_log_profiles(&self) -> Result<()> {
+        self.ensure_column("changes", "profile", "TEXT NOT NULL DEFAULT 'default'")?;
+        self.ensure_column("profile_info", "change_log_head", "TEXT")?;
+        self.conn.execute(
+            "INSERT INTO profile_info (profile, change_log_head) \
+             SELECT ?1, change_log_head FROM sync_info WHERE id = 1 AND change_log_head IS NOT NULL \
+             ON CONFLICT(profile) DO UPDATE SET change_log_head = excluded.change_log_head",
+            [DEFAULT_PROFILE],
+        )?;
+        self.conn.execute("UPDATE sync_info SET change_log_head = NULL WHERE id = 1", [])?;
         Ok(())
     }
 
+    /// Add a column to databases created before it existed; true when added
+    fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<bool> {
+        let exists = self.column_exists(table, column)?;
+        if !exists {
+            info!("Adding column {}.{}", table, column);
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
+
+        Ok(!exists)
+    }
+
+    fn column_exists(&self, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt.query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .any(|name| name == column);
+        Ok(exists)
+    }
+
+    fn table_exists(&self, table: &str) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
     /// Create optimized indices
     fn create_indices(&self) -> Result<()> {
         let indices = [
@@ -143,6 +878,11 @@ impl FimDb {
             "CREATE INDEX IF NOT EXISTS idx_file_scanned ON file_data(scanned)",
             "CREATE INDEX IF NOT EXISTS idx_file_mtime ON file_data(mtime)",
             "CREATE INDEX IF NOT EXISTS idx_file_blake3 ON file_data(blake3)",
+            "CREATE INDEX IF NOT EXISTS idx_changes_detected_at ON changes(detected_at)",
+            "CREATE INDEX IF NOT EXISTS idx_changes_change_id ON changes(change_id)",
+            "CREATE INDEX IF NOT EXISTS idx_changes_profile ON changes(profile, detected_at)",
+            "CREATE INDEX IF NOT EXISTS idx_file_history_profile_path ON file_history(profile, path)",
+            "CREATE INDEX IF NOT EXISTS idx_file_history_profile ON file_history(profile, id)",
         ];
 
         for index_sql in &indices {
@@ -155,23 +895,101 @@ impl FimDb {
     /// Set all entries to unscanned state
     pub fn set_all_unscanned(&mut self) -> Result<i32> {
         let updated = self.conn.execute(
-            "UPDATE file_data SET scanned = 0",
-            [],
+            "UPDATE file_data SET scanned = 0 WHERE profile = ?1",
+            [&self.profile],
         )?;
         
         debug!("Set {} entries to unscanned", updated);
         Ok(FIMDB_OK)
     }
 
+    /// Mark a single entry as visited by the current scan, leaving its data alone
+    pub fn mark_scanned(&mut self, file_path: &Path) -> Result<i32> {
+        self.conn.execute(
+            "UPDATE file_data SET scanned = 1 WHERE profile = ?1 AND path = ?2",
+            params![self.profile, file_path.to_string_lossy()],
+        )?;
+        Ok(FIMDB_OK)
+    }
+
+    /// Mark entries at or below `root` as unscanned
+    pub fn set_unscanned_under(&mut self, root: &Path) -> Result<i32> {
+        let updated = self.conn.execute(
+            &format!("UPDATE file_data SET scanned = 0 WHERE {}", SUBTREE_FILTER),
+            subtree_params(&self.profile, root),
+        )?;
+
+        debug!("Set {} entries under {} to unscanned", updated, root.display());
+        Ok(FIMDB_OK)
+    }
+
+    /// Get unscanned entries, optionally restricted to those at or below `root`
+    pub fn get_not_scanned(&self, root: Option<&Path>) -> Result<Vec<FimEntry>> {
+        let entries = match root {
+            Some(root) => {
+                let mut stmt = self.conn.prepare(&format!(
+                    "SELECT {} FROM file_data WHERE scanned = 0 AND {} ORDER BY path",
+                    ENTRY_COLUMNS, SUBTREE_FILTER
+                ))?;
+                let rows = stmt.query_map(subtree_params(&self.profile, root), Self::entry_from_row)?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            }
+            None => {
+                let mut stmt = self.conn.prepare(&format!(
+                    "SELECT {} FROM file_data WHERE profile = ?1 AND scanned = 0 ORDER BY path",
+                    ENTRY_COLUMNS
+                ))?;
+                let rows = stmt.query_map([&self.profile], Self::entry_from_row)?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        Ok(entries)
+    }
+
     /// Clean/remove the database
     pub fn clean() -> Result<i32> {
-        if Path::new("fim_integrity.db").exists() {
-            std::fs::remove_file("fim_integrity.db")
-                .context("Failed to remove database file")?;
+        Self::clean_at(Path::new(DEFAULT_DB_FILE))
+    }
+
+    /// Remove the database at `db_path` along with its SQLite companion files
+    pub fn clean_at(db_path: &Path) -> Result<i32> {
+        for file in Self::companion_files(db_path) {
+            if file.exists() {
+                std::fs::remove_file(&file)
+                    .with_context(|| format!("Failed to remove database file {}", file.display()))?;
+            }
         }
         Ok(FIMDB_OK)
     }
 
+    /// Resolved location of the database file, or None for an in-memory database
+    ///
+    /// Back up this file together with its `-wal` companion (see
+    /// [`FimDb::companion_files`]) while the engine is stopped.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Database file plus its SQLite companion files (empty for in-memory databases)
+    pub fn own_files(&self) -> Vec<PathBuf> {
+        self.path
+            .as_deref()
+            .map(Self::companion_files)
+            .unwrap_or_default()
+    }
+
+    /// Files SQLite maintains for a database at `db_path` (main, WAL, SHM, journal)
+    pub fn companion_files(db_path: &Path) -> Vec<PathBuf> {
+        let mut files = vec![db_path.to_path_buf()];
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let mut name = db_path.as_os_str().to_os_string();
+            name.push(suffix);
+            files.push(PathBuf::from(name));
+        }
+        files
+    }
+
     /// Begin transaction for batch operations
     pub fn begin_transaction(&mut self) -> Result<()> {
         if self.transaction_count == 0 {
@@ -186,6 +1004,7 @@ impl FimDb {
         if self.transaction_count > 0 {
             self.transaction_count -= 1;
             if self.transaction_count == 0 {
+                self.flush_checksum()?;
                 self.conn.execute("COMMIT", [])?;
             }
         }
@@ -194,10 +1013,30 @@ impl FimDb {
 
     /// Force commit (for periodic commits during long operations)
     pub fn force_commit(&mut self) {
+        self.commit_and_restart(false);
+    }
+
+    /// Force commit and run a passive WAL checkpoint before continuing
+    ///
+    /// Bounds WAL growth during long scans, where SQLite's opportunistic
+    /// automatic checkpoints may not keep up.
+    pub fn force_commit_with_checkpoint(&mut self) {
+        self.commit_and_restart(true);
+    }
+
+    fn commit_and_restart(&mut self, checkpoint: bool) {
         if self.transaction_count > 0 {
+            if let Err(e) = self.flush_checksum() {
+                warn!("Failed to write running checksum: {}", e);
+            }
             if let Err(e) = self.conn.execute("COMMIT", []) {
                 warn!("Failed to force commit: {}", e);
             } else {
+                if checkpoint {
+                    if let Err(e) = self.wal_checkpoint() {
+                        warn!("Failed to checkpoint WAL: {}", e);
+                    }
+                }
                 // Restart transaction
                 if let Err(e) = self.conn.execute("BEGIN IMMEDIATE", []) {
                     warn!("Failed to restart transaction: {}", e);
@@ -206,78 +1045,558 @@ impl FimDb {
         }
     }
 
+    /// Run a passive WAL checkpoint outside of any transaction
+    ///
+    /// Returns the number of frames in the WAL and how many were checkpointed
+    /// (both -1 when the database is not in WAL mode).
+    pub fn wal_checkpoint(&mut self) -> Result<(i64, i64)> {
+        let (busy, log_frames, checkpointed) = self.conn.query_row(
+            "PRAGMA wal_checkpoint(PASSIVE)",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)),
+        )?;
+        self.checkpoints += 1;
+        debug!("WAL checkpoint: busy={}, log={}, checkpointed={}", busy, log_frames, checkpointed);
+        Ok((log_frames, checkpointed))
+    }
+
+    /// Number of explicit WAL checkpoints run on this handle
+    pub fn checkpoint_count(&self) -> u64 {
+        self.checkpoints
+    }
+
+    /// Reclaim up to `pages` free pages (0 = all) in `auto_vacuum = incremental` mode
+    ///
+    /// Returns the number of free pages remaining. Does nothing for databases
+    /// created in another auto_vacuum mode.
+    pub fn incremental_vacuum(&mut self, pages: u32) -> Result<i64> {
+        // The pragma yields one row per freed page, which must be stepped through
+        let mut stmt = self.conn.prepare(&format!("PRAGMA incremental_vacuum({})", pages))?;
+        let mut rows = stmt.query([])?;
+        while rows.next()?.is_some() {}
+
+        let remaining = self.conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        debug!("Incremental vacuum left {} free pages", remaining);
+        Ok(remaining)
+    }
+
     /// Get file entry by path
     pub fn get_path(&self, file_path: &Path) -> Result<Option<FimEntry>> {
         let path_str = file_path.to_string_lossy();
         
         let entry = self.conn.query_row(
-            r#"
-            SELECT path, size, perm, uid, gid, md5, sha1, sha256, blake3,
-                   mtime, ctime, atime, inode, dev, scanned
-            FROM file_data WHERE path = ?1
-            "#,
-            [&path_str],
-            |row| {
-                Ok(FimEntry {
-                    path: PathBuf::from(row.get::<_, String>(0)?),
-                    data: FimEntryData {
-                        size: row.get(1)?,
-                        perm: row.get(2)?,
-                        uid: row.get(3)?,
-                        gid: row.get(4)?,
-                        md5: row.get(5)?,
-                        sha1: row.get(6)?,
-                        sha256: row.get(7)?,
-                        blake3: row.get(8)?,
-                        mtime: DateTime::from_timestamp(row.get::<_, i64>(9)?, 0).unwrap_or_default(),
-                        ctime: DateTime::from_timestamp(row.get::<_, i64>(10)?, 0).unwrap_or_default(),
-                        atime: DateTime::from_timestamp(row.get::<_, i64>(11)?, 0).unwrap_or_default(),
-                        inode: row.get(12)?,
-                        dev: row.get(13)?,
-                        scanned: row.get::<_, i32>(14)? != 0,
-                    },
-                })
-            }
+            &format!("SELECT {} FROM file_data WHERE profile = ?1 AND path = ?2", ENTRY_COLUMNS),
+            params![self.profile, path_str],
+            Self::entry_from_row,
         ).optional()?;
 
         Ok(entry)
     }
 
-    /// Check if inode exists
-    pub fn _get_inode(&self, inode: u64, dev: u64) -> Result<bool> {
-        let count: i32 = self.conn.query_row(
-            "SELECT COUNT(*) FROM file_data WHERE inode = ?1 AND dev = ?2",
-            params![inode, dev],
-            |row| row.get(0),
+    /// Get all file entries ordered by path
+    pub fn get_all_entries(&self) -> Result<Vec<FimEntry>> {
+        let mut stmt = self.conn.prepare(
+            &format!("SELECT {} FROM file_data WHERE profile = ?1 ORDER BY path", ENTRY_COLUMNS)
         )?;
-        
-        Ok(count > 0)
+
+        let entries = stmt.query_map([&self.profile], Self::entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
     }
 
-    /// Get all paths for a given inode
-    pub fn _get_paths_from_inode(&self, inode: u64, dev: u64) -> Result<Vec<String>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT path FROM file_data WHERE inode = ?1 AND dev = ?2"
-        )?;
-        
-        let paths = stmt.query_map(params![inode, dev], |row| {
-            Ok(row.get::<_, String>(0)?)
-        })?
+    /// Up to `limit` entries whose path sorts after `after`, ordered by path
+    ///
+    /// Keyed on the path index, so paging through the baseline never holds
+    /// more than a page of it.
+    pub fn get_entries_after(&self, after: Option<&Path>, limit: usize) -> Result<Vec<FimEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM file_data WHERE profile = ?1 AND path > ?2 ORDER BY path LIMIT ?3",
+            ENTRY_COLUMNS
+        ))?;
+        let after = after.map(|after| after.to_string_lossy().into_owned()).unwrap_or_default();
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+        let entries = stmt.query_map(params![self.profile, after, limit], Self::entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Entries at or below `prefix`, ordered by path string, at most `limit`
+    /// of them
+    ///
+    /// A range query on the path index, so listing a subtree does not read
+    /// the whole table.
+    pub fn get_entries_under(&self, prefix: &Path, limit: Option<usize>) -> Result<Vec<FimEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM file_data WHERE {} ORDER BY path LIMIT ?5",
+            ENTRY_COLUMNS, SUBTREE_FILTER
+        ))?;
+        let [profile, root, lower, upper] = subtree_params(&self.profile, prefix);
+        // A negative limit is no limit to SQLite
+        let limit = limit.map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX));
+
+        let entries = stmt.query_map(params![profile, root, lower, upper, limit], Self::entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Map a `file_data` row selected in canonical column order
+    fn entry_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<FimEntry> {
+        Ok(FimEntry {
+            path: PathBuf::from(row.get::<_, String>(0)?),
+            data: FimEntryData {
+                size: row.get(1)?,
+                perm: row.get(2)?,
+                uid: row.get(3)?,
+                gid: row.get(4)?,
+                md5: row.get(5)?,
+                sha1: row.get(6)?,
+                sha256: row.get(7)?,
+                blake3: row.get(8)?,
+                mtime: DateTime::from_timestamp(row.get::<_, i64>(9)?, 0).unwrap_or_default(),
+                ctime: DateTime::from_timestamp(row.get::<_, i64>(10)?, 0).unwrap_or_default(),
+                atime: DateTime::from_timestamp(row.get::<_, i64>(11)?, 0).unwrap_or_default(),
+                inode: row.get(12)?,
+                dev: row.get(13)?,
+                scanned: row.get::<_, i32>(14)? != 0,
+                capabilities: row.get(15)?,
+                content_type: row.get::<_, Option<String>>(16)?.and_then(|s| s.parse().ok()),
+                decompressed: row.get::<_, Option<String>>(17)?.and_then(|s| s.parse().ok()),
+                is_dir: row.get::<_, i32>(20)? != 0,
+                unstable: row.get::<_, i32>(21)? != 0,
+                sha512: row.get(22)?,
+                sha3_256: row.get(23)?,
+                link_target: row.get::<_, Option<String>>(24)?.map(PathBuf::from),
+                xxh3: row.get(25)?,
+                sampled: row.get::<_, i32>(26)? != 0,
+                owner_sid: row.get(27)?,
+                dacl: row.get(28)?,
+                xattr_hash: row.get(29)?,
+                mtime_nsec: row.get(30)?,
+                change_time_ns: row.get(31)?,
+            },
+            created_at: DateTime::from_timestamp(row.get::<_, i64>(18)?, 0),
+            updated_at: DateTime::from_timestamp(row.get::<_, i64>(19)?, 0),
+        })
+    }
+
+    /// Append a detected change to the audit log
+    ///
+    /// Each row stores the previous row's hash and its own hash over both, so
+    /// editing or deleting logged changes breaks [`FimDb::verify_change_log`].
+    /// Each profile has its own log.
+    pub fn record_change(&self, change: &FileChange) -> Result<i32> {
+        let path = change.path.to_string_lossy();
+        let change_type = change.change_type.name();
+        let detected_at = change.detected_at.timestamp();
+        let data = serde_json::to_string(change)?;
+
+        let prev_hash = self.change_log_head()?;
+        let hash = change_row_hash(&prev_hash, &path, change_type, detected_at, &data);
+        self.conn.execute(
+            "INSERT INTO changes (profile, path, change_type, detected_at, data, prev_hash, hash, change_id) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![self.profile, path, change_type, detected_at, data, prev_hash, hash, change.change_id],
+        )?;
+        self.set_profile_chain_value("change_log_head", &hash)?;
+
+        Ok(FIMDB_OK)
+    }
+
+    /// Check the change log hash chain from the first row to the recorded head
+    pub fn verify_change_log(&self) -> Result<ChangeLogVerification> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, change_type, detected_at, data, prev_hash, hash FROM changes \
+             WHERE profile = ?1 ORDER BY id"
+        )?;
+        let mut rows = stmt.query([&self.profile])?;
+
+        let mut verification = ChangeLogVerification::default();
+        let mut expected_prev = CHAIN_GENESIS.to_string();
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let prev_hash: Option<String> = row.get(5)?;
+            let hash: Option<String> = row.get(6)?;
+            let computed = change_row_hash(
+                &expected_prev,
+                &row.get::<_, String>(1)?,
+                &row.get::<_, String>(2)?,
+                row.get(3)?,
+                &row.get::<_, String>(4)?,
+            );
+
+            verification.rows_checked += 1;
+            if prev_hash.as_deref() != Some(expected_prev.as_str()) || hash.as_deref() != Some(computed.as_str()) {
+                verification.first_break = Some(id);
+                return Ok(verification);
+            }
+            expected_prev = computed;
+        }
+
+        // Rows removed from the end leave an intact but shorter chain
+        verification.head_matches = expected_prev == self.change_log_head()?;
+        Ok(verification)
+    }
+
+    /// Hash of the profile's newest change log row, or the genesis value when empty
+    fn change_log_head(&self) -> Result<String> {
+        Ok(self.profile_chain_value("change_log_head")?
+            .unwrap_or_else(|| CHAIN_GENESIS.to_string()))
+    }
+
+    /// Chain change log rows that have no hash yet, in id order
+    ///
+    /// Runs before the log was split by profile, so the head is the one in
+    /// `sync_info` that [`FimDb::migrate_change_log_profiles`] later moves.
+    fn chain_unhashed_changes(&self) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, change_type, detected_at, data FROM changes WHERE hash IS NULL ORDER BY id"
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?.collect::<Result<Vec<_>, _>>()?;
+
+        let head: Option<String> = self.conn.query_row(
+            "SELECT change_log_head FROM sync_info WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut prev_hash = head.unwrap_or_else(|| CHAIN_GENESIS.to_string());
+        for (id, path, change_type, detected_at, data) in &rows {
+            let hash = change_row_hash(&prev_hash, path, change_type, *detected_at, data);
+            self.conn.execute(
+                "UPDATE changes SET prev_hash = ?1, hash = ?2 WHERE id = ?3",
+                params![prev_hash, hash, id],
+            )?;
+            prev_hash = hash;
+        }
+        if !rows.is_empty() {
+            info!("Chained {} existing change log rows", rows.len());
+            self.conn.execute(
+                "UPDATE sync_info SET change_log_head = ?1 WHERE id = 1",
+                [&prev_hash],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Get logged changes detected within `[since, until]`, oldest first
+    pub fn get_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileChange>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT data FROM changes
+            WHERE profile = ?1 AND detected_at >= ?2 AND detected_at <= ?3
+            ORDER BY detected_at, id
+            "#
+        )?;
+
+        let rows = stmt.query_map(
+            params![
+                self.profile,
+                since.map(|t| t.timestamp()).unwrap_or(i64::MIN),
+                until.map(|t| t.timestamp()).unwrap_or(i64::MAX),
+            ],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        let mut changes = Vec::new();
+        for data in rows {
+            let change = self.change_from_json(&data?)?;
+            // Stored times are whole seconds; apply the exact window
+            if since.is_none_or(|t| change.detected_at >= t) && until.is_none_or(|t| change.detected_at <= t) {
+                changes.push(change);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Up to `limit` most recent previous states of a path, oldest first
+    ///
+    /// Each state is paired with when it was overwritten or removed.
+    pub fn get_history(&self, path: &Path, limit: usize) -> Result<Vec<(DateTime<Utc>, FimEntryData)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT changed_at, data FROM file_history WHERE profile = ?1 AND path = ?2 ORDER BY id DESC LIMIT ?3"
+        )?;
+
+        let rows = stmt.query_map(
+            params![self.profile, path.to_string_lossy(), i64::try_from(limit).unwrap_or(i64::MAX)],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )?;
+
+        let mut history = Vec::new();
+        for row in rows {
+            let (changed_at, data) = row?;
+            let data: FimEntryData = serde_json::from_str(&data)
+                .context("Corrupt file history record")?;
+            history.push((DateTime::from_timestamp(changed_at, 0).unwrap_or_default(), data));
+        }
+        history.reverse();
+
+        Ok(history)
+    }
+
+    /// Append the last recorded state of `path` to the profile's history
+    /// chain, pruning the oldest states beyond the history limit
+    fn append_history(&self, path: &str, data: &FimEntryData) -> Result<()> {
+        let changed_at = Utc::now().timestamp();
+        let data = serde_json::to_string(data)?;
+        let prev_hash = self.profile_chain_value("history_head")?
+            .unwrap_or_else(|| CHAIN_GENESIS.to_string());
+        let hash = history_row_hash(&prev_hash, &self.profile, path, changed_at, &data);
+        self.conn.execute(
+            "INSERT INTO file_history (profile, path, changed_at, data, prev_hash, hash) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![self.profile, path, changed_at, data, prev_hash, hash],
+        )?;
+        self.set_profile_chain_value("history_head", &hash)?;
+
+        // Pruning only ever removes the start of the chain; the new first
+        // row's predecessor becomes the base verification starts from
+        let oldest_kept: Option<(i64, String)> = self.conn.query_row(
+            "SELECT id, prev_hash FROM file_history WHERE profile = ?1 ORDER BY id DESC LIMIT 1 OFFSET ?2",
+            params![self.profile, i64::try_from(self.history_limit.max(1) - 1).unwrap_or(i64::MAX)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        if let Some((id, base)) = oldest_kept {
+            let pruned = self.conn.execute(
+                "DELETE FROM file_history WHERE profile = ?1 AND id < ?2",
+                params![self.profile, id],
+            )?;
+            if pruned > 0 {
+                debug!("Pruned {} file history rows", pruned);
+                self.set_profile_chain_value("history_base", &base)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the profile's file history hash chain from its base to its head
+    ///
+    /// States pruned by the history limit move the base along, so only
+    /// edits, removals and insertions are reported.
+    pub fn verify_history(&self) -> Result<ChangeLogVerification> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, path, changed_at, data, prev_hash, hash FROM file_history WHERE profile = ?1 ORDER BY id"
+        )?;
+        let mut rows = stmt.query([&self.profile])?;
+
+        let mut verification = ChangeLogVerification::default();
+        let mut expected_prev = self.profile_chain_value("history_base")?
+            .unwrap_or_else(|| CHAIN_GENESIS.to_string());
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let prev_hash: Option<String> = row.get(4)?;
+            let hash: Option<String> = row.get(5)?;
+            let computed = history_row_hash(
+                &expected_prev,
+                &self.profile,
+                &row.get::<_, String>(1)?,
+                row.get(2)?,
+                &row.get::<_, String>(3)?,
+            );
+
+            verification.rows_checked += 1;
+            if prev_hash.as_deref() != Some(expected_prev.as_str()) || hash.as_deref() != Some(computed.as_str()) {
+                verification.first_break = Some(id);
+                return Ok(verification);
+            }
+            expected_prev = computed;
+        }
+
+        verification.head_matches = expected_prev == self.profile_chain_value("history_head")?
+            .unwrap_or_else(|| CHAIN_GENESIS.to_string());
+        Ok(verification)
+    }
+
+    /// A hash chain column of the profile's `profile_info` row
+    fn profile_chain_value(&self, column: &str) -> Result<Option<String>> {
+        let value = self.conn.query_row(
+            &format!("SELECT {} FROM profile_info WHERE profile = ?1", column),
+            [&self.profile],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        Ok(value)
+    }
+
+    fn set_profile_chain_value(&self, column: &str, hash: &str) -> Result<()> {
+        self.conn.execute(
+            &format!(
+                "INSERT INTO profile_info (profile, {column}) VALUES (?1, ?2) \
+                 ON CONFLICT(profile) DO UPDATE SET {column} = excluded.{column}",
+                column = column,
+            ),
+            params![self.profile, hash],
+        )?;
+        Ok(())
+    }
+
+    /// Recorded change with the given [`FileChange::change_id`], e.g. from an alert id
+    pub fn get_change(&self, change_id: &str) -> Result<Option<FileChange>> {
+        let data: Option<String> = self.conn
+            .query_row(
+                "SELECT data FROM changes WHERE profile = ?1 AND change_id = ?2",
+                params![self.profile, change_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        data.map(|data| self.change_from_json(&data)).transpose()
+    }
+
+    /// Decode a change log row; rows recorded before changes carried a
+    /// severity are classified with the configured rules
+    fn change_from_json(&self, data: &str) -> Result<FileChange> {
+        let value: serde_json::Value = serde_json::from_str(data).context("Corrupt change record")?;
+        let unclassified = value.get("severity").is_none();
+        let mut change: FileChange = serde_json::from_value(value).context("Corrupt change record")?;
+        if unclassified {
+            self.severity_rules.classify(&mut change);
+        }
+        Ok(change)
+    }
+
+    /// Check if inode exists
+    pub fn _get_inode(&self, inode: u64, dev: u64) -> Result<bool> {
+        let count: i32 = self.conn.query_row(
+            "SELECT COUNT(*) FROM file_data WHERE profile = ?1 AND inode = ?2 AND dev = ?3",
+            params![self.profile, inode, dev],
+            |row| row.get(0),
+        )?;
+        
+        Ok(count > 0)
+    }
+
+    /// Get all paths for a given inode
+    pub fn get_paths_from_inode(&self, inode: u64, dev: u64) -> Result<Vec<PathBuf>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path FROM file_data WHERE profile = ?1 AND inode = ?2 AND dev = ?3"
+        )?;
+        
+        let paths = stmt.query_map(params![self.profile, inode, dev], |row| {
+            row.get::<_, String>(0).map(PathBuf::from)
+        })?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(paths)
     }
 
+    /// Move the entries at and below `from` to the same place under `to`,
+    /// returning each moved `(old, new)` path pair
+    ///
+    /// Entries already recorded at a destination are replaced, leaving their
+    /// last state in its history. Recorded data and first-seen times move
+    /// along unchanged; file history stays with the old path, as its chain
+    /// covers the paths.
+    pub fn rename_path(&mut self, from: &Path, to: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let old_paths = {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT path FROM file_data WHERE {} ORDER BY path",
+                SUBTREE_FILTER
+            ))?;
+            let rows = stmt.query_map(subtree_params(&self.profile, from), |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut moved = Vec::with_capacity(old_paths.len());
+        for old in old_paths {
+            let old = PathBuf::from(old);
+            let new = match old.strip_prefix(from) {
+                Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                _ => to.to_path_buf(),
+            };
+            let (old_str, new_str) = (old.to_string_lossy(), new.to_string_lossy());
+
+            self.delete_rows("profile = ?1 AND path = ?2", params![self.profile, new_str])?;
+            let mut delta = self.digest_rows("profile = ?1 AND path = ?2", params![self.profile, old_str])?;
+            self.conn.execute(
+                "UPDATE file_data SET path = ?3 WHERE profile = ?1 AND path = ?2",
+                params![self.profile, old_str, new_str],
+            )?;
+            xor_into(
+                &mut delta,
+                &self.digest_rows("profile = ?1 AND path = ?2", params![self.profile, new_str])?,
+            );
+            self.apply_checksum_delta(delta)?;
+
+            moved.push((old, new));
+        }
+
+        debug!("Moved {} entries from {} to {}", moved.len(), from.display(), to.display());
+        Ok(moved)
+    }
+
     /// Insert or update file entry
+    ///
+    /// `created_at` is kept on update; `updated_at` only moves when recorded
+    /// data other than the access time and scan marker changes. Such changes
+    /// also copy the previous data to `file_history` unless disabled with
+    /// [`FimDb::set_record_history`].
     pub fn insert_data(&mut self, file_path: &Path, entry: &FimEntryData) -> Result<i32> {
         let path_str = file_path.to_string_lossy();
+        let new_digest = row_digest(&path_str, entry);
+        let mut delta = new_digest;
+        if let Some(old) = self.get_path(file_path)? {
+            let old_digest = row_digest(&path_str, &old.data);
+            xor_into(&mut delta, &old_digest);
+            if self.record_history && old_digest != new_digest {
+                self.append_history(&path_str, &old.data)?;
+            }
+        }
         
         self.conn.execute(
             r#"
-            INSERT OR REPLACE INTO file_data 
+            INSERT INTO file_data 
             (path, size, perm, uid, gid, md5, sha1, sha256, blake3,
-             mtime, ctime, atime, inode, dev, scanned, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, strftime('%s', 'now'))
+             mtime, ctime, atime, inode, dev, scanned, capabilities, content_type, decompressed,
+             is_dir, unstable, sha512, sha3_256, link_target, xxh3, sampled, owner_sid, dacl,
+             xattr_hash, mtime_nsec, change_time_ns, profile)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                    ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31)
+            ON CONFLICT(profile, path) DO UPDATE SET
+                updated_at = CASE WHEN
+                    size IS NOT excluded.size OR perm IS NOT excluded.perm
+                    OR uid IS NOT excluded.uid OR gid IS NOT excluded.gid
+                    OR md5 IS NOT excluded.md5 OR sha1 IS NOT excluded.sha1
+                    OR sha256 IS NOT excluded.sha256 OR blake3 IS NOT excluded.blake3
+                    OR mtime IS NOT excluded.mtime OR ctime IS NOT excluded.ctime
+                    OR inode IS NOT excluded.inode OR dev IS NOT excluded.dev
+                    OR capabilities IS NOT excluded.capabilities
+                    OR content_type IS NOT excluded.content_type
+                    OR decompressed IS NOT excluded.decompressed
+                    OR is_dir IS NOT excluded.is_dir OR unstable IS NOT excluded.unstable
+                    OR sha512 IS NOT excluded.sha512 OR sha3_256 IS NOT excluded.sha3_256
+                    OR link_target IS NOT excluded.link_target OR xxh3 IS NOT excluded.xxh3
+                    OR sampled IS NOT excluded.sampled OR owner_sid IS NOT excluded.owner_sid
+                    OR dacl IS NOT excluded.dacl OR xattr_hash IS NOT excluded.xattr_hash
+                THEN excluded.updated_at ELSE updated_at END,
+                size = excluded.size, perm = excluded.perm, uid = excluded.uid,
+                gid = excluded.gid, md5 = excluded.md5, sha1 = excluded.sha1,
+                sha256 = excluded.sha256, blake3 = excluded.blake3, mtime = excluded.mtime,
+                ctime = excluded.ctime, atime = excluded.atime, inode = excluded.inode,
+                dev = excluded.dev, scanned = excluded.scanned,
+                capabilities = excluded.capabilities, content_type = excluded.content_type,
+                decompressed = excluded.decompressed, is_dir = excluded.is_dir,
+                unstable = excluded.unstable, sha512 = excluded.sha512,
+                sha3_256 = excluded.sha3_256, link_target = excluded.link_target,
+                xxh3 = excluded.xxh3, sampled = excluded.sampled,
+                owner_sid = excluded.owner_sid, dacl = excluded.dacl,
+                xattr_hash = excluded.xattr_hash, mtime_nsec = excluded.mtime_nsec,
+                change_time_ns = excluded.change_time_ns
             "#,
             params![
                 path_str,
@@ -295,8 +1614,25 @@ impl FimDb {
                 entry.inode,
                 entry.dev,
                 entry.scanned as i32,
+                entry.capabilities,
+                entry.content_type.map(|class| class.as_str()),
+                entry.decompressed.map(|compression| compression.as_str()),
+                entry.is_dir as i32,
+                entry.unstable as i32,
+                entry.sha512,
+                entry.sha3_256,
+                entry.link_target.as_ref().map(|target| target.to_string_lossy().into_owned()),
+                entry.xxh3,
+                entry.sampled as i32,
+                entry.owner_sid,
+                entry.dacl,
+                entry.xattr_hash,
+                entry.mtime_nsec,
+                entry.change_time_ns,
+                self.profile,
             ],
         )?;
+        self.apply_checksum_delta(delta)?;
         
         Ok(FIMDB_OK)
     }
@@ -304,21 +1640,33 @@ impl FimDb {
     /// Remove path from database
     pub fn remove_path(&mut self, file_path: &Path) -> Result<i32> {
         let path_str = file_path.to_string_lossy();
-        let deleted = self.conn.execute(
-            "DELETE FROM file_data WHERE path = ?1",
-            [&path_str],
-        )?;
+        let deleted = self.delete_rows("profile = ?1 AND path = ?2", params![self.profile, path_str])?;
         
         debug!("Removed {} entries for path: {}", deleted, path_str);
         Ok(FIMDB_OK)
     }
 
+    /// Remove the entries for several paths with one statement and one
+    /// checksum update, returning how many were removed
+    pub fn remove_paths(&mut self, paths: &[PathBuf]) -> Result<i32> {
+        if paths.is_empty() {
+            return Ok(0);
+        }
+        let paths = serde_json::to_string(
+            &paths.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>()
+        )?;
+        let deleted = self.delete_rows(
+            "profile = ?1 AND path IN (SELECT value FROM json_each(?2))",
+            params![self.profile, paths],
+        )?;
+
+        debug!("Removed {} entries", deleted);
+        Ok(deleted as i32)
+    }
+
     /// Delete unscanned entries
     pub fn delete_not_scanned(&mut self) -> Result<i32> {
-        let deleted = self.conn.execute(
-            "DELETE FROM file_data WHERE scanned = 0",
-            [],
-        )?;
+        let deleted = self.delete_rows("profile = ?1 AND scanned = 0", [&self.profile])?;
         
         info!("Deleted {} unscanned entries", deleted);
         Ok(deleted as i32)
@@ -326,20 +1674,41 @@ impl FimDb {
 
     /// Delete entries in path range (alphabetically sorted)
     pub fn _delete_range(&mut self, start: &str, top: &str) -> Result<i32> {
-        let deleted = self.conn.execute(
-            "DELETE FROM file_data WHERE path >= ?1 AND path <= ?2",
-            params![start, top],
+        let deleted = self.delete_rows(
+            "profile = ?1 AND path >= ?2 AND path <= ?3",
+            params![self.profile, start, top],
         )?;
         
         debug!("Deleted {} entries in range {} to {}", deleted, start, top);
         Ok(deleted as i32)
     }
 
+    /// Delete the `file_data` rows matching `filter`, updating the checksum
+    /// and recording each row's last state in `file_history`
+    fn delete_rows<P: rusqlite::Params + Copy>(&self, filter: &str, params: P) -> Result<usize> {
+        let mut delta = [0u8; 32];
+        let mut stmt = self.conn.prepare_cached(
+            &format!("SELECT {} FROM file_data WHERE {} ORDER BY path", ENTRY_COLUMNS, filter)
+        )?;
+        for entry in stmt.query_map(params, Self::entry_from_row)? {
+            let entry = entry?;
+            let path = entry.path.to_string_lossy();
+            xor_into(&mut delta, &row_digest(&path, &entry.data));
+            if self.record_history {
+                self.append_history(&path, &entry.data)?;
+            }
+        }
+
+        let deleted = self.conn.execute(&format!("DELETE FROM file_data WHERE {}", filter), params)?;
+        self.apply_checksum_delta(delta)?;
+        Ok(deleted)
+    }
+
     /// Get count of entries in range
     pub fn _get_count_range(&self, start: &str, top: &str) -> Result<i32> {
         let count: i32 = self.conn.query_row(
-            "SELECT COUNT(*) FROM file_data WHERE path >= ?1 AND path <= ?2",
-            params![start, top],
+            "SELECT COUNT(*) FROM file_data WHERE profile = ?1 AND path >= ?2 AND path <= ?3",
+            params![self.profile, start, top],
             |row| row.get(0),
         )?;
         
@@ -349,12 +1718,12 @@ impl FimDb {
     /// Get first or last row path
     pub fn _get_row_path(&self, mode: _RowMode) -> Result<Option<String>> {
         let sql = match mode {
-            _RowMode::First => "SELECT path FROM file_data ORDER BY path ASC LIMIT 1",
-            _RowMode::Last => "SELECT path FROM file_data ORDER BY path DESC LIMIT 1",
+            _RowMode::First => "SELECT path FROM file_data WHERE profile = ?1 ORDER BY path ASC LIMIT 1",
+            _RowMode::Last => "SELECT path FROM file_data WHERE profile = ?1 ORDER BY path DESC LIMIT 1",
         };
         
-        let path = self.conn.query_row(sql, [], |row| {
-            Ok(row.get::<_, String>(0)?)
+        let path = self.conn.query_row(sql, [&self.profile], |row| {
+            row.get::<_, String>(0)
         }).optional()?;
         
         Ok(path)
@@ -365,11 +1734,11 @@ impl FimDb {
         let mut hasher = blake3::Hasher::new();
         
         let mut stmt = self.conn.prepare(
-            "SELECT blake3 FROM file_data ORDER BY path"
+            "SELECT blake3 FROM file_data WHERE profile = ?1 ORDER BY path"
         )?;
         
-        let hashes = stmt.query_map([], |row| {
-            Ok(row.get::<_, String>(0)?)
+        let hashes = stmt.query_map([&self.profile], |row| {
+            row.get::<_, String>(0)
         })?;
         
         for hash_result in hashes {
@@ -380,17 +1749,262 @@ impl FimDb {
         Ok(hasher.finalize().to_hex().to_string())
     }
 
+    /// Run `read` in one deferred transaction, so its queries all see the
+    /// same state even while another connection commits
+    fn consistent_read<T>(&self, read: impl FnOnce() -> Result<T>) -> Result<T> {
+        if !self.conn.is_autocommit() {
+            // Our own open transaction already gives one view
+            return read();
+        }
+        let transaction = self.conn.unchecked_transaction()?;
+        let result = read()?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    /// Write every entry as a JSON [`FullSnapshot`], returning the entry count
+    ///
+    /// Rows are serialized one at a time as they are read, so memory use does
+    /// not grow with the size of the baseline. Rows and checksum are read in
+    /// one transaction, so they describe the same state.
+    pub fn export_json<W: Write>(&self, writer: W) -> Result<usize> {
+        self.consistent_read(|| self.write_json(writer))
+    }
+
+    fn write_json<W: Write>(&self, mut writer: W) -> Result<usize> {
+        let mut stmt = self.conn.prepare(
+            &format!("SELECT {} FROM file_data WHERE profile = ?1 ORDER BY path", ENTRY_COLUMNS)
+        )?;
+
+        writer.write_all(b"{\"entries\":[")?;
+        let mut count = 0;
+        let mut digest = [0u8; 32];
+        for entry in stmt.query_map([&self.profile], Self::entry_from_row)? {
+            let entry = entry?;
+            if count > 0 {
+                writer.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut writer, &entry)?;
+            xor_into(&mut digest, &row_digest(&entry.path.to_string_lossy(), &entry.data));
+            count += 1;
+        }
+        writer.write_all(b"],\"checksum\":")?;
+        serde_json::to_writer(&mut writer, &self.get_data_checksum()?)?;
+        writer.write_all(b",\"digest\":")?;
+        serde_json::to_writer(&mut writer, &hex::encode(digest))?;
+        writer.write_all(b"}")?;
+        writer.flush()?;
+
+        Ok(count)
+    }
+
+    /// Capture every entry along with the data checksum and digest
+    pub fn snapshot(&self) -> Result<FullSnapshot> {
+        self.consistent_read(|| {
+            let entries = self.get_all_entries()?;
+            Ok(FullSnapshot {
+                digest: entries_digest(&entries),
+                entries,
+                checksum: self.get_data_checksum()?,
+            })
+        })
+    }
+
+    /// Replace all entries with the contents of a snapshot
+    ///
+    /// The snapshot is checked against its recorded checksum and digest
+    /// before anything is written. The restored rows are checked against the
+    /// snapshot before the change is committed; on a mismatch it is rolled
+    /// back and the database keeps its previous entries.
+    pub fn restore_snapshot(&mut self, snapshot: &FullSnapshot) -> Result<usize> {
+        snapshot.validate()?;
+
+        let pending = self.pending_checksum.get();
+        self.conn.execute_batch("SAVEPOINT restore_snapshot")?;
+        let result = (|| -> Result<()> {
+            let delta = self.digest_rows("profile = ?1", [&self.profile])?;
+            self.conn.execute("DELETE FROM file_data WHERE profile = ?1", [&self.profile])?;
+            self.apply_checksum_delta(delta)?;
+            for entry in &snapshot.entries {
+                self.insert_data(&entry.path, &entry.data)?;
+            }
+
+            let restored = hex::encode(self.digest_rows("profile = ?1", [&self.profile])?);
+            let expected = snapshot.computed_digest();
+            if restored != expected || self.get_data_checksum()? != snapshot.checksum {
+                anyhow::bail!(
+                    "Restored entries (digest {}) do not match the snapshot (digest {})",
+                    restored,
+                    expected
+                );
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            let _ = self.conn.execute_batch("ROLLBACK TO restore_snapshot; RELEASE restore_snapshot");
+            self.pending_checksum.set(pending);
+            return Err(e);
+        }
+        self.conn.execute_batch("RELEASE restore_snapshot")?;
+
+        info!("Restored {} entries from snapshot", snapshot.entries.len());
+        Ok(snapshot.entries.len())
+    }
+
+    /// Check the database for corruption and tampering
+    ///
+    /// Runs SQLite's `PRAGMA integrity_check` and compares the running
+    /// checksum, which every API mutation maintains incrementally, against a
+    /// recomputation over all rows. Rows edited behind the API's back (e.g.
+    /// with the sqlite3 shell) make the two disagree.
+    pub fn integrity_check(&self) -> Result<DbIntegrityReport> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let sqlite_errors = stmt.query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|message| message != "ok")
+            .collect();
+
+        Ok(DbIntegrityReport {
+            sqlite_errors,
+            stored_checksum: self.stored_checksum()?.map(hex::encode),
+            computed_checksum: hex::encode(self.digest_rows("profile = ?1", [&self.profile])?),
+        })
+    }
+
+    /// Sign the current baseline with an Ed25519 secret key
+    ///
+    /// The signature covers the digest of every entry, so it should be made
+    /// right before the database is frozen with [`FimDb::open_read_only`].
+    pub fn sign_baseline(&self, secret_key: &[u8; 32]) -> Result<()> {
+        let signature = manifest::sign_payload(secret_key, &self.baseline_digest()?);
+        self.conn.execute(
+            "INSERT INTO profile_info (profile, baseline_signature) VALUES (?1, ?2) \
+             ON CONFLICT(profile) DO UPDATE SET baseline_signature = excluded.baseline_signature",
+            params![self.profile, signature],
+        )?;
+        Ok(())
+    }
+
+    /// Check the baseline signature against the entries as they are now
+    pub fn verify_baseline_signature(&self, public_key: &[u8; 32]) -> Result<()> {
+        let signature: Option<String> = self.conn.query_row(
+            "SELECT baseline_signature FROM profile_info WHERE profile = ?1",
+            [&self.profile],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        let signature = signature.ok_or_else(|| anyhow::anyhow!("Baseline is not signed"))?;
+        manifest::verify_payload(public_key, &self.baseline_digest()?, &signature)
+            .context("Baseline signature verification failed")
+    }
+
+    /// BLAKE3 over every row digest in path order, for signing
+    ///
+    /// Unlike the XOR of [`FimDb::digest_rows`], which rows added to cancel
+    /// out an edit leave unchanged, no set of rows can be crafted to keep it.
+    fn baseline_digest(&self) -> Result<[u8; 32]> {
+        let mut stmt = self.conn.prepare_cached(
+            &format!("SELECT {} FROM file_data WHERE profile = ?1 ORDER BY path", ENTRY_COLUMNS)
+        )?;
+        let mut hasher = blake3::Hasher::new();
+        for entry in stmt.query_map([&self.profile], Self::entry_from_row)? {
+            let entry = entry?;
+            hasher.update(&row_digest(&entry.path.to_string_lossy(), &entry.data));
+        }
+        Ok(*hasher.finalize().as_bytes())
+    }
+
+    /// XOR of the row digests of all `file_data` rows matching `filter`
+    fn digest_rows<P: rusqlite::Params>(&self, filter: &str, params: P) -> Result<[u8; 32]> {
+        let mut stmt = self.conn.prepare_cached(
+            &format!("SELECT {} FROM file_data WHERE {}", ENTRY_COLUMNS, filter)
+        )?;
+        let mut digest = [0u8; 32];
+        for entry in stmt.query_map(params, Self::entry_from_row)? {
+            let entry = entry?;
+            xor_into(&mut digest, &row_digest(&entry.path.to_string_lossy(), &entry.data));
+        }
+        Ok(digest)
+    }
+
+    /// The running checksum including changes not yet written by the open
+    /// transaction
+    fn stored_checksum(&self) -> Result<Option<[u8; 32]>> {
+        let pending = self.pending_checksum.get();
+        let persisted = self.persisted_checksum()?;
+        if pending == [0u8; 32] {
+            return Ok(persisted);
+        }
+        let mut checksum = persisted.unwrap_or_default();
+        xor_into(&mut checksum, &pending);
+        Ok(Some(checksum))
+    }
+
+    fn persisted_checksum(&self) -> Result<Option<[u8; 32]>> {
+        let stored: Option<String> = self.conn.query_row(
+            "SELECT running_checksum FROM profile_info WHERE profile = ?1",
+            [&self.profile],
+            |row| row.get(0),
+        ).optional()?.flatten();
+
+        stored
+            .map(|hex_value| {
+                hex::decode(&hex_value)
+                    .ok()
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .ok_or_else(|| anyhow::anyhow!("Malformed running checksum in profile_info"))
+            })
+            .transpose()
+    }
+
+    fn store_checksum(&self, checksum: [u8; 32]) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO profile_info (profile, running_checksum) VALUES (?1, ?2) \
+             ON CONFLICT(profile) DO UPDATE SET running_checksum = excluded.running_checksum",
+            params![self.profile, hex::encode(checksum)],
+        )?;
+        Ok(())
+    }
+
+    fn apply_checksum_delta(&self, delta: [u8; 32]) -> Result<()> {
+        if delta == [0u8; 32] {
+            return Ok(());
+        }
+        if self.transaction_count > 0 {
+            let mut pending = self.pending_checksum.get();
+            xor_into(&mut pending, &delta);
+            self.pending_checksum.set(pending);
+            return Ok(());
+        }
+        let mut checksum = self.persisted_checksum()?.unwrap_or_default();
+        xor_into(&mut checksum, &delta);
+        self.store_checksum(checksum)
+    }
+
+    /// Write the checksum changes accumulated by the open transaction
+    fn flush_checksum(&self) -> Result<()> {
+        let pending = self.pending_checksum.get();
+        if pending == [0u8; 32] {
+            return Ok(());
+        }
+        let mut checksum = self.persisted_checksum()?.unwrap_or_default();
+        xor_into(&mut checksum, &pending);
+        self.store_checksum(checksum)?;
+        self.pending_checksum.set([0u8; 32]);
+        Ok(())
+    }
+
     /// Get database statistics
     pub fn get_stats(&self) -> Result<FimStats> {
         let total_files: i32 = self.conn.query_row(
-            "SELECT COUNT(*) FROM file_data",
-            [],
+            "SELECT COUNT(*) FROM file_data WHERE profile = ?1",
+            [&self.profile],
             |row| row.get(0),
         )?;
         
         let scanned_files: i32 = self.conn.query_row(
-            "SELECT COUNT(*) FROM file_data WHERE scanned = 1",
-            [],
+            "SELECT COUNT(*) FROM file_data WHERE profile = ?1 AND scanned = 1",
+            [&self.profile],
             |row| row.get(0),
         )?;
         
@@ -402,63 +2016,421 @@ impl FimDb {
     }
 }
 
-/// Row selection mode
-#[derive(Debug, Clone)]
-pub enum _RowMode {
-    First,
-    Last,
+/// Unlock an SQLCipher database with the key derived from `passphrase`
+///
+/// SQLCipher only notices a wrong key when a page is first read, so one is
+/// read here to turn the resulting "file is not a database" into a clear error.
+#[cfg(feature = "encryption")]
+fn apply_encryption_key(conn: &Connection, passphrase: &str) -> Result<()> {
+    conn.pragma_update(None, "key", passphrase)
+        .context("Failed to set database encryption key")?;
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.sqlite_error_code() == Some(rusqlite::ErrorCode::NotADatabase) => Err(anyhow::anyhow!(
+            "Wrong database encryption key, or the database is not encrypted"
+        )),
+        Err(e) => Err(e).context("Failed to unlock encrypted database"),
+    }
 }
 
-/// Database statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FimStats {
-    pub total_files: i32,
-    pub scanned_files: i32,
-    pub unscanned_files: i32,
+#[cfg(not(feature = "encryption"))]
+fn apply_encryption_key(_conn: &Connection, _passphrase: &str) -> Result<()> {
+    anyhow::bail!("Database encryption needs rusty-fim built with the `encryption` feature")
 }
 
-impl Drop for FimDb {
-    fn drop(&mut self) {
-        // Ensure any pending transactions are committed
-        if self.transaction_count > 0 {
-            let _ = self.conn.execute("COMMIT", []);
-        }
+/// Digest of the integrity-relevant fields of one row
+///
+/// Timestamps are truncated to whole seconds, matching what is stored. The
+/// `scanned` flag and access time are bookkeeping and deliberately excluded.
+fn row_digest(path: &str, entry: &FimEntryData) -> [u8; 32] {
+    let mut canonical = format!(
+        "{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}\0{}",
+        path,
+        entry.size,
+        entry.perm,
+        entry.uid,
+        entry.gid,
+        entry.md5.as_deref().unwrap_or_default(),
+        entry.sha1.as_deref().unwrap_or_default(),
+        entry.sha256.as_deref().unwrap_or_default(),
+        entry.blake3,
+        entry.mtime.timestamp(),
+        entry.ctime.timestamp(),
+        entry.inode,
+        entry.dev,
+        entry.capabilities.as_deref().unwrap_or_default(),
+    );
+    // Appended only when known, so rows from before classification keep their digest
+    if let Some(class) = entry.content_type {
+        canonical.push('\0');
+        canonical.push_str(class.as_str());
+    }
+    if let Some(compression) = entry.decompressed {
+        canonical.push_str("\0decompressed:");
+        canonical.push_str(compression.as_str());
     }
+    if entry.is_dir {
+        canonical.push_str("\0dir");
+    }
+    if entry.unstable {
+        canonical.push_str("\0unstable");
+    }
+    if entry.sampled {
+        canonical.push_str("\0sampled");
+    }
+    if let Some(sha512) = &entry.sha512 {
+        canonical.push_str("\0sha512:");
+        canonical.push_str(sha512);
+    }
+    if let Some(sha3_256) = &entry.sha3_256 {
+        canonical.push_str("\0sha3_256:");
+        canonical.push_str(sha3_256);
+    }
+    if let Some(xxh3) = &entry.xxh3 {
+        canonical.push_str("\0xxh3:");
+        canonical.push_str(xxh3);
+    }
+    if let Some(owner_sid) = &entry.owner_sid {
+        canonical.push_str("\0owner:");
+        canonical.push_str(owner_sid);
+    }
+    if let Some(dacl) = &entry.dacl {
+        canonical.push_str("\0dacl:");
+        canonical.push_str(dacl);
+    }
+    if let Some(xattr_hash) = &entry.xattr_hash {
+        canonical.push_str("\0xattrs:");
+        canonical.push_str(xattr_hash);
+    }
+    if let Some(link_target) = &entry.link_target {
+        canonical.push_str("\0link:");
+        canonical.push_str(&link_target.to_string_lossy());
+    }
+    *blake3::hash(canonical.as_bytes()).as_bytes()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+/// `prev_hash` of the first change log row
+const CHAIN_GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
-    #[test]
-    fn test_database_init() -> Result<()> {
-        let db = FimDb::init(true)?;
-        assert!(db.memory_mode);
-        Ok(())
+/// Hash of a change log row chained to its predecessor
+fn change_row_hash(prev_hash: &str, path: &str, change_type: &str, detected_at: i64, data: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for field in [prev_hash, path, change_type, &detected_at.to_string(), data] {
+        hasher.update(field.as_bytes());
+        hasher.update(b"\0");
     }
+    hasher.finalize().to_hex().to_string()
+}
 
-    #[test]
-    fn test_file_operations() -> Result<()> {
-        let mut db = FimDb::init(true)?;
-        let test_path = PathBuf::from("/test/file.txt");
-        
-        let entry_data = FimEntryData {
-            size: 1024,
-            perm: "644".to_string(),
-            uid: 1000,
-            gid: 1000,
-            md5: None,
-            sha1: None,
-            sha256: None,
-            blake3: "test_hash".to_string(),
+/// Hash of a file history row chained to its predecessor in the profile
+fn history_row_hash(prev_hash: &str, profile: &str, path: &str, changed_at: i64, data: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for field in [prev_hash, profile, path, &changed_at.to_string(), data] {
+        hasher.update(field.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Matches `root` itself and every path below it within a profile; bind with
+/// [`subtree_params`]
+const SUBTREE_FILTER: &str = "profile = ?1 AND (path = ?2 OR (path >= ?3 AND path < ?4))";
+
+/// Parameters for [`SUBTREE_FILTER`]: the profile, the root, `root/`, and the
+/// first string sorting after every `root/...` path
+fn subtree_params(profile: &str, root: &Path) -> [String; 4] {
+    let separator = std::path::MAIN_SEPARATOR;
+    let root = root.to_string_lossy();
+    let root = root.trim_end_matches(separator);
+    let after_separator = char::from_u32(separator as u32 + 1).unwrap_or(char::MAX);
+    [
+        profile.to_string(),
+        root.to_string(),
+        format!("{}{}", root, separator),
+        format!("{}{}", root, after_separator),
+    ]
+}
+
+fn xor_into(target: &mut [u8; 32], other: &[u8; 32]) {
+    for (byte, other_byte) in target.iter_mut().zip(other) {
+        *byte ^= other_byte;
+    }
+}
+
+/// Result of [`FimDb::integrity_check`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbIntegrityReport {
+    /// Problems reported by `PRAGMA integrity_check` (empty when healthy)
+    pub sqlite_errors: Vec<String>,
+    /// Running checksum maintained by the API
+    pub stored_checksum: Option<String>,
+    /// Checksum recomputed from the current rows
+    pub computed_checksum: String,
+}
+
+impl DbIntegrityReport {
+    /// True when SQLite reports no corruption and the checksums agree
+    pub fn is_ok(&self) -> bool {
+        self.sqlite_errors.is_empty() && self.checksum_matches()
+    }
+
+    /// True when the stored running checksum matches the recomputation
+    pub fn checksum_matches(&self) -> bool {
+        self.stored_checksum.as_deref() == Some(self.computed_checksum.as_str())
+    }
+}
+
+/// Every database entry plus the data checksum, enough to re-seed a database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullSnapshot {
+    pub entries: Vec<FimEntry>,
+    /// [`FimDb::get_data_checksum`] at the time the snapshot was taken
+    pub checksum: String,
+    /// Digest over every recorded field of every entry, as kept by the
+    /// running checksum; empty in snapshots taken before it was added
+    #[serde(default)]
+    pub digest: String,
+}
+
+impl FullSnapshot {
+    /// Data checksum of the entries, computed as [`FimDb::get_data_checksum`] would
+    pub fn computed_checksum(&self) -> String {
+        data_checksum(&self.entries)
+    }
+
+    /// Digest of the entries over every recorded field
+    pub fn computed_digest(&self) -> String {
+        entries_digest(&self.entries)
+    }
+
+    /// Fail when the entries do not match the recorded checksum or digest
+    pub fn validate(&self) -> Result<()> {
+        let expected = self.computed_checksum();
+        if expected != self.checksum {
+            anyhow::bail!(
+                "Snapshot checksum mismatch (recorded {}, computed {})",
+                self.checksum,
+                expected
+            );
+        }
+        let expected = self.computed_digest();
+        if !self.digest.is_empty() && expected != self.digest {
+            anyhow::bail!(
+                "Snapshot digest mismatch (recorded {}, computed {})",
+                self.digest,
+                expected
+            );
+        }
+        Ok(())
+    }
+
+    /// Write the snapshot to `path` in `format`
+    pub fn save(&self, path: &Path, format: SnapshotFormat) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        match format {
+            SnapshotFormat::Json => serde_json::to_writer(&mut writer, self)?,
+            SnapshotFormat::Binary => {
+                writer.write_all(SNAPSHOT_MAGIC)?;
+                writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+                bincode::serialize_into(&mut writer, self)?;
+            }
+        }
+        writer.flush()
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Read a snapshot written by [`FullSnapshot::save`]
+    pub fn load(path: &Path, format: SnapshotFormat) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes, format)
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))
+    }
+
+    /// Decode a snapshot held in memory
+    pub fn from_bytes(bytes: &[u8], format: SnapshotFormat) -> Result<Self> {
+        match format {
+            SnapshotFormat::Json => Ok(serde_json::from_slice(bytes)?),
+            SnapshotFormat::Binary => {
+                let payload = bytes.strip_prefix(SNAPSHOT_MAGIC.as_slice())
+                    .context("Not a binary FIM snapshot")?;
+                let (version, payload) = payload.split_first_chunk::<4>()
+                    .context("Truncated binary snapshot header")?;
+                let version = u32::from_le_bytes(*version);
+                if version != SNAPSHOT_VERSION {
+                    anyhow::bail!(
+                        "Unsupported binary snapshot version {} (expected {})",
+                        version,
+                        SNAPSHOT_VERSION
+                    );
+                }
+                Ok(bincode::deserialize(payload)?)
+            }
+        }
+    }
+}
+
+/// Leading bytes of a binary snapshot
+const SNAPSHOT_MAGIC: &[u8; 8] = b"FIMSNAP\0";
+
+/// Layout version of binary snapshots, bumped whenever [`FimEntry`] changes shape
+const SNAPSHOT_VERSION: u32 = 10;
+
+/// Encoding of a [`FullSnapshot`] file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// JSON, for interoperability with other tools
+    Json,
+    /// Versioned bincode, much faster to load for large baselines
+    Binary,
+}
+
+impl SnapshotFormat {
+    /// Format implied by a file name: `.bin` is binary, anything else JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("bin") => SnapshotFormat::Binary,
+            _ => SnapshotFormat::Json,
+        }
+    }
+}
+
+impl std::str::FromStr for SnapshotFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(SnapshotFormat::Json),
+            "binary" | "bin" => Ok(SnapshotFormat::Binary),
+            other => Err(anyhow::anyhow!("Unknown snapshot format: {}", other)),
+        }
+    }
+}
+
+/// [`FimDb::get_data_checksum`] over entries held in memory, in any order
+pub(crate) fn data_checksum<'a>(entries: impl IntoIterator<Item = &'a FimEntry>) -> String {
+    let mut entries: Vec<_> = entries.into_iter()
+        .map(|entry| (entry.path.to_string_lossy(), entry.data.blake3.as_str()))
+        .collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hasher = blake3::Hasher::new();
+    for (_, hash) in entries {
+        hasher.update(hash.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Hex XOR of the digests of `entries` over every recorded field, as
+/// [`FimDb::integrity_check`] computes it over rows
+pub(crate) fn entries_digest<'a>(entries: impl IntoIterator<Item = &'a FimEntry>) -> String {
+    let mut digest = [0u8; 32];
+    for entry in entries {
+        xor_into(&mut digest, &row_digest(&entry.path.to_string_lossy(), &entry.data));
+    }
+    hex::encode(digest)
+}
+
+/// Result of [`FimDb::verify_change_log`] and [`FimDb::verify_history`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeLogVerification {
+    /// Rows checked before the first break (or all rows)
+    pub rows_checked: u64,
+    /// Id of the first row that was edited or follows a removed row
+    pub first_break: Option<i64>,
+    /// Whether the last row is the recorded chain head; false when trailing rows were removed
+    pub head_matches: bool,
+}
+
+impl ChangeLogVerification {
+    /// True when the whole change log chain is intact
+    pub fn is_intact(&self) -> bool {
+        self.first_break.is_none() && self.head_matches
+    }
+}
+
+/// Row selection mode
+#[derive(Debug, Clone)]
+pub enum _RowMode {
+    First,
+    Last,
+}
+
+/// Database statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FimStats {
+    pub total_files: i32,
+    pub scanned_files: i32,
+    pub unscanned_files: i32,
+}
+
+impl Drop for FimDb {
+    fn drop(&mut self) {
+        // Ensure any pending transactions are committed
+        if self.transaction_count > 0 {
+            let _ = self.flush_checksum();
+            let _ = self.conn.execute("COMMIT", []);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fim::ChangeType;
+    use crate::reporting::{AlertSeverity, SeverityRules};
+    use std::fs;
+
+    /// A plain 1 KiB file entry; tests override the fields they care about
+    pub(crate) fn test_entry() -> FimEntryData {
+        FimEntryData {
+            size: 1024,
+            perm: "644".to_string(),
+            uid: 1000,
+            gid: 1000,
+            md5: None,
+            sha1: None,
+            sha256: None,
+            blake3: "test_hash".to_string(),
             mtime: Utc::now(),
             ctime: Utc::now(),
             atime: Utc::now(),
             inode: 12345,
             dev: 2049,
             scanned: true,
-        };
+            capabilities: None,
+            content_type: None,
+            decompressed: None,
+            is_dir: false,
+            unstable: false,
+            sha512: None,
+            sha3_256: None,
+            xxh3: None,
+            sampled: false,
+            owner_sid: None,
+            dacl: None,
+            xattr_hash: None,
+            link_target: None,
+            mtime_nsec: None,
+            change_time_ns: None,
+        }
+    }
+
+    #[test]
+    fn test_database_init() -> Result<()> {
+        let db = FimDb::init(true)?;
+        assert!(db.memory_mode);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_operations() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let test_path = PathBuf::from("/test/file.txt");
+        
+        let entry_data = test_entry();
         
         // Insert entry
         db.insert_data(&test_path, &entry_data)?;
@@ -474,4 +2446,902 @@ mod tests {
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_wal_checkpoint_during_batches() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+        let mut db = FimDb::open(Some(&db_path), &DbOptions::default())?;
+        // Simulate automatic checkpoints not keeping up
+        db.conn.pragma_update(None, "wal_autocheckpoint", 0)?;
+        let wal_path = FimDb::companion_files(&db_path)[1].clone();
+
+        let entry_data = FimEntryData {
+            blake3: "test_hash".repeat(8),
+            ..test_entry()
+        };
+
+        db.begin_transaction()?;
+        let mut wal_sizes = Vec::new();
+        for batch in 0..5 {
+            for i in 0..500 {
+                db.insert_data(Path::new(&format!("/batch{}/file{}", batch, i)), &entry_data)?;
+            }
+            db.force_commit_with_checkpoint();
+            wal_sizes.push(std::fs::metadata(&wal_path)?.len());
+        }
+        db.commit_transaction()?;
+
+        assert_eq!(db.checkpoint_count(), 5);
+        // Checkpointed frames are reused, so the WAL is bounded by one batch
+        assert!(wal_sizes[4] < wal_sizes[0] * 2, "WAL grew: {:?}", wal_sizes);
+
+        let (log_frames, checkpointed) = db.wal_checkpoint()?;
+        assert_eq!(log_frames, checkpointed);
+        assert_eq!(db.get_stats()?.total_files, 2500);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_log_window() -> Result<()> {
+        let db = FimDb::init(true)?;
+        let now = Utc::now();
+        for (name, hours_ago) in [("old", 48), ("recent", 2), ("latest", 0)] {
+            db.record_change(&FileChange {
+                path: PathBuf::from(format!("/test/{}", name)),
+                change_type: ChangeType::Added,
+                old_entry: None,
+                new_entry: None,
+                detected_at: now - chrono::Duration::hours(hours_ago),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            })?;
+        }
+
+        let all = db.get_changes(None, None)?;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].path, PathBuf::from("/test/old"));
+
+        let window = db.get_changes(Some(now - chrono::Duration::hours(24)), Some(now - chrono::Duration::hours(1)))?;
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].path, PathBuf::from("/test/recent"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_logged_without_severity_are_classified() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let change = FileChange {
+            path: PathBuf::from("/etc/passwd"),
+            change_type: ChangeType::Deleted,
+            old_entry: None,
+            new_entry: None,
+            detected_at: Utc::now(),
+            severity: AlertSeverity::Critical,
+            is_critical: true,
+            change_id: FileChange::new_id(),
+        };
+        db.record_change(&change)?;
+
+        // As written before changes carried their severity
+        let mut old_row = serde_json::to_value(&change)?;
+        let fields = old_row.as_object_mut().unwrap();
+        fields.remove("severity");
+        fields.remove("is_critical");
+        db.conn.execute("UPDATE changes SET data = ?1", [old_row.to_string()])?;
+
+        let logged = db.get_change(&change.change_id)?.unwrap();
+        assert_eq!((logged.severity, logged.is_critical), (AlertSeverity::Critical, true));
+
+        let mut rules = SeverityRules::default();
+        rules.set_severity(ChangeType::Deleted, AlertSeverity::Warning);
+        rules.critical_paths.clear();
+        rules.critical_change_types.clear();
+        db.set_severity_rules(rules.compile()?);
+        let logged = db.get_changes(None, None)?;
+        assert_eq!((logged[0].severity, logged[0].is_critical), (AlertSeverity::Warning, false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_check_detects_tampering() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let entry_data = FimEntryData {
+            perm: "755".to_string(),
+            uid: 0,
+            gid: 0,
+            blake3: "good_hash".to_string(),
+            inode: 1,
+            dev: 1,
+            ..test_entry()
+        };
+
+        db.insert_data(Path::new("/usr/bin/sudo"), &entry_data)?;
+        db.insert_data(Path::new("/usr/bin/ls"), &entry_data)?;
+        db.insert_data(Path::new("/usr/bin/ls"), &FimEntryData { size: 2048, ..entry_data.clone() })?;
+        db.insert_data(Path::new("/usr/bin/tmp"), &entry_data)?;
+        db.remove_path(Path::new("/usr/bin/tmp"))?;
+        assert!(db.integrity_check()?.is_ok());
+
+        // Tamper with a hash directly, bypassing the API
+        db.conn.execute(
+            "UPDATE file_data SET blake3 = 'attacker_hash' WHERE path = '/usr/bin/sudo'",
+            [],
+        )?;
+
+        let report = db.integrity_check()?;
+        assert!(report.sqlite_errors.is_empty());
+        assert!(!report.checksum_matches());
+        assert!(!report.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_written_once_per_transaction() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let before = db.persisted_checksum()?;
+
+        db.begin_transaction()?;
+        db.insert_data(Path::new("/etc/passwd"), &test_entry())?;
+        db.insert_data(Path::new("/etc/shadow"), &test_entry())?;
+        db.insert_data(Path::new("/etc/shadow"), &FimEntryData { size: 2048, ..test_entry() })?;
+        assert_eq!(db.persisted_checksum()?, before);
+        assert!(db.integrity_check()?.is_ok());
+        db.commit_transaction()?;
+
+        let computed = db.digest_rows("profile = ?1", [&db.profile])?;
+        assert_eq!(db.persisted_checksum()?, Some(computed));
+        assert!(db.integrity_check()?.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_paths() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        for path in ["/etc/passwd", "/etc/shadow", "/etc/hosts"] {
+            db.insert_data(Path::new(path), &test_entry())?;
+        }
+
+        let removed = db.remove_paths(&[
+            PathBuf::from("/etc/passwd"),
+            PathBuf::from("/etc/hosts"),
+            PathBuf::from("/etc/unknown"),
+        ])?;
+        assert_eq!(removed, 2);
+        assert_eq!(db.get_all_entries()?.len(), 1);
+        assert!(db.get_path(Path::new("/etc/shadow"))?.is_some());
+        assert_eq!(db.get_history(Path::new("/etc/hosts"), 10)?.len(), 1);
+        assert!(db.integrity_check()?.is_ok());
+        assert_eq!(db.remove_paths(&[])?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_baseline_signature_resists_compensating_rows() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        db.insert_data(Path::new("/usr/bin/sudo"), &FimEntryData { blake3: "good".to_string(), ..test_entry() })?;
+        db.insert_data(Path::new("/usr/bin/ls"), &test_entry())?;
+        let secret = [7u8; 32];
+        let public = ed25519_dalek::SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+        db.sign_baseline(&secret)?;
+        db.verify_baseline_signature(&public)?;
+        let signed_xor = db.digest_rows("profile = ?1", [&db.profile])?;
+
+        // Swap in a backdoored binary, then find rows whose digests XOR to
+        // the same difference by elimination over GF(2)
+        let bit = |digest: &[u8; 32], k: usize| digest[k / 8] >> (k % 8) & 1 == 1;
+        let stored = db.get_path(Path::new("/usr/bin/sudo"))?.unwrap().data;
+        let evil = FimEntryData { blake3: "evil".to_string(), ..stored.clone() };
+        let mut target = row_digest("/usr/bin/sudo", &stored);
+        xor_into(&mut target, &row_digest("/usr/bin/sudo", &evil));
+        let filler = db.get_path(Path::new("/usr/bin/ls"))?.unwrap().data;
+
+        let candidates: Vec<_> = (0..400).map(|i| format!("/var/forged/{}", i)).collect();
+        let mut basis: Vec<Option<([u8; 32], Vec<bool>)>> = vec![None; 256];
+        for (index, path) in candidates.iter().enumerate() {
+            let mut vector = row_digest(path, &filler);
+            let mut combination = vec![false; candidates.len()];
+            combination[index] = true;
+            for k in (0..256).rev() {
+                if !bit(&vector, k) {
+                    continue;
+                }
+                match &basis[k] {
+                    Some((pivot, used)) => {
+                        xor_into(&mut vector, pivot);
+                        combination.iter_mut().zip(used).for_each(|(a, b)| *a ^= b);
+                    }
+                    None => {
+                        basis[k] = Some((vector, combination));
+                        break;
+                    }
+                }
+            }
+        }
+        let mut chosen = vec![false; candidates.len()];
+        for k in (0..256).rev() {
+            if bit(&target, k) {
+                let (pivot, used) = basis[k].as_ref().expect("candidates span every bit");
+                xor_into(&mut target, pivot);
+                chosen.iter_mut().zip(used).for_each(|(a, b)| *a ^= b);
+            }
+        }
+        assert_eq!(target, [0u8; 32]);
+
+        db.insert_data(Path::new("/usr/bin/sudo"), &evil)?;
+        for (path, _) in candidates.iter().zip(&chosen).filter(|(_, &chosen)| chosen) {
+            db.insert_data(Path::new(path), &filler)?;
+        }
+
+        // The XOR digest is fooled; the signature is not
+        assert_eq!(db.digest_rows("profile = ?1", [&db.profile])?, signed_xor);
+        assert!(db.verify_baseline_signature(&public).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_diff() {
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let old = FimEntryData {
+            size: 10,
+            uid: 0,
+            gid: 0,
+            blake3: "aaa".to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+        let new = FimEntryData {
+            perm: "600".to_string(),
+            uid: 1000,
+            capabilities: Some("cap_net_raw+ep".to_string()),
+            // Bookkeeping fields are ignored
+            atime: Utc::now(),
+            scanned: false,
+            ..old.clone()
+        };
+
+        let change = |field: &str, old: &str, new: &str| FieldChange {
+            field: field.to_string(),
+            old: old.to_string(),
+            new: new.to_string(),
+        };
+        assert_eq!(old.diff(&new), vec![
+            change("perm", "644", "600"),
+            change("uid", "0", "1000"),
+            change("capabilities", "none", "cap_net_raw+ep"),
+        ]);
+        assert_eq!(old.diff(&new)[1].to_string(), "uid 0 -> 1000");
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn test_page_size_and_incremental_vacuum() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+        let options = DbOptions {
+            page_size: Some(16384),
+            auto_vacuum: AutoVacuum::Incremental,
+            ..Default::default()
+        };
+        let mut db = FimDb::open(Some(&db_path), &options)?;
+
+        let page_size: u32 = db.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        assert_eq!(page_size, 16384);
+        let auto_vacuum: i32 = db.conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+        assert_eq!(auto_vacuum, 2);
+
+        let entry_data = FimEntryData {
+            blake3: "test_hash".repeat(8),
+            ..test_entry()
+        };
+        for i in 0..2000 {
+            db.insert_data(Path::new(&format!("/churn/file{}", i)), &entry_data)?;
+        }
+        db.set_all_unscanned()?;
+        db.delete_not_scanned()?;
+
+        let free_pages: i64 = db.conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+        assert!(free_pages > 0);
+        assert_eq!(db.incremental_vacuum(0)?, 0);
+        drop(db);
+
+        // Layout is fixed once the database exists
+        let db = FimDb::open(Some(&db_path), &DbOptions { page_size: Some(4096), ..options.clone() })?;
+        let page_size: u32 = db.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        assert_eq!(page_size, 16384);
+
+        let invalid = DbOptions { page_size: Some(1000), ..options };
+        assert!(FimDb::open(None, &invalid).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "encryption"))]
+    fn test_encryption_key_needs_feature() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let options = DbOptions {
+            encryption_key: Some("correct horse".to_string()),
+            ..Default::default()
+        };
+        let Err(error) = FimDb::open(Some(&temp_dir.path().join(DEFAULT_DB_FILE)), &options) else {
+            panic!("opened an encrypted database without SQLCipher");
+        };
+        assert!(error.to_string().contains("`encryption` feature"));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_encrypted_database() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+        let keyed = |key: &str| DbOptions {
+            encryption_key: Some(key.to_string()),
+            ..Default::default()
+        };
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let entry = FimEntryData {
+            size: 10,
+            perm: "600".to_string(),
+            uid: 0,
+            gid: 0,
+            blake3: "aaa".to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+
+        let mut db = FimDb::open(Some(&db_path), &keyed("correct horse"))?;
+        db.insert_data(Path::new("/etc/shadow"), &entry)?;
+        drop(db);
+        let raw = std::fs::read(&db_path)?;
+        assert!(!raw.windows(b"/etc/shadow".len()).any(|window| window == b"/etc/shadow"));
+
+        let db = FimDb::open(Some(&db_path), &keyed("correct horse"))?;
+        assert!(db.get_path(Path::new("/etc/shadow"))?.is_some());
+        drop(db);
+        let db = FimDb::open_read_only_with(&db_path, &keyed("correct horse"))?;
+        assert_eq!(db.get_all_entries()?.len(), 1);
+        drop(db);
+
+        let Err(error) = FimDb::open(Some(&db_path), &keyed("battery staple")) else {
+            panic!("opened with the wrong key");
+        };
+        assert!(error.to_string().contains("Wrong database encryption key"), "{:#}", error);
+        assert!(FimDb::open(Some(&db_path), &DbOptions::default()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_log_hash_chain() -> Result<()> {
+        let db = FimDb::init(true)?;
+        let base = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for i in 0..4 {
+            db.record_change(&FileChange {
+                path: PathBuf::from(format!("/etc/file{}", i)),
+                change_type: ChangeType::HashChanged,
+                old_entry: None,
+                new_entry: None,
+                detected_at: base + chrono::Duration::seconds(i),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            })?;
+        }
+
+        let verification = db.verify_change_log()?;
+        assert!(verification.is_intact());
+        assert_eq!(verification.rows_checked, 4);
+
+        // Rewriting history breaks the chain at the edited row
+        db.conn.execute("UPDATE changes SET path = '/etc/innocent' WHERE id = 2", [])?;
+        let verification = db.verify_change_log()?;
+        assert!(!verification.is_intact());
+        assert_eq!(verification.first_break, Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_log_deletions_detected() -> Result<()> {
+        let db = FimDb::init(true)?;
+        for i in 0..3 {
+            db.record_change(&FileChange {
+                path: PathBuf::from(format!("/etc/file{}", i)),
+                change_type: ChangeType::Deleted,
+                old_entry: None,
+                new_entry: None,
+                detected_at: Utc::now(),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            })?;
+        }
+
+        db.conn.execute("DELETE FROM changes WHERE id = 3", [])?;
+        let verification = db.verify_change_log()?;
+        assert_eq!(verification.first_break, None);
+        assert!(!verification.head_matches);
+
+        db.conn.execute("DELETE FROM changes WHERE id = 1", [])?;
+        assert_eq!(db.verify_change_log()?.first_break, Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiles() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+        let profile = |name: &str| DbOptions {
+            profile: Some(name.to_string()),
+            ..Default::default()
+        };
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let entry = |hash: &str| FimEntryData {
+            size: 10,
+            uid: 0,
+            gid: 0,
+            blake3: hash.to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+        let hosts = Path::new("/etc/hosts");
+
+        let mut web = FimDb::open(Some(&db_path), &profile("web"))?;
+        let mut db = FimDb::open(Some(&db_path), &profile("db"))?;
+        web.insert_data(hosts, &entry("web_hash"))?;
+        web.insert_data(Path::new("/srv/www/index.html"), &entry("page"))?;
+        db.insert_data(hosts, &entry("db_hash"))?;
+
+        // The same path is recorded independently per profile
+        assert_eq!(web.get_path(hosts)?.unwrap().data.blake3, "web_hash");
+        assert_eq!(db.get_path(hosts)?.unwrap().data.blake3, "db_hash");
+        assert_eq!(web.get_stats()?.total_files, 2);
+        assert_eq!(db.get_stats()?.total_files, 1);
+        assert_ne!(web.get_data_checksum()?, db.get_data_checksum()?);
+
+        // A full scan of one profile leaves the other alone
+        db.set_all_unscanned()?;
+        assert_eq!(db.delete_not_scanned()?, 1);
+        assert_eq!(web.get_stats()?.total_files, 2);
+        assert!(web.integrity_check()?.is_ok());
+        assert!(db.integrity_check()?.is_ok());
+
+        assert_eq!(web.list_profiles()?, vec!["db".to_string(), "web".to_string()]);
+        assert_eq!(FimDb::open(Some(&db_path), &DbOptions::default())?.get_stats()?.total_files, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_v0_database() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+
+        // Tables as created before versioning and profiles, with paths unique
+        // on their own and an unchained change log
+        let conn = Connection::open(&db_path)?;
+        conn.execute_batch(
+            "CREATE TABLE file_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                size INTEGER NOT NULL, perm TEXT NOT NULL, uid INTEGER NOT NULL,
+                gid INTEGER NOT NULL, md5 TEXT, sha1 TEXT, sha256 TEXT, blake3 TEXT NOT NULL,
+                mtime INTEGER NOT NULL, ctime INTEGER NOT NULL, atime INTEGER NOT NULL,
+                inode INTEGER NOT NULL, dev INTEGER NOT NULL,
+                scanned INTEGER NOT NULL DEFAULT 1,
+                created_at INTEGER NOT NULL DEFAULT 0,
+                updated_at INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT INTO file_data (path, size, perm, uid, gid, blake3, mtime, ctime, atime, inode, dev)
+            VALUES ('/etc/hosts', 10, '644', 0, 0, 'legacy_hash', 0, 0, 0, 1, 2049);
+            CREATE TABLE sync_info (
+                id INTEGER PRIMARY KEY,
+                last_sync_id INTEGER NOT NULL DEFAULT 0,
+                total_files INTEGER NOT NULL DEFAULT 0,
+                last_sync_time INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT INTO sync_info (id) VALUES (1);
+            CREATE TABLE changes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL,
+                change_type TEXT NOT NULL,
+                detected_at INTEGER NOT NULL,
+                data TEXT NOT NULL
+            );
+            INSERT INTO changes (path, change_type, detected_at, data)
+            VALUES ('/etc/hosts', 'Added', 0, '{}');",
+        )?;
+        drop(conn);
+
+        // Read-only handles cannot migrate, so they refuse the old layout
+        assert!(FimDb::open_read_only(&db_path).is_err());
+
+        let db = FimDb::open(Some(&db_path), &DbOptions::default())?;
+        assert_eq!(db.schema_version()?, SCHEMA_VERSION);
+        let migrated = db.get_path(Path::new("/etc/hosts"))?.unwrap();
+        assert_eq!(migrated.data.blake3, "legacy_hash");
+        assert!(db.integrity_check()?.is_ok());
+        let chain = db.verify_change_log()?;
+        assert!(chain.is_intact());
+        assert_eq!(chain.rows_checked, 1);
+        assert_eq!(db.list_profiles()?, vec![DEFAULT_PROFILE.to_string()]);
+
+        // Other profiles may now record the same path
+        let mut other = FimDb::open(Some(&db_path), &DbOptions {
+            profile: Some("other".to_string()),
+            ..Default::default()
+        })?;
+        other.insert_data(Path::new("/etc/hosts"), &migrated.data)?;
+        assert_eq!(db.get_stats()?.total_files, 1);
+        assert_eq!(other.get_stats()?.total_files, 1);
+
+        // Each profile logs and chains its own changes
+        other.record_change(&FileChange {
+            path: PathBuf::from("/etc/hosts"),
+            change_type: ChangeType::HashChanged,
+            old_entry: None,
+            new_entry: None,
+            detected_at: Utc::now(),
+            severity: AlertSeverity::Error,
+            is_critical: false,
+            change_id: FileChange::new_id(),
+        })?;
+        let logged = other.get_changes(None, None)?;
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].change_type, ChangeType::HashChanged);
+        assert_eq!(db.verify_change_log()?.rows_checked, 1);
+        assert!(db.verify_change_log()?.is_intact());
+        let chain = other.verify_change_log()?;
+        assert!(chain.is_intact());
+        assert_eq!(chain.rows_checked, 1);
+        drop((db, other));
+
+        assert_eq!(FimDb::open_read_only(&db_path)?.schema_version()?, SCHEMA_VERSION);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_schema_version_is_refused() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join(DEFAULT_DB_FILE);
+
+        let db = FimDb::init_at(&db_path, false)?;
+        assert_eq!(db.schema_version()?, SCHEMA_VERSION);
+        db.set_schema_version(SCHEMA_VERSION + 1)?;
+        drop(db);
+
+        let Err(error) = FimDb::init_at(&db_path, false) else {
+            panic!("opened a database from a newer version");
+        };
+        assert!(error.to_string().contains("newer than this build"), "{}", error);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_entries_under() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let entry = FimEntryData {
+            size: 10,
+            uid: 0,
+            gid: 0,
+            blake3: "aaa".to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+        for path in ["/etc", "/etc/hosts", "/etc/ssh/sshd_config", "/etc_backup/hosts", "/etcetera", "/usr/bin/ls"] {
+            db.insert_data(Path::new(path), &entry)?;
+        }
+
+        let paths = |entries: Vec<FimEntry>| -> Vec<PathBuf> {
+            entries.into_iter().map(|entry| entry.path).collect()
+        };
+        let expected: Vec<PathBuf> = ["/etc", "/etc/hosts", "/etc/ssh/sshd_config"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(paths(db.get_entries_under(Path::new("/etc"), None)?), expected);
+        assert_eq!(paths(db.get_entries_under(Path::new("/etc/"), None)?), expected);
+        assert_eq!(paths(db.get_entries_under(Path::new("/etc"), Some(2))?), expected[..2]);
+        // Prefixes match whole components, not LIKE patterns
+        assert_eq!(paths(db.get_entries_under(Path::new("/etc_"), None)?), Vec::<PathBuf>::new());
+        assert_eq!(db.get_entries_under(Path::new("/"), None)?.len(), 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_first_seen_and_last_changed() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let path = Path::new("/etc/hosts");
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut entry = FimEntryData {
+            size: 10,
+            uid: 0,
+            gid: 0,
+            blake3: "aaa".to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+        db.insert_data(path, &entry)?;
+
+        let recorded = db.get_path(path)?.unwrap();
+        assert!(recorded.created_at.is_some());
+        assert_eq!(recorded.created_at, recorded.updated_at);
+
+        // Age the row instead of sleeping past the one-second resolution
+        db.conn.execute(
+            "UPDATE file_data SET created_at = created_at - 100, updated_at = updated_at - 100",
+            [],
+        )?;
+        let aged = db.get_path(path)?.unwrap();
+
+        // Rescanning an unchanged file only touches bookkeeping
+        entry.atime += chrono::Duration::seconds(30);
+        entry.scanned = false;
+        db.insert_data(path, &entry)?;
+        let rescanned = db.get_path(path)?.unwrap();
+        assert_eq!(rescanned.created_at, aged.created_at);
+        assert_eq!(rescanned.updated_at, aged.updated_at);
+
+        entry.blake3 = "changed".to_string();
+        db.insert_data(path, &entry)?;
+        let changed = db.get_path(path)?.unwrap();
+        assert_eq!(changed.created_at, aged.created_at);
+        assert!(changed.updated_at > aged.updated_at);
+
+        // The checksum ignores the timestamps and still matches
+        assert_eq!(db.get_data_checksum()?, data_checksum(&db.get_all_entries()?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_history() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        let path = Path::new("/etc/passwd");
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut entry = FimEntryData {
+            size: 10,
+            uid: 0,
+            gid: 0,
+            blake3: "v0".to_string(),
+            mtime: timestamp,
+            ctime: timestamp,
+            atime: timestamp,
+            inode: 1,
+            ..test_entry()
+        };
+        db.insert_data(path, &entry)?;
+        assert!(db.get_history(path, 10)?.is_empty());
+
+        // Rescans of an unchanged file leave no history
+        entry.atime += chrono::Duration::seconds(30);
+        db.insert_data(path, &entry)?;
+        assert!(db.get_history(path, 10)?.is_empty());
+
+        for version in 1..=3 {
+            entry.blake3 = format!("v{}", version);
+            db.insert_data(path, &entry)?;
+        }
+        let history = db.get_history(path, 10)?;
+        let hashes: Vec<_> = history.iter().map(|(_, data)| data.blake3.as_str()).collect();
+        assert_eq!(hashes, ["v0", "v1", "v2"]);
+        assert!(history.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(db.get_history(path, 2)?[0].1.blake3, "v1");
+        assert!(db.get_history(Path::new("/etc/shadow"), 10)?.is_empty());
+
+        // History is kept out of the data checksum
+        assert_eq!(db.get_data_checksum()?, data_checksum(&db.get_all_entries()?));
+
+        // Removal keeps the last state
+        db.remove_path(path)?;
+        let history = db.get_history(path, 10)?;
+        assert_eq!(history.last().unwrap().1.blake3, "v3");
+        assert!(db.verify_history()?.is_intact());
+        db.insert_data(path, &entry)?;
+
+        db.set_record_history(false);
+        entry.blake3 = "v4".to_string();
+        db.insert_data(path, &entry)?;
+        db.remove_path(path)?;
+        assert_eq!(db.get_history(path, 10)?.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_history_chain_and_limit() -> Result<()> {
+        let mut db = FimDb::init(true)?;
+        db.set_history_limit(3);
+        let path = Path::new("/etc/hosts");
+        let mut entry = test_entry();
+        for version in 0..6 {
+            entry.blake3 = format!("v{}", version);
+            db.insert_data(path, &entry)?;
+        }
+
+        // Only the newest states are kept, and pruning leaves the chain intact
+        let hashes: Vec<_> = db.get_history(path, 10)?.into_iter().map(|(_, data)| data.blake3).collect();
+        assert_eq!(hashes, ["v2", "v3", "v4"]);
+        let verification = db.verify_history()?;
+        assert!(verification.is_intact());
+        assert_eq!(verification.rows_checked, 3);
+
+        // Other profiles have their own chain
+        db.profile = "web".to_string();
+        db.insert_data(path, &entry)?;
+        db.remove_path(path)?;
+        assert!(db.verify_history()?.is_intact());
+        db.profile = DEFAULT_PROFILE.to_string();
+
+        let ids: Vec<i64> = db.conn
+            .prepare("SELECT id FROM file_history WHERE profile = 'default' ORDER BY id")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+
+        // Edited states break the chain at the edited row
+        db.conn.execute("UPDATE file_history SET data = replace(data, 'v3', 'v9') WHERE id = ?1", [ids[1]])?;
+        assert_eq!(db.verify_history()?.first_break, Some(ids[1]));
+        db.conn.execute("UPDATE file_history SET data = replace(data, 'v9', 'v3') WHERE id = ?1", [ids[1]])?;
+        assert!(db.verify_history()?.is_intact());
+
+        // So do removed states, at either end
+        db.conn.execute("DELETE FROM file_history WHERE id = ?1", [ids[0]])?;
+        assert_eq!(db.verify_history()?.first_break, Some(ids[1]));
+        db.conn.execute("DELETE FROM file_history WHERE id = ?1", [ids[2]])?;
+        let verification = db.verify_history()?;
+        assert!(!verification.is_intact());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_json_reads_one_state() -> Result<()> {
+        /// Writer committing another entry through a second connection
+        /// once the export has read its first row
+        struct Interrupting {
+            other: Option<FimDb>,
+            out: Vec<u8>,
+        }
+        impl Write for Interrupting {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if buf != b"," {
+                    return self.out.write(buf);
+                }
+                if let Some(mut other) = self.other.take() {
+                    other.insert_data(Path::new("/data/late.txt"), &test_entry())
+                        .map_err(std::io::Error::other)?;
+                }
+                self.out.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let temp_dir = tempfile::tempdir()?;
+        let db_path = temp_dir.path().join("fim.db");
+        let mut db = FimDb::open(Some(&db_path), &DbOptions::default())?;
+        for i in 0..3 {
+            db.insert_data(&PathBuf::from(format!("/data/file{}", i)), &test_entry())?;
+        }
+
+        let mut writer = Interrupting {
+            other: Some(FimDb::open(Some(&db_path), &DbOptions::default())?),
+            out: Vec::new(),
+        };
+        assert_eq!(db.export_json(&mut writer)?, 3);
+        assert!(writer.other.is_none());
+        let snapshot: FullSnapshot = serde_json::from_slice(&writer.out)?;
+        snapshot.validate()?;
+
+        // The late entry is there for the next export
+        assert_eq!(db.export_json(std::io::sink())?, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_snapshot_round_trip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut db = FimDb::init(true)?;
+        let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for i in 0..50u64 {
+            let entry = FimEntryData {
+                size: i,
+                uid: 0,
+                gid: 0,
+                sha256: Some(format!("sha{}", i)),
+                blake3: format!("hash{}", i),
+                mtime: timestamp,
+                ctime: timestamp,
+                atime: timestamp,
+                inode: i,
+                content_type: Some(ContentClass::Text),
+                decompressed: (i % 2 == 0).then_some(Compression::Gzip),
+                ..test_entry()
+            };
+            db.insert_data(&PathBuf::from(format!("/data/file{}", i)), &entry)?;
+        }
+        let snapshot = db.snapshot()?;
+
+        let json_path = temp_dir.path().join("baseline.json");
+        let bin_path = temp_dir.path().join("baseline.bin");
+        assert_eq!(SnapshotFormat::from_path(&json_path), SnapshotFormat::Json);
+        assert_eq!(SnapshotFormat::from_path(&bin_path), SnapshotFormat::Binary);
+        snapshot.save(&json_path, SnapshotFormat::Json)?;
+        snapshot.save(&bin_path, SnapshotFormat::Binary)?;
+        assert!(fs::metadata(&bin_path)?.len() < fs::metadata(&json_path)?.len());
+
+        let loaded = FullSnapshot::load(&bin_path, SnapshotFormat::Binary)?;
+        assert_eq!(loaded.checksum, snapshot.checksum);
+        assert_eq!(loaded.computed_checksum(), snapshot.checksum);
+        assert_eq!(loaded.entries.len(), 50);
+        assert_eq!(loaded.entries[0].data.decompressed, Some(Compression::Gzip));
+        assert_eq!(loaded.entries[0].data.mtime, timestamp);
+        assert_eq!(loaded.entries[0].created_at, snapshot.entries[0].created_at);
+        assert!(fs::read_to_string(&json_path)?.contains("\"mtime\":\"2023-11-14T22:13:20Z\""));
+
+        let mut restored = FimDb::init(true)?;
+        restored.restore_snapshot(&loaded)?;
+        assert_eq!(restored.get_data_checksum()?, db.get_data_checksum()?);
+        assert!(restored.integrity_check()?.is_ok());
+
+        // Fields other than the hash are covered too
+        let mut tampered = loaded.clone();
+        tampered.entries[1].data.perm = "4755".to_string();
+        assert!(tampered.validate().is_err());
+        assert!(restored.restore_snapshot(&tampered).is_err());
+
+        // Rows that don't come out as listed are rolled back: a repeated
+        // path keeps only its last entry
+        let mut repeated = loaded.clone();
+        let mut duplicate = repeated.entries[1].clone();
+        duplicate.data.perm = "600".to_string();
+        repeated.entries.push(duplicate);
+        repeated.checksum = repeated.computed_checksum();
+        repeated.digest = repeated.computed_digest();
+        let err = restored.restore_snapshot(&repeated).unwrap_err();
+        assert!(err.to_string().contains("do not match the snapshot"), "{:#}", err);
+        assert_eq!(restored.get_path(&loaded.entries[1].path)?.unwrap().data.perm, loaded.entries[1].data.perm);
+        assert_eq!(restored.get_stats()?.total_files, 50);
+        assert!(restored.integrity_check()?.is_ok());
+
+        // JSON is not mistaken for binary, and other versions are refused
+        assert!(FullSnapshot::load(&json_path, SnapshotFormat::Binary).is_err());
+        let mut bytes = fs::read(&bin_path)?;
+        bytes[SNAPSHOT_MAGIC.len()] = 99;
+        let err = FullSnapshot::from_bytes(&bytes, SnapshotFormat::Binary).unwrap_err();
+        assert!(err.to_string().contains("version 99"));
+
+        Ok(())
+    }
+}
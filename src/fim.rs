@@ -3,19 +3,32 @@
 //! Coordinates scanning, hashing, database operations, and real-time monitoring
 //! to provide comprehensive file integrity monitoring capabilities.
 
-use crate::database::{FimDb, FimEntry, FimEntryData, FimStats};
-use crate::hasher::{FileHasher, HashConfig};
+use crate::database::{
+    AutoVacuum, ChangeLogVerification, DbIntegrityReport, DbOptions, FieldChange, FimDb, FimEntry,
+    FimEntryData, FimStats, FullSnapshot, SnapshotFormat, DEFAULT_DB_FILE, DEFAULT_HISTORY_LIMIT,
+    DEFAULT_PROFILE,
+};
+use crate::hasher::{Compression, ContentClass, FileHasher, FileHashes, HashConfig};
+use crate::manifest::{self, TreeManifest, TreeVerifyReport};
+use crate::reporting::{AlertSeverity, CompiledSeverityRules, SeverityRules, SyslogConfig};
+use crate::store::FimStore;
 use crate::watcher::{FimEvent, FimEventKind, FimWatcher, WatchConfig};
 
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SubsecRound, Utc};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use regex::bytes::Regex;
 use serde::{Deserialize, Serialize};
-// use std::collections::HashSet; // unused
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 /// Serde module for Duration serialization
 mod duration_serde {
@@ -26,7 +39,8 @@ mod duration_serde {
     where
         S: Serializer,
     {
-        duration.as_millis().serialize(serializer)
+        // u64 rather than u128, which TOML cannot represent
+        u64::try_from(duration.as_millis()).unwrap_or(u64::MAX).serialize(serializer)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
@@ -52,19 +66,82 @@ pub enum FimMode {
     Verify,
 }
 
+/// Which differences between a file and its baseline entry count as a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparePolicy {
+    /// Content only: hash, size and content class
+    ///
+    /// File capability and extended attribute changes are still reported
+    /// under every policy, as they can grant privileges without touching
+    /// content.
+    ContentOnly,
+    /// Content plus permissions and ownership
+    ContentAndPerms,
+    /// Content, permissions and timestamps
+    #[default]
+    Full,
+}
+
+impl ComparePolicy {
+    fn checks_perms(self) -> bool {
+        self != Self::ContentOnly
+    }
+
+    fn checks_timestamps(self) -> bool {
+        self == Self::Full
+    }
+}
+
+/// How symbolic links below a monitored path are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymlinkPolicy {
+    /// Skip links entirely
+    Ignore,
+    /// Record the link itself and its target path, without following it
+    RecordLink,
+    /// Follow links to the files and directories they point to
+    #[default]
+    Follow,
+}
+
 /// FIM configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FimConfig {
     /// Paths to monitor
     pub monitor_paths: Vec<PathBuf>,
-    /// Exclude patterns
+    /// Exclude patterns; `!pattern` keeps what an earlier pattern excluded,
+    /// and the last matching pattern decides
     pub exclude_patterns: Vec<String>,
+    /// When non-empty, only files matching one of these patterns (and no
+    /// exclude pattern) are monitored; directories are still walked
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
     /// Hash configuration
     pub hash_config: HashConfig,
     /// Watch configuration
     pub watch_config: WatchConfig,
     /// Database in memory vs disk
     pub memory_database: bool,
+    /// Location of the on-disk database (None = `fim_integrity.db` in the
+    /// working directory)
+    #[serde(default)]
+    pub database_path: Option<PathBuf>,
+    /// SQLite page size in bytes for newly created databases (None = SQLite default)
+    #[serde(default)]
+    pub db_page_size: Option<u32>,
+    /// SQLite auto_vacuum mode for newly created databases
+    #[serde(default)]
+    pub db_auto_vacuum: AutoVacuum,
+    /// Passphrase to encrypt the on-disk database with SQLCipher (needs the
+    /// `encryption` feature)
+    #[serde(default)]
+    pub db_encryption_key: Option<String>,
+    /// Baseline within the database that scans read and write, so one file
+    /// can hold several independent baselines
+    #[serde(default = "default_profile")]
+    pub profile: String,
     /// Scan performance settings
     pub scan_threads: Option<usize>,
     /// Maximum file size to hash (bytes)
@@ -73,6 +150,137 @@ pub struct FimConfig {
     pub enable_realtime: bool,
     /// Scan interval for incremental mode (seconds)
     pub scan_interval: u64,
+    /// Content normalizers applied before hashing
+    #[serde(default)]
+    pub content_normalizers: Vec<ContentNormalizer>,
+    /// Files hashed by their decompressed content
+    #[serde(default)]
+    pub decompress_rules: Vec<DecompressRule>,
+    /// Files that only grow, such as logs, tracked by a prefix hash and size
+    #[serde(default)]
+    pub append_only: Vec<AppendOnlyRule>,
+    /// Perform a baseline instead when an incremental scan finds an empty database
+    #[serde(default)]
+    pub auto_baseline: bool,
+    /// Seconds between passive WAL checkpoints during scans (None = SQLite's automatic checkpoints only)
+    #[serde(default)]
+    pub wal_checkpoint_interval: Option<u64>,
+    /// Record detected changes in the database audit log
+    #[serde(default = "default_true")]
+    pub record_changes: bool,
+    /// Keep each file's previous data in the database whenever a scan
+    /// overwrites or removes it, for `fim history`
+    #[serde(default = "default_true")]
+    pub record_history: bool,
+    /// Most previous states kept per profile; the oldest are pruned
+    #[serde(default = "default_history_limit")]
+    pub history_limit: usize,
+    /// Collapse real-time events for the same path within this window into a
+    /// single re-check (milliseconds, 0 = off); separate from the watcher debounce
+    #[serde(default, with = "duration_serde")]
+    pub coalesce_window: Duration,
+    /// Retries for files that are transiently locked by another process
+    #[serde(default)]
+    pub open_retry: RetryConfig,
+    /// Hashed entries queued for the database writer during a baseline;
+    /// hashing threads block when the queue is full (minimum 1)
+    #[serde(default = "default_write_queue_depth")]
+    pub write_queue_depth: usize,
+    /// Severity and criticality assigned to detected changes
+    #[serde(default)]
+    pub severity_rules: SeverityRules,
+    /// Metadata differences that count as changes
+    #[serde(default)]
+    pub compare_policy: ComparePolicy,
+    /// Treatment of symbolic links; monitored paths themselves are always followed
+    #[serde(default)]
+    pub symlink_policy: SymlinkPolicy,
+    /// Open the baseline database read-only; incremental scans and real-time
+    /// events then only report drift and never write (see [`FimEngine::verify_scan`])
+    #[serde(default)]
+    pub immutable_baseline: bool,
+    /// Hex Ed25519 public key the baseline signature must verify against
+    /// before the engine starts (see [`FimDb::sign_baseline`])
+    #[serde(default)]
+    pub baseline_public_key: Option<String>,
+    /// Match exclude patterns ignoring case (default: on for Windows and macOS)
+    #[serde(default = "default_case_insensitive_patterns")]
+    pub case_insensitive_patterns: bool,
+    /// Record directories too, so permission and ownership changes on them
+    /// are detected (their contents are not hashed)
+    #[serde(default)]
+    pub monitor_directories: bool,
+    /// Record a digest of each file's extended attributes (SELinux labels,
+    /// `com.apple.quarantine`, ...) and report changes to them as
+    /// `AttributeChanged`; Linux and macOS only, needs the `xattrs` feature
+    #[serde(default)]
+    pub monitor_xattrs: bool,
+    /// Skip hidden files and directories: dot-prefixed names on Unix, the
+    /// hidden attribute on Windows
+    #[serde(default)]
+    pub ignore_hidden: bool,
+    /// Files hashed at once at most, shared by all scan threads (default: a
+    /// quarter of the process open-file limit)
+    #[serde(default)]
+    pub max_open_files: Option<usize>,
+    /// Re-hash a file whose size or modification time changed while it was
+    /// hashed up to this many times before recording it as unstable
+    #[serde(default = "default_unstable_rehash_attempts")]
+    pub unstable_rehash_attempts: u32,
+    /// Keep the recorded entry of a file that is still unstable after the
+    /// re-hash attempts, and report nothing for it until a later scan
+    #[serde(default)]
+    pub defer_unstable: bool,
+    /// Skip hashing files whose size, mtime, inode, ownership, permissions
+    /// and capabilities all match their entry; off forces a full re-hash on
+    /// every scan
+    #[serde(default = "default_true")]
+    pub trust_mtime: bool,
+    /// Skip files owned by these users, counted as `files_skipped` (Unix
+    /// only); a recorded entry for such a file is kept as it is
+    #[serde(default)]
+    pub exclude_uids: Vec<u32>,
+    /// Skip files whose group is one of these, counted as `files_skipped`
+    /// (Unix only)
+    #[serde(default)]
+    pub exclude_gids: Vec<u32>,
+    /// Skip files whose owner is one of these SIDs, e.g. `S-1-5-18`, counted
+    /// as `files_skipped` (Windows only)
+    #[serde(default)]
+    pub exclude_owner_sids: Vec<String>,
+    /// Report a recorded file this process can no longer stat or read as
+    /// `AccessLost` rather than a scan error or deletion, keeping its entry;
+    /// unreadable files that were never recorded are still errors, and
+    /// unreadable directories are skipped by the walk
+    #[serde(default)]
+    pub report_access_loss: bool,
+    /// Syslog destination for database integrity alerts (None = no syslog)
+    #[serde(default)]
+    pub syslog: Option<SyslogConfig>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_limit() -> usize {
+    DEFAULT_HISTORY_LIMIT
+}
+
+fn default_profile() -> String {
+    DEFAULT_PROFILE.to_string()
+}
+
+fn default_write_queue_depth() -> usize {
+    1024
+}
+
+fn default_unstable_rehash_attempts() -> u32 {
+    1
+}
+
+fn default_case_insensitive_patterns() -> bool {
+    cfg!(any(windows, target_os = "macos"))
 }
 
 impl Default for FimConfig {
@@ -86,369 +294,510 @@ impl Default for FimConfig {
                 "**/*.tmp".to_string(),
                 "**/*.log".to_string(),
             ],
+            include_patterns: vec![],
             hash_config: HashConfig::default(),
             watch_config: WatchConfig::default(),
             memory_database: false,
+            database_path: None,
+            db_page_size: None,
+            db_auto_vacuum: AutoVacuum::None,
+            db_encryption_key: None,
+            profile: default_profile(),
             scan_threads: None,
             max_file_size: Some(1024 * 1024 * 1024), // 1GB limit
             enable_realtime: true,
             scan_interval: 3600, // 1 hour
+            content_normalizers: vec![],
+            decompress_rules: vec![],
+            append_only: vec![],
+            auto_baseline: false,
+            wal_checkpoint_interval: None,
+            record_changes: true,
+            record_history: true,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            coalesce_window: Duration::ZERO,
+            open_retry: RetryConfig::default(),
+            write_queue_depth: default_write_queue_depth(),
+            severity_rules: SeverityRules::default(),
+            compare_policy: ComparePolicy::default(),
+            symlink_policy: SymlinkPolicy::default(),
+            immutable_baseline: false,
+            baseline_public_key: None,
+            case_insensitive_patterns: default_case_insensitive_patterns(),
+            monitor_directories: false,
+            monitor_xattrs: false,
+            ignore_hidden: false,
+            max_open_files: None,
+            unstable_rehash_attempts: default_unstable_rehash_attempts(),
+            defer_unstable: false,
+            trust_mtime: true,
+            exclude_uids: vec![],
+            exclude_gids: vec![],
+            exclude_owner_sids: vec![],
+            report_access_loss: false,
+            syslog: None,
         }
     }
 }
 
-/// FIM scan results
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScanResults {
-    pub files_scanned: u64,
-    pub files_added: u64,
-    pub files_modified: u64,
-    pub files_deleted: u64,
-    pub errors: u64,
-    #[serde(with = "duration_serde")]
-    pub scan_duration: Duration,
-    pub total_size: u64,
+impl FimConfig {
+    /// Load a TOML configuration file, expanding `@file` lists
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        config.expand_file_lists(path.parent())?;
+        Ok(config)
+    }
+
+    /// On-disk database location: `database_path`, or the default file name
+    pub fn database_file(&self) -> &Path {
+        self.database_path.as_deref().unwrap_or(Path::new(DEFAULT_DB_FILE))
+    }
+
+    /// Replace `@list.txt` entries in `monitor_paths`, `exclude_patterns` and
+    /// `include_patterns` with the lines of that file
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Relative list
+    /// paths resolve against `base_dir` (the config file's directory).
+    pub fn expand_file_lists(&mut self, base_dir: Option<&Path>) -> Result<()> {
+        let mut monitor_paths = Vec::with_capacity(self.monitor_paths.len());
+        for path in self.monitor_paths.drain(..) {
+            match path.to_str().and_then(|path| path.strip_prefix('@')) {
+                Some(list) => monitor_paths.extend(read_file_list(list, base_dir)?.into_iter().map(PathBuf::from)),
+                None => monitor_paths.push(path),
+            }
+        }
+        self.monitor_paths = monitor_paths;
+
+        for patterns in [&mut self.exclude_patterns, &mut self.include_patterns] {
+            let mut expanded = Vec::with_capacity(patterns.len());
+            for pattern in patterns.drain(..) {
+                match pattern.strip_prefix('@') {
+                    Some(list) => expanded.extend(read_file_list(list, base_dir)?),
+                    None => expanded.push(pattern),
+                }
+            }
+            *patterns = expanded;
+        }
+
+        Ok(())
+    }
 }
 
-/// File integrity change types
-#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
-pub enum ChangeType {
-    Added,
-    Modified,
-    Deleted,
-    PermissionChanged,
-    SizeChanged,
-    HashChanged,
-    TimestampChanged,
+/// Entries of a one-per-line list file, without blanks and `#` comments
+fn read_file_list(list: &str, base_dir: Option<&Path>) -> Result<Vec<String>> {
+    let list_path = match base_dir {
+        Some(base_dir) => base_dir.join(list),
+        None => PathBuf::from(list),
+    };
+    let content = fs::read_to_string(&list_path)
+        .with_context(|| format!("Failed to read list file: {}", list_path.display()))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
 }
 
-/// File change record
+/// Retry policy for opening files held by another process
+///
+/// Only sharing/locking errors (`ERROR_SHARING_VIOLATION` and
+/// `ERROR_LOCK_VIOLATION` on Windows, `EBUSY` elsewhere) are retried; the
+/// delay doubles after each attempt.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FileChange {
-    pub path: PathBuf,
-    pub change_type: ChangeType,
-    pub old_entry: Option<FimEntryData>,
-    pub new_entry: Option<FimEntryData>,
-    pub detected_at: DateTime<Utc>,
+pub struct RetryConfig {
+    /// Retries after the first failed attempt (0 = fail immediately)
+    pub max_retries: u32,
+    /// Delay before the first retry, in milliseconds
+    pub initial_backoff_ms: u64,
 }
 
-/// Core FIM engine
-pub struct FimEngine {
-    config: FimConfig,
-    database: FimDb,
-    hasher: FileHasher,
-    watcher: Option<FimWatcher>,
-    is_running: Arc<Mutex<bool>>,
-    change_handlers: Vec<Box<dyn Fn(&FileChange) + Send + Sync>>,
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_ms: 50,
+        }
+    }
 }
 
-impl FimEngine {
-    /// Create new FIM engine
-    pub fn new(config: FimConfig) -> Result<Self> {
-        let database = FimDb::init(config.memory_database)
-            .context("Failed to initialize database")?;
-        
-        let hasher = FileHasher::new(config.hash_config.clone());
-        
-        let mut watch_config = config.watch_config.clone();
-        watch_config.paths = config.monitor_paths.clone();
-        
-        let watcher = if config.enable_realtime {
-            Some(FimWatcher::new(watch_config)?)
-        } else {
-            None
-        };
+impl RetryConfig {
+    /// Run `operation`, retrying while it fails with a transient lock error
+    pub fn run<T>(&self, operation: impl FnMut() -> Result<T>) -> Result<T> {
+        self.run_while(is_transient_lock_error, operation)
+    }
 
-        Ok(Self {
-            config,
-            database,
-            hasher,
-            watcher,
-            is_running: Arc::new(Mutex::new(false)),
-            change_handlers: Vec::new(),
-        })
+    /// Run `operation`, retrying while it fails with an error `retryable` accepts
+    pub fn run_while<T>(
+        &self,
+        retryable: impl Fn(&anyhow::Error) -> bool,
+        mut operation: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let mut backoff = Duration::from_millis(self.initial_backoff_ms);
+        let mut retries = 0;
+        loop {
+            match operation() {
+                Err(e) if retries < self.max_retries && retryable(&e) => {
+                    retries += 1;
+                    debug!("Transient error ({:#}), retry {} in {:?}", e, retries, backoff);
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
     }
+}
 
-    /// Add change handler callback
-    pub fn add_change_handler<F>(&mut self, handler: F)
-    where
-        F: Fn(&FileChange) + Send + Sync + 'static,
+/// Whether an error was caused by lacking permission to reach or read a file
+fn is_permission_denied(error: &anyhow::Error) -> bool {
+    error.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Whether `path` may still exist but a directory above it can no longer be
+/// searched
+fn is_unreachable(path: &Path) -> bool {
+    matches!(fs::metadata(path), Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Whether an error was caused by another process holding the file
+fn is_transient_lock_error(error: &anyhow::Error) -> bool {
+    error.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .filter_map(std::io::Error::raw_os_error)
+        .any(|code| {
+            #[cfg(windows)]
+            {
+                // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+                code == 32 || code == 33
+            }
+            #[cfg(not(windows))]
+            {
+                code == libc::EBUSY
+            }
+        })
+}
+
+/// Open files allowed when `max_open_files` is unset: a quarter of the soft
+/// `RLIMIT_NOFILE`, leaving room for the database, watcher and embedding
+/// application
+fn default_max_open_files() -> usize {
+    #[cfg(unix)]
     {
-        self.change_handlers.push(Box::new(handler));
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        // SAFETY: getrlimit only writes to the struct it is given
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0
+            && limit.rlim_cur != libc::RLIM_INFINITY
+        {
+            return (limit.rlim_cur / 4).clamp(1, 4096) as usize;
+        }
+        4096
+    }
+    #[cfg(not(unix))]
+    {
+        // The C runtime's default stdio limit
+        512 / 4
     }
+}
 
-    /// Start the FIM engine
-    pub fn start(&mut self) -> Result<()> {
-        *self.is_running.lock().unwrap() = true;
-        
-        info!("Starting FIM engine");
-        
-        // Start real-time watcher if enabled
-        if let Some(ref mut watcher) = self.watcher {
-            watcher.start().context("Failed to start filesystem watcher")?;
-            info!("Real-time monitoring enabled");
-        }
+/// Counting semaphore bounding how many files scan threads hold open at once
+struct OpenFileLimiter {
+    limit: usize,
+    state: Mutex<OpenFileCounts>,
+    released: std::sync::Condvar,
+}
 
-        Ok(())
-    }
+#[derive(Default)]
+struct OpenFileCounts {
+    open: usize,
+    /// Most files open at once so far
+    high_water: usize,
+}
 
-    /// Stop the FIM engine
-    pub fn stop(&mut self) {
-        *self.is_running.lock().unwrap() = false;
-        
-        if let Some(ref mut watcher) = self.watcher {
-            watcher.stop();
+impl OpenFileLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            state: Mutex::new(OpenFileCounts::default()),
+            released: std::sync::Condvar::new(),
         }
-        
-        info!("FIM engine stopped");
     }
 
-    /// Perform baseline scan
-    pub fn baseline_scan(&mut self) -> Result<ScanResults> {
-        info!("Starting baseline scan");
-        let _start_time = Instant::now();
-        
-        // Clear existing data
-        self.database.set_all_unscanned()?;
-        
-        let mut results = ScanResults {
-            files_scanned: 0,
-            files_added: 0,
-            files_modified: 0,
-            files_deleted: 0,
-            errors: 0,
-            scan_duration: Duration::default(),
-            total_size: 0,
-        };
+    /// Wait for a free slot, held until the permit is dropped
+    fn acquire(&self) -> OpenFilePermit<'_> {
+        let mut state = self.released
+            .wait_while(self.state.lock().unwrap(), |state| state.open >= self.limit)
+            .unwrap();
+        state.open += 1;
+        state.high_water = state.high_water.max(state.open);
+        OpenFilePermit(self)
+    }
+}
 
-        // Collect all files to scan
-        let files_to_scan = self.collect_files_to_scan()?;
-        info!("Found {} files to scan", files_to_scan.len());
+struct OpenFilePermit<'a>(&'a OpenFileLimiter);
 
-        // Configure parallelism
-        let thread_count = self.config.scan_threads
-            .unwrap_or_else(|| num_cpus::get());
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(thread_count)
-            .build_global()
-            .context("Failed to configure thread pool")?;
+impl Drop for OpenFilePermit<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().open -= 1;
+        self.0.released.notify_one();
+    }
+}
 
-        // Begin database transaction for batch operations
-        self.database.begin_transaction()?;
+/// Content normalization rule applied before hashing
+///
+/// Files whose path matches `path_pattern` have every match of `regex`
+/// replaced with `replacement` before hashing, so files that differ only in
+/// known-variable regions (rotating tokens, embedded timestamps) hash the same.
+///
+/// Security trade-off: bytes matched by `regex` are invisible to FIM. Anyone
+/// able to write content that still matches the expression can change those
+/// bytes undetected, so keep expressions as narrow as possible.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentNormalizer {
+    /// Glob pattern selecting the files this rule applies to
+    pub path_pattern: String,
+    /// Regular expression matching the variable region
+    pub regex: String,
+    /// Replacement text (supports `$1`-style capture references)
+    #[serde(default)]
+    pub replacement: String,
+}
 
-        let start_scan = Instant::now();
-        
-        // Process files sequentially (SQLite is not thread-safe)
-        let scan_results: Vec<_> = files_to_scan
-            .iter()
-            .map(|path| self.scan_single_file(path))
-            .collect();
+/// Rule hashing gzip or zstd files by their decompressed content
+///
+/// Matching files that start with gzip or zstd magic bytes are decompressed
+/// before hashing (and before any content normalizers), so recompressing the
+/// same content does not register as a change. Other matching files are
+/// hashed as usual.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecompressRule {
+    /// Glob pattern selecting the files this rule applies to
+    pub path_pattern: String,
+    /// Decompressed size in bytes beyond which hashing the file fails
+    #[serde(default = "default_max_decompressed_size")]
+    pub max_size: u64,
+}
 
-        // Process results and update database
-        for scan_result in scan_results {
-            match scan_result {
-                Ok((entry_data, file_size)) => {
-                    results.files_scanned += 1;
-                    results.total_size += file_size;
-                    results.files_added += 1;
-                    
-                    // Insert into database
-                    if let Err(e) = self.database.insert_data(&entry_data.path, &entry_data.data) {
-                        error!("Failed to insert file data: {}", e);
-                        results.errors += 1;
-                    }
-                }
-                Err(e) => {
-                    error!("Scan error: {}", e);
-                    results.errors += 1;
-                }
-            }
+fn default_max_decompressed_size() -> u64 {
+    256 * 1024 * 1024
+}
 
-            // Periodic commit for large scans
-            if results.files_scanned % 1000 == 0 {
-                self.database.force_commit();
-                debug!("Processed {} files", results.files_scanned);
-            }
-        }
+/// Rule treating matching files as append-only, such as logs
+///
+/// Only the first `prefix_bytes` of a matching file are hashed. A rescan that
+/// finds the file grown, with its recorded content still at its start,
+/// updates the entry without reporting a change. Rewriting bytes within the
+/// prefix is reported as `HashChanged`, and truncation as a size change.
+///
+/// Guarantees: the prefix is integrity-protected, as is everything recorded
+/// while the file was still shorter than the prefix. Bytes beyond the prefix
+/// are only tracked by size, so rewriting them in place goes unnoticed.
+/// Content normalizers and decompress rules do not apply to these files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendOnlyRule {
+    /// Glob pattern selecting the files this rule applies to
+    pub path_pattern: String,
+    /// Length of the hashed, immutable prefix in bytes
+    #[serde(default = "default_append_prefix_bytes")]
+    pub prefix_bytes: u64,
+}
 
-        // Final commit
-        self.database.commit_transaction()?;
-        
-        // Clean up unscanned entries
-        let deleted = self.database.delete_not_scanned()?;
-        results.files_deleted = deleted as u64;
+fn default_append_prefix_bytes() -> u64 {
+    64 * 1024
+}
 
-        results.scan_duration = start_scan.elapsed();
-        
-        info!(
-            "Baseline scan completed: {} files scanned, {} added, {} errors in {:?}",
-            results.files_scanned,
-            results.files_added,
-            results.errors,
-            results.scan_duration
-        );
+/// Append-only rule with its pattern compiled once at engine construction
+struct CompiledAppendOnlyRule {
+    path_pattern: glob::Pattern,
+    prefix_bytes: u64,
+}
 
-        Ok(results)
+impl CompiledAppendOnlyRule {
+    fn compile(rule: &AppendOnlyRule) -> Result<Self> {
+        Ok(Self {
+            path_pattern: glob::Pattern::new(&rule.path_pattern)
+                .with_context(|| format!("Invalid append-only path pattern: {}", rule.path_pattern))?,
+            prefix_bytes: rule.prefix_bytes,
+        })
     }
+}
 
-    /// Perform incremental scan
-    pub fn incremental_scan(&mut self) -> Result<ScanResults> {
-        info!("Starting incremental scan");
-        let _start_time = Instant::now();
-        
-        // Mark all entries as unscanned
-        self.database.set_all_unscanned()?;
-        
-        let mut results = ScanResults {
-            files_scanned: 0,
-            files_added: 0,
-            files_modified: 0,
-            files_deleted: 0,
-            errors: 0,
-            scan_duration: Duration::default(),
-            total_size: 0,
-        };
+/// Decompress rule with its pattern compiled once at engine construction
+struct CompiledDecompressRule {
+    path_pattern: glob::Pattern,
+    max_size: u64,
+}
 
-        let files_to_scan = self.collect_files_to_scan()?;
-        
-        self.database.begin_transaction()?;
+impl CompiledDecompressRule {
+    fn compile(rule: &DecompressRule) -> Result<Self> {
+        Ok(Self {
+            path_pattern: glob::Pattern::new(&rule.path_pattern)
+                .with_context(|| format!("Invalid decompress path pattern: {}", rule.path_pattern))?,
+            max_size: rule.max_size,
+        })
+    }
+}
 
-        // Process each file and check for changes
-        for file_path in files_to_scan {
-            match self.check_file_changes(&file_path) {
-                Ok(change) => {
-                    results.files_scanned += 1;
-                    
-                    if let Some(change) = change {
-                        self.handle_file_change(&change);
-                        
-                        match change.change_type {
-                            ChangeType::Added => results.files_added += 1,
-                            ChangeType::Modified | 
-                            ChangeType::HashChanged |
-                            ChangeType::PermissionChanged |
-                            ChangeType::SizeChanged |
-                            ChangeType::TimestampChanged => results.files_modified += 1,
-                            ChangeType::Deleted => results.files_deleted += 1,
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Error checking file {}: {}", file_path.display(), e);
-                    results.errors += 1;
-                }
-            }
+/// Normalizer with its patterns compiled once at engine construction
+struct CompiledNormalizer {
+    path_pattern: glob::Pattern,
+    regex: Regex,
+    replacement: Vec<u8>,
+}
+
+impl CompiledNormalizer {
+    fn compile(normalizer: &ContentNormalizer) -> Result<Self> {
+        Ok(Self {
+            path_pattern: glob::Pattern::new(&normalizer.path_pattern)
+                .with_context(|| format!("Invalid normalizer path pattern: {}", normalizer.path_pattern))?,
+            regex: Regex::new(&normalizer.regex)
+                .with_context(|| format!("Invalid normalizer regex: {}", normalizer.regex))?,
+            replacement: normalizer.replacement.as_bytes().to_vec(),
+        })
+    }
+}
 
-            if results.files_scanned % 1000 == 0 {
-                self.database.force_commit();
+/// Exclude or include patterns compiled once at engine construction
+///
+/// Patterns match the whole normalized path. As in `.gitignore`, a pattern
+/// starting with `!` takes back what earlier patterns matched, and the last
+/// matching pattern decides.
+struct CompiledPatternSet {
+    set: globset::GlobSet,
+    /// Whether each pattern, by index in `set`, is negated
+    negated: Vec<bool>,
+    /// Literal leading directories of each negated pattern; empty when it
+    /// starts with a wildcard and could match anywhere
+    negated_roots: Vec<PathBuf>,
+}
+
+impl CompiledPatternSet {
+    fn compile(patterns: &[String], case_insensitive: bool, kind: &str) -> Result<Self> {
+        let mut builder = globset::GlobSetBuilder::new();
+        let mut negated = Vec::with_capacity(patterns.len());
+        let mut negated_roots = Vec::new();
+        for pattern in patterns {
+            let (is_negated, glob) = match pattern.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, pattern.as_str()),
+            };
+            // A trailing separator cannot match a normalized path
+            let glob = match glob.trim_end_matches(['/', std::path::MAIN_SEPARATOR]) {
+                "" => glob,
+                trimmed => trimmed,
+            };
+            builder.add(
+                globset::GlobBuilder::new(glob)
+                    .case_insensitive(case_insensitive)
+                    .build()
+                    .with_context(|| format!("Invalid {} pattern: {}", kind, pattern))?,
+            );
+            negated.push(is_negated);
+            if is_negated {
+                // Case-folded patterns have no reliable literal part
+                let root = Path::new(glob)
+                    .components()
+                    .take_while(|component| {
+                        !case_insensitive
+                            && !component.as_os_str().to_string_lossy().contains(['*', '?', '[', '{'])
+                    })
+                    .collect();
+                negated_roots.push(root);
             }
         }
+        Ok(Self {
+            set: builder.build().with_context(|| format!("Failed to compile {} patterns", kind))?,
+            negated,
+            negated_roots,
+        })
+    }
 
-        // Handle deleted files
-        let deleted = self.database.delete_not_scanned()?;
-        results.files_deleted += deleted as u64;
+    fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
 
-        self.database.commit_transaction()?;
-        results.scan_duration = _start_time.elapsed();
+    /// Whether the last pattern matching `path` is not negated
+    fn matches(&self, path: &Path) -> bool {
+        if !self.negated.contains(&true) {
+            return self.set.is_match(path);
+        }
+        self.set
+            .matches(path)
+            .into_iter()
+            .max()
+            .is_some_and(|index| !self.negated[index])
+    }
 
-        info!(
-            "Incremental scan completed: {} scanned, {} added, {} modified, {} deleted",
-            results.files_scanned,
-            results.files_added,
-            results.files_modified,
-            results.files_deleted
-        );
-
-        Ok(results)
+    /// Whether a negated pattern could match a path below `dir`, so the
+    /// directory has to be walked even when it matches itself
+    fn may_match_below(&self, dir: &Path) -> bool {
+        self.negated_roots
+            .iter()
+            .any(|root| dir.starts_with(root) || root.starts_with(dir))
     }
+}
 
-    /// Process real-time events
-    pub fn process_realtime_events(&mut self) -> Result<()> {
-        if self.watcher.is_none() {
-            return Err(anyhow::anyhow!("Real-time monitoring not enabled"));
-        }
+/// Read-only engine state needed to scan files, shareable across hashing threads
+struct FileScanner<'a> {
+    config: &'a FimConfig,
+    hasher: &'a FileHasher,
+    normalizers: &'a [CompiledNormalizer],
+    decompress_rules: &'a [CompiledDecompressRule],
+    append_rules: &'a [CompiledAppendOnlyRule],
+    sampling_patterns: &'a [glob::Pattern],
+    open_files: &'a OpenFileLimiter,
+    #[cfg(test)]
+    after_hash: Option<&'a HashHook>,
+}
 
-        while *self.is_running.lock().unwrap() {
-            // Get event from watcher
-            let event = if let Some(watcher) = self.watcher.as_ref() {
-                watcher.try_next_event()
-            } else {
-                None
-            };
-            
-            // Handle event if present
-            if let Some(event) = event {
-                if let Err(e) = self.handle_realtime_event(event) {
-                    error!("Error handling real-time event: {}", e);
-                }
-            }
-            
-            std::thread::sleep(Duration::from_millis(10));
+impl FileScanner<'_> {
+    /// Scan a single file and return entry data and its size, or None when
+    /// the file's owner or group is excluded from monitoring
+    fn scan(&self, path: &Path) -> Result<Option<(FimEntry, u64)>> {
+        let link_target = match self.config.symlink_policy {
+            SymlinkPolicy::RecordLink if path.is_symlink() => Some(
+                fs::read_link(path)
+                    .with_context(|| format!("Failed to read link {}", path.display()))?,
+            ),
+            _ => None,
+        };
+        let mut metadata = if link_target.is_some() {
+            fs::symlink_metadata(path)
+        } else {
+            fs::metadata(path)
         }
+        .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
 
-        Ok(())
-    }
-
-    /// Handle real-time filesystem event
-    fn handle_realtime_event(&mut self, event: FimEvent) -> Result<()> {
-        debug!("Processing real-time event: {:?}", event);
-
-        if self.should_ignore_path(&event.path) {
-            return Ok(());
+        let is_dir = metadata.is_dir();
+        if is_dir && !self.config.monitor_directories {
+            anyhow::bail!("{} is a directory", path.display());
         }
 
-        let change = match event.kind {
-            FimEventKind::Created => {
-                if let Ok((entry, _)) = self.scan_single_file(&event.path) {
-                    self.database.insert_data(&event.path, &entry.data)?;
-                    Some(FileChange {
-                        path: event.path,
-                        change_type: ChangeType::Added,
-                        old_entry: None,
-                        new_entry: Some(entry.data),
-                        detected_at: event.timestamp,
-                    })
-                } else {
-                    None
-                }
-            }
-            FimEventKind::Modified => {
-                self.check_file_changes(&event.path)?
-            }
-            FimEventKind::Deleted => {
-                let old_entry = self.database.get_path(&event.path)?;
-                self.database.remove_path(&event.path)?;
-                
-                if let Some(old) = old_entry {
-                    Some(FileChange {
-                        path: event.path,
-                        change_type: ChangeType::Deleted,
-                        old_entry: Some(old.data),
-                        new_entry: None,
-                        detected_at: event.timestamp,
-                    })
-                } else {
+        // Owner and ACL (Windows-specific)
+        let (owner_sid, dacl) = match link_target {
+            Some(_) => (None, None),
+            None => crate::acl::read_security(path)
+                .unwrap_or_else(|e| {
+                    warn!("Failed to read the owner and ACL of {}: {:#}", path.display(), e);
                     None
-                }
-            }
-            _ => None,
+                })
+                .map(|security| (Some(security.owner_sid), Some(security.dacl)))
+                .unwrap_or_default(),
         };
 
-        if let Some(change) = change {
-            self.handle_file_change(&change);
+        if self.owner_excluded(&metadata, owner_sid.as_deref()) {
+            debug!("{} is owned by an excluded user or group", path.display());
+            return Ok(None);
         }
 
-        Ok(())
-    }
-
-    /// Scan a single file and return entry data
-    fn scan_single_file(&self, path: &Path) -> Result<(FimEntry, u64)> {
-        let metadata = fs::metadata(path)
-            .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
-
         // Check file size limit
         if let Some(max_size) = self.config.max_file_size {
-            if metadata.len() > max_size {
+            if !is_dir && metadata.len() > max_size {
                 return Err(anyhow::anyhow!(
                     "File {} exceeds size limit ({} > {})",
                     path.display(),
@@ -458,18 +807,70 @@ impl FimEngine {
             }
         }
 
-        // Get file times
-        let mtime = metadata.modified()
-            .map(|t| DateTime::from(t))
-            .unwrap_or_else(|_| Utc::now());
+        // Hash the file; directories only have metadata and links their target path
+        let mut unstable = false;
+        let (hashes, decompressed) = if let Some(target) = &link_target {
+            (self.hasher.hash_bytes(target.to_string_lossy().as_bytes())?, None)
+        } else if is_dir {
+            let hashes = FileHashes {
+                blake3: String::new(),
+                sha256: None,
+                sha1: None,
+                md5: None,
+                sha512: None,
+                sha3_256: None,
+                xxh3: None,
+                sampled: false,
+                content_class: ContentClass::Empty,
+            };
+            (hashes, None)
+        } else {
+            let mut attempts = 0;
+            loop {
+                let hashed = self.config.open_retry
+                    .run(|| {
+                        let _permit = self.open_files.acquire();
+                        self.hash_path(path)
+                    })
+                    .with_context(|| format!("Failed to hash file {}", path.display()))?;
+                #[cfg(test)]
+                if let Some(after_hash) = self.after_hash {
+                    after_hash(path);
+                }
+
+                // A file written to while being hashed may give a torn hash
+                let after = fs::metadata(path)
+                    .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
+                // Appending to an append-only file leaves its prefix alone
+                let stable = self.append_prefix(path).is_some()
+                    || (after.len() == metadata.len()
+                        && after.modified().ok() == metadata.modified().ok());
+                metadata = after;
+                if stable {
+                    break hashed;
+                }
+                if attempts >= self.config.unstable_rehash_attempts {
+                    warn!("{} changed while being hashed; recording it as unstable", path.display());
+                    unstable = true;
+                    break hashed;
+                }
+                attempts += 1;
+                debug!("{} changed while being hashed, re-hashing", path.display());
+            }
+        };
+
+        // Get file times, at the whole-second resolution the database keeps;
+        // the sub-second part goes in its own field
+        let modified = metadata.modified().map(DateTime::<Utc>::from);
+        let mtime = modified.as_ref()
+            .map_or_else(|_| Utc::now(), |modified| *modified)
+            .trunc_subsecs(0);
+        let mtime_nsec = modified.ok().map(|modified| modified.timestamp_subsec_nanos());
         
         let ctime = metadata.created()
-            .map(|t| DateTime::from(t))
-            .unwrap_or_else(|_| Utc::now());
-
-        // Hash the file
-        let hashes = self.hasher.hash_file(path)
-            .with_context(|| format!("Failed to hash file {}", path.display()))?;
+            .map(DateTime::from)
+            .unwrap_or_else(|_| Utc::now())
+            .trunc_subsecs(0);
 
         // Get file permissions and ownership (Unix-specific)
         #[cfg(unix)]
@@ -478,21 +879,34 @@ impl FimEngine {
             (
                 metadata.uid(),
                 metadata.gid(),
-                format!("{:o}", metadata.mode() & 0o777),
+                format!("{:o}", metadata.mode() & 0o7777),
             )
         };
 
         #[cfg(not(unix))]
         let (uid, gid, perm) = (0, 0, "644".to_string());
 
+        let xattr_hash = match link_target {
+            Some(_) => None,
+            None if self.config.monitor_xattrs => crate::xattrs::read_xattr_hash(path).unwrap_or_else(|e| {
+                warn!("Failed to read extended attributes of {}: {:#}", path.display(), e);
+                None
+            }),
+            None => None,
+        };
+
+        let size = if is_dir { 0 } else { metadata.len() };
         let entry_data = FimEntryData {
-            size: metadata.len(),
+            size,
             perm,
             uid,
             gid,
             md5: hashes.md5,
             sha1: hashes.sha1,
             sha256: hashes.sha256,
+            sha512: hashes.sha512,
+            sha3_256: hashes.sha3_256,
+            xxh3: hashes.xxh3,
             blake3: hashes.blake3,
             mtime,
             ctime,
@@ -516,244 +930,5522 @@ impl FimEngine {
                 0
             },
             scanned: true,
+            capabilities: match link_target {
+                Some(_) => None,
+                // Unreadable capabilities should not cost the file its content hash
+                None => crate::capabilities::read_capabilities(path).unwrap_or_else(|e| {
+                    warn!("Failed to read capabilities of {}: {:#}", path.display(), e);
+                    None
+                }),
+            },
+            content_type: (!is_dir && link_target.is_none()).then_some(hashes.content_class),
+            decompressed,
+            is_dir,
+            unstable,
+            link_target,
+            sampled: hashes.sampled,
+            owner_sid,
+            dacl,
+            xattr_hash,
+            mtime_nsec,
+            change_time_ns: change_time_ns(&metadata),
         };
 
-        Ok((FimEntry {
+        Ok(Some((FimEntry {
             path: path.to_path_buf(),
             data: entry_data,
-        }, metadata.len()))
+            created_at: None,
+            updated_at: None,
+        }, size)))
     }
 
-    /// Check for changes in a file
-    fn check_file_changes(&mut self, path: &Path) -> Result<Option<FileChange>> {
-        if !path.exists() {
-            // File was deleted
-            if let Some(old_entry) = self.database.get_path(path)? {
-                self.database.remove_path(path)?;
-                return Ok(Some(FileChange {
-                    path: path.to_path_buf(),
-                    change_type: ChangeType::Deleted,
-                    old_entry: Some(old_entry.data),
-                    new_entry: None,
-                    detected_at: Utc::now(),
-                }));
+    /// Whether the file's owner or group is excluded from monitoring
+    fn owner_excluded(&self, metadata: &fs::Metadata, owner_sid: Option<&str>) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if self.config.exclude_uids.contains(&metadata.uid())
+                || self.config.exclude_gids.contains(&metadata.gid())
+            {
+                return true;
             }
-            return Ok(None);
         }
+        #[cfg(not(unix))]
+        let _ = metadata;
 
-        let old_entry = self.database.get_path(path)?;
-        let (new_entry, _) = self.scan_single_file(path)?;
+        owner_sid.is_some_and(|owner_sid| {
+            self.config.exclude_owner_sids.iter().any(|sid| sid.eq_ignore_ascii_case(owner_sid))
+        })
+    }
+
+    /// Prefix length of the append-only rule matching `path`, if any
+    fn append_prefix(&self, path: &Path) -> Option<u64> {
+        let path_str = path.to_string_lossy();
+        self.append_rules
+            .iter()
+            .find(|rule| rule.path_pattern.matches(&path_str))
+            .map(|rule| rule.prefix_bytes)
+    }
 
-        // Update database
-        self.database.insert_data(path, &new_entry.data)?;
+    /// Hash the first `len` bytes of a file
+    fn hash_prefix(&self, path: &Path, len: u64) -> Result<FileHashes> {
+        let file = self.hasher.open_file(path)
+            .with_context(|| format!("Failed to open file {}", path.display()))?;
+        let mut content = Vec::new();
+        file.take(len).read_to_end(&mut content)?;
+        self.hasher.hash_bytes(&content)
+    }
 
-        match old_entry {
-            Some(old) => {
-                // File existed, check for changes
-                let change_type = self.detect_change_type(&old.data, &new_entry.data);
-                
-                if let Some(change_type) = change_type {
-                    Ok(Some(FileChange {
-                        path: path.to_path_buf(),
-                        change_type,
-                        old_entry: Some(old.data),
-                        new_entry: Some(new_entry.data),
-                        detected_at: Utc::now(),
-                    }))
-                } else {
-                    Ok(None) // No changes
-                }
+    /// Hash a file, decompressing it and applying any matching content
+    /// normalizers first, or fingerprint it when it opted into sampling
+    ///
+    /// Also returns the compression removed, if any.
+    fn hash_path(&self, path: &Path) -> Result<(FileHashes, Option<Compression>)> {
+        if let Some(prefix_bytes) = self.append_prefix(path) {
+            return Ok((self.hash_prefix(path, prefix_bytes)?, None));
+        }
+
+        let path_str = path.to_string_lossy();
+        if let Some(sampling) = &self.config.hash_config.sampling {
+            if self.sampling_patterns.iter().any(|pattern| pattern.matches(&path_str)) {
+                return Ok((self.hasher.hash_file_sampled(path, sampling.sample_bytes)?, None));
             }
+        }
+
+        let decompress = self.decompress_rules
+            .iter()
+            .find(|rule| rule.path_pattern.matches(&path_str));
+        let mut matching = self.normalizers
+            .iter()
+            .filter(|n| n.path_pattern.matches(&path_str))
+            .peekable();
+
+        if decompress.is_none() && matching.peek().is_none() {
+            return Ok((self.hasher.hash_file(path)?, None));
+        }
+
+        let mut file = self.hasher.open_file(path)
+            .with_context(|| format!("Failed to open file {}", path.display()))?;
+        let (mut content, compression) = match decompress {
+            Some(rule) => Compression::read_content(file, rule.max_size),
             None => {
-                // New file
-                Ok(Some(FileChange {
-                    path: path.to_path_buf(),
-                    change_type: ChangeType::Added,
-                    old_entry: None,
-                    new_entry: Some(new_entry.data),
-                    detected_at: Utc::now(),
-                }))
+                let mut content = Vec::new();
+                file.read_to_end(&mut content)
+                    .map(|_| (content, None))
+                    .map_err(Into::into)
             }
         }
+        .with_context(|| format!("Failed to read file {}", path.display()))?;
+        for normalizer in matching {
+            content = normalizer.regex
+                .replace_all(&content, normalizer.replacement.as_slice())
+                .into_owned();
+        }
+
+        Ok((self.hasher.hash_bytes(&content)?, compression))
     }
+}
 
-    /// Detect the type of change between old and new entries
-    fn detect_change_type(&self, old: &FimEntryData, new: &FimEntryData) -> Option<ChangeType> {
-        if old.blake3 != new.blake3 {
-            Some(ChangeType::HashChanged)
-        } else if old.size != new.size {
-            Some(ChangeType::SizeChanged)
-        } else if old.perm != new.perm || old.uid != new.uid || old.gid != new.gid {
-            Some(ChangeType::PermissionChanged)
-        } else if old.mtime != new.mtime || old.ctime != new.ctime {
-            Some(ChangeType::TimestampChanged)
-        } else {
-            None // No significant changes
+/// FIM scan results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanResults {
+    pub files_scanned: u64,
+    pub files_added: u64,
+    pub files_modified: u64,
+    pub files_deleted: u64,
+    pub errors: u64,
+    #[serde(with = "duration_serde")]
+    pub scan_duration: Duration,
+    pub total_size: u64,
+    /// Change handler failures during this scan
+    #[serde(default)]
+    pub handler_errors: u64,
+    /// Deepest the hasher-to-writer queue got, for tuning `write_queue_depth`
+    #[serde(default)]
+    pub write_queue_high_water: usize,
+    /// Files left out because their owner or group is excluded
+    #[serde(default)]
+    pub files_skipped: u64,
+    /// Files not re-hashed because their metadata was unchanged (see
+    /// [`FimConfig::trust_mtime`])
+    #[serde(default)]
+    pub files_hash_skipped: u64,
+    /// Time spent walking the monitored paths for files to scan
+    #[serde(default, with = "duration_serde")]
+    pub walk_duration: Duration,
+    /// Time spent hashing, as seen by the thread writing to the database
+    ///
+    /// Parallel hashing overlaps database writes, so this is the time the
+    /// writer waited on hashes rather than the CPU time of every thread.
+    #[serde(default, with = "duration_serde")]
+    pub hash_duration: Duration,
+    /// Time spent reading and writing the database
+    #[serde(default, with = "duration_serde")]
+    pub db_duration: Duration,
+    /// Whether the scan visited every file; `false` when it was stopped early
+    #[serde(default = "default_true")]
+    pub completed: bool,
+}
+
+/// Point-in-time engine counters, accumulated over every scan since the
+/// engine was created
+///
+/// Meant for batch and cron jobs that dump metrics once after a scan instead
+/// of running a scrape endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub captured_at: DateTime<Utc>,
+    pub scans_completed: u64,
+    pub files_scanned: u64,
+    pub files_added: u64,
+    pub files_modified: u64,
+    pub files_deleted: u64,
+    pub bytes_scanned: u64,
+    pub errors: u64,
+    pub handler_errors: u64,
+    #[serde(with = "duration_serde")]
+    pub scan_duration_total: Duration,
+    #[serde(with = "duration_serde")]
+    pub last_scan_duration: Duration,
+    /// Real-time events received but not yet processed
+    pub watcher_queue_depth: usize,
+    /// Paths waiting out their coalescing window
+    pub pending_coalesced_events: usize,
+    /// Detected changes by [`ChangeType::name`], from scans and real-time events
+    #[serde(default)]
+    pub changes_by_type: BTreeMap<String, u64>,
+    /// Real-time events dropped by the watcher's rate limit
+    #[serde(default)]
+    pub events_throttled: u64,
+    /// Changes a lagging subscriber missed (see [`FimEngine::subscribe`])
+    #[serde(default)]
+    pub dropped_changes: u64,
+}
+
+/// Progress of a running scan, passed to the handler set with
+/// [`FimEngine::set_progress_handler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanProgress {
+    /// Files processed so far, including those skipped or failed
+    pub files_done: u64,
+    /// Files found by the walk of the monitored paths
+    pub files_total: u64,
+    /// Size of the files processed so far, including those skipped or failed
+    pub bytes_done: u64,
+    /// Time left at the file rate so far; `None` before the first file is done
+    pub estimated_remaining: Option<Duration>,
+}
+
+/// File integrity change types
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub enum ChangeType {
+    Added,
+    Modified,
+    Deleted,
+    PermissionChanged,
+    SizeChanged,
+    HashChanged,
+    TimestampChanged,
+    /// Security-relevant attributes such as Linux file capabilities or
+    /// other extended attributes changed
+    AttributeChanged,
+    /// Content switched between text, binary and empty, e.g. a config file
+    /// replaced by a binary
+    ContentClassChanged,
+    /// The filesystem mounted at a directory disappeared or was replaced; the
+    /// entries below it are kept instead of being reported as deleted
+    MountChanged,
+    /// A recorded file can no longer be stat'ed or read by the monitor; its
+    /// entry is kept (see [`FimConfig::report_access_loss`])
+    AccessLost,
+    /// A recorded file was renamed; its entry moved with it
+    Moved { from: PathBuf, to: PathBuf },
+}
+
+impl ChangeType {
+    /// Name of the change type without any data it carries, e.g. `Moved`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeType::Added => "Added",
+            ChangeType::Modified => "Modified",
+            ChangeType::Deleted => "Deleted",
+            ChangeType::PermissionChanged => "PermissionChanged",
+            ChangeType::SizeChanged => "SizeChanged",
+            ChangeType::HashChanged => "HashChanged",
+            ChangeType::TimestampChanged => "TimestampChanged",
+            ChangeType::AttributeChanged => "AttributeChanged",
+            ChangeType::ContentClassChanged => "ContentClassChanged",
+            ChangeType::MountChanged => "MountChanged",
+            ChangeType::AccessLost => "AccessLost",
+            ChangeType::Moved { .. } => "Moved",
         }
     }
+}
 
-    /// Handle detected file change
-    fn handle_file_change(&self, change: &FileChange) {
-        info!("File change detected: {:?} - {}", change.change_type, change.path.display());
-        
-        // Notify all registered handlers
-        for handler in &self.change_handlers {
-            handler(change);
+/// File change record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub change_type: ChangeType,
+    pub old_entry: Option<FimEntryData>,
+    pub new_entry: Option<FimEntryData>,
+    pub detected_at: DateTime<Utc>,
+    /// Severity assigned by the engine's [`SeverityRules`]
+    #[serde(default)]
+    pub severity: AlertSeverity,
+    /// Whether the engine's [`SeverityRules`] consider this change critical
+    #[serde(default)]
+    pub is_critical: bool,
+    /// Correlation id, carried into the [`Alert`](crate::reporting::Alert) and
+    /// change log row for this change (empty for changes recorded before ids)
+    #[serde(default)]
+    pub change_id: String,
+}
+
+impl FileChange {
+    /// Fresh id for a new change: 128 bits, unique across processes and time
+    pub fn new_id() -> String {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&std::process::id().to_le_bytes());
+        hasher.update(&COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+        let now = Utc::now();
+        hasher.update(&now.timestamp().to_le_bytes());
+        hasher.update(&now.timestamp_subsec_nanos().to_le_bytes());
+        hasher.finalize().to_hex()[..32].to_string()
+    }
+
+    /// Fields that differ between the old and new entry
+    ///
+    /// Empty for additions and deletions, which have only one side.
+    pub fn field_changes(&self) -> Vec<FieldChange> {
+        match (&self.old_entry, &self.new_entry) {
+            (Some(old), Some(new)) => old.diff(new),
+            _ => Vec::new(),
         }
     }
+}
 
-    /// Collect all files to scan based on configuration
-    fn collect_files_to_scan(&self) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
-        
-        for monitor_path in &self.config.monitor_paths {
-            self.collect_files_recursive(monitor_path, &mut files)?;
+/// Result of checking one file against its recorded entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyOutcome {
+    /// Hashes, size and permissions all match the baseline
+    Match,
+    /// At least one verified field differs; each carries its old and new value
+    Mismatch { fields: Vec<FieldChange> },
+    /// The path has no entry in the database
+    NotInBaseline,
+    /// The path is recorded but no longer exists
+    Missing,
+    /// The path is recorded but can no longer be reached or read
+    AccessLost,
+    /// The file is owned by an excluded user or group, so it is not checked
+    ExcludedOwner,
+}
+
+impl VerifyOutcome {
+    /// Whether the file still matches its baseline
+    pub fn is_match(&self) -> bool {
+        matches!(self, VerifyOutcome::Match)
+    }
+}
+
+/// Fields compared by [`FimEngine::verify_path`]: content hashes, size,
+/// permissions and ownership
+const VERIFY_FIELDS: &[&str] = &[
+    "size", "perm", "uid", "gid", "owner_sid", "dacl", "md5", "sha1", "sha256", "sha512", "sha3_256",
+    "xxh3", "blake3",
+];
+
+/// Baseline snapshot: every entry keyed by path
+pub type Baseline = BTreeMap<PathBuf, FimEntryData>;
+
+/// Maximum number of operations produced by [`FimEngine::diff_baselines_jsonpatch`]
+pub const JSONPATCH_MAX_OPS: usize = 100_000;
+
+/// Changes buffered per [`FimEngine::subscribe`] receiver
+pub const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Longest the real-time loop waits for an event before checking whether the
+/// engine was stopped
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Least time between two calls of the progress handler during a scan
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Files below a set of roots, walked one directory at a time
+///
+/// Paths come out in the order of their strings, the order the database
+/// keeps them in: a directory's contents follow every sibling sorting
+/// between its name and its name plus a separator, so `a`, `a.txt` and then
+/// `a/b`. Only the unvisited paths of the directories being walked are held.
+struct FileWalk<'e, S: FimStore> {
+    engine: &'e FimEngine<S>,
+    /// Pending steps of each directory being walked, the next one last
+    stack: Vec<Vec<WalkStep>>,
+    /// Directories already walked, so a symlink loop followed under
+    /// [`SymlinkPolicy::Follow`] ends instead of recursing forever
+    #[cfg(unix)]
+    visited: HashSet<(u64, u64)>,
+}
+
+enum WalkStep {
+    /// Yield the path
+    Visit(PathBuf),
+    /// List the directory's contents
+    Descend(PathBuf),
+}
+
+impl<'e, S: FimStore> FileWalk<'e, S> {
+    fn new(engine: &'e FimEngine<S>, roots: &[PathBuf]) -> Self {
+        let mut walk = Self {
+            engine,
+            stack: Vec::new(),
+            #[cfg(unix)]
+            visited: HashSet::new(),
+        };
+        let mut steps = Vec::new();
+        for root in roots {
+            walk.add_steps(root.clone(), &mut steps);
         }
-        
-        // Remove duplicates and sort
-        files.sort();
-        files.dedup();
-        
-        Ok(files)
+        walk.push_frame(steps);
+        walk
     }
 
-    /// Recursively collect files from a directory
-    fn collect_files_recursive(&self, path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-        if self.should_ignore_path(path) {
-            return Ok(());
+    /// Steps for a path found in a directory or given as a root
+    fn add_steps(&mut self, path: PathBuf, steps: &mut Vec<WalkStep>) {
+        let ignored = self.engine.should_ignore_path(&path);
+        if ignored && !self.engine.may_include_below(&path) {
+            return;
         }
 
         if path.is_file() {
-            files.push(path.to_path_buf());
+            steps.push(WalkStep::Visit(path));
         } else if path.is_dir() {
-            let entries = fs::read_dir(path)
-                .with_context(|| format!("Failed to read directory {}", path.display()))?;
-
-            for entry in entries {
-                let entry = entry?;
-                let path = entry.path();
-                
-                if path.is_file() {
-                    if !self.should_ignore_path(&path) {
-                        files.push(path);
-                    }
-                } else if path.is_dir() {
-                    self.collect_files_recursive(&path, files)?;
-                }
+            if self.walked(&path, false) {
+                debug!("Skipping {}: directory already walked", path.display());
+                return;
             }
+            if self.engine.config.monitor_directories && !ignored {
+                steps.push(WalkStep::Visit(path.clone()));
+            }
+            steps.push(WalkStep::Descend(path));
         }
-
-        Ok(())
     }
 
-    /// Check if path should be ignored
-    fn should_ignore_path(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
-        
-        for pattern in &self.config.exclude_patterns {
-            if glob::Pattern::new(pattern)
-                .map(|p| p.matches(&path_str))
-                .unwrap_or(false)
-            {
-                return true;
+    /// Whether `dir` was already walked, marking it walked when `mark` is set
+    ///
+    /// Directories are marked when listed rather than when found, so a
+    /// monitored path below another is still walked in place.
+    fn walked(&mut self, dir: &Path, mark: bool) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if let Ok(metadata) = fs::metadata(dir) {
+                let id = (metadata.dev(), metadata.ino());
+                return if mark { !self.visited.insert(id) } else { self.visited.contains(&id) };
             }
         }
-        
+        #[cfg(not(unix))]
+        let _ = (dir, mark);
         false
     }
 
-    /// Get FIM statistics
-    pub fn get_stats(&self) -> Result<FimStats> {
-        self.database.get_stats()
-    }
+    /// Steps for the contents of a directory
+    fn list(&mut self, dir: &Path) -> Result<Vec<WalkStep>> {
+        if self.walked(dir, true) {
+            debug!("Skipping {}: directory already walked", dir.display());
+            return Ok(Vec::new());
+        }
+        let entries = match fs::read_dir(dir) {
+            Err(e) if self.engine.config.report_access_loss && e.kind() == std::io::ErrorKind::PermissionDenied => {
+                warn!("Skipping unreadable directory {}", dir.display());
+                return Ok(Vec::new());
+            }
+            entries => entries.with_context(|| format!("Failed to read directory {}", dir.display()))?,
+        };
 
-    /// Verify database integrity
-    pub fn verify_integrity(&self) -> Result<String> {
-        self.database.get_data_checksum()
+        let mut steps = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_symlink() {
+                match self.engine.config.symlink_policy {
+                    SymlinkPolicy::Ignore => continue,
+                    SymlinkPolicy::RecordLink => {
+                        if !self.engine.should_ignore_path(&path) {
+                            steps.push(WalkStep::Visit(path));
+                        }
+                        continue;
+                    }
+                    SymlinkPolicy::Follow => {}
+                }
+            }
+            self.add_steps(path, &mut steps);
+        }
+        Ok(steps)
     }
 
-    /// Export database to JSON
-    pub fn export_database(&self, output_path: &Path) -> Result<()> {
-        // Implementation would export the database contents
-        // This is a placeholder for the actual implementation
-        info!("Exporting database to {}", output_path.display());
-        Ok(())
+    /// Queue `steps` in path string order, a directory's contents sorting
+    /// as its name followed by a separator
+    fn push_frame(&mut self, mut steps: Vec<WalkStep>) {
+        let key = |step: &WalkStep| match step {
+            WalkStep::Visit(path) => path.to_string_lossy().into_owned(),
+            WalkStep::Descend(path) => format!("{}{}", path.to_string_lossy(), std::path::MAIN_SEPARATOR),
+        };
+        steps.sort_by_cached_key(|step| std::cmp::Reverse(key(step)));
+        self.stack.push(steps);
     }
 }
 
-impl Drop for FimEngine {
-    fn drop(&mut self) {
-        self.stop();
+impl<S: FimStore> Iterator for FileWalk<'_, S> {
+    type Item = Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.pop() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(WalkStep::Visit(path)) => return Some(Ok(path)),
+                Some(WalkStep::Descend(dir)) => match self.list(&dir) {
+                    Ok(steps) => self.push_frame(steps),
+                    Err(e) => {
+                        self.stack.clear();
+                        return Some(Err(e));
+                    }
+                },
+            }
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// Entries read from a store a page at a time, in path string order
+struct EntryCursor<'a, S: FimStore> {
+    store: &'a S,
+    page: VecDeque<FimEntry>,
+    /// Path of the last entry read, where the next page starts
+    last: Option<PathBuf>,
+    exhausted: bool,
+}
+
+/// Entries an [`EntryCursor`] reads at once
+const ENTRY_PAGE_SIZE: usize = 1000;
+
+impl<'a, S: FimStore> EntryCursor<'a, S> {
+    fn new(store: &'a S) -> Self {
+        Self { store, page: VecDeque::new(), last: None, exhausted: false }
+    }
+
+    /// Next entry sorting before the visited path `upto`, or any next entry
+    /// when None; the entry of `upto` itself is passed over
+    fn next_unvisited(&mut self, upto: Option<&str>) -> Result<Option<FimEntry>> {
+        if self.page.is_empty() && !self.exhausted {
+            self.page = self.store.get_entries_after(self.last.as_deref(), ENTRY_PAGE_SIZE)?.into();
+            self.exhausted = self.page.len() < ENTRY_PAGE_SIZE;
+            if let Some(entry) = self.page.back() {
+                self.last = Some(entry.path.clone());
+            }
+        }
+
+        let Some(entry) = self.page.front() else { return Ok(None) };
+        match upto.map(|upto| (*entry.path.to_string_lossy()).cmp(upto)) {
+            Some(std::cmp::Ordering::Greater) => Ok(None),
+            Some(std::cmp::Ordering::Equal) => {
+                self.page.pop_front();
+                Ok(None)
+            }
+            _ => Ok(self.page.pop_front()),
+        }
+    }
+}
+
+/// Boxed change handler callback
+type ChangeHandler = Box<dyn Fn(&FileChange) -> Result<()> + Send + Sync>;
+type ProgressHandler = Arc<dyn Fn(ScanProgress) + Send + Sync>;
+#[cfg(test)]
+type HashHook = dyn Fn(&Path) + Send + Sync;
+
+/// Error a fallible change handler returns to ask the engine to stop processing
+///
+/// Scans stop at the current file without deleting unvisited entries, and the
+/// real-time loop exits as if [`FimEngine::stop`] had been called.
+#[derive(Debug, thiserror::Error)]
+#[error("change handler requested processing stop")]
+pub struct StopProcessing;
+
+/// Shared flag for cancelling a running scan from another thread or task
+///
+/// Cloning shares the flag. A cancelled scan stops at the current file just
+/// like one stopped by [`StopProcessing`].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask scans observing this token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Handle for stopping an engine from another thread, e.g. while a scan
+/// holds it borrowed
+///
+/// Stopping has the effect of [`FimEngine::stop`] on running scans and the
+/// real-time loop; the watcher itself is left for `stop` to shut down.
+#[derive(Debug, Clone)]
+pub struct StopHandle(Arc<Mutex<bool>>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        *self.0.lock().unwrap() = false;
+    }
+}
+
+/// Marks the engine running for the length of a scan, so a stop during the
+/// scan is seen even when the engine was never started
+struct ScanRunGuard {
+    is_running: Arc<Mutex<bool>>,
+    was_running: bool,
+}
+
+impl ScanRunGuard {
+    fn enter(is_running: &Arc<Mutex<bool>>) -> Self {
+        let was_running = std::mem::replace(&mut *is_running.lock().unwrap(), true);
+        Self { is_running: Arc::clone(is_running), was_running }
+    }
+}
+
+impl Drop for ScanRunGuard {
+    fn drop(&mut self) {
+        if !self.was_running {
+            *self.is_running.lock().unwrap() = false;
+        }
+    }
+}
+
+/// Database entries an incremental scan may report as deleted
+#[derive(Clone, Copy)]
+enum DeletionScope<'a> {
+    /// Every entry not visited by the scan
+    All,
+    /// Unvisited entries at or below one of these roots
+    Under(&'a [PathBuf]),
+    /// None; only the visited files are compared
+    Nothing,
+}
+
+/// Core FIM engine
+pub struct FimEngine<S: FimStore = FimDb> {
+    config: FimConfig,
+    database: S,
+    hasher: FileHasher,
+    /// Pool scanning files; the hasher splits large files on a pool of its
+    /// own, so work stolen while a scan holds an open-file permit never
+    /// waits for another permit
+    scan_pool: ThreadPool,
+    watcher: Option<FimWatcher>,
+    is_running: Arc<Mutex<bool>>,
+    change_handlers: Vec<ChangeHandler>,
+    /// Channels from [`FimEngine::subscribe`]; closed ones are pruned on send
+    subscribers: Mutex<Vec<tokio::sync::mpsc::Sender<FileChange>>>,
+    /// Changes not delivered to a subscriber whose buffer was full
+    dropped_changes: AtomicU64,
+    progress_handler: Option<ProgressHandler>,
+    normalizers: Vec<CompiledNormalizer>,
+    decompress_rules: Vec<CompiledDecompressRule>,
+    append_rules: Vec<CompiledAppendOnlyRule>,
+    sampling_patterns: Vec<glob::Pattern>,
+    severity_rules: CompiledSeverityRules,
+    exclude_patterns: CompiledPatternSet,
+    include_patterns: CompiledPatternSet,
+    database_files: Vec<PathBuf>,
+    handler_errors: AtomicU64,
+    stop_requested: AtomicBool,
+    held_mounts: HashSet<PathBuf>,
+    /// Paths with coalesced real-time events, and when to re-check them
+    pending_events: HashMap<PathBuf, Instant>,
+    /// Token of the cancellable scan in progress
+    cancel: Option<CancellationToken>,
+    /// Scan counters for [`FimEngine::metrics_snapshot`]
+    metrics: MetricsSnapshot,
+    /// Bound on files open at once while hashing
+    open_files: OpenFileLimiter,
+    /// Called after each file is hashed, so tests can write to it mid-scan
+    #[cfg(test)]
+    after_hash: Option<Box<HashHook>>,
+}
+
+impl FimEngine {
+    /// Create new FIM engine
+    pub fn new(config: FimConfig) -> Result<Self> {
+        let db_options = DbOptions {
+            page_size: config.db_page_size,
+            auto_vacuum: config.db_auto_vacuum,
+            encryption_key: config.db_encryption_key.clone(),
+            profile: Some(config.profile.clone()),
+        };
+        let mut database = if config.immutable_baseline {
+            if config.memory_database {
+                anyhow::bail!("An immutable baseline needs an on-disk database");
+            }
+            FimDb::open_read_only_with(config.database_file(), &db_options)?
+        } else {
+            let db_path = (!config.memory_database).then(|| config.database_file());
+            FimDb::open(db_path, &db_options)
+                .context("Failed to initialize database")?
+        };
+        database.set_record_history(config.record_history);
+        database.set_history_limit(config.history_limit);
+        database.set_severity_rules(config.severity_rules.compile()?);
+
+        if let Some(public_key) = &config.baseline_public_key {
+            let public_key: [u8; 32] = hex::decode(public_key)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow::anyhow!("baseline_public_key must be 32 hex-encoded bytes"))?;
+            database.verify_baseline_signature(&public_key)?;
+        }
+
+        Self::with_store(config, database)
+    }
+
+    /// Diff two baselines as an RFC 6902 JSON Patch
+    ///
+    /// The patch applies to a JSON object mapping each path to its serialized
+    /// [`FimEntryData`]: new paths become `add`, vanished paths `remove`, and
+    /// changed fields of a surviving entry one `replace` each. The `scanned`
+    /// bookkeeping flag is ignored. Fails rather than building an unbounded
+    /// document once the patch exceeds [`JSONPATCH_MAX_OPS`] operations.
+    pub fn diff_baselines_jsonpatch(old: &Baseline, new: &Baseline) -> Result<serde_json::Value> {
+        let mut ops = Vec::new();
+        let mut push = |op: serde_json::Value| -> Result<()> {
+            if ops.len() >= JSONPATCH_MAX_OPS {
+                return Err(anyhow::anyhow!(
+                    "Baseline diff exceeds {} operations",
+                    JSONPATCH_MAX_OPS
+                ));
+            }
+            ops.push(op);
+            Ok(())
+        };
+
+        for (path, old_data) in old {
+            let pointer = json_pointer_token(path);
+            match new.get(path) {
+                None => push(json!({ "op": "remove", "path": format!("/{}", pointer) }))?,
+                Some(new_data) => {
+                    let old_value = serde_json::to_value(old_data)?;
+                    let new_value = serde_json::to_value(new_data)?;
+                    if let (Some(old_fields), Some(new_fields)) = (old_value.as_object(), new_value.as_object()) {
+                        for (field, value) in new_fields {
+                            if field != "scanned" && old_fields.get(field) != Some(value) {
+                                push(json!({
+                                    "op": "replace",
+                                    "path": format!("/{}/{}", pointer, field),
+                                    "value": value,
+                                }))?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (path, new_data) in new {
+            if !old.contains_key(path) {
+                push(json!({
+                    "op": "add",
+                    "path": format!("/{}", json_pointer_token(path)),
+                    "value": new_data,
+                }))?;
+            }
+        }
+
+        Ok(serde_json::Value::Array(ops))
+    }
+}
+
+impl<S: FimStore> FimEngine<S> {
+    /// Create a FIM engine keeping its baseline in `database`
+    ///
+    /// The `memory_database`, `db_page_size`, `db_auto_vacuum`,
+    /// `db_encryption_key` and `profile` settings only apply to the SQLite
+    /// store created by [`FimEngine::new`].
+    pub fn with_store(config: FimConfig, database: S) -> Result<Self> {
+        let database_files = database.own_files();
+        
+        // Dedicated pools so embedding applications keep the global rayon pool
+        let thread_count = config.scan_threads
+            .unwrap_or_else(num_cpus::get);
+        let pool = |name: &'static str| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(thread_count)
+                .thread_name(move |index| format!("fim-{}-{}", name, index))
+                .build()
+                .context("Failed to configure thread pool")
+        };
+        let scan_pool = pool("scan")?;
+        let hasher = FileHasher::new(config.hash_config.clone())
+            .with_thread_pool(Arc::new(pool("hash")?));
+
+        let normalizers = config.content_normalizers
+            .iter()
+            .map(CompiledNormalizer::compile)
+            .collect::<Result<Vec<_>>>()?;
+        let decompress_rules = config.decompress_rules
+            .iter()
+            .map(CompiledDecompressRule::compile)
+            .collect::<Result<Vec<_>>>()?;
+        let severity_rules = config.severity_rules.compile()?;
+        if config.monitor_xattrs && !cfg!(feature = "xattrs") {
+            anyhow::bail!("Extended attribute monitoring needs rusty-fim built with the `xattrs` feature");
+        }
+        let append_rules = config.append_only
+            .iter()
+            .map(CompiledAppendOnlyRule::compile)
+            .collect::<Result<Vec<_>>>()?;
+        if config.hash_config.sampling.as_ref().is_some_and(|sampling| sampling.sample_bytes == 0) {
+            anyhow::bail!("Sampling sample_bytes must be at least 1");
+        }
+        let sampling_patterns = config.hash_config.sampling
+            .iter()
+            .flat_map(|sampling| &sampling.path_patterns)
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("Invalid sampling path pattern: {}", pattern))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let exclude_patterns = CompiledPatternSet::compile(
+            &config.exclude_patterns,
+            config.case_insensitive_patterns,
+            "exclude",
+        )?;
+        let include_patterns = CompiledPatternSet::compile(
+            &config.include_patterns,
+            config.case_insensitive_patterns,
+            "include",
+        )?;
+        let open_files = OpenFileLimiter::new(
+            config.max_open_files.unwrap_or_else(default_max_open_files),
+        );
+        
+        let mut watch_config = config.watch_config.clone();
+        watch_config.paths = config.monitor_paths.clone();
+        
+        let watcher = if config.enable_realtime {
+            Some(FimWatcher::new(watch_config)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            config,
+            database,
+            hasher,
+            scan_pool,
+            watcher,
+            is_running: Arc::new(Mutex::new(false)),
+            change_handlers: Vec::new(),
+            subscribers: Mutex::new(Vec::new()),
+            dropped_changes: AtomicU64::new(0),
+            progress_handler: None,
+            normalizers,
+            decompress_rules,
+            append_rules,
+            sampling_patterns,
+            severity_rules,
+            exclude_patterns,
+            include_patterns,
+            database_files,
+            handler_errors: AtomicU64::new(0),
+            stop_requested: AtomicBool::new(false),
+            held_mounts: HashSet::new(),
+            pending_events: HashMap::new(),
+            cancel: None,
+            metrics: MetricsSnapshot::default(),
+            open_files,
+            #[cfg(test)]
+            after_hash: None,
+        })
+    }
+
+    /// Add change handler callback
+    pub fn add_change_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&FileChange) + Send + Sync + 'static,
+    {
+        self.change_handlers.push(Box::new(move |change| {
+            handler(change);
+            Ok(())
+        }));
+    }
+
+    /// Add fallible change handler callback
+    ///
+    /// Errors are logged and counted (see [`FimEngine::handler_errors`]) without
+    /// interrupting other handlers. Returning [`StopProcessing`] stops the
+    /// current scan or real-time loop instead.
+    pub fn add_change_handler_fallible<F>(&mut self, handler: F)
+    where
+        F: Fn(&FileChange) -> Result<()> + Send + Sync + 'static,
+    {
+        self.change_handlers.push(Box::new(handler));
+    }
+
+    /// Receive every detected change on a channel, for async consumers
+    ///
+    /// Changes are sent after the change handlers run. The channel buffers
+    /// [`SUBSCRIBER_BUFFER`] changes; while a lagging receiver's buffer is full,
+    /// further changes to it are dropped and counted in
+    /// [`FimEngine::dropped_changes`] rather than stalling scans or real-time
+    /// monitoring. Dropping the receiver unsubscribes it.
+    pub fn subscribe(&mut self) -> tokio::sync::mpsc::Receiver<FileChange> {
+        self.subscribe_with_capacity(SUBSCRIBER_BUFFER)
+    }
+
+    /// [`FimEngine::subscribe`] with a buffer of `capacity` changes (minimum 1)
+    pub fn subscribe_with_capacity(&mut self, capacity: usize) -> tokio::sync::mpsc::Receiver<FileChange> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(capacity.max(1));
+        self.subscribers.get_mut().unwrap().push(sender);
+        receiver
+    }
+
+    /// Report progress of baseline and incremental scans to `handler`
+    ///
+    /// The handler runs on the thread writing to the database after the first
+    /// file, then at most once per [`PROGRESS_INTERVAL`] and once more when
+    /// the last file is done, so it should return quickly. Replaces any
+    /// previous progress handler.
+    pub fn set_progress_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(ScanProgress) + Send + Sync + 'static,
+    {
+        self.progress_handler = Some(Arc::new(handler));
+    }
+
+    /// Changes dropped because a subscriber's buffer was full
+    pub fn dropped_changes(&self) -> u64 {
+        self.dropped_changes.load(Ordering::Relaxed)
+    }
+
+    /// Total change handler failures since the engine was created
+    pub fn handler_errors(&self) -> u64 {
+        self.handler_errors.load(Ordering::Relaxed)
+    }
+
+    /// Whether a change handler has requested processing stop
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested.load(Ordering::Relaxed)
+    }
+
+    /// Whether the current scan should end early, by handler request,
+    /// cancellation or [`FimEngine::stop`]
+    fn should_stop(&self) -> bool {
+        self.stop_requested()
+            || self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled)
+            || !*self.is_running.lock().unwrap()
+    }
+
+    /// Counters accumulated over all scans so far, plus current queue depths
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            captured_at: Utc::now(),
+            handler_errors: self.handler_errors(),
+            watcher_queue_depth: self.watcher.as_ref().map_or(0, FimWatcher::queued_events),
+            pending_coalesced_events: self.pending_events.len(),
+            events_throttled: self.watcher.as_ref().map_or(0, FimWatcher::throttled_events),
+            dropped_changes: self.dropped_changes(),
+            ..self.metrics.clone()
+        }
+    }
+
+    /// Metrics in the Prometheus text exposition format, for a scrape endpoint
+    pub fn metrics_text(&self) -> String {
+        crate::metrics::render_prometheus(&self.metrics_snapshot())
+    }
+
+    /// Count a detected change in the engine metrics
+    fn record_change_metric(&mut self, change: &FileChange) {
+        *self.metrics.changes_by_type.entry(change.change_type.name().to_string()).or_default() += 1;
+    }
+
+    /// Fold a finished scan into the engine metrics
+    fn record_scan_metrics(&mut self, results: &ScanResults) {
+        let metrics = &mut self.metrics;
+        metrics.scans_completed += 1;
+        metrics.files_scanned += results.files_scanned;
+        metrics.files_added += results.files_added;
+        metrics.files_modified += results.files_modified;
+        metrics.files_deleted += results.files_deleted;
+        metrics.bytes_scanned += results.total_size;
+        metrics.errors += results.errors;
+        metrics.scan_duration_total += results.scan_duration;
+        metrics.last_scan_duration = results.scan_duration;
+    }
+
+    /// Start the FIM engine
+    pub fn start(&mut self) -> Result<()> {
+        *self.is_running.lock().unwrap() = true;
+        self.stop_requested.store(false, Ordering::Relaxed);
+        
+        info!("Starting FIM engine");
+        
+        // Start real-time watcher if enabled
+        if let Some(ref mut watcher) = self.watcher {
+            watcher.start().context("Failed to start filesystem watcher")?;
+            info!("Real-time monitoring enabled");
+        }
+
+        Ok(())
+    }
+
+    /// Handle for stopping the engine from another thread
+    ///
+    /// A scan stopped this way returns early with
+    /// [`ScanResults::completed`] unset; [`FimEngine::resume_scan`] finishes
+    /// an interrupted baseline.
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(Arc::clone(&self.is_running))
+    }
+
+    /// Stop the FIM engine
+    pub fn stop(&mut self) {
+        *self.is_running.lock().unwrap() = false;
+        
+        if let Some(ref mut watcher) = self.watcher {
+            watcher.stop();
+        }
+        
+        info!("FIM engine stopped");
+    }
+
+    /// Perform baseline scan
+    ///
+    /// Checks for [`FimEngine::stop`] after every file. A stopped scan commits
+    /// what it has hashed, keeps entries it has not reached yet and returns
+    /// with [`ScanResults::completed`] unset; [`FimEngine::resume_scan`] picks
+    /// up where it left off.
+    pub fn baseline_scan(&mut self) -> Result<ScanResults> {
+        self.run_baseline(false)
+    }
+
+    /// Finish an interrupted baseline scan, hashing only the files it did not
+    /// reach
+    ///
+    /// Files whose entries the interrupted scan already marked scanned are
+    /// skipped; entries still unscanned at the end are deleted as in a full
+    /// baseline. Safe to call in a new process after the previous one was
+    /// killed, since the scan commits every 1000 files.
+    pub fn resume_scan(&mut self) -> Result<ScanResults> {
+        self.run_baseline(true)
+    }
+
+    fn run_baseline(&mut self, resume: bool) -> Result<ScanResults> {
+        self.ensure_mutable()?;
+        info!("Starting {} scan", if resume { "resumed baseline" } else { "baseline" });
+        let start_time = Instant::now();
+        let _running = ScanRunGuard::enter(&self.is_running);
+
+        // Clear existing data
+        if !resume {
+            self.database.set_all_unscanned()?;
+        }
+        
+        let mut results = ScanResults {
+            files_scanned: 0,
+            files_added: 0,
+            files_modified: 0,
+            files_deleted: 0,
+            errors: 0,
+            scan_duration: Duration::default(),
+            total_size: 0,
+            handler_errors: 0,
+            write_queue_high_water: 0,
+            files_skipped: 0,
+            files_hash_skipped: 0,
+            walk_duration: Duration::default(),
+            hash_duration: Duration::default(),
+            db_duration: start_time.elapsed(),
+            completed: true,
+        };
+
+        // Collect all files to scan
+        let mut files_to_scan = timed(&mut results.walk_duration, || self.collect_files_to_scan())?;
+        if resume {
+            let database = &self.database;
+            let pending = timed(&mut results.db_duration, || -> Result<Vec<PathBuf>> {
+                let mut pending = Vec::with_capacity(files_to_scan.len());
+                for path in files_to_scan {
+                    if !database.get_path(&path)?.is_some_and(|entry| entry.data.scanned) {
+                        pending.push(path);
+                    }
+                }
+                Ok(pending)
+            })?;
+            files_to_scan = pending;
+        }
+        info!("Found {} files to scan", files_to_scan.len());
+
+        // Begin database transaction for batch operations
+        timed(&mut results.db_duration, || self.database.begin_transaction())?;
+
+        let pipeline_start = Instant::now();
+        let mut last_checkpoint = pipeline_start;
+        let mut pipeline_db_duration = Duration::default();
+        
+        // Hash in parallel, writing to the database from this thread only
+        // (SQLite is not thread-safe)
+        let scanner = FileScanner {
+            config: &self.config,
+            hasher: &self.hasher,
+            normalizers: &self.normalizers,
+            decompress_rules: &self.decompress_rules,
+            append_rules: &self.append_rules,
+            sampling_patterns: &self.sampling_patterns,
+            open_files: &self.open_files,
+            #[cfg(test)]
+            after_hash: self.after_hash.as_deref(),
+        };
+        let database = &mut self.database;
+        let wal_checkpoint_interval = self.config.wal_checkpoint_interval;
+        let mut progress = ProgressTracker::new(self.progress_handler.as_deref(), files_to_scan.len());
+        let is_running = &self.is_running;
+        // Seen by the hashing threads, so files queued after a stop are skipped
+        let halted = AtomicBool::new(false);
+        results.write_queue_high_water = bounded_pipeline(
+            &self.scan_pool,
+            &files_to_scan,
+            self.config.write_queue_depth,
+            |path| (!halted.load(Ordering::Relaxed)).then(|| {
+                let scan_result = scanner.scan(path);
+                let file_size = match &scan_result {
+                    Ok(Some((_, file_size))) => *file_size,
+                    _ => file_len(path),
+                };
+                (scan_result, file_size)
+            }),
+            |scanned| {
+                let Some((scan_result, file_size)) = scanned else { return };
+                if halted.load(Ordering::Relaxed) {
+                    return;
+                }
+                match scan_result {
+                    Ok(Some((entry_data, _))) => {
+                        results.files_scanned += 1;
+                        results.total_size += file_size;
+                        results.files_added += 1;
+
+                        // Insert into database
+                        let inserted = timed(&mut pipeline_db_duration, || {
+                            database.insert_data(&entry_data.path, &entry_data.data)
+                        });
+                        if let Err(e) = inserted {
+                            error!("Failed to insert file data: {}", e);
+                            results.errors += 1;
+                        }
+                    }
+                    // Owned by an excluded user or group
+                    Ok(None) => results.files_skipped += 1,
+                    Err(e) => {
+                        error!("Scan error: {}", e);
+                        results.errors += 1;
+                    }
+                }
+
+                // Periodic commit for large scans
+                if results.files_scanned.is_multiple_of(1000) {
+                    timed(&mut pipeline_db_duration, || {
+                        Self::commit_database(database, wal_checkpoint_interval, &mut last_checkpoint)
+                    });
+                    debug!("Processed {} files", results.files_scanned);
+                }
+                progress.file_done(|| file_size);
+
+                if !*is_running.lock().unwrap() {
+                    halted.store(true, Ordering::Relaxed);
+                }
+            },
+        );
+        results.hash_duration = pipeline_start.elapsed().saturating_sub(pipeline_db_duration);
+        results.db_duration += pipeline_db_duration;
+        results.completed = !halted.into_inner();
+
+        let db_start = Instant::now();
+
+        // Final commit
+        self.database.commit_transaction()?;
+        
+        // Clean up unscanned entries, unless the scan never got to them
+        if results.completed {
+            results.files_deleted = self.database.delete_not_scanned()?;
+        } else {
+            warn!(
+                "Baseline scan stopped after {} of {} files; resume_scan continues it",
+                progress.files_done,
+                files_to_scan.len()
+            );
+        }
+        results.db_duration += db_start.elapsed();
+
+        results.scan_duration = start_time.elapsed();
+        self.record_scan_metrics(&results);
+        
+        info!(
+            "Baseline scan completed: {} files scanned, {} added, {} errors in {:?}",
+            results.files_scanned,
+            results.files_added,
+            results.errors,
+            results.scan_duration
+        );
+
+        Ok(results)
+    }
+
+    /// Perform incremental scan
+    pub fn incremental_scan(&mut self) -> Result<ScanResults> {
+        if self.config.immutable_baseline {
+            info!("Starting verification against the immutable baseline");
+            let handler_errors_before = self.handler_errors();
+            let (mut results, changes) = self.verify_scan()?;
+            for change in &changes {
+                self.record_change_metric(change);
+                self.notify_handlers(change);
+            }
+            results.handler_errors = self.handler_errors() - handler_errors_before;
+            self.record_scan_metrics(&results);
+            return Ok(results);
+        }
+
+        if self.config.auto_baseline && self.database.get_stats()?.total_files == 0 {
+            info!("Database is empty, performing automatic baseline instead of incremental scan");
+            return self.baseline_scan();
+        }
+
+        info!("Starting incremental scan");
+        let walk_start = Instant::now();
+        let files_to_scan = self.collect_files_to_scan()?;
+        let (results, _) = self.compare_files(files_to_scan, walk_start.elapsed(), DeletionScope::All)?;
+
+        Ok(results)
+    }
+
+    /// Incremental scan that also passes each change to `on_change`, stopping
+    /// early once `cancel` is triggered
+    ///
+    /// `on_change` runs after the registered handlers and only for this scan;
+    /// it may return [`StopProcessing`] too. As with any early stop, files not
+    /// visited yet are not reported as deleted.
+    pub fn incremental_scan_cancellable<F>(
+        &mut self,
+        cancel: &CancellationToken,
+        on_change: F,
+    ) -> Result<ScanResults>
+    where
+        F: Fn(&FileChange) -> Result<()> + Send + Sync + 'static,
+    {
+        let handler_count = self.change_handlers.len();
+        self.change_handlers.push(Box::new(on_change));
+        self.cancel = Some(cancel.clone());
+
+        let results = self.incremental_scan();
+
+        self.cancel = None;
+        self.change_handlers.truncate(handler_count);
+        results
+    }
+
+    /// Compare the monitored files with the baseline without modifying it
+    ///
+    /// Returns the drift an incremental scan would report, classified by the
+    /// severity rules, but writes nothing: the database, the change log and
+    /// the change handlers are left alone. Missing files are reported as
+    /// deleted even when their filesystem was unmounted.
+    pub fn verify_scan(&self) -> Result<(ScanResults, Vec<FileChange>)> {
+        let mut changes = Vec::new();
+        let results = self.verify_scan_each(|change| changes.push(change))?;
+        Ok((results, changes))
+    }
+
+    /// [`FimEngine::verify_scan`], passing each drift to `on_diff` as it is
+    /// found instead of collecting them
+    ///
+    /// Suited to trees too large to hold every change in memory, e.g. when
+    /// writing the drift straight to a JSON Lines file: the walk and the
+    /// baseline are read as they are compared, not loaded up front.
+    pub fn verify_scan_each(&self, mut on_diff: impl FnMut(FileChange)) -> Result<ScanResults> {
+        let start_time = Instant::now();
+        let mut results = ScanResults {
+            files_scanned: 0,
+            files_added: 0,
+            files_modified: 0,
+            files_deleted: 0,
+            errors: 0,
+            scan_duration: Duration::default(),
+            total_size: 0,
+            handler_errors: 0,
+            write_queue_high_water: 0,
+            files_skipped: 0,
+            files_hash_skipped: 0,
+            walk_duration: Duration::default(),
+            hash_duration: Duration::default(),
+            db_duration: Duration::default(),
+            completed: true,
+        };
+        let mut report = |results: &mut ScanResults, mut change: FileChange| {
+            self.severity_rules.classify(&mut change);
+            Self::count_change(results, &change);
+            on_diff(change);
+        };
+
+        // Files are walked in the order entries are stored, so recorded entries
+        // the walk passes over without visiting are the deleted ones
+        let mut walk = FileWalk::new(self, &self.config.monitor_paths);
+        let mut recorded = EntryCursor::new(&self.database);
+        let mut last_visited: Option<String> = None;
+        while let Some(file_path) = timed(&mut results.walk_duration, || walk.next()).transpose()? {
+            let key = file_path.to_string_lossy().into_owned();
+            // Files below two monitored paths come up twice
+            if last_visited.as_ref().is_some_and(|last| key <= *last) {
+                continue;
+            }
+            while let Some(entry) = timed(&mut results.db_duration, || recorded.next_unvisited(Some(&key)))? {
+                report(&mut results, self.unvisited_change(entry));
+            }
+            last_visited = Some(key);
+
+            match timed(&mut results.hash_duration, || self.diff_file(&file_path)) {
+                Ok(Some((_, change))) => {
+                    results.files_scanned += 1;
+                    if let Some(change) = change {
+                        report(&mut results, change);
+                    }
+                }
+                Ok(None) => results.files_skipped += 1,
+                Err(e) => {
+                    error!("Error verifying file {}: {}", file_path.display(), e);
+                    results.errors += 1;
+                }
+            }
+        }
+
+        while let Some(entry) = timed(&mut results.db_duration, || recorded.next_unvisited(None))? {
+            report(&mut results, self.unvisited_change(entry));
+        }
+
+        results.scan_duration = start_time.elapsed();
+        info!(
+            "Verification completed: {} scanned, {} added, {} modified, {} deleted",
+            results.files_scanned,
+            results.files_added,
+            results.files_modified,
+            results.files_deleted
+        );
+
+        Ok(results)
+    }
+
+    /// Change for a recorded entry a verification walk did not visit:
+    /// `AccessLost` when it can no longer be reached, otherwise `Deleted`
+    fn unvisited_change(&self, entry: FimEntry) -> FileChange {
+        if self.config.report_access_loss && is_unreachable(&entry.path) {
+            return Self::access_lost(&entry.path, entry.data);
+        }
+        FileChange {
+            path: entry.path,
+            change_type: ChangeType::Deleted,
+            old_entry: Some(entry.data),
+            new_entry: None,
+            detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
+        }
+    }
+
+    /// Refuse to write to an immutable baseline
+    fn ensure_mutable(&self) -> Result<()> {
+        if self.config.immutable_baseline {
+            anyhow::bail!("The baseline is immutable; only verification is allowed");
+        }
+        Ok(())
+    }
+
+    /// Perform an incremental scan of `roots` only
+    ///
+    /// Each root must lie within a monitored path once `.` and `..` are
+    /// resolved. Entries outside the roots are left untouched, so files missing
+    /// below a root are the only deletions.
+    pub fn incremental_scan_paths(&mut self, roots: &[PathBuf]) -> Result<(ScanResults, Vec<FileChange>)> {
+        let mut roots: Vec<PathBuf> = roots.iter().map(crate::utils::normalize_path).collect();
+        // Drop roots nested under another so no subtree is walked or reported twice
+        roots.sort();
+        roots.dedup();
+        let mut kept: Vec<PathBuf> = Vec::with_capacity(roots.len());
+        for root in roots {
+            if !kept.iter().any(|outer| root.starts_with(outer)) {
+                kept.push(root);
+            }
+        }
+        let roots = kept;
+
+        for root in &roots {
+            let within = self.config.monitor_paths
+                .iter()
+                .any(|monitored| root.starts_with(crate::utils::normalize_path(monitored)));
+            if !within {
+                anyhow::bail!("{} is not within a monitored path", root.display());
+            }
+        }
+
+        info!("Starting incremental scan of {} path(s)", roots.len());
+        let walk_start = Instant::now();
+        let mut files_to_scan = FileWalk::new(self, &roots).collect::<Result<Vec<_>>>()?;
+        files_to_scan.sort();
+        files_to_scan.dedup();
+
+        self.compare_files(files_to_scan, walk_start.elapsed(), DeletionScope::Under(&roots))
+    }
+
+    /// Hash and compare exactly the given files, skipping directory walking
+    ///
+    /// Useful when an external tool already produced the file list. Listed
+    /// paths that no longer exist are reported as deleted; entries that are not
+    /// listed are left untouched. Paths a walk would ignore, such as excluded
+    /// ones and the database's own files, are skipped.
+    pub fn scan_from_iter(
+        &mut self,
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<(ScanResults, Vec<FileChange>)> {
+        info!("Starting scan of provided file list");
+        let files_to_scan: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| !self.should_ignore_path(path))
+            .collect();
+
+        self.compare_files(files_to_scan, Duration::default(), DeletionScope::Nothing)
+    }
+
+    /// Compare `files` against the database, then report entries in `scope`
+    /// that were not visited as deleted
+    ///
+    /// `walk_duration` is the time the caller spent collecting `files`, which
+    /// counts towards the scan duration.
+    fn compare_files(
+        &mut self,
+        files_to_scan: Vec<PathBuf>,
+        walk_duration: Duration,
+        scope: DeletionScope<'_>,
+    ) -> Result<(ScanResults, Vec<FileChange>)> {
+        self.ensure_mutable()?;
+        let start_time = Instant::now();
+        let _running = ScanRunGuard::enter(&self.is_running);
+
+        // Mark entries in scope as unscanned
+        match scope {
+            DeletionScope::All => {
+                self.database.set_all_unscanned()?;
+            }
+            DeletionScope::Under(roots) => {
+                for root in roots {
+                    self.database.set_unscanned_under(root)?;
+                }
+            }
+            DeletionScope::Nothing => {}
+        }
+
+        let mut results = ScanResults {
+            files_scanned: 0,
+            files_added: 0,
+            files_modified: 0,
+            files_deleted: 0,
+            errors: 0,
+            scan_duration: Duration::default(),
+            total_size: 0,
+            handler_errors: 0,
+            write_queue_high_water: 0,
+            files_skipped: 0,
+            files_hash_skipped: 0,
+            walk_duration,
+            hash_duration: Duration::default(),
+            db_duration: start_time.elapsed(),
+            completed: true,
+        };
+        let mut changes = Vec::new();
+
+        let handler_errors_before = self.handler_errors();
+        self.stop_requested.store(false, Ordering::Relaxed);
+        let mut last_checkpoint = Instant::now();
+        
+        timed(&mut results.db_duration, || self.database.begin_transaction())?;
+
+        // Cloned so the handler stays callable while `self` is borrowed mutably
+        let progress_handler = self.progress_handler.clone();
+        let mut progress = ProgressTracker::new(progress_handler.as_deref(), files_to_scan.len());
+
+        // Process each file and check for changes
+        for file_path in files_to_scan {
+            let checked = self.check_file_changes_timed(&file_path, &mut results);
+            match checked {
+                Ok(Some(mut change)) => {
+                    self.handle_file_change(&mut change);
+                    Self::count_change(&mut results, &change);
+                    changes.push(change);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Error checking file {}: {}", file_path.display(), e);
+                    results.errors += 1;
+                }
+            }
+
+            if results.files_scanned.is_multiple_of(1000) {
+                timed(&mut results.db_duration, || self.periodic_commit(&mut last_checkpoint));
+            }
+            progress.file_done(|| file_len(&file_path));
+
+            if self.should_stop() {
+                warn!("Incremental scan stopped early by a change handler or cancellation");
+                break;
+            }
+        }
+
+        // Handle deleted files; unvisited entries are not deletions after an early stop
+        if !self.should_stop() {
+            let db_start = Instant::now();
+            let unscanned = match scope {
+                DeletionScope::All => self.database.get_not_scanned(None)?,
+                DeletionScope::Under(roots) => {
+                    let mut unscanned = Vec::new();
+                    for root in roots {
+                        unscanned.extend(self.database.get_not_scanned(Some(root))?);
+                    }
+                    unscanned
+                }
+                DeletionScope::Nothing => Vec::new(),
+            };
+            results.db_duration += db_start.elapsed();
+
+            let (held, unscanned) = Self::partition_unmounted(unscanned);
+            self.report_held_mounts(&held, scope, &mut changes);
+
+            // Removed together once every deletion is reported
+            let mut deleted = Vec::new();
+            for entry in unscanned {
+                if self.should_stop() {
+                    break;
+                }
+
+                let mut change = if self.config.report_access_loss && is_unreachable(&entry.path) {
+                    Self::access_lost(&entry.path, entry.data)
+                } else {
+                    deleted.push(entry.path.clone());
+                    FileChange {
+                        path: entry.path,
+                        change_type: ChangeType::Deleted,
+                        old_entry: Some(entry.data),
+                        new_entry: None,
+                        detected_at: Utc::now(),
+                        severity: AlertSeverity::Info,
+                        is_critical: false,
+                        change_id: FileChange::new_id(),
+                    }
+                };
+                self.handle_file_change(&mut change);
+                Self::count_change(&mut results, &change);
+                changes.push(change);
+            }
+            timed(&mut results.db_duration, || self.database.remove_paths(&deleted))?;
+        }
+        results.handler_errors = self.handler_errors() - handler_errors_before;
+        results.completed = !self.should_stop();
+
+        timed(&mut results.db_duration, || self.database.commit_transaction())?;
+        results.scan_duration = walk_duration + start_time.elapsed();
+        self.record_scan_metrics(&results);
+
+        info!(
+            "Incremental scan completed: {} scanned, {} added, {} modified, {} deleted",
+            results.files_scanned,
+            results.files_added,
+            results.files_modified,
+            results.files_deleted
+        );
+
+        Ok((results, changes))
+    }
+
+    /// Tally a detected change into scan results
+    fn count_change(results: &mut ScanResults, change: &FileChange) {
+        match change.change_type {
+            ChangeType::Added => results.files_added += 1,
+            ChangeType::Modified | 
+            ChangeType::HashChanged |
+            ChangeType::PermissionChanged |
+            ChangeType::SizeChanged |
+            ChangeType::TimestampChanged |
+            ChangeType::AttributeChanged |
+            ChangeType::ContentClassChanged |
+            ChangeType::AccessLost |
+            ChangeType::Moved { .. } => results.files_modified += 1,
+            ChangeType::Deleted => results.files_deleted += 1,
+            ChangeType::MountChanged => {}
+        }
+    }
+
+    /// Split missing entries into those below a directory whose filesystem
+    /// changed, keyed by that directory, and genuine deletions
+    ///
+    /// A deleted file lives on the same device as its nearest surviving
+    /// ancestor. When that ancestor is a real directory now reporting another
+    /// device, the file's filesystem was unmounted (or covered by a different
+    /// mount) instead. An ancestor replaced by a symlink is never a mount, so
+    /// pointing it elsewhere cannot hide deletions.
+    fn partition_unmounted(missing: Vec<FimEntry>) -> (BTreeMap<PathBuf, Vec<FimEntry>>, Vec<FimEntry>) {
+        let mut dir_devices: HashMap<PathBuf, Option<Option<u64>>> = HashMap::new();
+        let mut held: BTreeMap<PathBuf, Vec<FimEntry>> = BTreeMap::new();
+        let mut deleted = Vec::new();
+
+        for entry in missing {
+            let mount_point = entry.path.ancestors().skip(1).find_map(|ancestor| {
+                let device = *dir_devices
+                    .entry(ancestor.to_path_buf())
+                    .or_insert_with(|| directory_device(ancestor));
+                device.map(|device| {
+                    device.filter(|&device| device != entry.data.dev).map(|_| ancestor.to_path_buf())
+                })
+            });
+
+            match mount_point.flatten() {
+                Some(mount_point) => held.entry(mount_point).or_default().push(entry),
+                None => deleted.push(entry),
+            }
+        }
+
+        (held, deleted)
+    }
+
+    /// Emit one `MountChanged` per newly affected mount point
+    ///
+    /// Held entries stay in the database, so they compare unchanged once the
+    /// filesystem returns. A mount point is reported again only after it has
+    /// been seen intact in between.
+    fn report_held_mounts(
+        &mut self,
+        held: &BTreeMap<PathBuf, Vec<FimEntry>>,
+        scope: DeletionScope<'_>,
+        changes: &mut Vec<FileChange>,
+    ) {
+        if let DeletionScope::All = scope {
+            self.held_mounts.retain(|mount_point| held.contains_key(mount_point));
+        }
+
+        for (mount_point, entries) in held {
+            if !self.held_mounts.insert(mount_point.clone()) {
+                continue;
+            }
+
+            warn!(
+                "Filesystem at {} changed, keeping {} entries until it returns",
+                mount_point.display(),
+                entries.len()
+            );
+            let mut change = FileChange {
+                path: mount_point.clone(),
+                change_type: ChangeType::MountChanged,
+                old_entry: None,
+                new_entry: None,
+                detected_at: Utc::now(),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            };
+            self.handle_file_change(&mut change);
+            changes.push(change);
+        }
+    }
+
+    /// Periodic commit during scans, checkpointing the WAL when one is due
+    fn periodic_commit(&mut self, last_checkpoint: &mut Instant) {
+        Self::commit_database(&mut self.database, self.config.wal_checkpoint_interval, last_checkpoint);
+    }
+
+    /// [`Self::periodic_commit`] for callers holding only the database
+    fn commit_database(database: &mut S, wal_checkpoint_interval: Option<u64>, last_checkpoint: &mut Instant) {
+        match wal_checkpoint_interval {
+            Some(interval) if last_checkpoint.elapsed() >= Duration::from_secs(interval) => {
+                database.force_commit_with_checkpoint();
+                *last_checkpoint = Instant::now();
+            }
+            _ => database.force_commit(),
+        }
+    }
+
+    /// Process real-time events
+    pub fn process_realtime_events(&mut self) -> Result<()> {
+        if self.watcher.is_none() {
+            return Err(anyhow::anyhow!("Real-time monitoring not enabled"));
+        }
+
+        while *self.is_running.lock().unwrap() && !self.stop_requested() {
+            // Block until an event arrives, a coalesced path is due, or it is
+            // time to look for a stop again
+            let now = Instant::now();
+            let timeout = self.pending_events
+                .values()
+                .min()
+                .map_or(STOP_CHECK_INTERVAL, |deadline| {
+                    deadline.saturating_duration_since(now).min(STOP_CHECK_INTERVAL)
+                });
+            let event = self.watcher.as_ref().and_then(|watcher| watcher.next_event_timeout(timeout));
+
+            if let Some(event) = event {
+                if let Err(e) = self.handle_realtime_event(event) {
+                    error!("Error handling real-time event: {}", e);
+                }
+            }
+            self.flush_coalesced_events(false);
+        }
+
+        // Don't drop changes still waiting out their window
+        self.flush_coalesced_events(true);
+
+        Ok(())
+    }
+
+    /// Re-check paths whose coalescing window has elapsed (or all, if `force`)
+    fn flush_coalesced_events(&mut self, force: bool) {
+        let now = Instant::now();
+        let due: Vec<PathBuf> = self.pending_events
+            .iter()
+            .filter(|(_, deadline)| force || **deadline <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in due {
+            self.pending_events.remove(&path);
+            if self.config.immutable_baseline {
+                self.report_drift(&path);
+                continue;
+            }
+            match self.check_file_changes(&path) {
+                Ok(Some(mut change)) => self.handle_file_change(&mut change),
+                Ok(None) => {}
+                Err(e) => error!("Error re-checking {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Report how the file at `path` differs from an immutable baseline,
+    /// leaving the database alone
+    fn report_drift(&mut self, path: &Path) {
+        if self.should_ignore_path(path) {
+            return;
+        }
+        match self.diff_file(path) {
+            Ok(Some((_, Some(mut change)))) => {
+                self.severity_rules.classify(&mut change);
+                info!("File drift detected: {:?} - {}", change.change_type, change.path.display());
+                self.record_change_metric(&change);
+                self.notify_handlers(&change);
+            }
+            Ok(Some((_, None)) | None) => {}
+            Err(e) => error!("Error comparing {}: {}", path.display(), e),
+        }
+    }
+
+    /// Handle real-time filesystem event
+    fn handle_realtime_event(&mut self, event: FimEvent) -> Result<()> {
+        debug!("Processing real-time event: {:?}", event);
+
+        // An immutable baseline is only compared against, so both ends of a
+        // rename are checked on their own
+        if self.config.immutable_baseline {
+            let paths = match &event.kind {
+                FimEventKind::MovedFrom(Some(from)) => vec![from.clone(), event.path.clone()],
+                FimEventKind::MovedTo(Some(to)) => vec![event.path.clone(), to.clone()],
+                _ => vec![event.path.clone()],
+            };
+            for path in paths {
+                self.report_drift(&path);
+            }
+            return Ok(());
+        }
+
+        // Either end of a rename may be ignored, so those are filtered later
+        match &event.kind {
+            FimEventKind::MovedFrom(Some(from)) => return self.handle_move(from, &event.path),
+            FimEventKind::MovedTo(Some(to)) => return self.handle_move(&event.path, to),
+            _ => {}
+        }
+
+        if self.should_ignore_path(&event.path) {
+            return Ok(());
+        }
+
+        // Within the coalescing window, only the final state of the path matters
+        if !self.config.coalesce_window.is_zero()
+            && matches!(event.kind, FimEventKind::Created | FimEventKind::Modified | FimEventKind::Deleted)
+        {
+            let deadline = Instant::now() + self.config.coalesce_window;
+            self.pending_events.entry(event.path).or_insert(deadline);
+            return Ok(());
+        }
+
+        let change = match event.kind {
+            FimEventKind::Created | FimEventKind::Modified => self.check_file_changes(&event.path)?,
+            FimEventKind::MovedFrom(None) => {
+                if let Some(from) = self.moved_from_inode(&event.path)? {
+                    return self.handle_move(&from, &event.path);
+                }
+                self.check_file_changes(&event.path)?
+            }
+            FimEventKind::MovedTo(None) => {
+                // Keep the entry while the destination may still show up and be
+                // matched by inode; if it doesn't, the re-check reports a deletion
+                let window = self.config.watch_config.debounce_timeout.max(self.config.coalesce_window);
+                self.pending_events.entry(event.path).or_insert(Instant::now() + window);
+                None
+            }
+            FimEventKind::Deleted => {
+                let old_entry = self.database.get_path(&event.path)?;
+                self.database.remove_path(&event.path)?;
+                
+                if let Some(old) = old_entry {
+                    Some(FileChange {
+                        path: event.path,
+                        change_type: ChangeType::Deleted,
+                        old_entry: Some(old.data),
+                        new_entry: None,
+                        detected_at: event.timestamp,
+                        severity: AlertSeverity::Info,
+                        is_critical: false,
+                        change_id: FileChange::new_id(),
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(mut change) = change {
+            self.handle_file_change(&mut change);
+        }
+
+        Ok(())
+    }
+
+    /// Follow a rename of `from` to `to`, moving the recorded entries at and
+    /// below `from` instead of reporting a deletion and an addition
+    fn handle_move(&mut self, from: &Path, to: &Path) -> Result<()> {
+        let changes = if self.should_ignore_path(to) {
+            // Renamed out of what we monitor, which to us is a deletion
+            self.check_file_changes(from)?.into_iter().collect()
+        } else {
+            self.move_entries(from, to)?
+        };
+
+        for mut change in changes {
+            self.handle_file_change(&mut change);
+        }
+        Ok(())
+    }
+
+    /// Move recorded entries from `from` to `to` and re-check each at its new
+    /// path, returning a `Moved` change per entry still present
+    ///
+    /// A recorded entry the move replaces is reported as changed into the
+    /// moved file, and content that changed along the way as its own change.
+    fn move_entries(&mut self, from: &Path, to: &Path) -> Result<Vec<FileChange>> {
+        let mut replaced: HashMap<PathBuf, FimEntryData> = self.database
+            .get_entries_under(to, None)?
+            .into_iter()
+            .map(|entry| (entry.path, entry.data))
+            .collect();
+        let moved = self.database.rename_path(from, to)?;
+        if moved.is_empty() {
+            // Nothing was recorded at the source, so the file is new to us
+            return Ok(self.check_file_changes(to)?.into_iter().collect());
+        }
+
+        let mut changes = Vec::with_capacity(moved.len());
+        for (old_path, new_path) in moved {
+            let Some(old) = self.database.get_path(&new_path)? else { continue };
+            let replaced = replaced.remove(&new_path);
+            // Left as recorded when its owner is excluded
+            let Some((new_data, change)) = self.diff_file(&new_path)? else {
+                continue;
+            };
+            match new_data {
+                Some(new_data) => {
+                    self.database.insert_data(&new_path, &new_data)?;
+                    changes.push(FileChange {
+                        path: new_path.clone(),
+                        change_type: ChangeType::Moved { from: old_path, to: new_path.clone() },
+                        old_entry: Some(old.data),
+                        new_entry: Some(new_data.clone()),
+                        detected_at: Utc::now(),
+                        severity: AlertSeverity::Info,
+                        is_critical: false,
+                        change_id: FileChange::new_id(),
+                    });
+                    if let Some(replaced) = replaced {
+                        changes.push(FileChange {
+                            path: new_path.clone(),
+                            change_type: self.detect_change_type(&replaced, &new_data)
+                                .unwrap_or(ChangeType::Modified),
+                            old_entry: Some(replaced),
+                            new_entry: Some(new_data),
+                            detected_at: Utc::now(),
+                            severity: AlertSeverity::Info,
+                            is_critical: false,
+                            change_id: FileChange::new_id(),
+                        });
+                    }
+                    changes.extend(change);
+                }
+                None => {
+                    self.database.remove_path(&new_path)?;
+                    changes.extend(change);
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Recorded path of the file now at `path`, found by inode and device,
+    /// when that path no longer exists (the source of an unpaired rename)
+    fn moved_from_inode(&self, path: &Path) -> Result<Option<PathBuf>> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let Ok(metadata) = fs::symlink_metadata(path) else {
+                return Ok(None);
+            };
+            let candidates = self.database.get_paths_from_inode(metadata.ino(), metadata.dev())?;
+            Ok(candidates
+                .into_iter()
+                .find(|candidate| candidate != path && fs::symlink_metadata(candidate).is_err()))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            Ok(None)
+        }
+    }
+
+    /// Borrow the parts of the engine needed to scan files
+    fn scanner(&self) -> FileScanner<'_> {
+        FileScanner {
+            config: &self.config,
+            hasher: &self.hasher,
+            normalizers: &self.normalizers,
+            decompress_rules: &self.decompress_rules,
+            append_rules: &self.append_rules,
+            sampling_patterns: &self.sampling_patterns,
+            open_files: &self.open_files,
+            #[cfg(test)]
+            after_hash: self.after_hash.as_deref(),
+        }
+    }
+
+    /// Scan a single file and return entry data, or None when its owner is
+    /// excluded
+    fn scan_single_file(&self, path: &Path) -> Result<Option<(FimEntry, u64)>> {
+        self.scanner().scan(path)
+    }
+
+    /// Check for changes in a file, updating the database
+    fn check_file_changes(&mut self, path: &Path) -> Result<Option<FileChange>> {
+        self.check_file_changes_timed(path, &mut ScanResults::default())
+    }
+
+    /// [`Self::check_file_changes`], counting the file as scanned or skipped
+    /// and adding the time spent scanning it and updating the database, and
+    /// a skipped hash, to `results`
+    fn check_file_changes_timed(&mut self, path: &Path, results: &mut ScanResults) -> Result<Option<FileChange>> {
+        if self.config.trust_mtime {
+            let db_start = Instant::now();
+            let unchanged = self.database.get_path(path)?
+                .is_some_and(|old| self.metadata_unchanged(path, &old));
+            if unchanged {
+                self.database.mark_scanned(path)?;
+                results.db_duration += db_start.elapsed();
+                results.files_scanned += 1;
+                results.files_hash_skipped += 1;
+                return Ok(None);
+            }
+            results.db_duration += db_start.elapsed();
+        }
+
+        let diff = timed(&mut results.hash_duration, || self.diff_file(path))?;
+        let db_start = Instant::now();
+        let Some((new_entry, change)) = diff else {
+            // Not monitored, but a recorded entry is not a deletion either
+            self.database.mark_scanned(path)?;
+            results.db_duration += db_start.elapsed();
+            results.files_skipped += 1;
+            return Ok(None);
+        };
+        results.files_scanned += 1;
+        match new_entry {
+            Some(new_entry) => {
+                self.database.insert_data(path, &new_entry)?;
+            }
+            None if change.is_some() => {
+                // File was deleted
+                self.database.remove_path(path)?;
+            }
+            None => {}
+        }
+        results.db_duration += db_start.elapsed();
+
+        Ok(change)
+    }
+
+    /// Whether `path` still matches the metadata of its entry, so hashing it
+    /// again can be skipped
+    ///
+    /// An entry hashed within the same second as its mtime is never trusted:
+    /// a write later in that second would leave the (whole-second) mtime alone.
+    fn metadata_unchanged(&self, path: &Path, old: &FimEntry) -> bool {
+        let data = &old.data;
+        if data.is_dir || data.unstable || data.link_target.is_some() {
+            return false;
+        }
+        // A hash enabled since the entry was recorded has to be computed
+        let hashes = &self.config.hash_config;
+        if (hashes.use_sha256 && data.sha256.is_none())
+            || (hashes.use_sha1 && data.sha1.is_none())
+            || (hashes.use_md5 && data.md5.is_none())
+            || (hashes.use_sha512 && data.sha512.is_none())
+            || (hashes.use_sha3_256 && data.sha3_256.is_none())
+            || (hashes.use_xxh3 && data.xxh3.is_none())
+        {
+            return false;
+        }
+        // Entries recorded before sub-second times were kept are rehashed once
+        let Some(mtime_nsec) = data.mtime_nsec else {
+            return false;
+        };
+        if old.updated_at.is_none_or(|hashed_at| data.mtime >= hashed_at) {
+            return false;
+        }
+        let Ok(metadata) = fs::metadata(path) else {
+            return false;
+        };
+        // A file whose owner is excluded is skipped, not taken as unchanged
+        if self.scanner().owner_excluded(&metadata, data.owner_sid.as_deref()) {
+            return false;
+        }
+
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+        if metadata.len() != data.size
+            || modified.map(|modified| (modified.trunc_subsecs(0), modified.timestamp_subsec_nanos()))
+                != Some((data.mtime, mtime_nsec))
+        {
+            return false;
+        }
+        // The inode change time moves on any write or metadata edit, even when
+        // the modification time is set back
+        if cfg!(unix) && (data.change_time_ns.is_none() || change_time_ns(&metadata) != data.change_time_ns) {
+            return false;
+        }
+        // Elsewhere the birth time is all there is; filesystems without one
+        // recorded the scan time instead
+        if !cfg!(unix)
+            && metadata.created().ok()
+                .is_some_and(|ctime| DateTime::<Utc>::from(ctime).trunc_subsecs(0) != data.ctime)
+        {
+            return false;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if metadata.ino() != data.inode
+                || metadata.dev() != data.dev
+                || metadata.uid() != data.uid
+                || metadata.gid() != data.gid
+                || format!("{:o}", metadata.mode() & 0o7777) != data.perm
+            {
+                return false;
+            }
+        }
+
+        // Owner and ACL edits leave the modification time alone
+        let security_unchanged = crate::acl::read_security(path).is_ok_and(|security| match security {
+            Some(security) => {
+                data.owner_sid.as_ref() == Some(&security.owner_sid) && data.dacl.as_ref() == Some(&security.dacl)
+            }
+            None => data.owner_sid.is_none() && data.dacl.is_none(),
+        });
+
+        // So do extended attribute edits
+        if self.config.monitor_xattrs
+            && !crate::xattrs::read_xattr_hash(path).is_ok_and(|xattr_hash| xattr_hash == data.xattr_hash)
+        {
+            return false;
+        }
+
+        security_unchanged
+            && crate::capabilities::read_capabilities(path).is_ok_and(|capabilities| capabilities == data.capabilities)
+    }
+
+    /// Compare a file with its recorded entry without touching the database
+    ///
+    /// Also returns the freshly scanned entry, or None when the file is gone.
+    /// Returns None altogether for a file whose owner or group is excluded,
+    /// leaving any recorded entry as it is.
+    fn diff_file(&self, path: &Path) -> Result<Option<(Option<FimEntryData>, Option<FileChange>)>> {
+        let old_entry = self.database.get_path(path)?;
+        let keep_lost = |old: &FimEntry| {
+            let kept = FimEntryData {
+                scanned: true,
+                ..old.data.clone()
+            };
+            Some((Some(kept), Some(Self::access_lost(path, old.data.clone()))))
+        };
+
+        if !path.exists() {
+            if let Some(old) = old_entry.as_ref().filter(|_| self.config.report_access_loss) {
+                if is_unreachable(path) {
+                    return Ok(keep_lost(old));
+                }
+            }
+            let change = old_entry.map(|old| FileChange {
+                path: path.to_path_buf(),
+                change_type: ChangeType::Deleted,
+                old_entry: Some(old.data),
+                new_entry: None,
+                detected_at: Utc::now(),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            });
+            return Ok(Some((None, change)));
+        }
+
+        let new_entry = match (self.scan_single_file(path), old_entry.as_ref()) {
+            (Err(e), Some(old)) if self.config.report_access_loss && is_permission_denied(&e) => {
+                debug!("Reading {} failed: {:#}", path.display(), e);
+                return Ok(keep_lost(old));
+            }
+            (result, _) => match result? {
+                Some((entry, _)) => entry,
+                None => return Ok(None),
+            },
+        };
+
+        if new_entry.data.unstable && self.config.defer_unstable {
+            debug!("Deferring unstable file {}", path.display());
+            let kept = old_entry.map(|old| FimEntryData {
+                scanned: true,
+                ..old.data
+            });
+            return Ok(Some((kept, None)));
+        }
+
+        let change = match old_entry {
+            // File existed, check for changes
+            Some(old) => {
+                let grown = self.append_growth(path, &old.data, &new_entry.data)?;
+                self.detect_change_type(grown.as_ref().unwrap_or(&old.data), &new_entry.data)
+                    .map(|change_type| FileChange {
+                        path: path.to_path_buf(),
+                        change_type,
+                        old_entry: Some(old.data),
+                        new_entry: Some(new_entry.data.clone()),
+                        detected_at: Utc::now(),
+                        severity: AlertSeverity::Info,
+                        is_critical: false,
+                        change_id: FileChange::new_id(),
+                    })
+            }
+            // New file
+            None => Some(FileChange {
+                path: path.to_path_buf(),
+                change_type: ChangeType::Added,
+                old_entry: None,
+                new_entry: Some(new_entry.data.clone()),
+                detected_at: Utc::now(),
+                severity: AlertSeverity::Info,
+                is_critical: false,
+                change_id: FileChange::new_id(),
+            }),
+        };
+
+        Ok(Some((Some(new_entry.data), change)))
+    }
+
+    /// `AccessLost` change for a recorded file, carrying its recorded entry
+    fn access_lost(path: &Path, old: FimEntryData) -> FileChange {
+        warn!("Lost access to {}, keeping its entry", path.display());
+        FileChange {
+            path: path.to_path_buf(),
+            change_type: ChangeType::AccessLost,
+            old_entry: Some(old),
+            new_entry: None,
+            detected_at: Utc::now(),
+            severity: AlertSeverity::Info,
+            is_critical: false,
+            change_id: FileChange::new_id(),
+        }
+    }
+
+    /// For an append-only file that only grew, the recorded entry updated with
+    /// the new size, times and prefix hashes, so the growth is not a change
+    ///
+    /// The file only grew if the recorded content is still its start: the
+    /// prefix hash is unchanged, or while the file was shorter than the
+    /// prefix, the recorded hash matches that many bytes of the file now.
+    fn append_growth(&self, path: &Path, old: &FimEntryData, new: &FimEntryData) -> Result<Option<FimEntryData>> {
+        let scanner = self.scanner();
+        let Some(prefix_bytes) = scanner.append_prefix(path) else {
+            return Ok(None);
+        };
+        if old.is_dir || new.is_dir || new.size < old.size {
+            return Ok(None);
+        }
+
+        let intact = old.blake3 == new.blake3
+            || (old.size < prefix_bytes && scanner.hash_prefix(path, old.size)?.blake3 == old.blake3);
+        if !intact {
+            return Ok(None);
+        }
+
+        Ok(Some(FimEntryData {
+            size: new.size,
+            mtime: new.mtime,
+            ctime: new.ctime,
+            md5: new.md5.clone(),
+            sha1: new.sha1.clone(),
+            sha256: new.sha256.clone(),
+            sha512: new.sha512.clone(),
+            sha3_256: new.sha3_256.clone(),
+            xxh3: new.xxh3.clone(),
+            blake3: new.blake3.clone(),
+            content_type: new.content_type,
+            mtime_nsec: new.mtime_nsec,
+            change_time_ns: new.change_time_ns,
+            ..old.clone()
+        }))
+    }
+
+    /// Detect the type of change between old and new entries
+    fn detect_change_type(&self, old: &FimEntryData, new: &FimEntryData) -> Option<ChangeType> {
+        let policy = self.config.compare_policy;
+
+        // Directories have no content; only their permissions and ownership count
+        if old.is_dir || new.is_dir {
+            return if old.is_dir != new.is_dir {
+                Some(ChangeType::Modified)
+            } else if policy.checks_perms() && permissions_changed(old, new) {
+                Some(ChangeType::PermissionChanged)
+            } else {
+                None
+            };
+        }
+
+        // Capability grants escalate privileges, so report them ahead of
+        // content and whatever the policy; so can relabelling, so other
+        // extended attributes go with them
+        if old.capabilities != new.capabilities
+            || (old.xattr_hash.is_some() && new.xattr_hash.is_some() && old.xattr_hash != new.xattr_hash)
+        {
+            Some(ChangeType::AttributeChanged)
+        } else if old.content_type.is_some() && new.content_type.is_some()
+            && old.content_type != new.content_type
+        {
+            Some(ChangeType::ContentClassChanged)
+        } else if (old.sampled || new.sampled)
+            && (old.sampled != new.sampled || old.size != new.size || self.content_hash_changed(old, new))
+        {
+            // A sampled fingerprint does not hash the whole content
+            Some(ChangeType::Modified)
+        } else if self.content_hash_changed(old, new) {
+            Some(ChangeType::HashChanged)
+        } else if old.size != new.size {
+            Some(ChangeType::SizeChanged)
+        } else if policy.checks_perms() && permissions_changed(old, new) {
+            Some(ChangeType::PermissionChanged)
+        } else if policy.checks_timestamps() && (old.mtime != new.mtime || old.ctime != new.ctime) {
+            Some(ChangeType::TimestampChanged)
+        } else {
+            None // No significant changes
+        }
+    }
+
+    /// Whether the content hash differs: BLAKE3, or xxh3 when BLAKE3 is
+    /// disabled and xxh3 is enabled
+    fn content_hash_changed(&self, old: &FimEntryData, new: &FimEntryData) -> bool {
+        let hashes = &self.config.hash_config;
+        // Sampled fingerprints are only kept as BLAKE3
+        if !hashes.use_blake3 && hashes.use_xxh3 && !old.sampled && !new.sampled {
+            old.xxh3 != new.xxh3
+        } else {
+            old.blake3 != new.blake3
+        }
+    }
+
+    /// Handle detected file change
+    ///
+    /// Assigns the change its severity before it is recorded or passed to handlers.
+    fn handle_file_change(&mut self, change: &mut FileChange) {
+        self.severity_rules.classify(change);
+        info!("File change detected: {:?} - {}", change.change_type, change.path.display());
+        self.record_change_metric(change);
+
+        if self.config.record_changes {
+            if let Err(e) = self.database.record_change(change) {
+                error!("Failed to record change for {}: {}", change.path.display(), e);
+            }
+        }
+        
+        self.notify_handlers(change);
+    }
+
+    /// Pass a change to every registered handler
+    fn notify_handlers(&self, change: &FileChange) {
+        for handler in &self.change_handlers {
+            if let Err(e) = handler(change) {
+                if e.is::<StopProcessing>() {
+                    info!("Change handler requested processing stop");
+                    self.stop_requested.store(true, Ordering::Relaxed);
+                    *self.is_running.lock().unwrap() = false;
+                } else {
+                    error!("Change handler failed for {}: {:#}", change.path.display(), e);
+                    self.handler_errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        self.subscribers.lock().unwrap().retain(|sender| {
+            match sender.try_send(change.clone()) {
+                Ok(()) => true,
+                Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                    warn!("Subscriber is lagging, dropped change for {}", change.path.display());
+                    self.dropped_changes.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+
+    /// Collect all files to scan based on configuration
+    fn collect_files_to_scan(&self) -> Result<Vec<PathBuf>> {
+        let mut files = FileWalk::new(self, &self.config.monitor_paths).collect::<Result<Vec<_>>>()?;
+
+        // Remove duplicates and sort
+        files.sort();
+        files.dedup();
+        
+        Ok(files)
+    }
+
+    /// Files a scan would process under the current configuration, without hashing
+    pub fn list_scannable(&self) -> Result<Vec<PathBuf>> {
+        self.collect_files_to_scan()
+    }
+
+    /// Check if path should be ignored
+    fn should_ignore_path(&self, path: &Path) -> bool {
+        // Never monitor our own constantly-changing database files
+        if self.is_database_file(path) {
+            return true;
+        }
+
+        if self.config.ignore_hidden && self.is_hidden_path(path) {
+            return true;
+        }
+
+        if self.config.symlink_policy == SymlinkPolicy::Ignore
+            && path.is_symlink()
+            && !self.config.monitor_paths.iter().any(|monitored| monitored == path)
+        {
+            return true;
+        }
+
+        // Match the same decision for `a//b`, `a/b/` and `a/x/../b`
+        let path = crate::utils::normalize_path(path);
+        if self.exclude_patterns.matches(&path) {
+            return true;
+        }
+
+        // Include patterns select files; directories must be walked to find them
+        !self.include_patterns.is_empty()
+            && !path.is_dir()
+            && !self.include_patterns.matches(&path)
+    }
+
+    /// Whether an ignored directory is only excluded by pattern, and a negated
+    /// exclude pattern could still pick out files below it
+    fn may_include_below(&self, path: &Path) -> bool {
+        if !path.is_dir() || self.is_database_file(path) {
+            return false;
+        }
+        let hidden = self.config.ignore_hidden && self.is_hidden_path(path);
+        let ignored_link = self.config.symlink_policy == SymlinkPolicy::Ignore && path.is_symlink();
+        !hidden && !ignored_link && self.exclude_patterns.may_match_below(&crate::utils::normalize_path(path))
+    }
+
+    /// Whether `path`, or a directory between it and its monitored root, is hidden
+    fn is_hidden_path(&self, path: &Path) -> bool {
+        let root = self.config.monitor_paths
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.as_os_str().len());
+        match root {
+            Some(root) => path.ancestors()
+                .take_while(|dir| *dir != root.as_path())
+                .any(is_hidden),
+            None => is_hidden(path),
+        }
+    }
+
+    /// Check if path is the active database or one of its companion files
+    fn is_database_file(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+
+        // Only resolve the parent directory when the file name matches
+        self.database_files
+            .iter()
+            .filter(|db_file| db_file.file_name() == Some(name))
+            .any(|db_file| {
+                let parent = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                fs::canonicalize(parent)
+                    .map(|parent| parent.join(name) == *db_file)
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Recorded entry for a path, if any
+    pub fn get_entry(&self, path: &Path) -> Result<Option<FimEntry>> {
+        self.database.get_path(path)
+    }
+
+    /// Recorded entries at or below `prefix`, ordered by path
+    pub fn list_entries(&self, prefix: &Path) -> Result<Vec<FimEntry>> {
+        self.database.get_entries_under(prefix, None)
+    }
+
+    /// Rehash a file and compare it with its recorded entry, leaving the
+    /// database untouched
+    ///
+    /// Only hashes, size, permissions and ownership are compared; timestamps
+    /// and inode numbers change too easily to say anything about integrity.
+    pub fn verify_path(&self, path: &Path) -> Result<VerifyOutcome> {
+        let Some(recorded) = self.database.get_path(path)? else {
+            return Ok(VerifyOutcome::NotInBaseline);
+        };
+        match fs::symlink_metadata(path) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return Ok(VerifyOutcome::AccessLost),
+            Err(_) => return Ok(VerifyOutcome::Missing),
+        }
+
+        let current = match self.scan_single_file(path) {
+            Ok(Some((current, _))) => current,
+            Ok(None) => return Ok(VerifyOutcome::ExcludedOwner),
+            Err(e) if is_permission_denied(&e) => return Ok(VerifyOutcome::AccessLost),
+            Err(e) => return Err(e.context(format!("Failed to rehash {}", path.display()))),
+        };
+        let fields: Vec<_> = recorded.data.diff(&current.data)
+            .into_iter()
+            .filter(|change| VERIFY_FIELDS.contains(&change.field.as_str()))
+            .collect();
+
+        Ok(if fields.is_empty() {
+            VerifyOutcome::Match
+        } else {
+            VerifyOutcome::Mismatch { fields }
+        })
+    }
+
+    /// Resolved path of the database file, or None for an in-memory or
+    /// non-file store
+    pub fn database_path(&self) -> Option<&Path> {
+        self.database.path()
+    }
+
+    /// Storage backend holding the baseline and change log
+    pub fn store(&self) -> &S {
+        &self.database
+    }
+
+    /// Get FIM statistics
+    pub fn get_stats(&self) -> Result<FimStats> {
+        self.database.get_stats()
+    }
+
+    /// Snapshot the current baseline
+    pub fn snapshot_baseline(&self) -> Result<Baseline> {
+        Ok(self.database
+            .get_all_entries()?
+            .into_iter()
+            .map(|entry| (entry.path, entry.data))
+            .collect())
+    }
+
+    /// Verify a directory tree against a signed manifest
+    ///
+    /// The manifest signature is checked first and any failure is an error,
+    /// since nothing in an unauthenticated manifest can be trusted. Every
+    /// listed file is then hashed, links and special files are compared by
+    /// target and kind, and entries not in the manifest are reported as
+    /// extra. This never touches the engine database. The manifest file
+    /// itself is skipped when it lives inside `root`.
+    pub fn verify_tree_against_manifest(
+        &self,
+        root: &Path,
+        manifest_path: &Path,
+        pubkey: &[u8; 32],
+    ) -> Result<TreeVerifyReport> {
+        let manifest = TreeManifest::load(manifest_path)?;
+        manifest.verify_signature(pubkey)?;
+
+        let manifest_file = fs::canonicalize(manifest_path).ok();
+        let mut report = TreeVerifyReport::default();
+        let mut present = std::collections::HashSet::new();
+
+        for path in manifest::collect_tree_files(root)? {
+            if manifest_file.is_some() && fs::canonicalize(&path).ok() == manifest_file {
+                continue;
+            }
+
+            let key = manifest::manifest_key(root, &path)?;
+            match manifest.files.get(&key) {
+                Some(expected) => {
+                    if manifest::entry_value(&path, &self.hasher)? == *expected {
+                        report.verified.push(path);
+                    } else {
+                        report.mismatched.push(path);
+                    }
+                    present.insert(key);
+                }
+                None => report.extra.push(path),
+            }
+        }
+
+        report.missing = manifest.files
+            .keys()
+            .filter(|key| !present.contains(*key))
+            .map(|key| root.join(key))
+            .collect();
+
+        info!(
+            "Tree verification of {}: {} verified, {} mismatched, {} missing, {} extra",
+            root.display(),
+            report.verified.len(),
+            report.mismatched.len(),
+            report.missing.len(),
+            report.extra.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Get changes from the audit log detected within `[since, until]`
+    pub fn get_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileChange>> {
+        self.database.get_changes(since, until)
+    }
+
+    /// Previous states of a file, oldest first, each with when it was
+    /// overwritten; at most the `limit` most recent are returned
+    pub fn get_history(&self, path: &Path, limit: usize) -> Result<Vec<(DateTime<Utc>, FimEntryData)>> {
+        self.database.get_history(path, limit)
+    }
+
+    /// Check the database itself for corruption and tampering
+    pub fn check_database_integrity(&self) -> Result<DbIntegrityReport> {
+        let report = self.database.integrity_check()?;
+        if !report.is_ok() {
+            error!(
+                "FIM database integrity check failed: {} SQLite errors, checksum match: {}",
+                report.sqlite_errors.len(),
+                report.checksum_matches()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Check that no logged change was edited or removed
+    pub fn verify_change_log(&self) -> Result<ChangeLogVerification> {
+        let verification = self.database.verify_change_log()?;
+        if !verification.is_intact() {
+            error!(
+                "Change log hash chain broken (first break: {:?}, head matches: {})",
+                verification.first_break,
+                verification.head_matches
+            );
+        }
+        Ok(verification)
+    }
+
+    /// Check that no recorded file history was edited or removed
+    pub fn verify_history(&self) -> Result<ChangeLogVerification> {
+        let verification = self.database.verify_history()?;
+        if !verification.is_intact() {
+            error!(
+                "File history hash chain broken (first break: {:?}, head matches: {})",
+                verification.first_break,
+                verification.head_matches
+            );
+        }
+        Ok(verification)
+    }
+
+    /// Verify database integrity
+    pub fn verify_integrity(&self) -> Result<String> {
+        self.database.get_data_checksum()
+    }
+
+    /// Capture all entries for a full snapshot report
+    pub fn snapshot(&self) -> Result<FullSnapshot> {
+        self.database.snapshot()
+    }
+
+    /// Re-seed the database from a full snapshot, returning the entry count
+    pub fn import_snapshot(&mut self, snapshot: &FullSnapshot) -> Result<usize> {
+        self.database.restore_snapshot(snapshot)
+    }
+
+    /// Export the baseline, as binary for a `.bin` path and JSON otherwise
+    pub fn export_database(&self, output_path: &Path) -> Result<()> {
+        self.export_snapshot(output_path, SnapshotFormat::from_path(output_path))
+    }
+
+    /// Export the baseline as a [`FullSnapshot`] in `format`
+    ///
+    /// JSON is streamed straight from the database; binary snapshots are
+    /// built in memory first.
+    pub fn export_snapshot(&self, output_path: &Path, format: SnapshotFormat) -> Result<()> {
+        info!("Exporting database to {}", output_path.display());
+        match format {
+            SnapshotFormat::Json => {
+                let file = fs::File::create(output_path)
+                    .with_context(|| format!("Failed to create {}", output_path.display()))?;
+                let count = self.database.export_json(&mut std::io::BufWriter::new(file))
+                    .with_context(|| format!("Failed to write {}", output_path.display()))?;
+                debug!("Exported {} entries", count);
+                Ok(())
+            }
+            SnapshotFormat::Binary => self.database.snapshot()?.save(output_path, format),
+        }
+    }
+}
+
+/// Throttled calls of the progress handler over one scan
+struct ProgressTracker<'a> {
+    handler: Option<&'a (dyn Fn(ScanProgress) + Send + Sync)>,
+    files_done: u64,
+    files_total: u64,
+    bytes_done: u64,
+    started: Instant,
+    last_report: Option<Instant>,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(handler: Option<&'a (dyn Fn(ScanProgress) + Send + Sync)>, files_total: usize) -> Self {
+        Self {
+            handler,
+            files_done: 0,
+            files_total: files_total as u64,
+            bytes_done: 0,
+            started: Instant::now(),
+            last_report: None,
+        }
+    }
+
+    /// Count one more file done, reporting if it was the first or last one or
+    /// the interval has passed; `file_size` is only asked for with a handler
+    fn file_done(&mut self, file_size: impl FnOnce() -> u64) {
+        self.files_done += 1;
+        let Some(handler) = self.handler else { return };
+        self.bytes_done += file_size();
+        let now = Instant::now();
+        let due = self.last_report.is_none_or(|last| now.duration_since(last) >= PROGRESS_INTERVAL);
+        if !due && self.files_done < self.files_total {
+            return;
+        }
+        self.last_report = Some(now);
+
+        let remaining = self.files_total.saturating_sub(self.files_done);
+        let estimated_remaining = (self.files_done > 0).then(|| {
+            now.duration_since(self.started)
+                .mul_f64(remaining as f64 / self.files_done as f64)
+        });
+        handler(ScanProgress {
+            files_done: self.files_done,
+            files_total: self.files_total,
+            bytes_done: self.bytes_done,
+            estimated_remaining,
+        });
+    }
+}
+
+/// Size of the file at `path`, or 0 when it cannot be read
+fn file_len(path: &Path) -> u64 {
+    fs::symlink_metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// Inode change time (`st_ctime`) in nanoseconds; None off Unix
+fn change_time_ns(metadata: &fs::Metadata) -> Option<i64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.ctime().checked_mul(1_000_000_000)?.checked_add(metadata.ctime_nsec())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// Whether Unix mode or ownership, or the Windows owner or ACL, differ;
+/// entries recorded before the Windows fields existed only compare the rest,
+/// but a recorded owner or ACL that can no longer be read is a change
+fn permissions_changed(old: &FimEntryData, new: &FimEntryData) -> bool {
+    let windows_changed = |old: &Option<String>, new: &Option<String>| {
+        old.is_some() && old != new
+    };
+    old.perm != new.perm
+        || old.uid != new.uid
+        || old.gid != new.gid
+        || windows_changed(&old.owner_sid, &new.owner_sid)
+        || windows_changed(&old.dacl, &new.dacl)
+}
+
+/// Run `f`, adding the time it took to `total`
+fn timed<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    *total += start.elapsed();
+    result
+}
+
+/// Produce items on `pool` and consume them on the calling thread through a
+/// queue of at most `depth` items, returning the deepest the queue got
+///
+/// Producers block while the queue is full, so memory stays bounded when
+/// producing outpaces consuming.
+fn bounded_pipeline<T, R, P, C>(pool: &ThreadPool, items: &[T], depth: usize, produce: P, mut consume: C) -> usize
+where
+    T: Sync,
+    R: Send,
+    P: Fn(&T) -> R + Sync,
+    C: FnMut(R),
+{
+    let (sender, receiver) = crossbeam_channel::bounded(depth.max(1));
+    let produce = &produce;
+    let mut high_water = 0;
+
+    std::thread::scope(|scope| {
+        // Owned here so a panicking consumer drops it and unblocks producers
+        let receiver = receiver;
+        scope.spawn(move || {
+            pool.install(|| {
+                items.par_iter().for_each_with(sender, |sender, item| {
+                    // Only fails once the consumer has gone away
+                    let _ = sender.send(produce(item));
+                });
+            });
+        });
+
+        loop {
+            high_water = high_water.max(receiver.len());
+            let Ok(result) = receiver.recv() else { break };
+            consume(result);
+        }
+    });
+
+    high_water
+}
+
+/// Device of a path that is a real directory, without following symlinks
+///
+/// None when nothing exists at `path`, and `Some(None)` when something other
+/// than a directory does, or off Unix.
+fn directory_device(path: &Path) -> Option<Option<u64>> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.is_dir().then(|| metadata.dev()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        Some(None)
+    }
+}
+
+/// Whether a file or directory is hidden: its name starts with `.`, or on
+/// Windows it carries the hidden attribute
+fn is_hidden(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        fs::symlink_metadata(path)
+            .is_ok_and(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+    #[cfg(not(windows))]
+    {
+        path.file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+    }
+}
+
+/// Escape a path as a single JSON Pointer reference token (RFC 6901)
+fn json_pointer_token(path: &Path) -> String {
+    path.to_string_lossy().replace('~', "~0").replace('/', "~1")
+}
+
+impl<S: FimStore> Drop for FimEngine<S> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::database::tests::test_entry;
+    use crate::hasher::{ContentClass, SamplingConfig};
     use tempfile::{tempdir, NamedTempFile};
     use std::io::Write;
 
     #[test]
-    fn test_fim_config_default() {
-        let config = FimConfig::default();
-        assert!(!config.memory_database);
-        assert!(config.enable_realtime);
-        assert_eq!(config.scan_interval, 3600);
+    fn test_fim_config_default() {
+        let config = FimConfig::default();
+        assert!(!config.memory_database);
+        assert!(config.enable_realtime);
+        assert_eq!(config.scan_interval, 3600);
+    }
+
+    #[test]
+    fn test_config_durations_render_as_toml() -> Result<()> {
+        let config = FimConfig {
+            coalesce_window: Duration::from_millis(250),
+            ..Default::default()
+        };
+        let rendered = toml::to_string(&config)?;
+        assert!(rendered.contains("coalesce_window = 250"), "{}", rendered);
+        let parsed: FimConfig = toml::from_str(&rendered)?;
+        assert_eq!(parsed.coalesce_window, Duration::from_millis(250));
+        assert_eq!(parsed.watch_config.debounce_timeout, config.watch_config.debounce_timeout);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_single_file() -> Result<()> {
+        let config = FimConfig {
+            memory_database: true,
+            ..Default::default()
+        };
+        
+        let engine = FimEngine::new(config)?;
+        
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "Test file content")?;
+        
+        let (entry, size) = engine.scan_single_file(temp_file.path())?.unwrap();
+        
+        assert_eq!(entry.path, temp_file.path());
+        assert!(size > 0);
+        assert!(!entry.data.blake3.is_empty());
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_detection() -> Result<()> {
+        let old_data = FimEntryData {
+            size: 100,
+            blake3: "old_hash".to_string(),
+            inode: 123,
+            dev: 456,
+            ..test_entry()
+        };
+
+        let mut new_data = old_data.clone();
+        new_data.blake3 = "new_hash".to_string();
+
+        let config = FimConfig {
+            memory_database: true,
+            ..Default::default()
+        };
+        
+        let engine = FimEngine::new(config)?;
+        
+        let change_type = engine.detect_change_type(&old_data, &new_data);
+        assert_eq!(change_type, Some(ChangeType::HashChanged));
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_windows_security_change_detection() -> Result<()> {
+        let engine = FimEngine::new(FimConfig {
+            memory_database: true,
+            ..Default::default()
+        })?;
+        let old_data = FimEntryData {
+            size: 100,
+            uid: 0,
+            gid: 0,
+            blake3: "hash".to_string(),
+            inode: 0,
+            dev: 0,
+            owner_sid: Some("S-1-5-32-544".to_string()),
+            dacl: Some("D:PAI(A;;FA;;;SY)(A;;FA;;;BA)".to_string()),
+            ..test_entry()
+        };
+
+        let mut new_data = old_data.clone();
+        new_data.dacl = Some("D:PAI(A;;FA;;;SY)(A;;FA;;;BA)(A;;FA;;;WD)".to_string());
+        assert_eq!(engine.detect_change_type(&old_data, &new_data), Some(ChangeType::PermissionChanged));
+        let fields: Vec<_> = old_data.diff(&new_data).into_iter().map(|change| change.field).collect();
+        assert_eq!(fields, ["dacl"]);
+
+        let mut new_data = old_data.clone();
+        new_data.owner_sid = Some("S-1-5-21-1004336348-1177238915-682003330-1001".to_string());
+        assert_eq!(engine.detect_change_type(&old_data, &new_data), Some(ChangeType::PermissionChanged));
+
+        // Losing a recorded owner or ACL is a change too
+        let mut new_data = old_data.clone();
+        new_data.dacl = None;
+        assert_eq!(engine.detect_change_type(&old_data, &new_data), Some(ChangeType::PermissionChanged));
+        let mut new_data = old_data.clone();
+        new_data.owner_sid = None;
+        assert_eq!(engine.detect_change_type(&old_data, &new_data), Some(ChangeType::PermissionChanged));
+
+        // Entries recorded before owners and ACLs were captured don't report them
+        let mut legacy = old_data.clone();
+        legacy.owner_sid = None;
+        legacy.dacl = None;
+        assert_eq!(engine.detect_change_type(&legacy, &old_data), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_normalizer() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file1 = temp_dir.path().join("first.conf");
+        let file2 = temp_dir.path().join("second.conf");
+        fs::write(&file1, "name=app\ngenerated=2024-01-01T10:00:00Z\n")?;
+        fs::write(&file2, "name=app\ngenerated=2025-06-30T23:59:59Z\n")?;
+
+        let config = FimConfig {
+            memory_database: true,
+            content_normalizers: vec![ContentNormalizer {
+                path_pattern: "**/*.conf".to_string(),
+                regex: r"generated=\S+".to_string(),
+                replacement: "generated=<normalized>".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let engine = FimEngine::new(config)?;
+
+        let (entry1, _) = engine.scan_single_file(&file1)?.unwrap();
+        let (entry2, _) = engine.scan_single_file(&file2)?.unwrap();
+        assert_eq!(entry1.data.blake3, entry2.data.blake3);
+
+        // Changes outside the normalized region are still detected
+        fs::write(&file2, "name=other\ngenerated=2025-06-30T23:59:59Z\n")?;
+        let (entry2, _) = engine.scan_single_file(&file2)?.unwrap();
+        assert_ne!(entry1.data.blake3, entry2.data.blake3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_baseline_on_empty_database() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), b"first")?;
+        fs::write(temp_dir.path().join("b.txt"), b"second")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            auto_baseline: true,
+            ..Default::default()
+        };
+
+        let mut engine = FimEngine::new(config)?;
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_clone = changes.clone();
+        engine.add_change_handler(move |change| {
+            changes_clone.lock().unwrap().push(change.clone());
+        });
+
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_scanned, 2);
+        assert_eq!(engine.get_stats()?.total_files, 2);
+        assert!(changes.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_database_files_excluded() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = fs::canonicalize(temp_dir.path())?;
+        let db_path = root.join(crate::database::DEFAULT_DB_FILE);
+        for db_file in FimDb::companion_files(&db_path) {
+            fs::write(db_file, b"sqlite data")?;
+        }
+        fs::write(root.join("watched.txt"), b"content")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+
+        let mut engine = FimEngine::new(config)?;
+        engine.database_files = FimDb::companion_files(&db_path);
+
+        let files = engine.collect_files_to_scan()?;
+        assert_eq!(files, vec![root.join("watched.txt")]);
+
+        // Relative spellings of the same file are excluded as well
+        assert!(engine.should_ignore_path(&root.join(".").join(crate::database::DEFAULT_DB_FILE)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fallible_handler_errors_counted() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), b"first")?;
+        fs::write(temp_dir.path().join("b.txt"), b"second")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+
+        let mut engine = FimEngine::new(config)?;
+        let seen = Arc::new(Mutex::new(0));
+        let seen_clone = seen.clone();
+        engine.add_change_handler_fallible(|change| {
+            Err(anyhow::anyhow!("sink unavailable for {}", change.path.display()))
+        });
+        engine.add_change_handler(move |_| *seen_clone.lock().unwrap() += 1);
+
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_added, 2);
+        assert_eq!(results.handler_errors, 2);
+        assert_eq!(engine.handler_errors(), 2);
+        // Later handlers still run after an earlier one fails
+        assert_eq!(*seen.lock().unwrap(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fallible_handler_stop_processing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(temp_dir.path().join(name), name)?;
+        }
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+
+        let mut engine = FimEngine::new(config)?;
+        engine.add_change_handler_fallible(|_| Err(StopProcessing.into()));
+
+        let results = engine.incremental_scan()?;
+        assert!(engine.stop_requested());
+        assert_eq!(results.files_scanned, 1);
+        assert_eq!(results.handler_errors, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_baselines_jsonpatch() -> Result<()> {
+        let entry = FimEntryData {
+            size: 10,
+            blake3: "old_hash".to_string(),
+            inode: 1,
+            dev: 1,
+            ..test_entry()
+        };
+
+        let mut old = Baseline::new();
+        old.insert(PathBuf::from("/etc/app.conf"), entry.clone());
+        old.insert(PathBuf::from("/etc/hosts"), entry.clone());
+
+        let mut new = old.clone();
+        new.get_mut(Path::new("/etc/app.conf")).unwrap().blake3 = "new_hash".to_string();
+        new.get_mut(Path::new("/etc/hosts")).unwrap().scanned = false;
+
+        let patch = FimEngine::diff_baselines_jsonpatch(&old, &new)?;
+        assert_eq!(patch, json!([
+            { "op": "replace", "path": "/~1etc~1app.conf/blake3", "value": "new_hash" }
+        ]));
+
+        new.remove(Path::new("/etc/hosts"));
+        new.insert(PathBuf::from("/etc/new~file"), entry);
+        let patch = FimEngine::diff_baselines_jsonpatch(&old, &new)?;
+        let ops: Vec<_> = patch.as_array().unwrap()
+            .iter()
+            .map(|op| (op["op"].as_str().unwrap(), op["path"].as_str().unwrap()))
+            .collect();
+        assert_eq!(ops, vec![
+            ("replace", "/~1etc~1app.conf/blake3"),
+            ("remove", "/~1etc~1hosts"),
+            ("add", "/~1etc~1new~0file"),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_baseline_scans() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), b"first")?;
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+
+        // The scan pool belongs to the engine, so nothing global is set up twice
+        let mut engine = FimEngine::new(config.clone())?;
+        assert_eq!(engine.baseline_scan()?.files_added, 1);
+        fs::write(temp_dir.path().join("b.txt"), b"second")?;
+        assert_eq!(engine.baseline_scan()?.files_added, 2);
+        assert_eq!(FimEngine::new(config)?.baseline_scan()?.files_added, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engines_with_separate_thread_pools() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), b"first")?;
+
+        let mut engines = Vec::new();
+        for threads in [1, 3] {
+            let config = FimConfig {
+                monitor_paths: vec![temp_dir.path().to_path_buf()],
+                memory_database: true,
+                enable_realtime: false,
+                scan_threads: Some(threads),
+                ..Default::default()
+            };
+            let mut engine = FimEngine::new(config)?;
+            assert_eq!(engine.scan_pool.current_num_threads(), threads);
+            assert_eq!(engine.hasher.thread_pool().current_num_threads(), threads);
+            assert_eq!(engine.baseline_scan()?.files_scanned, 1);
+            engines.push(engine);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(all(unix, feature = "xattrs"))]
+    #[test]
+    fn test_xattr_change_detected() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file = temp_dir.path().join("download.dmg");
+        fs::write(&file, b"payload")?;
+        if let Err(e) = xattr::set(&file, "user.fim_test", b"0081;quarantined") {
+            eprintln!("skipping: cannot set extended attributes: {}", e);
+            return Ok(());
+        }
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        // Off by default: no digest and no extra syscalls
+        let mut engine = FimEngine::new(config.clone())?;
+        engine.baseline_scan()?;
+        assert!(engine.database.get_path(&file)?.unwrap().data.xattr_hash.is_none());
+
+        let mut engine = FimEngine::new(FimConfig { monitor_xattrs: true, ..config })?;
+        engine.baseline_scan()?;
+        let recorded = engine.database.get_path(&file)?.unwrap().data.xattr_hash;
+        assert!(recorded.is_some());
+
+        // Rewriting an attribute leaves content and mtime alone
+        xattr::set(&file, "user.fim_test", b"0000;cleared")?;
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::AttributeChanged);
+        assert!(changes[0].field_changes().iter().any(|change| change.field == "xattr_hash"));
+
+        // So does removing it
+        xattr::remove(&file, "user.fim_test")?;
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::AttributeChanged);
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert!(changes.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "xattrs"))]
+    #[test]
+    fn test_monitor_xattrs_needs_feature() {
+        let config = FimConfig {
+            memory_database: true,
+            monitor_xattrs: true,
+            ..Default::default()
+        };
+        let Err(error) = FimEngine::new(config) else {
+            panic!("monitored extended attributes without the xattrs feature");
+        };
+        assert!(error.to_string().contains("`xattrs` feature"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_capability_change_detected() -> Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = tempdir()?;
+        let binary = temp_dir.path().join("server");
+        fs::write(&binary, b"#!/bin/sh\n")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        // cap_net_bind_service+ep as revision 2 vfs_cap_data
+        let raw: Vec<u8> = [0x0200_0001u32, 1 << 10, 0, 0, 0]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        let c_path = CString::new(binary.as_os_str().as_bytes())?;
+        let c_name = CString::new(crate::capabilities::CAPABILITY_XATTR)?;
+        let rc = unsafe {
+            libc::setxattr(c_path.as_ptr(), c_name.as_ptr(), raw.as_ptr() as *const libc::c_void, raw.len(), 0)
+        };
+        if rc != 0 {
+            // Needs CAP_SETFCAP and a filesystem supporting security xattrs
+            eprintln!("skipping: cannot set file capabilities: {}", std::io::Error::last_os_error());
+            return Ok(());
+        }
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_clone = changes.clone();
+        engine.add_change_handler(move |change| changes_clone.lock().unwrap().push(change.clone()));
+        engine.incremental_scan()?;
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::AttributeChanged);
+        let new_entry = changes[0].new_entry.as_ref().unwrap();
+        assert_eq!(new_entry.capabilities.as_deref(), Some("cap_net_bind_service+ep"));
+
+        let alert = crate::reporting::AlertGenerator::new().generate_alert(&changes[0]);
+        assert_eq!(alert.severity, crate::reporting::AlertSeverity::Critical);
+        assert!(alert.message.contains("none -> cap_net_bind_service+ep"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_tree_against_manifest() -> Result<()> {
+        let release = tempdir()?;
+        let root = release.path();
+        fs::create_dir(root.join("bin"))?;
+        fs::write(root.join("bin").join("tool"), b"tool v1")?;
+        fs::write(root.join("README"), b"docs")?;
+        fs::write(root.join("config.toml"), b"key = 1")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("tool", root.join("bin").join("current"))?;
+
+        let secret = [42u8; 32];
+        let public = ed25519_dalek::SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+        let mut tree_manifest = TreeManifest::from_tree(root, &FileHasher::blake3_only())?;
+        tree_manifest.sign(&secret)?;
+        let manifest_path = root.join("MANIFEST.json");
+        tree_manifest.save(&manifest_path)?;
+
+        fs::write(root.join("bin").join("tool"), b"tool v1 + backdoor")?;
+        fs::write(root.join("bin").join("dropper"), b"payload")?;
+        let mut mismatched = vec![root.join("bin").join("tool")];
+        let mut extra = vec![root.join("bin").join("dropper")];
+        // Links are checked by target, and count as extra like files
+        #[cfg(unix)]
+        {
+            fs::remove_file(root.join("bin").join("current"))?;
+            std::os::unix::fs::symlink("dropper", root.join("bin").join("current"))?;
+            std::os::unix::fs::symlink("/bin/sh", root.join("bin").join("sh"))?;
+            mismatched.insert(0, root.join("bin").join("current"));
+            extra.push(root.join("bin").join("sh"));
+        }
+
+        let config = FimConfig {
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let engine = FimEngine::new(config)?;
+
+        let report = engine.verify_tree_against_manifest(root, &manifest_path, &public)?;
+        assert!(!report.is_clean());
+        assert_eq!(report.mismatched, mismatched);
+        assert_eq!(report.extra, extra);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.verified.len(), 2);
+
+        // A manifest checked with the wrong key is rejected outright
+        let wrong = ed25519_dalek::SigningKey::from_bytes(&[1u8; 32]).verifying_key().to_bytes();
+        assert!(engine.verify_tree_against_manifest(root, &manifest_path, &wrong).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wal_checkpoint_interval() -> Result<()> {
+        let temp_dir = tempdir()?;
+        for i in 0..1000 {
+            fs::write(temp_dir.path().join(format!("file{}.txt", i)), i.to_string())?;
+        }
+
+        for (interval, expected) in [(None, 0), (Some(0), 1)] {
+            let config = FimConfig {
+                monitor_paths: vec![temp_dir.path().to_path_buf()],
+                memory_database: true,
+                enable_realtime: false,
+                wal_checkpoint_interval: interval,
+                ..Default::default()
+            };
+            let mut engine = FimEngine::new(config)?;
+            engine.baseline_scan()?;
+            assert_eq!(engine.database.checkpoint_count(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_scan_paths() -> Result<()> {
+        let temp_dir = tempdir()?;
+        // Sibling sharing the scoped root's name as a prefix
+        let etc = temp_dir.path().join("etc");
+        let etc_old = temp_dir.path().join("etc-old");
+        fs::create_dir_all(etc.join("ssh"))?;
+        fs::create_dir_all(&etc_old)?;
+        fs::write(etc.join("hosts"), b"127.0.0.1 localhost")?;
+        fs::write(etc.join("ssh").join("sshd_config"), b"PermitRootLogin no")?;
+        fs::write(etc_old.join("hosts"), b"old hosts")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![etc.clone(), etc_old.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        fs::write(etc.join("hosts"), b"10.0.0.1 attacker")?;
+        fs::remove_file(etc.join("ssh").join("sshd_config"))?;
+        fs::remove_file(etc_old.join("hosts"))?;
+
+        let (results, changes) = engine.incremental_scan_paths(std::slice::from_ref(&etc))?;
+        assert_eq!(results.files_scanned, 1);
+        assert_eq!(results.files_modified, 1);
+        assert_eq!(results.files_deleted, 1);
+
+        let mut summary: Vec<_> = changes.iter()
+            .map(|change| (change.path.clone(), change.change_type.clone()))
+            .collect();
+        summary.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(summary, vec![
+            (etc.join("hosts"), ChangeType::HashChanged),
+            (etc.join("ssh").join("sshd_config"), ChangeType::Deleted),
+        ]);
+
+        // The other subtree is untouched, even though its file is gone
+        assert!(engine.database.get_path(&etc_old.join("hosts"))?.is_some());
+        assert!(engine.database.get_path(&etc.join("ssh").join("sshd_config"))?.is_none());
+
+        assert!(engine.incremental_scan_paths(&[temp_dir.path().join("var")]).is_err());
+
+        // Overlapping roots report each deletion once
+        fs::write(etc.join("ssh").join("sshd_config"), b"PermitRootLogin no")?;
+        engine.baseline_scan()?;
+        fs::remove_file(etc.join("ssh").join("sshd_config"))?;
+        let (results, changes) = engine.incremental_scan_paths(&[etc.clone(), etc.join("ssh")])?;
+        assert_eq!(results.files_deleted, 1);
+        assert_eq!(changes.iter().filter(|change| change.change_type == ChangeType::Deleted).count(), 1);
+
+        // A root climbing out of a monitored path is rejected
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir(&outside)?;
+        fs::write(outside.join("x"), b"unmonitored")?;
+        assert!(engine.incremental_scan_paths(&[etc.join("../outside")]).is_err());
+        assert!(engine.database.get_all_entries()?.iter().all(|entry| !entry.path.ends_with("x")));
+
+        // Resolving `..` that stays inside is fine, and records clean paths
+        let (results, _) = engine.incremental_scan_paths(&[etc.join("ssh/../")])?;
+        assert_eq!(results.files_scanned, 1);
+        assert!(engine.database.get_all_entries()?.iter().all(|entry| !entry.path.to_string_lossy().contains("..")));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unmounted_filesystem_held() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mount_point = temp_dir.path().join("mnt");
+        fs::create_dir(&mount_point)?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(mount_point.join(name), name)?;
+        }
+        fs::write(temp_dir.path().join("local.txt"), b"local")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        // Pretend the files under mnt/ lived on another filesystem
+        let mounted_dev = directory_device(&mount_point).flatten().unwrap() + 1;
+        for entry in engine.database.get_all_entries()? {
+            if entry.path.starts_with(&mount_point) {
+                let mut data = entry.data;
+                data.dev = mounted_dev;
+                engine.database.insert_data(&entry.path, &data)?;
+            }
+        }
+
+        // Unmount: the mount point is left as an empty directory
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::remove_file(mount_point.join(name))?;
+        }
+        fs::remove_file(temp_dir.path().join("local.txt"))?;
+
+        let root = [temp_dir.path().to_path_buf()];
+        let (results, changes) = engine.incremental_scan_paths(&root)?;
+        let summary: Vec<_> = changes.iter()
+            .map(|change| (change.path.clone(), change.change_type.clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            (mount_point.clone(), ChangeType::MountChanged),
+            (temp_dir.path().join("local.txt"), ChangeType::Deleted),
+        ]);
+        assert_eq!(results.files_deleted, 1);
+        assert!(engine.database.get_path(&mount_point.join("a.txt"))?.is_some());
+
+        // Still unmounted: nothing new to report
+        let (_, changes) = engine.incremental_scan_paths(&root)?;
+        assert!(changes.is_empty());
+
+        // Remount: the held entries are found again rather than re-added
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(mount_point.join(name), name)?;
+        }
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_scanned, 3);
+        assert_eq!(results.files_added + results.files_deleted, 0);
+        assert!(engine.held_mounts.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_directory_is_not_a_mount() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("d");
+        let sub = root.join("sub");
+        fs::create_dir_all(&sub)?;
+        for name in ["a.txt", "b.txt"] {
+            fs::write(sub.join(name), name)?;
+        }
+        let elsewhere = temp_dir.path().join("elsewhere");
+        fs::create_dir(&elsewhere)?;
+
+        let config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        // The files seem to live on another filesystem, and the directory is
+        // swapped for a symlink to one
+        let other_dev = directory_device(&elsewhere).flatten().unwrap() + 1;
+        for entry in engine.database.get_all_entries()? {
+            let data = FimEntryData { dev: other_dev, ..entry.data };
+            engine.database.insert_data(&entry.path, &data)?;
+        }
+        fs::remove_dir_all(&sub)?;
+        std::os::unix::fs::symlink(&elsewhere, &sub)?;
+
+        let (results, changes) = engine.incremental_scan_paths(std::slice::from_ref(&root))?;
+        assert!(changes.iter().all(|change| change.change_type != ChangeType::MountChanged), "{:?}", changes);
+        assert_eq!(results.files_deleted, 2);
+        assert!(engine.database.get_path(&sub.join("a.txt"))?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_from_iter() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let files: Vec<_> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| temp_dir.path().join(name))
+            .collect();
+        for file in &files {
+            fs::write(file, b"original")?;
+        }
+
+        let excluded = temp_dir.path().join("debug.log");
+        fs::write(&excluded, b"noise")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            exclude_patterns: vec!["*.log".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+        let baseline_c = engine.database.get_path(&files[2])?.unwrap();
+
+        for file in &files {
+            fs::write(file, b"tampered")?;
+        }
+        fs::write(temp_dir.path().join("new.txt"), b"unlisted")?;
+
+        // Excluded paths are skipped even when listed
+        let listed = files[..2].iter().cloned().chain([excluded.clone()]);
+        let (results, changes) = engine.scan_from_iter(listed)?;
+        assert_eq!(results.files_scanned, 2);
+        assert_eq!(results.files_modified, 2);
+        let changed: Vec<_> = changes.iter().map(|change| change.path.clone()).collect();
+        assert_eq!(changed, files[..2].to_vec());
+
+        // Unlisted files are neither compared nor added
+        let entry_c = engine.database.get_path(&files[2])?.unwrap();
+        assert_eq!(entry_c.data.blake3, baseline_c.data.blake3);
+        assert!(engine.database.get_path(&temp_dir.path().join("new.txt"))?.is_none());
+        assert!(engine.database.get_path(&excluded)?.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_realtime_moves() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("watched");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(root.join("dir"))?;
+        fs::create_dir(&outside)?;
+        for name in ["a.txt", "d.txt", "gone.txt"] {
+            fs::write(root.join(name), name)?;
+        }
+        fs::write(root.join("dir").join("x.txt"), b"nested")?;
+
+        let mut config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        config.watch_config.debounce_timeout = Duration::from_millis(50);
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+        let first_seen = engine.database.get_path(&root.join("a.txt"))?.unwrap().created_at;
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&changes);
+        engine.add_change_handler(move |change| {
+            recorded.lock().unwrap().push(change.change_type.clone());
+        });
+        let event = |engine: &mut FimEngine, kind, path: PathBuf| {
+            engine.handle_realtime_event(FimEvent {
+                kind,
+                path,
+                timestamp: Utc::now(),
+                size: None,
+                is_directory: false,
+            })
+        };
+        let moved = |from: &str, to: &str| ChangeType::Moved { from: root.join(from), to: root.join(to) };
+
+        // Both halves observed: the entry moves along with its first-seen time
+        fs::rename(root.join("a.txt"), root.join("b.txt"))?;
+        event(&mut engine, FimEventKind::MovedFrom(Some(root.join("a.txt"))), root.join("b.txt"))?;
+        assert!(engine.database.get_path(&root.join("a.txt"))?.is_none());
+        assert_eq!(engine.database.get_path(&root.join("b.txt"))?.unwrap().created_at, first_seen);
+
+        // Only the destination observed: the source is found by inode
+        fs::rename(root.join("b.txt"), root.join("c.txt"))?;
+        event(&mut engine, FimEventKind::MovedFrom(None), root.join("c.txt"))?;
+
+        // Only the source observed, then the destination within the window
+        fs::rename(root.join("d.txt"), root.join("e.txt"))?;
+        event(&mut engine, FimEventKind::MovedTo(None), root.join("d.txt"))?;
+        event(&mut engine, FimEventKind::MovedFrom(None), root.join("e.txt"))?;
+
+        // A directory rename moves every entry below it
+        fs::rename(root.join("dir"), root.join("renamed"))?;
+        event(&mut engine, FimEventKind::MovedFrom(Some(root.join("dir"))), root.join("renamed"))?;
+        assert!(engine.database.get_path(&root.join("renamed").join("x.txt"))?.is_some());
+
+        assert_eq!(*changes.lock().unwrap(), vec![
+            moved("a.txt", "b.txt"),
+            moved("b.txt", "c.txt"),
+            moved("d.txt", "e.txt"),
+            moved("dir/x.txt", "renamed/x.txt"),
+        ]);
+        changes.lock().unwrap().clear();
+
+        // Moved out of the monitored tree: deleted once the window passes
+        fs::rename(root.join("gone.txt"), outside.join("gone.txt"))?;
+        event(&mut engine, FimEventKind::MovedTo(None), root.join("gone.txt"))?;
+        engine.flush_coalesced_events(false);
+        assert!(changes.lock().unwrap().is_empty());
+        assert!(engine.database.get_path(&root.join("gone.txt"))?.is_some());
+        std::thread::sleep(Duration::from_millis(60));
+        engine.flush_coalesced_events(false);
+        assert_eq!(*changes.lock().unwrap(), vec![ChangeType::Deleted]);
+        changes.lock().unwrap().clear();
+
+        // Moved onto a recorded file, edited on the way: the replaced entry
+        // and the edit are reported along with the move
+        fs::write(root.join("c.txt"), b"edited in flight")?;
+        fs::rename(root.join("c.txt"), root.join("e.txt"))?;
+        event(&mut engine, FimEventKind::MovedFrom(Some(root.join("c.txt"))), root.join("e.txt"))?;
+        assert_eq!(*changes.lock().unwrap(), vec![
+            moved("c.txt", "e.txt"),
+            ChangeType::HashChanged,
+            ChangeType::HashChanged,
+        ]);
+
+        assert_eq!(engine.get_stats()?.total_files, 2);
+        assert!(engine.check_database_integrity()?.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesced_realtime_events() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("burst.txt");
+        fs::write(&file_path, b"v0")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            coalesce_window: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&changes);
+        engine.add_change_handler(move |change| {
+            recorded.lock().unwrap().push(change.change_type.clone());
+        });
+
+        // A burst of writes, each reported by the watcher
+        for i in 1..=5 {
+            fs::write(&file_path, format!("v{}", i))?;
+            engine.handle_realtime_event(FimEvent {
+                kind: FimEventKind::Modified,
+                path: file_path.clone(),
+                timestamp: Utc::now(),
+                size: None,
+                is_directory: false,
+            })?;
+        }
+
+        engine.flush_coalesced_events(false);
+        assert!(changes.lock().unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        engine.flush_coalesced_events(false);
+        assert_eq!(*changes.lock().unwrap(), vec![ChangeType::HashChanged]);
+        assert_eq!(engine.database.get_path(&file_path)?.unwrap().data.blake3,
+            blake3::hash(b"v5").to_hex().to_string());
+
+        // Writes followed by a delete within the window are a single delete
+        changes.lock().unwrap().clear();
+        fs::write(&file_path, b"v6")?;
+        fs::remove_file(&file_path)?;
+        for kind in [FimEventKind::Modified, FimEventKind::Deleted] {
+            engine.handle_realtime_event(FimEvent {
+                kind,
+                path: file_path.clone(),
+                timestamp: Utc::now(),
+                size: None,
+                is_directory: false,
+            })?;
+        }
+        std::thread::sleep(Duration::from_millis(60));
+        engine.flush_coalesced_events(false);
+        assert_eq!(*changes.lock().unwrap(), vec![ChangeType::Deleted]);
+        assert!(engine.database.get_path(&file_path)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_realtime_loop_wakes_on_events() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("watched.txt");
+        fs::write(&file_path, b"before")?;
+
+        let mut config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            ..Default::default()
+        };
+        config.watch_config.debounce_timeout = Duration::from_millis(20);
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        let (sender, detected) = std::sync::mpsc::channel();
+        engine.add_change_handler(move |change| {
+            let _ = sender.send((Instant::now(), change.path.clone()));
+        });
+        engine.start()?;
+        let stop = engine.stop_handle();
+        let realtime = std::thread::spawn(move || engine.process_realtime_events());
+
+        // Give the watcher a moment to register before writing
+        std::thread::sleep(Duration::from_millis(100));
+        let written = Instant::now();
+        fs::write(&file_path, b"after")?;
+        let (seen, path) = detected.recv_timeout(Duration::from_secs(5))?;
+        assert_eq!(path, file_path);
+        // Debounce and event delivery, with no polling interval on top
+        assert!(seen.duration_since(written) < Duration::from_secs(1), "{:?}", seen - written);
+
+        // A stop is noticed while the loop waits for events
+        let stopped = Instant::now();
+        stop.stop();
+        realtime.join().unwrap()?;
+        assert!(stopped.elapsed() < STOP_CHECK_INTERVAL * 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_scannable() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::create_dir_all(temp_dir.path().join("target").join("debug"))?;
+        fs::create_dir(temp_dir.path().join("src"))?;
+        fs::write(temp_dir.path().join("src").join("main.rs"), b"fn main() {}")?;
+        fs::write(temp_dir.path().join("Cargo.toml"), b"[package]")?;
+        fs::write(temp_dir.path().join("scratch.tmp"), b"scratch")?;
+        fs::write(temp_dir.path().join("target").join("debug").join("app"), b"binary")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let engine = FimEngine::new(config)?;
+
+        let files = engine.list_scannable()?;
+        assert_eq!(files, vec![
+            temp_dir.path().join("Cargo.toml"),
+            temp_dir.path().join("src").join("main.rs"),
+        ]);
+        // Nothing was hashed or stored
+        assert_eq!(engine.get_stats()?.total_files, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_class_change_detected() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let config_path = temp_dir.path().join("sshd_config");
+        fs::write(&config_path, b"PermitRootLogin no\n")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+        assert_eq!(
+            engine.database.get_path(&config_path)?.unwrap().data.content_type,
+            Some(ContentClass::Text)
+        );
+
+        fs::write(&config_path, b"\x7fELF\x02\x01\x01\x00\x00\x00")?;
+        let (_, changes) = engine.scan_from_iter([config_path.clone()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::ContentClassChanged);
+        assert!(changes[0].field_changes().contains(&FieldChange {
+            field: "content_type".to_string(),
+            old: "text".to_string(),
+            new: "binary".to_string(),
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_retry_on_transient_lock() -> Result<()> {
+        #[cfg(windows)]
+        let locked = || std::io::Error::from_raw_os_error(32);
+        #[cfg(not(windows))]
+        let locked = || std::io::Error::from_raw_os_error(libc::EBUSY);
+
+        let policy = RetryConfig {
+            max_retries: 3,
+            initial_backoff_ms: 1,
+        };
+
+        // Locked twice, then released
+        let mut attempts = 0;
+        let hashed = policy.run(|| {
+            attempts += 1;
+            if attempts <= 2 {
+                Err(anyhow::Error::new(locked()).context("Failed to open file"))
+            } else {
+                Ok("hash")
+            }
+        })?;
+        assert_eq!(hashed, "hash");
+        assert_eq!(attempts, 3);
+
+        // Other errors fail immediately
+        let mut attempts = 0;
+        let result: Result<()> = policy.run(|| {
+            attempts += 1;
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound).into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        // Giving up after the configured retries
+        let mut attempts = 0;
+        let result: Result<()> = policy.run(|| {
+            attempts += 1;
+            Err(locked().into())
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded_write_queue_with_slow_writer() -> Result<()> {
+        use std::sync::atomic::AtomicUsize;
+
+        let workers = 4;
+        let depth = 8;
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(workers).build()?;
+        let items: Vec<usize> = (0..200).collect();
+        let produced = AtomicUsize::new(0);
+        let mut written = Vec::new();
+        let mut max_outstanding = 0;
+
+        let high_water = bounded_pipeline(
+            &pool,
+            &items,
+            depth,
+            |&item| {
+                produced.fetch_add(1, Ordering::SeqCst);
+                item
+            },
+            |item| {
+                max_outstanding = max_outstanding.max(produced.load(Ordering::SeqCst) - written.len());
+                std::thread::sleep(Duration::from_millis(1));
+                written.push(item);
+            },
+        );
+
+        assert!(high_water >= 1 && high_water <= depth);
+        // Queued items, one blocked send per worker, and the item being written
+        assert!(max_outstanding <= depth + workers + 1, "{} outstanding", max_outstanding);
+        written.sort_unstable();
+        assert_eq!(written, items);
+
+        // Baselines persist everything through a tiny queue
+        let temp_dir = tempdir()?;
+        for i in 0..50 {
+            fs::write(temp_dir.path().join(format!("file{}.txt", i)), format!("content {}", i))?;
+        }
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            write_queue_depth: 2,
+            ..Default::default()
+        })?;
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_scanned, 50);
+        assert!(results.write_queue_high_water <= 2);
+        assert_eq!(engine.get_stats()?.total_files, 50);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_hash_algorithms_recorded() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file = temp_dir.path().join("app.conf");
+        fs::write(&file, b"abc")?;
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            hash_config: HashConfig {
+                use_sha512: true,
+                use_sha3_256: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+
+        let entry = engine.database.get_path(&file)?.unwrap().data;
+        assert!(entry.sha512.as_deref().is_some_and(|hash| hash.starts_with("ddaf35a1")));
+        assert!(entry.sha3_256.as_deref().is_some_and(|hash| hash.starts_with("3a985da7")));
+        assert_eq!(entry.sha256, None);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sampled_hashing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        let image = root.join("vm.img");
+        let conf = root.join("vm.conf");
+        fs::write(&image, vec![0u8; 4096])?;
+        fs::write(&conf, vec![0u8; 4096])?;
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            trust_mtime: false,
+            compare_policy: ComparePolicy::ContentOnly,
+            hash_config: HashConfig {
+                sampling: Some(SamplingConfig {
+                    path_patterns: vec!["**/*.img".to_string()],
+                    sample_bytes: 512,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+        assert!(engine.database.get_path(&image)?.unwrap().data.sampled);
+        assert!(!engine.database.get_path(&conf)?.unwrap().data.sampled);
+
+        // A same-size write to the middle is only seen in files hashed in full
+        let write_at = |path: &Path, offset: u64| -> Result<()> {
+            use std::os::unix::fs::FileExt;
+            fs::OpenOptions::new().write(true).open(path)?.write_all_at(b"changed", offset)?;
+            Ok(())
+        };
+        write_at(&image, 2048)?;
+        write_at(&conf, 2048)?;
+        let (_, changes) = engine.incremental_scan_paths(&[root.to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, conf);
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+
+        // Changes to the ends or the size are modifications
+        write_at(&image, 4000)?;
+        let (_, changes) = engine.incremental_scan_paths(&[root.to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::Modified);
+        fs::OpenOptions::new().write(true).open(&image)?.set_len(8192)?;
+        let (_, changes) = engine.incremental_scan_paths(&[root.to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::Modified);
+
+        // Sampling nothing would fingerprint files by size alone
+        let empty_sample = FimEngine::new(FimConfig {
+            memory_database: true,
+            enable_realtime: false,
+            hash_config: HashConfig {
+                sampling: Some(SamplingConfig {
+                    path_patterns: vec!["**/*.img".to_string()],
+                    sample_bytes: 0,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert!(empty_sample.err().unwrap().to_string().contains("sample_bytes"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xxh3_change_detection_without_blake3() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file = temp_dir.path().join("movie.mkv");
+        fs::write(&file, b"frame one")?;
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            trust_mtime: false,
+            hash_config: HashConfig {
+                use_blake3: false,
+                use_xxh3: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+
+        let entry = engine.database.get_path(&file)?.unwrap().data;
+        assert!(entry.blake3.is_empty());
+        assert_eq!(entry.xxh3.as_deref().map(str::len), Some(16));
+
+        // Same size, different content
+        fs::write(&file, b"frame two")?;
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_phase_durations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        for i in 0..200 {
+            let dir = temp_dir.path().join(format!("dir{}", i % 10));
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join(format!("file{}.txt", i)), format!("content {}", i).repeat(500))?;
+        }
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        })?;
+
+        let check = |results: &ScanResults| {
+            assert!(results.walk_duration > Duration::ZERO);
+            assert!(results.hash_duration > Duration::ZERO);
+            assert!(results.db_duration > Duration::ZERO);
+
+            // The phases are disjoint and cover most of the scan
+            let phases = results.walk_duration + results.hash_duration + results.db_duration;
+            assert!(phases <= results.scan_duration, "{:?} > {:?}", phases, results.scan_duration);
+            assert!(phases * 2 >= results.scan_duration, "{:?} of {:?}", phases, results.scan_duration);
+        };
+
+        check(&engine.baseline_scan()?);
+
+        for i in 0..20 {
+            fs::write(temp_dir.path().join(format!("dir{}/file{}.txt", i % 10, i)), "edited")?;
+        }
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_modified, 20);
+        check(&results);
+
+        check(&engine.verify_scan()?.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_carry_severity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let sbin = temp_dir.path().join("sbin");
+        fs::create_dir_all(&sbin)?;
+        fs::write(sbin.join("sshd"), b"original binary")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![sbin.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        fs::write(sbin.join("sshd"), b"trojaned binary")?;
+        fs::write(sbin.join("notes.txt"), b"new file")?;
+        let (_, changes) = engine.incremental_scan_paths(std::slice::from_ref(&sbin))?;
+
+        let modified = changes.iter().find(|c| c.path == sbin.join("sshd")).unwrap();
+        assert_eq!(modified.change_type, ChangeType::HashChanged);
+        assert_eq!(modified.severity, AlertSeverity::Error);
+        assert!(modified.is_critical);
+
+        // Anything new in an sbin directory counts as a new executable
+        let added = changes.iter().find(|c| c.path == sbin.join("notes.txt")).unwrap();
+        assert_eq!(added.severity, AlertSeverity::Warning);
+        assert!(added.is_critical);
+
+        // The audit log keeps the classification
+        let recorded = engine.get_changes(None, None)?;
+        assert!(recorded.iter().any(|c| c.path == sbin.join("sshd") && c.severity == AlertSeverity::Error));
+
+        // Configured rules replace the defaults
+        let mut rules = SeverityRules::default();
+        rules.severities.insert(ChangeType::HashChanged, AlertSeverity::Critical);
+        let config: FimConfig = toml::from_str(&toml::to_string(&FimConfig {
+            severity_rules: rules,
+            ..Default::default()
+        })?)?;
+        assert_eq!(config.severity_rules.severity(&ChangeType::HashChanged), AlertSeverity::Critical);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompressed_content_hash() -> Result<()> {
+        use std::io::Write as _;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        let content: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("artifact line {} of the release bundle\n", i % 37).into_bytes())
+            .collect();
+
+        let gzip = |options: libflate::gzip::EncodeOptions<libflate::lz77::DefaultLz77Encoder>| -> Result<Vec<u8>> {
+            let mut encoder = libflate::gzip::Encoder::with_options(Vec::new(), options)?;
+            encoder.write_all(&content)?;
+            Ok(encoder.finish().into_result()?)
+        };
+        fs::write(root.join("bundle.tar.zst"), zstd::encode_all(content.as_slice(), 1)?)?;
+        fs::write(root.join("bundle.tar.gz"), gzip(libflate::gzip::EncodeOptions::new())?)?;
+        fs::write(root.join("other.zst"), zstd::encode_all(content.as_slice(), 1)?)?;
+
+        let config = FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            decompress_rules: vec![DecompressRule {
+                path_pattern: "**/bundle.tar.*".to_string(),
+                max_size: 1024 * 1024,
+            }],
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        let expected = blake3::hash(&content).to_hex().to_string();
+        let zst = engine.database.get_path(&root.join("bundle.tar.zst"))?.unwrap().data;
+        assert_eq!(zst.blake3, expected);
+        assert_eq!(zst.decompressed, Some(Compression::Zstd));
+        let gz = engine.database.get_path(&root.join("bundle.tar.gz"))?.unwrap().data;
+        assert_eq!(gz.blake3, expected);
+        assert_eq!(gz.decompressed, Some(Compression::Gzip));
+        // Files outside the rule keep their compressed-bytes hash
+        let other = engine.database.get_path(&root.join("other.zst"))?.unwrap().data;
+        assert_ne!(other.blake3, expected);
+        assert_eq!(other.decompressed, None);
+
+        // Recompressing at another level changes the bytes but not the stored hash
+        let recompressed = zstd::encode_all(content.as_slice(), 19)?;
+        assert_ne!(recompressed, fs::read(root.join("bundle.tar.zst"))?);
+        fs::write(root.join("bundle.tar.zst"), recompressed)?;
+        fs::write(root.join("bundle.tar.gz"), gzip(libflate::gzip::EncodeOptions::new().no_compression())?)?;
+
+        let (_, changes) = engine.incremental_scan_paths(&[root.to_path_buf()])?;
+        assert!(changes.iter().all(|change| change.change_type != ChangeType::HashChanged));
+        assert_eq!(engine.database.get_path(&root.join("bundle.tar.zst"))?.unwrap().data.blake3, expected);
+
+        // Decompression is bounded
+        let bomb = zstd::encode_all(vec![0u8; 2 * 1024 * 1024].as_slice(), 3)?;
+        fs::write(root.join("bundle.tar.zst"), bomb)?;
+        let error = engine.scan_single_file(&root.join("bundle.tar.zst")).unwrap_err();
+        assert!(format!("{:#}", error).contains("exceeds"));
+        // So is reading a matching file that turns out not to be compressed
+        fs::write(root.join("bundle.tar.gz"), vec![b'x'; 2 * 1024 * 1024])?;
+        let error = engine.scan_single_file(&root.join("bundle.tar.gz")).unwrap_err();
+        assert!(format!("{:#}", error).contains("exceeds"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_path() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        let (kept, edited, removed) = (root.join("kept.txt"), root.join("edited.txt"), root.join("removed.txt"));
+        for path in [&kept, &edited, &removed] {
+            fs::write(path, b"original")?;
+        }
+
+        let config = FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        fs::write(&edited, b"changed content")?;
+        fs::remove_file(&removed)?;
+        fs::write(root.join("new.txt"), b"new")?;
+
+        assert_eq!(engine.verify_path(&kept)?, VerifyOutcome::Match);
+        assert_eq!(engine.verify_path(&removed)?, VerifyOutcome::Missing);
+        assert_eq!(engine.verify_path(&root.join("new.txt"))?, VerifyOutcome::NotInBaseline);
+        let VerifyOutcome::Mismatch { fields } = engine.verify_path(&edited)? else {
+            panic!("edited file should not verify");
+        };
+        let names: Vec<_> = fields.iter().map(|f| f.field.as_str()).collect();
+        assert!(names.contains(&"size") && names.contains(&"blake3"));
+        assert!(!names.contains(&"mtime"));
+        let size = fields.iter().find(|f| f.field == "size").unwrap();
+        assert_eq!((size.old.as_str(), size.new.as_str()), ("8", "15"));
+
+        // Verification never updates the baseline
+        assert!(!engine.verify_path(&edited)?.is_match());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_database_round_trip() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let data = temp_dir.path().join("data");
+        fs::create_dir(&data)?;
+        for i in 0..20 {
+            fs::write(data.join(format!("file{}.txt", i)), format!("contents {}", i))?;
+        }
+        let config = FimConfig {
+            monitor_paths: vec![data],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config.clone())?;
+        engine.baseline_scan()?;
+
+        let export = temp_dir.path().join("baseline.json");
+        engine.export_database(&export)?;
+        let snapshot = FullSnapshot::load(&export, SnapshotFormat::Json)?;
+        let paths = |entries: &[FimEntry]| entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&snapshot.entries), paths(&engine.snapshot()?.entries));
+        assert_eq!(snapshot.checksum, engine.verify_integrity()?);
+
+        let mut imported = FimEngine::new(config)?;
+        assert_eq!(imported.import_snapshot(&snapshot)?, 20);
+        assert_eq!(imported.verify_integrity()?, engine.verify_integrity()?);
+
+        // An empty baseline still exports a loadable document
+        let empty = temp_dir.path().join("empty.json");
+        FimEngine::new(FimConfig { memory_database: true, ..Default::default() })?
+            .export_database(&empty)?;
+        assert!(FullSnapshot::load(&empty, SnapshotFormat::Json)?.entries.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metrics_snapshot_after_scan() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        fs::write(root.join("a.txt"), b"first file")?;
+        fs::write(root.join("b.txt"), b"second file")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        assert_eq!(engine.metrics_snapshot().scans_completed, 0);
+
+        engine.baseline_scan()?;
+        fs::write(root.join("a.txt"), b"first file, edited")?;
+        fs::remove_file(root.join("b.txt"))?;
+        fs::write(root.join("c.txt"), b"third file")?;
+        engine.incremental_scan()?;
+
+        let metrics = engine.metrics_snapshot();
+        assert_eq!(metrics.scans_completed, 2);
+        assert_eq!(metrics.files_scanned, 4);
+        assert_eq!(metrics.files_added, 3);
+        assert_eq!(metrics.files_modified, 1);
+        assert_eq!(metrics.files_deleted, 1);
+        assert!(metrics.bytes_scanned >= 21);
+        assert!(!metrics.scan_duration_total.is_zero());
+        assert!(metrics.scan_duration_total >= metrics.last_scan_duration);
+        assert_eq!(metrics.errors, 0);
+        assert_eq!(metrics.watcher_queue_depth, 0);
+        // The baseline's files are not changes
+        assert_eq!(metrics.changes_by_type.get("Added"), Some(&1));
+        assert_eq!(metrics.changes_by_type.get("HashChanged"), Some(&1));
+        assert_eq!(metrics.changes_by_type.get("Deleted"), Some(&1));
+        let text = engine.metrics_text();
+        assert!(text.contains("\nfim_files_scanned_total 4\n"));
+        assert!(text.contains("\nfim_changes_detected_total{change_type=\"Deleted\"} 1\n"));
+
+        let json = serde_json::to_value(&metrics)?;
+        assert_eq!(json["files_added"], 3);
+        let restored: MetricsSnapshot = serde_json::from_value(json)?;
+        assert_eq!(restored.files_scanned, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trust_mtime_skips_unchanged_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        let an_hour_ago = std::time::SystemTime::now() - Duration::from_secs(3600);
+        for name in ["untouched.txt", "touched.txt"] {
+            fs::write(root.join(name), b"original")?;
+            fs::File::options().write(true).open(root.join(name))?.set_modified(an_hour_ago)?;
+        }
+
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_scanned, 2);
+        assert_eq!(results.files_hash_skipped, 2);
+
+        // Same size, mtime set back: the inode change time still moved
+        fs::write(root.join("untouched.txt"), b"tampered")?;
+        fs::File::options().write(true).open(root.join("untouched.txt"))?.set_modified(an_hour_ago)?;
+        let (results, changes) = engine.incremental_scan_paths(&[root.to_path_buf()])?;
+        assert_eq!(results.files_hash_skipped, 1);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, root.join("untouched.txt"));
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+
+        // New mtime, same content: re-hashed
+        fs::File::options().write(true).open(root.join("touched.txt"))?.set_modified(std::time::SystemTime::now())?;
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_hash_skipped, 1);
+        assert_eq!(results.files_modified, 1);
+        assert_eq!(engine.get_stats()?.unscanned_files, 0);
+
+        // Without trust every file is hashed
+        engine.config.trust_mtime = false;
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_hash_skipped, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_handler() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        for index in 0..5 {
+            fs::write(root.join(format!("file{}.txt", index)), b"0123456789")?;
+        }
+
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            trust_mtime: false,
+            ..Default::default()
+        })?;
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let reports_sink = Arc::clone(&reports);
+        engine.set_progress_handler(move |progress| sink.lock().unwrap().push(progress));
+
+        engine.baseline_scan()?;
+        let last = *reports.lock().unwrap().last().unwrap();
+        assert_eq!(last.files_done, 5);
+        assert_eq!(last.files_total, 5);
+        assert_eq!(last.bytes_done, 50);
+        assert_eq!(last.estimated_remaining, Some(Duration::ZERO));
+
+        reports.lock().unwrap().clear();
+        fs::write(root.join("file0.txt"), b"edited")?;
+        engine.incremental_scan()?;
+        let reports = reports.lock().unwrap();
+        // Throttled, but the final count is always reported
+        assert!(!reports.is_empty() && reports.len() <= 5);
+        assert!(reports.windows(2).all(|pair| pair[0].files_done < pair[1].files_done));
+        let last = reports.last().unwrap();
+        assert_eq!((last.files_done, last.files_total), (5, 5));
+        assert_eq!(last.bytes_done, 46);
+        drop(reports);
+
+        // Files skipped without hashing still count towards the bytes done
+        engine.config.trust_mtime = true;
+        let an_hour_ago = std::time::SystemTime::now() - Duration::from_secs(3600);
+        for index in 0..5 {
+            fs::File::options().write(true).open(root.join(format!("file{}.txt", index)))?.set_modified(an_hour_ago)?;
+        }
+        engine.baseline_scan()?;
+        reports_sink.lock().unwrap().clear();
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_hash_skipped, 5);
+        let last = *reports_sink.lock().unwrap().last().unwrap();
+        assert_eq!((last.files_done, last.bytes_done), (5, 46));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_and_resume_baseline_scan() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        for index in 0..6 {
+            fs::write(root.join(format!("file{}.txt", index)), b"content")?;
+        }
+
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            scan_threads: Some(1),
+            write_queue_depth: 1,
+            ..Default::default()
+        })?;
+        assert!(engine.baseline_scan()?.completed);
+        fs::remove_file(root.join("file5.txt"))?;
+
+        // Stop from the first progress report, as another thread would
+        let stop = engine.stop_handle();
+        engine.set_progress_handler(move |_| stop.stop());
+        let results = engine.baseline_scan()?;
+        assert!(!results.completed);
+        assert_eq!(results.files_scanned, 1);
+        // Entries the scan did not reach are kept, including the stale one
+        assert_eq!(results.files_deleted, 0);
+        assert_eq!(engine.get_stats()?.total_files, 6);
+
+        engine.set_progress_handler(|_| {});
+        let results = engine.resume_scan()?;
+        assert!(results.completed);
+        assert_eq!(results.files_scanned, 4);
+        assert_eq!(results.files_deleted, 1);
+        assert_eq!(engine.get_stats()?.total_files, 5);
+
+        // Nothing left to resume
+        assert_eq!(engine.resume_scan()?.files_scanned, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_expands_file_lists() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path();
+        fs::create_dir(dir.join("lists"))?;
+        fs::write(
+            dir.join("lists/paths.txt"),
+            "# generated by the packaging manifest\n/etc/ssh\n\n  /usr/local/bin  \n/opt/app\n",
+        )?;
+        fs::write(dir.join("excludes.txt"), "**/*.bak\n# comment\n**/cache/**\n")?;
+        fs::write(
+            dir.join("fim.toml"),
+            format!(
+                "monitor_paths = [\"/srv\", \"@lists/paths.txt\"]\n\
+                 exclude_patterns = [\"**/*.tmp\", \"@{}\"]\n\
+                 memory_database = true\n\
+                 enable_realtime = false\n\
+                 scan_interval = 60\n\
+                 [hash_config]\n\
+                 use_blake3 = true\n\
+                 use_sha256 = false\n\
+                 use_sha1 = false\n\
+                 use_md5 = false\n\
+                 use_mmap = false\n\
+                 parallel_threshold = 1048576\n\
+                 [watch_config]\n\
+                 paths = []\n\
+                 recursive = true\n\
+                 debounce_timeout = 250\n\
+                 ignore_patterns = []\n\
+                 ignore_extensions = []\n\
+                 ignore_directories = []\n\
+                 max_events_per_second = 1000\n\
+                 [syslog]\n\
+                 facility = \"auth\"\n",
+                dir.join("excludes.txt").display()
+            ),
+        )?;
+
+        let config = FimConfig::load(&dir.join("fim.toml"))?;
+        assert_eq!(
+            config.monitor_paths,
+            ["/srv", "/etc/ssh", "/usr/local/bin", "/opt/app"].map(PathBuf::from)
+        );
+        assert_eq!(config.exclude_patterns, ["**/*.tmp", "**/*.bak", "**/cache/**"]);
+        assert_eq!(config.syslog.map(|syslog| syslog.facility).as_deref(), Some("auth"));
+        assert!(FimConfig::default().syslog.is_none());
+
+        let mut missing = FimConfig {
+            monitor_paths: vec![PathBuf::from("@missing.txt")],
+            ..Default::default()
+        };
+        assert!(missing.expand_file_lists(Some(dir)).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_policy_content_only() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        let file = temp_dir.path().join("app.conf");
+        fs::write(&file, b"listen = 8080")?;
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644))?;
+
+        let scan = |policy: ComparePolicy| -> Result<Vec<FileChange>> {
+            let config = FimConfig {
+                monitor_paths: vec![temp_dir.path().to_path_buf()],
+                memory_database: true,
+                enable_realtime: false,
+                compare_policy: policy,
+                ..Default::default()
+            };
+            let mut engine = FimEngine::new(config)?;
+            engine.baseline_scan()?;
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o600))?;
+            let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o644))?;
+            Ok(changes)
+        };
+
+        assert!(scan(ComparePolicy::ContentOnly)?.is_empty());
+        let changes = scan(ComparePolicy::ContentAndPerms)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::PermissionChanged);
+        assert_eq!(scan(ComparePolicy::Full)?[0].change_type, ChangeType::PermissionChanged);
+
+        // Content changes are still reported under ContentOnly
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            compare_policy: ComparePolicy::ContentOnly,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+        fs::write(&file, b"listen = 9090")?;
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o600))?;
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+
+        let config: FimConfig = toml::from_str(&toml::to_string(&engine.config)?)?;
+        assert_eq!(config.compare_policy, ComparePolicy::ContentOnly);
+
+        // Capability and extended attribute changes are not suppressed
+        let old_data = FimEntryData { xattr_hash: Some("a".into()), ..test_entry() };
+        let granted = FimEntryData { capabilities: Some("cap_setuid+ep".into()), ..old_data.clone() };
+        assert_eq!(engine.detect_change_type(&old_data, &granted), Some(ChangeType::AttributeChanged));
+        let relabelled = FimEntryData { xattr_hash: Some("b".into()), ..old_data.clone() };
+        assert_eq!(engine.detect_change_type(&old_data, &relabelled), Some(ChangeType::AttributeChanged));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_immutable_baseline_reports_drift_without_writing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root)?;
+        fs::write(root.join("keep.txt"), b"unchanged")?;
+        fs::write(root.join("edit.txt"), b"before")?;
+        fs::write(root.join("gone.txt"), b"deleted soon")?;
+        let db_path = temp_dir.path().join(crate::database::DEFAULT_DB_FILE);
+
+        let config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let secret = [7u8; 32];
+        let public = ed25519_dalek::SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+        {
+            let database = FimDb::open(Some(&db_path), &DbOptions::default())?;
+            let mut engine = FimEngine::with_store(config.clone(), database)?;
+            engine.baseline_scan()?;
+            engine.database.sign_baseline(&secret)?;
+        }
+        let frozen = fs::read(&db_path)?;
+
+        fs::write(root.join("edit.txt"), b"after, and longer")?;
+        fs::remove_file(root.join("gone.txt"))?;
+        fs::write(root.join("new.txt"), b"added")?;
+
+        let database = FimDb::open_read_only(&db_path)?;
+        assert!(database.is_read_only());
+        database.verify_baseline_signature(&public)?;
+        assert!(database.verify_baseline_signature(&[1u8; 32]).is_err());
+
+        let immutable = FimConfig {
+            immutable_baseline: true,
+            ..config
+        };
+        let mut engine = FimEngine::with_store(immutable, database)?;
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler_seen = seen.clone();
+        engine.add_change_handler(move |change| {
+            handler_seen.lock().unwrap().push(change.change_type.clone());
+        });
+
+        let results = engine.incremental_scan()?;
+        assert_eq!((results.files_added, results.files_modified, results.files_deleted), (1, 1, 1));
+        assert_eq!(seen.lock().unwrap().len(), 3);
+
+        // Drift stays reported because nothing was written
+        let (results, changes) = engine.verify_scan()?;
+        assert_eq!((results.files_added, results.files_modified, results.files_deleted), (1, 1, 1));
+        let deleted = changes.iter().find(|change| change.path == root.join("gone.txt")).unwrap();
+        assert_eq!(deleted.change_type, ChangeType::Deleted);
+        assert_eq!(deleted.severity, AlertSeverity::Critical);
+
+        assert!(engine.baseline_scan().is_err());
+        assert!(engine.scan_from_iter([root.join("new.txt")]).is_err());
+        assert!(engine.database.insert_data(&root.join("new.txt"), &deleted.old_entry.clone().unwrap()).is_err());
+
+        // Real-time events are compared against the baseline too, not recorded
+        seen.lock().unwrap().clear();
+        fs::rename(root.join("keep.txt"), root.join("kept.txt"))?;
+        for (kind, path) in [
+            (FimEventKind::Modified, root.join("edit.txt")),
+            (FimEventKind::MovedFrom(Some(root.join("keep.txt"))), root.join("kept.txt")),
+        ] {
+            engine.handle_realtime_event(FimEvent {
+                kind,
+                path,
+                timestamp: Utc::now(),
+                size: None,
+                is_directory: false,
+            })?;
+        }
+        engine.flush_coalesced_events(true);
+        assert_eq!(*seen.lock().unwrap(), vec![ChangeType::HashChanged, ChangeType::Deleted, ChangeType::Added]);
+        drop(engine);
+
+        assert_eq!(fs::read(&db_path)?, frozen);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_insensitive_exclude_patterns() -> Result<()> {
+        let config = FimConfig {
+            exclude_patterns: vec!["**/*.LOG".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            case_insensitive_patterns: false,
+            ..Default::default()
+        };
+        let engine = FimEngine::new(config.clone())?;
+        assert!(!engine.should_ignore_path(Path::new("/var/app/file.log")));
+        assert!(engine.should_ignore_path(Path::new("/var/app/FILE.LOG")));
+
+        let engine = FimEngine::new(FimConfig {
+            case_insensitive_patterns: true,
+            ..config
+        })?;
+        assert!(engine.should_ignore_path(Path::new("/var/app/file.log")));
+        assert!(engine.should_ignore_path(Path::new("/var/app/File.Log")));
+        assert!(!engine.should_ignore_path(Path::new("/var/app/file.logs")));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exclude_patterns_normalize_paths() -> Result<()> {
+        let engine = FimEngine::new(FimConfig {
+            exclude_patterns: vec!["/var/log/**".to_string(), "/srv/cache/".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            case_insensitive_patterns: false,
+            ..Default::default()
+        })?;
+
+        for excluded in [
+            "/var/log/syslog",
+            "/var//log/syslog",
+            "/var/log//syslog/",
+            "/var/./log/syslog",
+            "/var/app/../log/syslog",
+            "/srv/cache",
+            "/srv//cache/",
+        ] {
+            assert!(engine.should_ignore_path(Path::new(excluded)), "{} kept", excluded);
+        }
+        for kept in [
+            "/var/log/../app/config",
+            "/var/log/../../etc/passwd",
+            "/var/logs/syslog",
+            "/srv/cache/../www",
+        ] {
+            assert!(!engine.should_ignore_path(Path::new(kept)), "{} excluded", kept);
+        }
+
+        Ok(())
     }
 
     #[test]
-    fn test_scan_single_file() -> Result<()> {
+    fn test_negated_exclude_walks_excluded_directory() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("cache").join("keep"))?;
+        fs::create_dir_all(root.join("cache").join("tmp"))?;
+        fs::write(root.join("cache").join("keep").join("state.json"), b"{}")?;
+        fs::write(root.join("cache").join("tmp").join("junk.bin"), b"junk")?;
+        fs::write(root.join("cache").join("index.dat"), b"index")?;
+        fs::write(root.join("config.toml"), b"key = 1")?;
+
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            exclude_patterns: vec!["**/cache/**".to_string(), "!**/cache/keep/**".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            monitor_directories: true,
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+
+        let recorded: Vec<PathBuf> = engine.snapshot()?.entries.into_iter().map(|entry| entry.path).collect();
+        assert!(recorded.contains(&root.join("cache").join("keep").join("state.json")));
+        assert!(recorded.contains(&root.join("config.toml")));
+        for excluded in [
+            root.join("cache").join("keep"),
+            root.join("cache").join("tmp"),
+            root.join("cache").join("tmp").join("junk.bin"),
+            root.join("cache").join("index.dat"),
+        ] {
+            assert!(!recorded.contains(&excluded), "{} recorded", excluded.display());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_patterns_deep_and_negated() -> Result<()> {
+        let engine = FimEngine::new(FimConfig {
+            exclude_patterns: vec![
+                "**/target/**".to_string(),
+                "**/*.log".to_string(),
+                "!**/audit/*.log".to_string(),
+                "**/cache/**".to_string(),
+                "!**/cache/keep/**".to_string(),
+            ],
+            memory_database: true,
+            enable_realtime: false,
+            case_insensitive_patterns: false,
+            ..Default::default()
+        })?;
+
+        for excluded in [
+            "/home/dev/src/project/crates/core/target/debug/build/out.o",
+            "/a/b/c/d/e/f/g/target/release/deps/libfoo.rlib",
+            "/var/log/app/2024/01/01/service.log",
+            "/srv/app/cache/images/thumb.png",
+            "/srv/app/cache/keep.txt",
+        ] {
+            assert!(engine.should_ignore_path(Path::new(excluded)), "{} kept", excluded);
+        }
+        for kept in [
+            "/home/dev/src/project/targets/notes.txt",
+            "/home/dev/src/target.rs",
+            "/var/log/audit/audit.log",
+            "/srv/app/cache/keep/state.json",
+            "/srv/app/cache/keep/deep/nested/state.json",
+        ] {
+            assert!(!engine.should_ignore_path(Path::new(kept)), "{} excluded", kept);
+        }
+
+        // The last matching pattern decides, as in .gitignore
+        let engine = FimEngine::new(FimConfig {
+            exclude_patterns: vec!["!**/audit/*.log".to_string(), "**/*.log".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        })?;
+        assert!(engine.should_ignore_path(Path::new("/var/log/audit/audit.log")));
+
+        // An invalid pattern is reported when the engine is built
+        for (exclude_patterns, include_patterns) in [
+            (vec!["**/cache/[a-".to_string()], vec![]),
+            (vec![], vec!["**/*.{conf,key".to_string()]),
+        ] {
+            let Err(error) = FimEngine::new(FimConfig {
+                exclude_patterns,
+                include_patterns,
+                memory_database: true,
+                enable_realtime: false,
+                ..Default::default()
+            }) else {
+                panic!("built an engine with an invalid pattern");
+            };
+            assert!(format!("{:#}", error).contains("Invalid"), "{:#}", error);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_patterns() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("etc");
+        fs::create_dir_all(root.join("ssl").join("private"))?;
+        fs::write(root.join("app.conf"), b"setting = 1")?;
+        fs::write(root.join("motd"), b"welcome")?;
+        fs::write(root.join("ssl").join("server.key"), b"key")?;
+        fs::write(root.join("ssl").join("private").join("ca.key"), b"ca key")?;
+        fs::write(root.join("ssl").join("readme.txt"), b"docs")?;
+
+        let engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root.clone()],
+            exclude_patterns: vec!["**/private/**".to_string()],
+            include_patterns: vec!["**/*.conf".to_string(), "**/*.key".to_string()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        })?;
+
+        // Only included files are collected, from nested directories too,
+        // and an exclude wins over a matching include
+        let mut files = engine.collect_files_to_scan()?;
+        files.sort();
+        assert_eq!(files, vec![root.join("app.conf"), root.join("ssl").join("server.key")]);
+        assert!(engine.should_ignore_path(&root.join("motd")));
+        assert!(!engine.should_ignore_path(&root.join("ssl")));
+
+        // Real-time events for files that don't exist yet follow the same rules
+        assert!(!engine.should_ignore_path(&root.join("new.conf")));
+        assert!(engine.should_ignore_path(&root.join("new.txt")));
+
+        // No includes keeps every file that isn't excluded
+        let engine = FimEngine::new(FimConfig {
+            include_patterns: vec![],
+            ..engine.config.clone()
+        })?;
+        assert_eq!(engine.collect_files_to_scan()?.len(), 4);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("etc");
+        fs::create_dir_all(root.join("conf.d"))?;
+        fs::write(root.join("real.conf"), b"setting = 1")?;
+        fs::write(root.join("other.conf"), b"setting = 2")?;
+        fs::write(root.join("conf.d").join("nested.conf"), b"nested")?;
+        symlink("real.conf", root.join("current.conf"))?;
+        // A loop back to the monitored root
+        symlink(&root, root.join("conf.d").join("loop"))?;
+
+        let engine_with = |symlink_policy| {
+            FimEngine::new(FimConfig {
+                monitor_paths: vec![root.clone()],
+                symlink_policy,
+                memory_database: true,
+                enable_realtime: false,
+                ..Default::default()
+            })
+        };
+
+        // Follow reaches the target content and stops at the loop
+        let engine = engine_with(SymlinkPolicy::Follow)?;
+        assert_eq!(engine.collect_files_to_scan()?.len(), 4);
+
+        // Ignore skips links wherever they appear
+        let engine = engine_with(SymlinkPolicy::Ignore)?;
+        let files = engine.collect_files_to_scan()?;
+        assert_eq!(files.len(), 3);
+        assert!(!files.contains(&root.join("current.conf")));
+        assert!(engine.should_ignore_path(&root.join("current.conf")));
+
+        // RecordLink stores the link and notices it being retargeted
+        let mut engine = engine_with(SymlinkPolicy::RecordLink)?;
+        assert_eq!(engine.collect_files_to_scan()?.len(), 5);
+        engine.baseline_scan()?;
+        let link = engine.get_entry(&root.join("current.conf"))?.expect("link recorded");
+        assert_eq!(link.data.link_target, Some(PathBuf::from("real.conf")));
+        assert_eq!(link.data.content_type, None);
+        assert!(engine.get_entry(&root.join("conf.d").join("loop"))?.is_some());
+
+        fs::remove_file(root.join("current.conf"))?;
+        symlink("other.conf", root.join("current.conf"))?;
+        let (_, changes) = engine.incremental_scan_paths(std::slice::from_ref(&root))?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, root.join("current.conf"));
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+        let link = engine.get_entry(&root.join("current.conf"))?.expect("link recorded");
+        assert_eq!(link.data.link_target, Some(PathBuf::from("other.conf")));
+
+        Ok(())
+    }
+
+    fn hidden_fixture() -> Result<(tempfile::TempDir, FimEngine)> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("home");
+        fs::create_dir_all(root.join("cache").join("nested"))?;
+        fs::write(root.join("visible.txt"), b"shown")?;
+        fs::write(root.join("secret.txt"), b"hidden file")?;
+        fs::write(root.join("cache").join("nested").join("data"), b"hidden dir")?;
+
+        let engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![root],
+            memory_database: true,
+            enable_realtime: false,
+            ignore_hidden: true,
+            ..Default::default()
+        })?;
+        Ok((temp_dir, engine))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ignore_hidden_dotfiles() -> Result<()> {
+        let (temp_dir, mut engine) = hidden_fixture()?;
+        let root = temp_dir.path().join("home");
+        fs::rename(root.join("secret.txt"), root.join(".secret.txt"))?;
+        fs::rename(root.join("cache"), root.join(".cache"))?;
+
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_added, 1);
+        assert!(engine.database.get_path(&root.join("visible.txt"))?.is_some());
+
+        // Watcher events below a hidden directory are dropped too
+        assert!(engine.should_ignore_path(&root.join(".cache").join("nested").join("data")));
+        assert!(!engine.should_ignore_path(&root.join("visible.txt")));
+
+        // A hidden monitored root is still scanned
+        engine.config.monitor_paths = vec![root.join(".cache")];
+        assert!(!engine.should_ignore_path(&root.join(".cache").join("nested").join("data")));
+
+        engine.config.ignore_hidden = false;
+        engine.config.monitor_paths = vec![root];
+        assert_eq!(engine.baseline_scan()?.files_added, 3);
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_acl_change() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file = temp_dir.path().join("config.ini");
+        fs::write(&file, "key=value")?;
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        })?;
+        engine.baseline_scan()?;
+        let entry = engine.database.get_path(&file)?.unwrap().data;
+        assert!(entry.owner_sid.as_deref().is_some_and(|sid| sid.starts_with("S-1-")));
+        assert!(entry.dacl.as_deref().is_some_and(|dacl| dacl.starts_with("D:")));
+
+        // Grant Everyone read access; the content and mtime stay the same
+        let status = std::process::Command::new("icacls").arg(&file).arg("/grant").arg("*S-1-1-0:R").status()?;
+        assert!(status.success());
+        let (_, changes) = engine.incremental_scan_paths(&[temp_dir.path().to_path_buf()])?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::PermissionChanged);
+        assert!(changes[0].field_changes().iter().any(|change| change.field == "dacl"));
+
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_ignore_hidden_attribute() -> Result<()> {
+        let (temp_dir, mut engine) = hidden_fixture()?;
+        let root = temp_dir.path().join("home");
+        for path in [root.join("secret.txt"), root.join("cache")] {
+            let status = std::process::Command::new("attrib").arg("+h").arg(&path).status()?;
+            assert!(status.success());
+        }
+
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_added, 1);
+        assert!(engine.database.get_path(&root.join("visible.txt"))?.is_some());
+        assert!(engine.should_ignore_path(&root.join("cache").join("nested").join("data")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_open_files_bounds_parallel_hashing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("many");
+        fs::create_dir(&root)?;
+        for i in 0..300 {
+            fs::write(root.join(format!("file{:03}", i)), format!("content {}", i).repeat(64))?;
+        }
+
         let config = FimConfig {
+            monitor_paths: vec![root],
             memory_database: true,
+            enable_realtime: false,
+            scan_threads: Some(8),
+            max_open_files: Some(2),
             ..Default::default()
         };
-        
-        let engine = FimEngine::new(config)?;
-        
-        let mut temp_file = NamedTempFile::new()?;
-        writeln!(temp_file, "Test file content")?;
-        
-        let (entry, size) = engine.scan_single_file(temp_file.path())?;
-        
-        assert_eq!(entry.path, temp_file.path());
-        assert!(size > 0);
-        assert!(!entry.data.blake3.is_empty());
-        
+        let mut engine = FimEngine::new(config)?;
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_added, 300);
+        assert_eq!(results.errors, 0);
+
+        let high_water = engine.open_files.state.lock().unwrap().high_water;
+        assert!((1..=2).contains(&high_water), "{} files open at once", high_water);
+        assert_eq!(engine.open_files.state.lock().unwrap().open, 0);
+
+        assert!(default_max_open_files() >= 1);
+
         Ok(())
     }
 
     #[test]
-    fn test_change_detection() -> Result<()> {
-        let old_data = FimEntryData {
-            size: 100,
-            perm: "644".to_string(),
-            uid: 1000,
-            gid: 1000,
-            md5: None,
-            sha1: None,
-            sha256: None,
-            blake3: "old_hash".to_string(),
-            mtime: Utc::now(),
-            ctime: Utc::now(),
-            atime: Utc::now(),
-            inode: 123,
-            dev: 456,
-            scanned: true,
+    fn test_max_open_files_with_parallel_hashed_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("large");
+        fs::create_dir(&root)?;
+        // Above the parallel threshold, so hashing each one fans out
+        let content = vec![0x5au8; 3 * 1024 * 1024];
+        for i in 0..12 {
+            fs::write(root.join(format!("file{:02}", i)), &content)?;
+        }
+
+        let config = FimConfig {
+            monitor_paths: vec![root],
+            memory_database: true,
+            enable_realtime: false,
+            scan_threads: Some(4),
+            max_open_files: Some(1),
+            ..Default::default()
         };
+        let mut engine = FimEngine::new(config)?;
+        assert!(engine.config.hash_config.parallel_threshold < content.len() as u64);
+        let (done, finished) = std::sync::mpsc::channel();
+        let scan = std::thread::spawn(move || {
+            let results = engine.baseline_scan();
+            let _ = done.send(());
+            results.map(|results| (results, engine))
+        });
+        finished
+            .recv_timeout(Duration::from_secs(60))
+            .expect("scan deadlocked on open-file permits");
+        let (results, engine) = scan.join().unwrap()?;
+        assert_eq!(results.files_added, 12);
+        assert_eq!(results.errors, 0);
+        assert_eq!(engine.open_files.state.lock().unwrap().high_water, 1);
 
-        let mut new_data = old_data.clone();
-        new_data.blake3 = "new_hash".to_string();
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_written_during_hash_is_unstable() -> Result<()> {
+        use std::io::Write;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("logs");
+        fs::create_dir(&root)?;
+        let growing = root.join("growing.dat");
+        fs::write(&growing, b"first line\n")?;
+        fs::write(root.join("quiet.txt"), b"settled")?;
 
         let config = FimConfig {
+            monitor_paths: vec![root.clone()],
             memory_database: true,
+            enable_realtime: false,
+            unstable_rehash_attempts: 2,
             ..Default::default()
         };
-        
-        let engine = FimEngine::new(config)?;
-        
-        let change_type = engine.detect_change_type(&old_data, &new_data);
-        assert_eq!(change_type, Some(ChangeType::HashChanged));
-        
+        let mut engine = FimEngine::new(config)?;
+
+        // Append to the file every time it has just been hashed, until told to stop
+        let writing = Arc::new(AtomicBool::new(true));
+        let attempts = Arc::new(AtomicU64::new(0));
+        engine.after_hash = Some(Box::new({
+            let writing = writing.clone();
+            let attempts = attempts.clone();
+            let growing = growing.clone();
+            move |path| {
+                if path == growing && writing.load(Ordering::SeqCst) {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+                    file.write_all(b"more data\n").unwrap();
+                }
+            }
+        }));
+
+        engine.baseline_scan()?;
+        let torn = engine.database.get_path(&growing)?.unwrap().data;
+        assert!(torn.unstable);
+        // The first hash plus both re-hashes
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert!(!engine.database.get_path(&root.join("quiet.txt"))?.unwrap().data.unstable);
+
+        // Deferred: the torn entry is kept and nothing is reported
+        engine.config.defer_unstable = true;
+        let (_, changes) = engine.incremental_scan_paths(std::slice::from_ref(&root))?;
+        assert!(changes.is_empty());
+        assert_eq!(engine.database.get_path(&growing)?.unwrap().data.blake3, torn.blake3);
+
+        writing.store(false, Ordering::SeqCst);
+
+        // Once writes stop the file is stable again and its final content is recorded
+        let (_, changes) = engine.incremental_scan_paths(std::slice::from_ref(&root))?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, growing);
+        let settled = engine.database.get_path(&growing)?.unwrap().data;
+        assert!(!settled.unstable);
+        assert_eq!(settled.blake3, crate::utils::quick_hash(&growing)?);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exclude_uids_and_gids() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("home");
+        fs::create_dir(&root)?;
+        fs::write(root.join("mine.txt"), b"owned by the test user")?;
+        fs::write(root.join("service.txt"), b"owned by a service account")?;
+
+        // Without root, "service" ownership is simulated by excluding our own ids
+        let me = fs::metadata(root.join("mine.txt"))?;
+        let nobody = 65534;
+        let service_owned = std::os::unix::fs::chown(root.join("service.txt"), Some(nobody), Some(nobody)).is_ok();
+
+        let config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            exclude_uids: vec![if service_owned { nobody } else { me.uid() }],
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config.clone())?;
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.errors, 0);
+        if service_owned {
+            assert_eq!((results.files_added, results.files_skipped), (1, 1));
+            assert!(engine.database.get_path(&root.join("service.txt"))?.is_none());
+            assert!(engine.database.get_path(&root.join("mine.txt"))?.is_some());
+        } else {
+            assert_eq!((results.files_added, results.files_skipped), (0, 2));
+        }
+
+        // Groups work the same way, and unrelated ids exclude nothing
+        let mut engine = FimEngine::new(FimConfig {
+            exclude_uids: vec![],
+            exclude_gids: vec![me.gid()],
+            ..config.clone()
+        })?;
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_skipped, if service_owned { 1 } else { 2 });
+
+        let mut engine = FimEngine::new(FimConfig {
+            exclude_uids: vec![u32::MAX - 1],
+            ..config
+        })?;
+        let results = engine.baseline_scan()?;
+        assert_eq!((results.files_added, results.files_skipped), (2, 0));
+
+        // Once recorded files' owner is excluded, every scan skips them and
+        // keeps their entries rather than reporting them deleted
+        engine.config.exclude_uids = vec![me.uid()];
+        let excluded = if service_owned { 1 } else { 2 };
+        let (results, changes) = engine.verify_scan()?;
+        assert!(changes.is_empty());
+        assert_eq!(results.files_skipped, excluded);
+        assert_eq!(engine.verify_path(&root.join("mine.txt"))?, VerifyOutcome::ExcludedOwner);
+
+        let (results, changes) = engine.incremental_scan_paths(std::slice::from_ref(&root))?;
+        assert!(changes.is_empty());
+        assert_eq!(results.files_skipped, excluded);
+        assert!(engine.database.get_path(&root.join("mine.txt"))?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_only_files() -> Result<()> {
+        use std::io::Write;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("var");
+        fs::create_dir(&root)?;
+        let log = root.join("audit.log");
+        fs::write(&log, b"boot ok\n")?;
+
+        let roots = [root.clone()];
+        let config = FimConfig {
+            monitor_paths: roots.to_vec(),
+            exclude_patterns: vec![],
+            memory_database: true,
+            enable_realtime: false,
+            append_only: vec![AppendOnlyRule {
+                path_pattern: "**/*.log".to_string(),
+                prefix_bytes: 64,
+            }],
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        let append = |line: &[u8]| -> Result<()> {
+            fs::OpenOptions::new().append(true).open(&log)?.write_all(line)?;
+            Ok(())
+        };
+
+        // Growing within and then past the prefix is not a change
+        append(b"login alice\n")?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert!(changes.is_empty(), "{:?}", changes);
+        append(&b"request served\n".repeat(10))?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert!(changes.is_empty(), "{:?}", changes);
+        append(b"logout alice\n")?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert!(changes.is_empty(), "{:?}", changes);
+        let entry = engine.database.get_path(&log)?.unwrap().data;
+        assert_eq!(entry.size, fs::metadata(&log)?.len());
+
+        // Rewriting the start of the log is the integrity event
+        let mut content = fs::read(&log)?;
+        content[..7].copy_from_slice(b"hacked!");
+        fs::write(&log, &content)?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+
+        // So is cutting it short
+        fs::write(&log, &content[..20])?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::HashChanged);
+        assert_eq!(changes[0].new_entry.as_ref().unwrap().size, 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_database_path() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let db_path = temp_dir.path().join("state").join("fim.db");
+        fs::create_dir(db_path.parent().unwrap())?;
+
+        let config = FimConfig {
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let database = FimDb::open(Some(&db_path), &DbOptions::default())?;
+        let engine = FimEngine::with_store(config.clone(), database)?;
+        let path = engine.database_path().unwrap();
+        assert!(path.is_absolute());
+        assert!(path.is_file());
+        assert_eq!(path, fs::canonicalize(&db_path)?);
+
+        let engine = FimEngine::new(FimConfig {
+            memory_database: true,
+            ..config.clone()
+        })?;
+        assert!(engine.database_path().is_none());
+
+        // A configured database_path is opened by FimEngine::new and cleaned
+        let configured = temp_dir.path().join("configured.db");
+        let engine = FimEngine::new(FimConfig {
+            database_path: Some(configured.clone()),
+            ..config
+        })?;
+        assert_eq!(engine.database_path(), Some(fs::canonicalize(&configured)?.as_path()));
+        drop(engine);
+        FimDb::clean_at(&configured)?;
+        assert!(FimDb::companion_files(&configured).iter().all(|file| !file.exists()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_scan_merges_walk_with_stored_order() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("srv");
+        // Separators sort after `-` and `.`, so `a/…` comes after `a.txt`
+        for dir in ["a/b", "a-b", "c"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        for file in ["a.txt", "a-b/x", "a/b/x", "a/y", "a0", "c/z"] {
+            fs::write(root.join(file), file)?;
+        }
+
+        let config = FimConfig {
+            // Overlapping monitored paths visit `a` twice
+            monitor_paths: vec![root.join("a"), root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            monitor_directories: true,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        let (results, changes) = engine.verify_scan()?;
+        assert!(changes.is_empty(), "{:?}", changes);
+        assert_eq!(results.files_scanned, 11);
+
+        fs::remove_file(root.join("a.txt"))?;
+        fs::remove_file(root.join("a/b/x"))?;
+        fs::remove_dir_all(root.join("c"))?;
+        fs::write(root.join("a/new"), b"new")?;
+        let (_, changes) = engine.verify_scan()?;
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|change| (change.path.strip_prefix(&root).unwrap().to_path_buf(), change.change_type))
+            .collect();
+        assert_eq!(changes, vec![
+            (PathBuf::from("a.txt"), ChangeType::Deleted),
+            (PathBuf::from("a/b/x"), ChangeType::Deleted),
+            (PathBuf::from("a/new"), ChangeType::Added),
+            (PathBuf::from("c"), ChangeType::Deleted),
+            (PathBuf::from("c/z"), ChangeType::Deleted),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_scan_each_streams_drift() -> Result<()> {
+        use std::io::{BufRead, Write};
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("data");
+        fs::create_dir(&root)?;
+        for i in 0..20 {
+            fs::write(root.join(format!("file{:02}.txt", i)), format!("content {}", i))?;
+        }
+
+        let config = FimConfig {
+            monitor_paths: vec![root.clone()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        engine.baseline_scan()?;
+
+        for i in 0..5 {
+            fs::write(root.join(format!("file{:02}.txt", i)), "rewritten content")?;
+        }
+        fs::remove_file(root.join("file10.txt"))?;
+        fs::remove_file(root.join("file11.txt"))?;
+        fs::write(root.join("extra.txt"), b"new")?;
+
+        // Stream straight to a JSON Lines file
+        let jsonl_path = temp_dir.path().join("drift.jsonl");
+        let mut jsonl = std::io::BufWriter::new(fs::File::create(&jsonl_path)?);
+        let streamed = engine.verify_scan_each(|change| {
+            serde_json::to_writer(&mut jsonl, &change).unwrap();
+            writeln!(jsonl).unwrap();
+        })?;
+        jsonl.flush()?;
+        drop(jsonl);
+
+        let (collected, changes) = engine.verify_scan()?;
+        assert_eq!(
+            (streamed.files_scanned, streamed.files_added, streamed.files_modified, streamed.files_deleted),
+            (collected.files_scanned, collected.files_added, collected.files_modified, collected.files_deleted)
+        );
+        assert_eq!((streamed.files_added, streamed.files_modified, streamed.files_deleted), (1, 5, 2));
+
+        let mut streamed_changes = Vec::new();
+        for line in std::io::BufReader::new(fs::File::open(&jsonl_path)?).lines() {
+            let change: FileChange = serde_json::from_str(&line?)?;
+            streamed_changes.push((change.path, change.change_type));
+        }
+        let collected_changes: Vec<_> = changes
+            .into_iter()
+            .map(|change| (change.path, change.change_type))
+            .collect();
+        assert_eq!(streamed_changes, collected_changes);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_report_access_loss() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if unsafe { libc::geteuid() } == 0 {
+            eprintln!("skipping: permission checks do not apply to root");
+            return Ok(());
+        }
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("etc");
+        let secret_dir = root.join("secret");
+        fs::create_dir_all(&secret_dir)?;
+        fs::write(secret_dir.join("key.conf"), b"key = 1")?;
+        fs::write(root.join("app.conf"), b"enabled = true")?;
+
+        let roots = [root.clone()];
+        let mut engine = FimEngine::new(FimConfig {
+            monitor_paths: roots.to_vec(),
+            memory_database: true,
+            enable_realtime: false,
+            report_access_loss: true,
+            ..Default::default()
+        })?;
+        assert_eq!(engine.baseline_scan()?.files_added, 2);
+
+        // A file never recorded that cannot be read is still an error
+        let locked = root.join("locked.conf");
+        fs::write(&locked, b"new")?;
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))?;
+        fs::set_permissions(&secret_dir, fs::Permissions::from_mode(0o000))?;
+        let scanned = engine.incremental_scan_paths(&roots);
+        let verified = engine.verify_path(&secret_dir.join("key.conf"));
+        fs::set_permissions(&secret_dir, fs::Permissions::from_mode(0o755))?;
+        let (results, changes) = scanned?;
+        assert_eq!(verified?, VerifyOutcome::AccessLost);
+
+        assert_eq!(results.errors, 1);
+        assert_eq!(results.files_deleted, 0);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, secret_dir.join("key.conf"));
+        assert_eq!(changes[0].change_type, ChangeType::AccessLost);
+        assert!(changes[0].old_entry.is_some());
+
+        // The entry was kept, so regaining access is not a change
+        fs::remove_file(&locked)?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert!(changes.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_monitor_directories_permission_change() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path().join("etc");
+        let conf_dir = root.join("conf.d");
+        fs::create_dir_all(&conf_dir)?;
+        fs::set_permissions(&conf_dir, fs::Permissions::from_mode(0o755))?;
+        fs::write(conf_dir.join("app.conf"), b"enabled = true")?;
+
+        let roots = [root.clone()];
+        let config = FimConfig {
+            monitor_paths: roots.to_vec(),
+            memory_database: true,
+            enable_realtime: false,
+            monitor_directories: true,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::new(config)?;
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_added, 3);
+
+        let entry = engine.database.get_path(&conf_dir)?.unwrap().data;
+        assert!(entry.is_dir);
+        assert!(entry.blake3.is_empty());
+        assert_eq!(entry.perm, "755");
+
+        // New children change the directory mtime, which is not a directory change
+        fs::write(conf_dir.join("extra.conf"), b"debug = false")?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, conf_dir.join("extra.conf"));
+
+        fs::set_permissions(&conf_dir, fs::Permissions::from_mode(0o777))?;
+        let (_, changes) = engine.incremental_scan_paths(&roots)?;
+        fs::set_permissions(&conf_dir, fs::Permissions::from_mode(0o755))?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, conf_dir);
+        assert_eq!(changes[0].change_type, ChangeType::PermissionChanged);
+        assert_eq!(changes[0].new_entry.as_ref().unwrap().perm, "777");
+
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,18 +1,25 @@
 //! High-performance file hashing module for FIM
 //! 
 //! Provides optimized hashing using BLAKE3 as primary hash with optional
-//! legacy algorithm support (SHA-256, SHA-1, MD5) for compatibility.
+//! legacy algorithm support (SHA-256, SHA-1, MD5) for compatibility, and
+//! xxHash3 for fast change detection where tampering is not a concern.
 
 use anyhow::{Context, Result};
 use blake3::Hasher as Blake3Hasher;
 use memmap2::Mmap;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use sha3::Sha3_256;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::{Arc, OnceLock};
 use tracing::debug;
+use xxhash_rust::xxh3::Xxh3;
 
 /// File hash container supporting multiple algorithms
 #[derive(Debug, Clone)]
@@ -21,6 +28,143 @@ pub struct FileHashes {
     pub sha256: Option<String>,
     pub sha1: Option<String>,
     pub md5: Option<String>,
+    pub sha512: Option<String>,
+    pub sha3_256: Option<String>,
+    /// 64-bit xxHash3, for change detection only
+    ///
+    /// xxh3 is not cryptographic: anyone able to write the file can make
+    /// different content with the same hash, so it must not be relied on for
+    /// tamper detection.
+    pub xxh3: Option<String>,
+    /// Text/binary classification of the hashed bytes
+    pub content_class: ContentClass,
+    /// The hashes are a fingerprint of the file's ends and size rather than
+    /// of its whole content (see [`SamplingConfig`])
+    pub sampled: bool,
+}
+
+/// Coarse classification of file content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ContentClass {
+    Text,
+    Binary,
+    Empty,
+}
+
+/// Bytes inspected from the start of a file when classifying it
+pub const CLASSIFY_SAMPLE_SIZE: usize = 8192;
+
+impl ContentClass {
+    /// Classify content from its leading bytes
+    ///
+    /// Binary if the sample holds a NUL byte or is not UTF-8; a multi-byte
+    /// character cut off by the end of the sample still counts as text.
+    pub fn classify(data: &[u8]) -> Self {
+        let sample = &data[..data.len().min(CLASSIFY_SAMPLE_SIZE)];
+        if sample.is_empty() {
+            return ContentClass::Empty;
+        }
+        if sample.contains(&0) {
+            return ContentClass::Binary;
+        }
+        match std::str::from_utf8(sample) {
+            Ok(_) => ContentClass::Text,
+            Err(e) if e.error_len().is_none() && data.len() > sample.len() => ContentClass::Text,
+            Err(_) => ContentClass::Binary,
+        }
+    }
+
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentClass::Text => "text",
+            ContentClass::Binary => "binary",
+            ContentClass::Empty => "empty",
+        }
+    }
+}
+
+impl std::str::FromStr for ContentClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(ContentClass::Text),
+            "binary" => Ok(ContentClass::Binary),
+            "empty" => Ok(ContentClass::Empty),
+            other => Err(anyhow::anyhow!("Unknown content class: {}", other)),
+        }
+    }
+}
+
+/// Compression removed from file content before hashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Format of a stream starting with `header`, from its magic bytes
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Read a stream, decompressing it when it is gzip or zstd
+    ///
+    /// Fails once the content read exceeds `max_size` bytes, so neither a
+    /// small compressed file nor a large uncompressed one is read without
+    /// bound.
+    pub fn read_content(mut reader: impl Read, max_size: u64) -> Result<(Vec<u8>, Option<Self>)> {
+        let mut header = Vec::with_capacity(4);
+        reader.by_ref().take(4).read_to_end(&mut header)?;
+        let compression = Self::detect(&header);
+        let stream = std::io::Cursor::new(header).chain(reader);
+        let limit = max_size.saturating_add(1);
+
+        let mut content = Vec::new();
+        match compression {
+            None => stream.take(limit).read_to_end(&mut content)?,
+            Some(Compression::Gzip) => libflate::gzip::MultiDecoder::new(stream)
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut content))
+                .context("Failed to decompress content")?,
+            Some(Compression::Zstd) => zstd::stream::read::Decoder::new(stream)
+                .and_then(|decoder| decoder.take(limit).read_to_end(&mut content))
+                .context("Failed to decompress content")?,
+        };
+
+        if content.len() as u64 > max_size {
+            anyhow::bail!("Content exceeds {} bytes", max_size);
+        }
+        Ok((content, compression))
+    }
+
+    /// Name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" => Ok(Compression::Zstd),
+            other => Err(anyhow::anyhow!("Unknown compression: {}", other)),
+        }
+    }
 }
 
 /// Hashing configuration options
@@ -30,8 +174,66 @@ pub struct HashConfig {
     pub use_sha256: bool,
     pub use_sha1: bool,
     pub use_md5: bool,
+    #[serde(default)]
+    pub use_sha512: bool,
+    #[serde(default)]
+    pub use_sha3_256: bool,
+    /// Compute xxHash3, a much faster non-cryptographic hash; change detection
+    /// compares it instead of BLAKE3 when `use_blake3` is off
+    ///
+    /// Suitable for spotting accidental changes in large, low-risk trees such
+    /// as media libraries, but not for tamper detection (see [`FileHashes::xxh3`]).
+    #[serde(default)]
+    pub use_xxh3: bool,
     pub use_mmap: bool,
     pub parallel_threshold: u64, // Minimum file size for parallel hashing
+    /// Open files with `O_NOATIME` on Linux so hashing leaves access times alone;
+    /// files the process does not own are opened normally
+    #[serde(default = "default_true")]
+    pub no_atime: bool,
+    /// Hash sparse files by their allocated extents and hole map instead of
+    /// reading the holes (Linux only, via `SEEK_DATA`/`SEEK_HOLE`; ignored
+    /// elsewhere and on filesystems without hole reporting)
+    ///
+    /// The hash covers each extent's offset, length and bytes plus the logical
+    /// size, so a write into a hole changes it. It differs from the plain
+    /// content hash, so a file that stops being sparse reports a change.
+    #[serde(default)]
+    pub sparse_aware: bool,
+    /// Fingerprint very large files instead of hashing all of their content,
+    /// for the paths that opt in
+    #[serde(default)]
+    pub sampling: Option<SamplingConfig>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Sampled hashing for files too large to hash in full on every scan, such
+/// as VM images
+///
+/// A matching file larger than twice `sample_bytes` is hashed as its first
+/// and last `sample_bytes` plus its size. The BLAKE3 hash of that composite
+/// is stored as the fingerprint, whatever algorithms are enabled, and the
+/// other digests are left empty so it is never taken for a hash of the whole
+/// content; the entry is flagged as sampled. Smaller files are hashed in full.
+///
+/// Tradeoff: any change to the ends or the size is caught, but a write to the
+/// middle of the file that keeps its size goes unnoticed, so only opt in
+/// paths where that risk is acceptable. Content normalizers and decompress
+/// rules do not apply to sampled files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingConfig {
+    /// Glob patterns selecting the files hashed by sample
+    pub path_patterns: Vec<String>,
+    /// Bytes hashed from each end of a sampled file; must be at least 1
+    #[serde(default = "default_sample_bytes")]
+    pub sample_bytes: u64,
+}
+
+fn default_sample_bytes() -> u64 {
+    1024 * 1024
 }
 
 impl Default for HashConfig {
@@ -41,21 +243,56 @@ impl Default for HashConfig {
             use_sha256: false,
             use_sha1: false,
             use_md5: false,
+            use_sha512: false,
+            use_sha3_256: false,
+            use_xxh3: false,
             use_mmap: true,
             parallel_threshold: 1024 * 1024, // 1MB
+            no_atime: true,
+            sparse_aware: false,
+            sampling: None,
         }
     }
 }
 
 /// High-performance file hasher
+///
+/// Parallel work runs on a dedicated rayon pool, never the global one, so
+/// hashing cannot starve a host application that also uses rayon.
 pub struct FileHasher {
     config: HashConfig,
+    pool: OnceLock<Arc<ThreadPool>>,
 }
 
 impl FileHasher {
     /// Create new hasher with configuration
     pub fn new(config: HashConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            pool: OnceLock::new(),
+        }
+    }
+
+    /// Use the given thread pool for parallel hashing
+    ///
+    /// Without one, a pool sized to the CPU count is created on first use.
+    pub fn with_thread_pool(self, pool: Arc<ThreadPool>) -> Self {
+        Self {
+            config: self.config,
+            pool: OnceLock::from(pool),
+        }
+    }
+
+    /// Thread pool used for parallel hashing
+    pub fn thread_pool(&self) -> &ThreadPool {
+        self.pool.get_or_init(|| {
+            Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(num_cpus::get())
+                    .build()
+                    .expect("Failed to build hashing thread pool"),
+            )
+        })
     }
 
     /// Create hasher with BLAKE3 only (fastest configuration)
@@ -70,8 +307,14 @@ impl FileHasher {
             use_sha256: true,
             use_sha1: true,
             use_md5: true,
+            use_sha512: true,
+            use_sha3_256: true,
+            use_xxh3: true,
             use_mmap: true,
             parallel_threshold: 1024 * 1024,
+            no_atime: true,
+            sparse_aware: false,
+            sampling: None,
         })
     }
 
@@ -82,6 +325,13 @@ impl FileHasher {
             .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
         
         let file_size = metadata.len();
+
+        #[cfg(target_os = "linux")]
+        if self.config.sparse_aware && is_sparse(&metadata) {
+            if let Some(hashes) = self.hash_file_sparse(path, file_size)? {
+                return Ok(hashes);
+            }
+        }
         
         // Choose hashing strategy based on file size and configuration
         if self.config.use_mmap && file_size > 0 {
@@ -91,9 +341,67 @@ impl FileHasher {
         }
     }
 
+    /// Fingerprint a file by its first and last `sample_bytes` and its size
+    /// (see [`SamplingConfig`]); files no larger than both samples are hashed
+    /// in full
+    pub fn hash_file_sampled<P: AsRef<Path>>(&self, path: P, sample_bytes: u64) -> Result<FileHashes> {
+        let path = path.as_ref();
+        let mut file = self.open_file(path)
+            .with_context(|| format!("Failed to open file {}", path.display()))?;
+        let size = file.metadata()
+            .with_context(|| format!("Failed to get metadata for {}", path.display()))?
+            .len();
+        if size <= sample_bytes.saturating_mul(2) {
+            return self.hash_file(path);
+        }
+
+        let mut head = vec![0u8; sample_bytes as usize];
+        file.read_exact(&mut head)
+            .with_context(|| format!("Failed to read file {}", path.display()))?;
+        let mut tail = vec![0u8; sample_bytes as usize];
+        file.seek(SeekFrom::Start(size - sample_bytes))
+            .and_then(|_| file.read_exact(&mut tail))
+            .with_context(|| format!("Failed to read file {}", path.display()))?;
+
+        let mut fingerprint = blake3::Hasher::new();
+        fingerprint.update(&head);
+        fingerprint.update(&tail);
+        fingerprint.update(&size.to_le_bytes());
+        Ok(FileHashes {
+            blake3: fingerprint.finalize().to_hex().to_string(),
+            sha256: None,
+            sha1: None,
+            md5: None,
+            sha512: None,
+            sha3_256: None,
+            xxh3: None,
+            content_class: ContentClass::classify(&head),
+            sampled: true,
+        })
+    }
+
+    /// Open a file for reading its content, without updating its access time
+    /// where the platform and configuration allow
+    pub fn open_file(&self, path: &Path) -> std::io::Result<File> {
+        #[cfg(target_os = "linux")]
+        if self.config.no_atime {
+            use std::os::unix::fs::OpenOptionsExt;
+            match std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NOATIME)
+                .open(path)
+            {
+                // O_NOATIME needs file ownership (or CAP_FOWNER)
+                Err(e) if e.raw_os_error() == Some(libc::EPERM) => {}
+                result => return result,
+            }
+        }
+        File::open(path)
+    }
+
     /// Hash file using memory mapping (fastest for large files)
     fn hash_file_mmap(&self, path: &Path, file_size: u64) -> Result<FileHashes> {
-        let file = File::open(path)
+        let file = self.open_file(path)
             .with_context(|| format!("Failed to open file {}", path.display()))?;
         
         if file_size == 0 {
@@ -111,25 +419,59 @@ impl FileHasher {
         }
     }
 
+    /// Hash the allocated extents of a sparse file, or None when the
+    /// filesystem cannot report holes
+    #[cfg(target_os = "linux")]
+    fn hash_file_sparse(&self, path: &Path, file_size: u64) -> Result<Option<FileHashes>> {
+        use std::os::unix::fs::FileExt;
+
+        let file = self.open_file(path)
+            .with_context(|| format!("Failed to open file {}", path.display()))?;
+        let extents = match data_extents(&file, file_size) {
+            Ok(extents) => extents,
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to map holes of {}", path.display())),
+        };
+        debug!("Hashing {} data extents of sparse file {}", extents.len(), path.display());
+
+        let mut hashers = Hashers::new(&self.config);
+        let mut update = |data: &[u8]| hashers.update(data);
+
+        update(b"rusty-fim sparse v1");
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut sample = ClassifySample::default();
+        for &(start, len) in &extents {
+            update(&start.to_le_bytes());
+            update(&len.to_le_bytes());
+
+            // Holes read as zeros, which classify as binary
+            sample.skip_to(start);
+            let mut offset = start;
+            while offset < start + len {
+                let chunk = (start + len - offset).min(buffer.len() as u64) as usize;
+                file.read_exact_at(&mut buffer[..chunk], offset)
+                    .with_context(|| format!("Failed to read file {}", path.display()))?;
+                sample.update(&buffer[..chunk]);
+                update(&buffer[..chunk]);
+                offset += chunk as u64;
+            }
+        }
+        update(&file_size.to_le_bytes());
+        sample.skip_to(file_size);
+
+        Ok(Some(hashers.finish(sample.classify())))
+    }
+
     /// Hash file using buffered reading (safer for special files)
     fn hash_file_buffered(&self, path: &Path) -> Result<FileHashes> {
-        let file = File::open(path)
+        let file = self.open_file(path)
             .with_context(|| format!("Failed to open file {}", path.display()))?;
         
         let mut reader = BufReader::new(file);
         let mut buffer = vec![0u8; 64 * 1024]; // 64KB buffer
         
-        let mut blake3_hasher = if self.config.use_blake3 {
-            Some(Blake3Hasher::new())
-        } else {
-            None
-        };
-        
-        let mut sha256_hasher = if self.config.use_sha256 {
-            Some(Sha256::new())
-        } else {
-            None
-        };
+        let mut hashers = Hashers::new(&self.config);
+        let mut sample = ClassifySample::default();
 
         loop {
             let bytes_read = reader.read(&mut buffer)
@@ -140,94 +482,51 @@ impl FileHasher {
             }
             
             let data = &buffer[..bytes_read];
-            
-            if let Some(ref mut hasher) = blake3_hasher {
-                hasher.update(data);
-            }
-            
-            if let Some(ref mut hasher) = sha256_hasher {
-                hasher.update(data);
-            }
+            sample.update(data);
+            hashers.update(data);
         }
 
-        Ok(FileHashes {
-            blake3: blake3_hasher
-                .map(|h| h.finalize().to_hex().to_string())
-                .unwrap_or_default(),
-            sha256: sha256_hasher
-                .map(|h| format!("{:x}", h.finalize())),
-            sha1: None, // Implement if needed
-            md5: None,  // Implement if needed
-        })
+        Ok(hashers.finish(sample.classify()))
     }
 
     /// Hash data using parallel BLAKE3 (fastest method)
     fn hash_data_parallel(&self, data: &[u8]) -> Result<FileHashes> {
         debug!("Using parallel BLAKE3 hashing for {} bytes", data.len());
-        
-        let blake3 = if self.config.use_blake3 {
-            // BLAKE3 supports parallel hashing natively via Rayon
-            let mut hasher = Blake3Hasher::new();
-            hasher.update_rayon(data);
-            Some(hasher.finalize().to_hex().to_string())
-        } else {
-            None
-        };
-
-        // For other algorithms, we could implement chunked parallel processing
-        // but they don't benefit as much from parallelization
-        let sha256 = if self.config.use_sha256 {
-            Some(format!("{:x}", Sha256::digest(data)))
-        } else {
-            None
-        };
 
-        Ok(FileHashes {
-            blake3: blake3.unwrap_or_default(),
-            sha256,
-            sha1: None,
-            md5: None,
-        })
+        let mut hashers = Hashers::new(&self.config);
+        hashers.update_parallel(data, self.thread_pool());
+        Ok(hashers.finish(ContentClass::classify(data)))
     }
 
     /// Hash data sequentially
     fn hash_data_sequential(&self, data: &[u8]) -> Result<FileHashes> {
-        let blake3 = if self.config.use_blake3 {
-            Some(blake3::hash(data).to_hex().to_string())
-        } else {
-            None
-        };
-
-        let sha256 = if self.config.use_sha256 {
-            Some(format!("{:x}", Sha256::digest(data)))
-        } else {
-            None
-        };
-
-        Ok(FileHashes {
-            blake3: blake3.unwrap_or_default(),
-            sha256,
-            sha1: None,
-            md5: None,
-        })
+        let mut hashers = Hashers::new(&self.config);
+        hashers.update(data);
+        Ok(hashers.finish(ContentClass::classify(data)))
     }
 
     /// Handle empty files
     fn hash_empty_file(&self) -> Result<FileHashes> {
-        Ok(FileHashes {
-            blake3: if self.config.use_blake3 {
-                blake3::hash(b"").to_hex().to_string()
-            } else {
-                String::new()
-            },
-            sha256: if self.config.use_sha256 {
-                Some(format!("{:x}", Sha256::digest(b"")))
-            } else {
-                None
-            },
-            sha1: None,
-            md5: None,
-        })
+        Ok(Hashers::new(&self.config).finish(ContentClass::Empty))
+    }
+
+    /// Hash an in-memory buffer using the configured algorithms
+    pub fn hash_bytes(&self, data: &[u8]) -> Result<FileHashes> {
+        if data.is_empty() {
+            self.hash_empty_file()
+        } else {
+            self.hash_data_sequential(data)
+        }
+    }
+
+    /// Wrap a reader so the bytes read through it are hashed with the
+    /// configured algorithms, e.g. to copy a file and hash it in one pass
+    pub fn hashing_reader<R: Read>(&self, inner: R) -> HashingReader<R> {
+        HashingReader {
+            inner,
+            hashers: Hashers::new(&self.config),
+            sample: ClassifySample::default(),
+        }
     }
 
     /// Verify file integrity against known hash
@@ -238,17 +537,219 @@ impl FileHasher {
 
     /// Batch hash multiple files in parallel
     pub fn _hash_files_parallel<P: AsRef<Path> + Sync>(&self, paths: &[P]) -> Vec<Result<FileHashes>> {
-        paths.par_iter()
-            .map(|path| self.hash_file(path))
-            .collect()
+        self.thread_pool().install(|| {
+            paths.par_iter()
+                .map(|path| self.hash_file(path))
+                .collect()
+        })
     }
 }
 
+/// Reader hashing everything read through it, from [`FileHasher::hashing_reader`]
+pub struct HashingReader<R> {
+    inner: R,
+    hashers: Hashers,
+    sample: ClassifySample,
+}
+
+impl<R> HashingReader<R> {
+    /// The wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Hashes of the bytes read so far; read to the end first to hash the
+    /// whole stream
+    pub fn into_hashes(self) -> FileHashes {
+        self.hashers.finish(self.sample.classify())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let data = &buf[..read];
+        self.sample.update(data);
+        self.hashers.update(data);
+        Ok(read)
+    }
+}
+
+/// Leading bytes of content read in chunks, for classifying it once read
+///
+/// Kept to one past the sample size so a character cut off by a chunk or the
+/// sample boundary is recognised.
+#[derive(Default)]
+struct ClassifySample(Vec<u8>);
+
+impl ClassifySample {
+    fn wanted(&self) -> usize {
+        (CLASSIFY_SAMPLE_SIZE + 1).saturating_sub(self.0.len())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let wanted = self.wanted();
+        self.0.extend_from_slice(&data[..data.len().min(wanted)]);
+    }
+
+    /// Zeros up to `offset`, for a hole in a sparse file
+    #[cfg(target_os = "linux")]
+    fn skip_to(&mut self, offset: u64) {
+        let gap = offset.saturating_sub(self.0.len() as u64).min(self.wanted() as u64) as usize;
+        self.0.resize(self.0.len() + gap, 0);
+    }
+
+    fn classify(&self) -> ContentClass {
+        ContentClass::classify(&self.0)
+    }
+}
+
+/// Incremental state of every algorithm enabled in a [`HashConfig`]
+struct Hashers {
+    blake3: Option<Blake3Hasher>,
+    sha256: Option<Sha256>,
+    sha1: Option<Sha1>,
+    md5: Option<Md5>,
+    sha512: Option<Sha512>,
+    sha3_256: Option<Sha3_256>,
+    xxh3: Option<Xxh3>,
+}
+
+impl Hashers {
+    fn new(config: &HashConfig) -> Self {
+        Self {
+            blake3: config.use_blake3.then(Blake3Hasher::new),
+            sha256: config.use_sha256.then(Sha256::new),
+            sha1: config.use_sha1.then(Sha1::new),
+            md5: config.use_md5.then(Md5::new),
+            sha512: config.use_sha512.then(Sha512::new),
+            sha3_256: config.use_sha3_256.then(Sha3_256::new),
+            xxh3: config.use_xxh3.then(Xxh3::new),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if let Some(ref mut hasher) = self.blake3 {
+            hasher.update(data);
+        }
+        self.update_legacy(data);
+    }
+
+    /// [`Self::update`], hashing BLAKE3 in parallel on `pool`
+    fn update_parallel(&mut self, data: &[u8], pool: &ThreadPool) {
+        if let Some(ref mut hasher) = self.blake3 {
+            // BLAKE3 supports parallel hashing natively via Rayon. A worker of
+            // another pool waiting on `pool` runs its own pool's jobs meanwhile,
+            // which may block on something the caller holds; a plain thread
+            // just waits.
+            if rayon::current_thread_index().is_some() && pool.current_thread_index().is_none() {
+                std::thread::scope(|scope| {
+                    scope.spawn(|| pool.install(|| hasher.update_rayon(data)));
+                });
+            } else {
+                pool.install(|| hasher.update_rayon(data));
+            }
+        }
+        // The other algorithms do not benefit as much from parallelization
+        self.update_legacy(data);
+    }
+
+    fn update_legacy(&mut self, data: &[u8]) {
+        if let Some(ref mut hasher) = self.sha256 {
+            hasher.update(data);
+        }
+        if let Some(ref mut hasher) = self.sha1 {
+            hasher.update(data);
+        }
+        if let Some(ref mut hasher) = self.md5 {
+            hasher.update(data);
+        }
+        if let Some(ref mut hasher) = self.sha512 {
+            hasher.update(data);
+        }
+        if let Some(ref mut hasher) = self.sha3_256 {
+            hasher.update(data);
+        }
+        if let Some(ref mut hasher) = self.xxh3 {
+            hasher.update(data);
+        }
+    }
+
+    fn finish(self, content_class: ContentClass) -> FileHashes {
+        FileHashes {
+            blake3: self.blake3
+                .map(|h| h.finalize().to_hex().to_string())
+                .unwrap_or_default(),
+            sha256: self.sha256.map(|h| format!("{:x}", h.finalize())),
+            sha1: self.sha1.map(|h| format!("{:x}", h.finalize())),
+            md5: self.md5.map(|h| format!("{:x}", h.finalize())),
+            sha512: self.sha512.map(|h| format!("{:x}", h.finalize())),
+            sha3_256: self.sha3_256.map(|h| format!("{:x}", h.finalize())),
+            xxh3: self.xxh3.map(|h| format!("{:016x}", h.digest())),
+            content_class,
+            sampled: false,
+        }
+    }
+}
+
+/// Whether fewer bytes are allocated to a file than its logical size
+#[cfg(target_os = "linux")]
+fn is_sparse(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.is_file() && metadata.blocks() * 512 < metadata.len()
+}
+
+/// `(offset, length)` of each data extent of a file up to `size`, found
+/// with `SEEK_DATA`/`SEEK_HOLE`
+///
+/// Fails with `EINVAL` when the filesystem does not support them.
+#[cfg(target_os = "linux")]
+fn data_extents(file: &File, size: u64) -> std::io::Result<Vec<(u64, u64)>> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let mut extents = Vec::new();
+    let mut offset = 0u64;
+    while offset < size {
+        // SAFETY: lseek only moves the offset of a descriptor we own
+        let start = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+        if start < 0 {
+            let e = std::io::Error::last_os_error();
+            // No data past the offset: the rest is a hole
+            if e.raw_os_error() == Some(libc::ENXIO) {
+                break;
+            }
+            return Err(e);
+        }
+        // SAFETY: as above
+        let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+        if end < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // The file may have grown since it was stat'ed
+        let (start, end) = (start as u64, (end as u64).min(size));
+        if start >= end {
+            break;
+        }
+        extents.push((start, end - start));
+        offset = end;
+    }
+    Ok(extents)
+}
+
 /// Specialized hasher for checksum verification
 pub struct _ChecksumVerifier {
     hasher: FileHasher,
 }
 
+impl Default for _ChecksumVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[allow(dead_code)]
 impl _ChecksumVerifier {
     pub fn new() -> Self {
@@ -264,9 +765,11 @@ impl _ChecksumVerifier {
 
     /// Batch verify multiple files
     pub fn batch_verify<P: AsRef<Path> + Sync>(&self, files: &[(P, &str)]) -> Vec<Result<bool>> {
-        files.par_iter()
-            .map(|(path, expected)| self.hasher.verify_file(path, expected))
-            .collect()
+        self.hasher.thread_pool().install(|| {
+            files.par_iter()
+                .map(|(path, expected)| self.hasher.verify_file(path, expected))
+                .collect()
+        })
     }
 }
 
@@ -283,7 +786,7 @@ pub mod utils {
             .collect();
         
         let mut entries = entries.context("Failed to collect directory entries")?;
-        entries.sort_by(|a: &std::fs::DirEntry, b: &std::fs::DirEntry| a.path().cmp(&b.path()));
+        entries.sort_by_key(|entry| entry.path());
         
         for entry in entries {
             let path = entry.path();
@@ -317,10 +820,11 @@ mod tests {
     use super::*;
     use std::io::Write;
     use tempfile::{NamedTempFile, tempdir};
+    use xxhash_rust::xxh3::xxh3_64;
 
     #[test]
     fn test_empty_file_hash() -> Result<()> {
-        let mut temp_file = NamedTempFile::new()?;
+        let temp_file = NamedTempFile::new()?;
         
         let hasher = FileHasher::blake3_only();
         let hashes = hasher.hash_file(temp_file.path())?;
@@ -406,4 +910,265 @@ mod tests {
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_with_thread_pool() -> Result<()> {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(2).build()?);
+        let hasher = FileHasher::new(HashConfig {
+            parallel_threshold: 0,
+            ..Default::default()
+        })
+        .with_thread_pool(pool.clone());
+        assert_eq!(hasher.thread_pool().current_num_threads(), 2);
+
+        let mut temp_file = NamedTempFile::new()?;
+        let test_data = vec![7u8; 256 * 1024];
+        temp_file.write_all(&test_data)?;
+
+        let hashes = hasher.hash_file(temp_file.path())?;
+        assert_eq!(hashes.blake3, blake3::hash(&test_data).to_hex().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_algorithm_vectors() -> Result<()> {
+        // (data, SHA-1, MD5, SHA-512, SHA3-256)
+        const VECTORS: [(&[u8], &str, &str, &str, &str); 2] = [
+            (
+                b"",
+                "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+                "d41d8cd98f00b204e9800998ecf8427e",
+                "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                 47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+                "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+            ),
+            (
+                b"abc",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+                "900150983cd24fb0d6963f7d28e17f72",
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                 2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+                "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            ),
+        ];
+
+        // Memory mapped, parallel and buffered paths, plus in-memory buffers
+        let configs = [
+            HashConfig { parallel_threshold: u64::MAX, ..FileHasher::_all_algorithms().config },
+            HashConfig { parallel_threshold: 0, ..FileHasher::_all_algorithms().config },
+            HashConfig { use_mmap: false, ..FileHasher::_all_algorithms().config },
+        ];
+        for config in configs {
+            let hasher = FileHasher::new(config);
+            for (data, sha1, md5, sha512, sha3_256) in VECTORS {
+                let mut temp_file = NamedTempFile::new()?;
+                temp_file.write_all(data)?;
+
+                for hashes in [hasher.hash_file(temp_file.path())?, hasher.hash_bytes(data)?] {
+                    assert_eq!(hashes.sha1.as_deref(), Some(sha1));
+                    assert_eq!(hashes.md5.as_deref(), Some(md5));
+                    assert_eq!(hashes.sha512.as_deref(), Some(sha512));
+                    assert_eq!(hashes.sha3_256.as_deref(), Some(sha3_256));
+                    assert_eq!(hashes.blake3, blake3::hash(data).to_hex().to_string());
+                    assert_eq!(hashes.sha256, Some(format!("{:x}", Sha256::digest(data))));
+                    assert_eq!(hashes.xxh3, Some(format!("{:016x}", xxh3_64(data))));
+                }
+            }
+        }
+
+        // Disabled algorithms stay empty
+        let hashes = FileHasher::blake3_only().hash_bytes(b"abc")?;
+        assert_eq!((hashes.sha1, hashes.md5), (None, None));
+        assert_eq!((hashes.sha512, hashes.sha3_256, hashes.xxh3), (None, None, None));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_file_sampled() -> Result<()> {
+        let hasher = FileHasher::_all_algorithms();
+        let mut content = vec![b'x'; 100];
+        let mut temp_file = NamedTempFile::new()?;
+        temp_file.write_all(&content)?;
+
+        let sampled = hasher.hash_file_sampled(temp_file.path(), 10)?;
+        assert!(sampled.sampled);
+        let mut composite = content[..10].to_vec();
+        composite.extend_from_slice(&content[90..]);
+        composite.extend_from_slice(&100u64.to_le_bytes());
+        assert_eq!(sampled.blake3, blake3::hash(&composite).to_hex().to_string());
+        // Only the fingerprint is kept; no digest claims to cover the whole file
+        assert_eq!((sampled.sha256, sampled.sha1, sampled.md5), (None, None, None));
+        assert_eq!((sampled.sha512, sampled.sha3_256, sampled.xxh3), (None, None, None));
+
+        // The middle is not sampled; the ends are
+        content[50] = b'y';
+        std::fs::write(temp_file.path(), &content)?;
+        assert_eq!(hasher.hash_file_sampled(temp_file.path(), 10)?.blake3, sampled.blake3);
+        content[95] = b'y';
+        std::fs::write(temp_file.path(), &content)?;
+        assert_ne!(hasher.hash_file_sampled(temp_file.path(), 10)?.blake3, sampled.blake3);
+
+        // Files the samples would cover are hashed in full
+        let full = hasher.hash_file_sampled(temp_file.path(), 50)?;
+        assert!(!full.sampled);
+        assert_eq!(full.blake3, hasher.hash_file(temp_file.path())?.blake3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hashing_reader() -> Result<()> {
+        let hasher = FileHasher::_all_algorithms();
+        let mut temp_file = NamedTempFile::new()?;
+        let content: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        temp_file.write_all(&content)?;
+
+        // Copy and hash in a single pass
+        let mut reader = hasher.hashing_reader(std::fs::File::open(temp_file.path())?);
+        let mut copy = Vec::new();
+        std::io::copy(&mut reader, &mut copy)?;
+        let streamed = reader.into_hashes();
+        assert_eq!(copy, content);
+
+        let direct = hasher.hash_file(temp_file.path())?;
+        assert_eq!(streamed.blake3, direct.blake3);
+        assert_eq!(streamed.sha256, direct.sha256);
+        assert_eq!(streamed.sha1, direct.sha1);
+        assert_eq!(streamed.md5, direct.md5);
+        assert_eq!(streamed.sha512, direct.sha512);
+        assert_eq!(streamed.sha3_256, direct.sha3_256);
+        assert_eq!(streamed.content_class, ContentClass::Binary);
+
+        // Only enabled algorithms are computed
+        let mut reader = FileHasher::blake3_only().hashing_reader(&b"key = value\n"[..]);
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        let hashes = reader.into_hashes();
+        assert_eq!(hashes.blake3, blake3::hash(b"key = value\n").to_hex().to_string());
+        assert_eq!(hashes.sha256, None);
+        assert_eq!(hashes.content_class, ContentClass::Text);
+
+        let hashes = FileHasher::blake3_only().hashing_reader(std::io::empty()).into_hashes();
+        assert_eq!(hashes.content_class, ContentClass::Empty);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_across_reads() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let fifo_path = temp_dir.path().join("pipe");
+        let fifo = std::ffi::CString::new(fifo_path.to_string_lossy().as_bytes())?;
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        // The first read ends inside the two-byte "é"
+        let writer = std::thread::spawn(move || -> std::io::Result<()> {
+            let mut pipe = std::fs::OpenOptions::new().write(true).open(&fifo_path)?;
+            let content = "a".repeat(100) + "é and more text";
+            pipe.write_all(&content.as_bytes()[..101])?;
+            pipe.flush()?;
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            pipe.write_all(&content.as_bytes()[101..])
+        });
+        let hashes = FileHasher::blake3_only().hash_file(temp_dir.path().join("pipe"))?;
+        writer.join().unwrap()?;
+        assert_eq!(hashes.content_class, ContentClass::Text);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_content_limit() -> Result<()> {
+        let compressed = zstd::encode_all(&b"payload"[..], 3)?;
+        let (content, compression) = Compression::read_content(compressed.as_slice(), u64::MAX)?;
+        assert_eq!(content, b"payload");
+        assert_eq!(compression, Some(Compression::Zstd));
+
+        let (content, compression) = Compression::read_content(&b"payload"[..], 7)?;
+        assert_eq!(content, b"payload");
+        assert_eq!(compression, None);
+        assert!(Compression::read_content(&b"payload"[..], 6).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_classification() -> Result<()> {
+        assert_eq!(ContentClass::classify(b""), ContentClass::Empty);
+        assert_eq!(ContentClass::classify(b"key = value\n"), ContentClass::Text);
+        assert_eq!(ContentClass::classify(b"\x7fELF\x00\x01"), ContentClass::Binary);
+        assert_eq!(ContentClass::classify(b"caf\xe9"), ContentClass::Binary);
+
+        // A character split by the sample boundary is still text
+        let mut text = "a".repeat(CLASSIFY_SAMPLE_SIZE - 1).into_bytes();
+        text.extend_from_slice("é".as_bytes());
+        assert_eq!(ContentClass::classify(&text), ContentClass::Text);
+
+        let mut temp_file = NamedTempFile::new()?;
+        temp_file.write_all(b"\x00\x01\x02")?;
+        assert_eq!(FileHasher::blake3_only().hash_file(temp_file.path())?.content_class, ContentClass::Binary);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sparse_file_hashing() -> Result<()> {
+        use std::os::unix::fs::FileExt;
+        use std::time::{Duration, Instant};
+
+        let temp_file = NamedTempFile::new()?;
+        let file = temp_file.as_file();
+        file.set_len(4 << 30)?;
+        file.write_all_at(b"header", 0)?;
+        file.write_all_at(b"trailer", (2 << 30) + 100)?;
+        if !is_sparse(&file.metadata()?) {
+            // The filesystem does not support holes
+            return Ok(());
+        }
+
+        let hasher = FileHasher::new(HashConfig {
+            sparse_aware: true,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let before = hasher.hash_file(temp_file.path())?;
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+        assert_eq!(before.content_class, ContentClass::Binary);
+        assert_eq!(hasher.hash_file(temp_file.path())?.blake3, before.blake3);
+
+        // Writing into a hole, even zeros, changes the hash
+        file.write_all_at(&[0u8; 16], 1 << 30)?;
+        let after = hasher.hash_file(temp_file.path())?;
+        assert_ne!(after.blake3, before.blake3);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_hashing_preserves_atime() -> Result<()> {
+        use std::fs::{self, FileTimes};
+        use std::time::{Duration, SystemTime};
+
+        let mut temp_file = NamedTempFile::new()?;
+        temp_file.write_all(b"content whose access time must not move")?;
+        let accessed = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+
+        for use_mmap in [true, false] {
+            // An atime older than mtime would be bumped by a plain read, even on relatime
+            temp_file.as_file().set_times(FileTimes::new().set_accessed(accessed))?;
+            let before = fs::metadata(temp_file.path())?.accessed()?;
+
+            let hasher = FileHasher::new(HashConfig {
+                use_mmap,
+                ..Default::default()
+            });
+            hasher.hash_file(temp_file.path())?;
+
+            assert_eq!(fs::metadata(temp_file.path())?.accessed()?, before);
+        }
+        Ok(())
+    }
+}
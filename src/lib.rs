@@ -17,7 +17,7 @@
 //! use rusty_fim::{FimEngine, FimConfig};
 //! use std::path::PathBuf;
 //! 
-//! # tokio_test::block_on(async {
+//! # fn main() -> anyhow::Result<()> {
 //! let mut config = FimConfig::default();
 //! config.monitor_paths = vec![PathBuf::from("/important/files")];
 //! 
@@ -37,8 +37,8 @@
 //! 
 //! // Start real-time monitoring
 //! engine.process_realtime_events()?;
-//! # Ok::<(), anyhow::Error>(())
-//! # }).unwrap();
+//! # Ok(())
+//! # }
 //! ```
 //! 
 //! ## Architecture
@@ -46,9 +46,14 @@
 //! The library is organized into several key modules:
 //! 
 //! - [`database`] - SQLite-based storage layer with optimized schemas
+//! - [`store`] - Storage backend trait, implemented by the SQLite database
 //! - [`hasher`] - High-performance file hashing using BLAKE3 and other algorithms
 //! - [`watcher`] - Real-time filesystem monitoring with event debouncing
 //! - [`fim`] - Core FIM engine that orchestrates all components
+//! - [`capabilities`] - Linux file capability decoding
+//! - [`content_diff`] - Line and word diffs of text file content
+//! - [`manifest`] - Signed tree manifests for offline verification
+//! - [`metrics`] - Prometheus exposition of engine metrics
 //! 
 //! ## Performance
 //! 
@@ -60,22 +65,40 @@
 //! - **Optimized Database**: SQLite with WAL mode and prepared statements
 //! - **Event Debouncing**: Intelligent filtering of filesystem events
 
+pub mod acl;
+pub mod capabilities;
+pub mod content_diff;
 pub mod database;
 pub mod fim;
+pub mod manifest;
 pub mod hasher;
+pub mod metrics;
 pub mod reporting;
+pub mod store;
 pub mod watcher;
+pub mod xattrs;
 
 // Re-export main types for convenience
 pub use fim::{
-    ChangeType, FileChange, FimConfig, FimEngine, FimMode, ScanResults,
+    AppendOnlyRule, Baseline, CancellationToken, ChangeType, ComparePolicy, DecompressRule,
+    FileChange, FimConfig, FimEngine, FimMode, MetricsSnapshot, RetryConfig, ScanProgress,
+    ScanResults, StopHandle, StopProcessing, SymlinkPolicy, VerifyOutcome,
+};
+pub use database::{
+    AutoVacuum, ChangeLogVerification, DbIntegrityReport, DbOptions, FieldChange, FimDb, FimEntry,
+    FimEntryData, FimStats, FullSnapshot, SnapshotFormat, DEFAULT_HISTORY_LIMIT, DEFAULT_PROFILE, SCHEMA_VERSION,
+};
+pub use store::FimStore;
+pub use hasher::{
+    Compression, ContentClass, FileHasher, FileHashes, HashConfig, HashingReader, SamplingConfig,
 };
-pub use database::{FimDb, FimEntry, FimEntryData, FimStats};
-pub use hasher::{FileHasher, FileHashes, HashConfig};
 pub use watcher::{FimEvent, FimEventKind, FimWatcher, WatchConfig};
 pub use reporting::{
-    Alert, AlertGenerator, AlertSeverity, FimReport, OutputFormat, 
-    ReportConfig, ReportGenerator, RiskLevel,
+    Alert, AlertFileSink, AlertGenerator, AlertSeverity, CompiledSeverityRules, CriticalPath,
+    DigestSink, EmailConfig, EmailTls, FimReport, LineEnding, MonitorOutput, OutputFormat,
+    PathSeverity, ReportConfig, ReportGenerator, ReportGeneratorBuilder, ReportHandler, ReportOutput,
+    RiskLevel, RotationConfig, SeverityRules, SyslogConfig, SyslogTransport, WebhookConfig,
+    WebhookFormat,
 };
 
 /// Result type alias for the library
@@ -97,16 +120,58 @@ pub fn version_info() -> String {
     format!("{} v{}", DESCRIPTION, VERSION)
 }
 
-/// Initialize default logging for the library
-pub fn init_logging() -> Result<()> {
-    use tracing_subscriber;
-    
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+/// Log line format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of the enclosing spans
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(anyhow::anyhow!("Unknown log format: {}", other)),
+        }
+    }
+}
+
+/// Subscriber writing events at `level` and above to `writer` in `format`
+pub fn log_subscriber<W>(
+    level: tracing::Level,
+    format: LogFormat,
+    writer: W,
+) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
         .with_target(false)
-        .init();
-    
-    Ok(())
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .finish()),
+    }
+}
+
+/// Initialize default logging for the library
+pub fn init_logging(format: LogFormat) -> Result<()> {
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    log_subscriber(tracing::Level::INFO, format, std::io::stdout)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))
 }
 
 /// Utility functions for common FIM operations
@@ -133,9 +198,10 @@ pub mod utils {
 
     /// Create a default FIM configuration for a directory
     pub fn default_config_for_path<P: AsRef<Path>>(path: P) -> FimConfig {
-        let mut config = FimConfig::default();
-        config.monitor_paths = vec![path.as_ref().to_path_buf()];
-        config
+        FimConfig {
+            monitor_paths: vec![path.as_ref().to_path_buf()],
+            ..Default::default()
+        }
     }
 
     /// Format file size in human-readable format
@@ -156,9 +222,59 @@ pub mod utils {
         }
     }
 
+    /// Parse a point in time given as an age (`30s`, `15m`, `24h`, `7d`, `2w`)
+    /// relative to `now`, or as an RFC 3339 timestamp
+    pub fn parse_time_spec(spec: &str, now: chrono::DateTime<chrono::Utc>) -> Result<chrono::DateTime<chrono::Utc>> {
+        let spec = spec.trim();
+        if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(spec) {
+            return Ok(timestamp.with_timezone(&chrono::Utc));
+        }
+
+        let split = spec.find(|c: char| !c.is_ascii_digit()).unwrap_or(spec.len());
+        let (amount, unit) = spec.split_at(split);
+        let amount: i64 = amount.parse()
+            .map_err(|_| anyhow::anyhow!("Invalid time '{}': expected e.g. 24h or an RFC 3339 timestamp", spec))?;
+        let age = match unit {
+            "s" => chrono::Duration::seconds(amount),
+            "m" => chrono::Duration::minutes(amount),
+            "h" => chrono::Duration::hours(amount),
+            "d" => chrono::Duration::days(amount),
+            "w" => chrono::Duration::weeks(amount),
+            _ => return Err(anyhow::anyhow!("Invalid time unit in '{}': expected s, m, h, d or w", spec)),
+        };
+
+        Ok(now - age)
+    }
+
+    /// Lexically normalize a path, without touching the filesystem
+    ///
+    /// Repeated and trailing separators and `.` components are dropped, and
+    /// `..` removes the component before it (`..` at the root stays at the
+    /// root). Symlinks are not resolved, so `a/link/..` becomes `a`.
+    pub fn normalize_path<P: AsRef<Path>>(path: P) -> std::path::PathBuf {
+        use std::path::Component;
+
+        let mut normalized = std::path::PathBuf::new();
+        for component in path.as_ref().components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => match normalized.components().next_back() {
+                    Some(Component::Normal(_)) => {
+                        normalized.pop();
+                    }
+                    Some(Component::RootDir | Component::Prefix(_)) => {}
+                    _ => normalized.push(".."),
+                },
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    }
+
     /// Check if path matches any of the given glob patterns
     pub fn matches_patterns<P: AsRef<Path>>(path: P, patterns: &[String]) -> bool {
-        let path_str = path.as_ref().to_string_lossy();
+        let path = normalize_path(path);
+        let path_str = path.to_string_lossy();
         
         patterns.iter().any(|pattern| {
             glob::Pattern::new(pattern)
@@ -171,8 +287,19 @@ pub mod utils {
 /// Integration helpers for embedding FIM in other applications
 pub mod integration {
     use super::*;
+    use crate::fim::StopProcessing;
+    use futures::Stream;
+    use std::pin::Pin;
     use std::sync::Arc;
-    use tokio::sync::Mutex;
+    use std::task::{Context, Poll};
+    use tokio::sync::{mpsc, Mutex};
+    use tokio::task::JoinHandle;
+
+    /// Changes buffered by [`ScanStream`] before the scan waits for the consumer
+    const SCAN_STREAM_BUFFER: usize = 64;
+
+    /// Async name for [`SharedFimEngine`]
+    pub type AsyncFimEngine = SharedFimEngine;
 
     /// Thread-safe FIM engine wrapper
     pub struct SharedFimEngine {
@@ -210,6 +337,74 @@ pub mod integration {
             let engine = self.engine.lock().await;
             engine.get_stats()
         }
+
+        /// Receive every change the engine detects (see [`FimEngine::subscribe`])
+        pub async fn subscribe(&self) -> mpsc::Receiver<FileChange> {
+            self.engine.lock().await.subscribe()
+        }
+
+        /// Run an incremental scan in the background, yielding changes as they
+        /// are detected
+        ///
+        /// The scan stops at the current file once `cancel` is triggered or the
+        /// stream is dropped, and waits while the consumer falls behind.
+        /// [`ScanStream::finish`] returns the scan results.
+        ///
+        /// Each change is committed to the baseline when it is detected, before
+        /// the consumer receives it, so a stopped scan does not report it again.
+        /// `finish` hands back any such change the stream never yielded;
+        /// dropping the stream instead discards them.
+        pub fn scan_stream(&self, cancel: CancellationToken) -> ScanStream {
+            let (sender, receiver) = mpsc::channel(SCAN_STREAM_BUFFER);
+            let unsent = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let engine = self.engine.clone();
+            let rejected = unsent.clone();
+            let scan = tokio::task::spawn_blocking(move || {
+                let mut engine = engine.blocking_lock();
+                engine.incremental_scan_cancellable(&cancel, move |change| {
+                    sender.blocking_send(change.clone()).map_err(|e| {
+                        rejected.lock().unwrap().push(e.0);
+                        StopProcessing.into()
+                    })
+                })
+            });
+
+            ScanStream { receiver, scan, unsent }
+        }
+    }
+
+    /// Changes from [`SharedFimEngine::scan_stream`]
+    pub struct ScanStream {
+        receiver: mpsc::Receiver<FileChange>,
+        scan: JoinHandle<Result<ScanResults>>,
+        /// Changes the scan could not send once the stream was closed
+        unsent: Arc<std::sync::Mutex<Vec<FileChange>>>,
+    }
+
+    impl ScanStream {
+        /// Wait for the scan to end and return its results, with the changes
+        /// detected but not yielded by the stream
+        ///
+        /// Closing the stream stops a scan that is still running at its next
+        /// change. The changes returned are already in the baseline.
+        pub async fn finish(mut self) -> Result<(ScanResults, Vec<FileChange>)> {
+            self.receiver.close();
+            let mut undelivered = Vec::new();
+            while let Some(change) = self.receiver.recv().await {
+                undelivered.push(change);
+            }
+            let results = self.scan.await.map_err(|e| anyhow::anyhow!("Scan task failed: {}", e))??;
+            undelivered.append(&mut self.unsent.lock().unwrap());
+            Ok((results, undelivered))
+        }
+    }
+
+    impl Stream for ScanStream {
+        type Item = FileChange;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FileChange>> {
+            self.receiver.poll_recv(cx)
+        }
     }
 
     /// Simple callback-based FIM monitor
@@ -250,6 +445,7 @@ pub mod prelude {
     pub use crate::{
         ChangeType, FileChange, FimConfig, FimEngine, FimEvent, FimEventKind,
         FimMode, ScanResults, Result, Alert, AlertGenerator, AlertSeverity,
+        FileHasher, FileHashes, HashConfig,
         FimReport, OutputFormat, ReportConfig, ReportGenerator, RiskLevel,
     };
     pub use crate::utils::*;
@@ -261,6 +457,61 @@ mod tests {
     use tempfile::tempdir;
     use std::fs;
 
+    #[test]
+    fn test_json_log_format() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = log_subscriber(tracing::Level::INFO, LogFormat::Json, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("scan", root = "/etc");
+            let _entered = span.enter();
+            tracing::info!(files = 3, "Scan completed");
+            tracing::debug!("filtered out");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["fields"]["message"], "Scan completed");
+        assert_eq!(lines[0]["fields"]["files"], 3);
+        assert_eq!(lines[0]["span"]["root"], "/etc");
+        assert_eq!(lines[0]["spans"][0]["name"], "scan");
+
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_utils_normalize_path() {
+        use std::path::PathBuf;
+
+        assert_eq!(utils::normalize_path("/var//log/"), PathBuf::from("/var/log"));
+        assert_eq!(utils::normalize_path("/var/./log/."), PathBuf::from("/var/log"));
+        assert_eq!(utils::normalize_path("/var/app/../log"), PathBuf::from("/var/log"));
+        assert_eq!(utils::normalize_path("/../etc"), PathBuf::from("/etc"));
+        assert_eq!(utils::normalize_path("a/../../b"), PathBuf::from("../b"));
+        assert_eq!(utils::normalize_path("./a"), PathBuf::from("a"));
+    }
+
     #[test]
     fn test_version_info() {
         let version = version();
@@ -324,6 +575,22 @@ mod tests {
         assert!(!utils::matches_patterns("important.txt", &patterns));
     }
 
+    #[test]
+    fn test_utils_parse_time_spec() -> Result<()> {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-03-02T12:00:00Z")?.with_timezone(&chrono::Utc);
+
+        assert_eq!(utils::parse_time_spec("24h", now)?.to_rfc3339(), "2024-03-01T12:00:00+00:00");
+        assert_eq!(utils::parse_time_spec("90m", now)?.to_rfc3339(), "2024-03-02T10:30:00+00:00");
+        assert_eq!(
+            utils::parse_time_spec("2024-01-01T00:00:00Z", now)?.to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert!(utils::parse_time_spec("24x", now).is_err());
+        assert!(utils::parse_time_spec("yesterday", now).is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_shared_engine() -> Result<()> {
         let config = FimConfig {
@@ -339,6 +606,99 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_scan_stream_cancellation() -> Result<()> {
+        use futures::StreamExt;
+
+        let temp_dir = tempdir()?;
+        for i in 0..200 {
+            fs::write(temp_dir.path().join(format!("file{:03}.txt", i)), b"original")?;
+        }
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let engine = integration::AsyncFimEngine::new(config)?;
+        engine.baseline_scan().await?;
+        for i in 0..200 {
+            fs::write(temp_dir.path().join(format!("file{:03}.txt", i)), b"modified content")?;
+        }
+
+        let cancel = CancellationToken::new();
+        let mut stream = engine.scan_stream(cancel.clone());
+        let mut seen = Vec::new();
+        while let Some(change) = stream.next().await {
+            seen.push(change.path);
+            if seen.len() == 3 {
+                cancel.cancel();
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 3);
+
+        let (results, undelivered) = stream.finish().await?;
+        assert!(results.files_scanned < 200, "scan continued after cancel: {:?}", results);
+        assert!(results.files_modified >= 3);
+        assert_eq!(results.files_deleted, 0);
+
+        // Every committed change was either yielded or handed back
+        assert_eq!(seen.len() + undelivered.len(), results.files_modified as usize);
+        let mut all: Vec<_> = seen.iter().chain(undelivered.iter().map(|c| &c.path)).collect();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), results.files_modified as usize);
+
+        // The engine is usable again and finishes the remaining files
+        let results = engine.incremental_scan().await?;
+        assert!(results.files_modified > 0);
+        assert_eq!(engine.get_stats().await?.unscanned_files, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        for i in 0..3 {
+            fs::write(temp_dir.path().join(format!("file{}.txt", i)), b"original")?;
+        }
+        let config = FimConfig {
+            monitor_paths: vec![temp_dir.path().to_path_buf()],
+            memory_database: true,
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let engine = integration::AsyncFimEngine::new(config)?;
+        engine.baseline_scan().await?;
+
+        let mut changes = engine.subscribe().await;
+        let mut lagging = engine.engine().lock().await.subscribe_with_capacity(1);
+        let closed = engine.subscribe().await;
+        drop(closed);
+        for i in 0..3 {
+            fs::write(temp_dir.path().join(format!("file{}.txt", i)), b"modified content")?;
+        }
+        engine.incremental_scan().await?;
+
+        let mut seen = Vec::new();
+        while seen.len() < 3 {
+            seen.push(changes.recv().await.unwrap().path);
+        }
+        seen.sort();
+        assert_eq!(seen[0], temp_dir.path().join("file0.txt"));
+
+        // The full buffer kept the first change and dropped the rest
+        assert!(lagging.try_recv().is_ok());
+        assert!(lagging.try_recv().is_err());
+        let engine = engine.engine();
+        let engine = engine.lock().await;
+        assert_eq!(engine.dropped_changes(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn test_default_config_for_path() {
         let temp_dir = tempdir().unwrap();
@@ -395,7 +755,7 @@ mod doc_examples {
     /// println!("File hash: {}", hash);
     /// 
     /// // Full hash with multiple algorithms
-    /// let hasher = FileHasher::all_algorithms();
+    /// let hasher = FileHasher::_all_algorithms();
     /// let hashes = hasher.hash_file(&file_path)?;
     /// println!("BLAKE3: {}", hashes.blake3);
     /// if let Some(sha256) = hashes.sha256 {
@@ -3,21 +3,24 @@
 //! A modern, fast, and reliable file integrity monitoring system written in Rust.
 //! Features BLAKE3 hashing, SQLite storage, real-time monitoring, and comprehensive CLI.
 
-mod database;
-mod fim;
-mod hasher;
-mod watcher;
-
-use crate::fim::{FimConfig, FimEngine, ChangeType};
+use rusty_fim::database::{FullSnapshot, SnapshotFormat};
+use rusty_fim::LogFormat;
+use rusty_fim::manifest::HashCheckStatus;
+use rusty_fim::fim::{FimConfig, FimEngine, ChangeType, FileChange, ScanResults, VerifyOutcome};
+use rusty_fim::reporting::{
+    AlertFileSink, AlertGenerator, AlertSeverity, DigestSink, FimReport, MonitorOutput,
+    OutputFormat, ReportConfig, ReportGenerator, ReportOutput, RotationConfig,
+};
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::signal;
 use tracing::{error, info, Level};
-use tracing_subscriber;
+use tracing_subscriber::util::SubscriberInitExt;
 
 #[derive(Parser)]
 #[command(
@@ -39,6 +42,10 @@ struct Cli {
     #[arg(short, long, default_value = "info")]
     log_level: String,
 
+    /// Log format (text, json)
+    #[arg(long, default_value = "text")]
+    log_format: String,
+
     /// Use in-memory database (no persistence)
     #[arg(long)]
     memory_db: bool,
@@ -47,6 +54,18 @@ struct Cli {
     #[arg(short, long)]
     threads: Option<usize>,
 
+    /// Perform a baseline automatically when scanning an empty database
+    #[arg(long)]
+    auto_baseline: bool,
+
+    /// Open the baseline read-only; scans only report drift
+    #[arg(long)]
+    immutable: bool,
+
+    /// Baseline profile within the database to use
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -71,6 +90,10 @@ enum Commands {
         /// Output baseline to JSON file
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Write a JSON metrics snapshot to this file after the scan
+        #[arg(long, value_name = "FILE")]
+        metrics_out: Option<PathBuf>,
     },
 
     /// Perform incremental scan
@@ -82,9 +105,24 @@ enum Commands {
         #[arg(long)]
         changes_only: bool,
 
-        /// Output format
+        /// Output format (text, json, csv); json and csv print the results and
+        /// changes to stdout, with logs on stderr
         #[arg(long, default_value = "text")]
         format: String,
+
+        /// Write a JSON metrics snapshot to this file after the scan
+        #[arg(long, value_name = "FILE")]
+        metrics_out: Option<PathBuf>,
+    },
+
+    /// List the files a scan would process, without hashing them
+    List {
+        /// Paths to list (uses configured paths if not specified)
+        paths: Vec<PathBuf>,
+
+        /// Exclude patterns (glob format)
+        #[arg(short, long)]
+        exclude: Vec<String>,
     },
 
     /// Start real-time monitoring
@@ -103,6 +141,53 @@ enum Commands {
         /// Output alerts to file
         #[arg(long)]
         alerts_file: Option<PathBuf>,
+
+        /// Rotate the alerts file once it reaches this size (MB, 0 = never)
+        #[arg(long, default_value = "10")]
+        alerts_max_size_mb: u64,
+
+        /// Rotate the alerts file after this many hours
+        #[arg(long)]
+        alerts_rotate_hours: Option<u64>,
+
+        /// Number of rotated alerts files to keep
+        #[arg(long, default_value = "5")]
+        alerts_max_files: usize,
+
+        /// Only print changes at or above this severity (info, warning, error,
+        /// critical); the alerts file still gets every change
+        #[arg(long, default_value = "info")]
+        min_severity: String,
+
+        /// Also write a digest report of all changes to this file every
+        /// --digest-interval seconds
+        #[arg(long)]
+        digest_file: Option<PathBuf>,
+
+        /// Seconds of changes collected into each digest
+        #[arg(long, default_value = "900")]
+        digest_interval: u64,
+
+        /// Write the digest early once this many changes are pending
+        #[arg(long)]
+        digest_max_changes: Option<usize>,
+
+        /// Digest format (json, csv, html, text, xml, cef, leef)
+        #[arg(long, default_value = "text")]
+        digest_format: String,
+
+        /// Keep this file replaced with a report of the changes seen in the
+        /// last --report-interval, e.g. for a dashboard
+        #[arg(long)]
+        report_file: Option<PathBuf>,
+
+        /// Seconds between rolling reports
+        #[arg(long, default_value = "300")]
+        report_interval: u64,
+
+        /// Rolling report format (html, json, csv, text, xml)
+        #[arg(long, default_value = "html")]
+        report_format: String,
     },
 
     /// Verify file integrity
@@ -114,11 +199,49 @@ enum Commands {
         #[arg(long)]
         hash: Option<String>,
 
+        /// Verify every file listed in a `<blake3>  <path>` file (b3sum
+        /// format), exiting non-zero on any mismatch
+        #[arg(long, conflicts_with_all = ["path", "hash"])]
+        hashes: Option<PathBuf>,
+
         /// Show detailed verification results
         #[arg(long)]
         detailed: bool,
     },
 
+    /// Show how a file's recorded data changed across scans
+    History {
+        /// File to show the history of
+        path: PathBuf,
+
+        /// Show at most this many of the most recent changes
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Generate a report from the recorded change log
+    Report {
+        /// Only include changes since this time (e.g. 24h, 7d, or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only include changes until this time (e.g. 1h, or RFC 3339)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Output format (json, csv, html, text, xml, cef, leef)
+        #[arg(long, default_value = "text")]
+        format: String,
+
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Embed every baseline entry so the report can re-seed a database (JSON only)
+        #[arg(long)]
+        full_snapshot: bool,
+    },
+
     /// Database operations
     Db {
         #[command(subcommand)]
@@ -136,6 +259,10 @@ enum Commands {
         /// Show detailed statistics
         #[arg(long)]
         detailed: bool,
+
+        /// Summarize only the entries at or below this path
+        #[arg(long, value_name = "PATH")]
+        under: Option<PathBuf>,
     },
 }
 
@@ -143,17 +270,25 @@ enum Commands {
 enum DbCommands {
     /// Show database statistics
     Stats,
-    /// Export database to JSON
+    /// Export database to JSON or compact binary
     Export {
         /// Output file path
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Snapshot format (json, binary); defaults to binary for .bin files
+        #[arg(long)]
+        format: Option<String>,
     },
-    /// Import database from JSON
+    /// Import database from an export, or a JSON report made with --full-snapshot
     Import {
         /// Input file path
         #[arg(short, long)]
         input: PathBuf,
+
+        /// Snapshot format (json, binary); defaults to binary for .bin files
+        #[arg(long)]
+        format: Option<String>,
     },
     /// Clean/reset database
     Clean {
@@ -163,6 +298,8 @@ enum DbCommands {
     },
     /// Verify database integrity
     Verify,
+    /// List the baseline profiles in the database
+    Profiles,
 }
 
 #[derive(Subcommand)]
@@ -184,11 +321,15 @@ enum ConfigCommands {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    init_logging(&cli.log_level, cli.verbose)?;
+    // Initialize logging, keeping stdout clean for machine-readable scan output
+    let logs_to_stderr = matches!(
+        &cli.command,
+        Commands::Scan { format, .. } if !format.eq_ignore_ascii_case("text")
+    );
+    init_logging(&cli.log_level, cli.verbose, cli.log_format.parse()?, logs_to_stderr)?;
 
     // Load configuration
     let mut config = load_config(&cli)?;
@@ -202,30 +343,111 @@ async fn main() -> Result<()> {
         config.scan_threads = Some(threads);
     }
 
+    if cli.auto_baseline {
+        config.auto_baseline = true;
+    }
+
+    if cli.immutable {
+        config.immutable_baseline = true;
+    }
+
+    if let Some(profile) = cli.profile {
+        config.profile = profile;
+    }
+
     // Execute commands
     match cli.command {
-        Commands::Baseline { paths, exclude, max_size_mb, output } => {
-            handle_baseline(config, paths, exclude, max_size_mb, output).await
+        Commands::Baseline { paths, exclude, max_size_mb, output, metrics_out } => {
+            handle_baseline(config, paths, exclude, max_size_mb, output, metrics_out).await
         }
-        Commands::Scan { paths, changes_only, format } => {
-            handle_scan(config, paths, changes_only, format).await
+        Commands::Scan { paths, changes_only, format, metrics_out } => {
+            handle_scan(config, paths, changes_only, format, metrics_out).await
         }
-        Commands::Monitor { paths, exclude, interval, alerts_file } => {
-            handle_monitor(config, paths, exclude, interval, alerts_file).await
+        Commands::List { paths, exclude } => {
+            handle_list(config, paths, exclude).await
         }
-        Commands::Verify { path, hash, detailed } => {
-            handle_verify(config, path, hash, detailed).await
+        Commands::Monitor {
+            paths,
+            exclude,
+            interval,
+            alerts_file,
+            alerts_max_size_mb,
+            alerts_rotate_hours,
+            alerts_max_files,
+            min_severity,
+            digest_file,
+            digest_interval,
+            digest_max_changes,
+            digest_format,
+            report_file,
+            report_interval,
+            report_format,
+        } => {
+            let rotation = RotationConfig {
+                max_size: (alerts_max_size_mb > 0).then(|| alerts_max_size_mb * 1024 * 1024),
+                max_age: alerts_rotate_hours.map(|hours| hours * 3600),
+                max_files: alerts_max_files,
+            };
+            let min_severity: AlertSeverity = min_severity.parse()?;
+            let alerts_sink = alerts_file
+                .map(|path| AlertFileSink::open(path, rotation))
+                .transpose()?;
+            let mut output = MonitorOutput::new(std::io::stdout(), min_severity, alerts_sink);
+            if let Some(digest_file) = digest_file {
+                let mut digest = DigestSink::new(
+                    ReportOutput::Append(digest_file),
+                    digest_format.parse()?,
+                    Duration::from_secs(digest_interval),
+                );
+                if let Some(max_changes) = digest_max_changes {
+                    digest = digest.with_max_changes(max_changes);
+                }
+                output = output.with_digest(digest);
+            }
+            if let Some(report_file) = report_file {
+                let report = DigestSink::new(
+                    ReportOutput::File(report_file),
+                    report_format.parse()?,
+                    Duration::from_secs(report_interval),
+                )
+                .with_report_config(ReportConfig {
+                    title: "FIM Rolling Report".to_string(),
+                    ..Default::default()
+                })
+                .with_every_interval();
+                output = output.with_report(report);
+            }
+            handle_monitor(config, paths, exclude, interval, output).await
+        }
+        Commands::Verify { path, hash, hashes, detailed } => {
+            let verified = match hashes {
+                Some(hashes) => handle_verify_hashes(&hashes, detailed)?,
+                None => handle_verify(config, path, hash, detailed).await?,
+            };
+            if !verified {
+                return Ok(ExitCode::FAILURE);
+            }
+            Ok(())
+        }
+        Commands::History { path, limit } => handle_history(config, &path, limit),
+        Commands::Report { since, until, format, output, full_snapshot } => {
+            handle_report(config, since, until, format, output, full_snapshot).await
         }
         Commands::Db { action } => {
-            handle_db_commands(config, action).await
+            if !handle_db_commands(config, action).await? {
+                return Ok(ExitCode::FAILURE);
+            }
+            Ok(())
         }
         Commands::Config { action } => {
             handle_config_commands(action).await
         }
-        Commands::Status { detailed } => {
-            handle_status(config, detailed).await
+        Commands::Status { detailed, under } => {
+            handle_status(config, detailed, under).await
         }
-    }
+    }?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
 async fn handle_baseline(
@@ -234,6 +456,7 @@ async fn handle_baseline(
     exclude: Vec<String>,
     max_size_mb: u64,
     output: Option<PathBuf>,
+    metrics_out: Option<PathBuf>,
 ) -> Result<()> {
     info!("Starting baseline scan for {} paths", paths.len());
 
@@ -246,11 +469,8 @@ async fn handle_baseline(
 
     // Add change handler for reporting
     engine.add_change_handler(|change| {
-        match change.change_type {
-            ChangeType::Added => {
-                println!("+ {}", change.path.display());
-            }
-            _ => {}
+        if change.change_type == ChangeType::Added {
+            println!("+ {}", change.path.display());
         }
     });
 
@@ -261,22 +481,54 @@ async fn handle_baseline(
     println!("Files added: {}", results.files_added);
     println!("Total size: {} MB", results.total_size / (1024 * 1024));
     println!("Scan duration: {:?}", results.scan_duration);
+    println!(
+        "  walk {:?}, hash {:?}, database {:?}",
+        results.walk_duration, results.hash_duration, results.db_duration
+    );
     println!("Errors: {}", results.errors);
+    if results.files_skipped > 0 {
+        println!("Skipped (excluded owner): {}", results.files_skipped);
+    }
 
     if let Some(output_path) = output {
         engine.export_database(&output_path)?;
         println!("Baseline exported to: {}", output_path.display());
     }
 
+    if let Some(metrics_path) = metrics_out {
+        write_metrics(&engine, &metrics_path)?;
+    }
+
     Ok(())
 }
 
+/// Write the engine's metrics snapshot as JSON
+fn write_metrics(engine: &FimEngine, path: &std::path::Path) -> Result<()> {
+    let json = serde_json::to_string_pretty(&engine.metrics_snapshot())?;
+    std::fs::write(path, json)
+        .with_context(|| format!("Failed to write metrics to {}", path.display()))?;
+    info!("Metrics written to {}", path.display());
+    Ok(())
+}
+
+/// Output of `fim scan --format json`
+#[derive(serde::Serialize)]
+struct ScanOutput<'a> {
+    results: &'a ScanResults,
+    changes: &'a [FileChange],
+}
+
 async fn handle_scan(
     mut config: FimConfig,
     paths: Vec<PathBuf>,
     changes_only: bool,
-    _format: String,
+    format: String,
+    metrics_out: Option<PathBuf>,
 ) -> Result<()> {
+    let format = match format.parse() {
+        Ok(format @ (OutputFormat::Text | OutputFormat::Json | OutputFormat::Csv)) => format,
+        _ => anyhow::bail!("Unknown scan output format '{}' (expected text, json or csv)", format),
+    };
     if !paths.is_empty() {
         config.monitor_paths = paths;
     }
@@ -284,11 +536,34 @@ async fn handle_scan(
     let mut engine = FimEngine::new(config)?;
     engine.start()?;
 
+    if !matches!(format, OutputFormat::Text) {
+        // Collect the changes and print everything once the scan is done
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let collected = changes.clone();
+        engine.add_change_handler(move |change| collected.lock().unwrap().push(change.clone()));
+
+        let results = engine.incremental_scan()?;
+        let changes = std::mem::take(&mut *changes.lock().unwrap());
+        if matches!(format, OutputFormat::Json) {
+            let output = ScanOutput { results: &results, changes: &changes };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        } else {
+            let generator = ReportGenerator::new(ReportConfig::default());
+            let report = generator.generate_report(changes, Some(results), None);
+            print!("{}", generator.render_report(&report, format)?);
+        }
+
+        if let Some(metrics_path) = metrics_out {
+            write_metrics(&engine, &metrics_path)?;
+        }
+        return Ok(());
+    }
+
     // Add change handler for reporting
     let changes_only_flag = changes_only;
     engine.add_change_handler(move |change| {
         if changes_only_flag {
-            match change.change_type {
+            match &change.change_type {
                 ChangeType::Added => println!("+ {}", change.path.display()),
                 ChangeType::Modified | ChangeType::HashChanged => {
                     println!("M {}", change.path.display());
@@ -297,9 +572,14 @@ async fn handle_scan(
                 ChangeType::PermissionChanged => println!("P {}", change.path.display()),
                 ChangeType::SizeChanged => println!("S {}", change.path.display()),
                 ChangeType::TimestampChanged => println!("T {}", change.path.display()),
+                ChangeType::AttributeChanged => println!("A {}", change.path.display()),
+                ChangeType::ContentClassChanged => println!("C {}", change.path.display()),
+                ChangeType::MountChanged => println!("U {}", change.path.display()),
+                ChangeType::AccessLost => println!("! {}", change.path.display()),
+                ChangeType::Moved { from, to } => println!("R {} -> {}", from.display(), to.display()),
             }
         } else {
-            println!("{:?}: {}", change.change_type, change.path.display());
+            println!("{}: {}", change.change_type.name(), change.path.display());
         }
     });
 
@@ -312,7 +592,18 @@ async fn handle_scan(
         println!("Files modified: {}", results.files_modified);
         println!("Files deleted: {}", results.files_deleted);
         println!("Scan duration: {:?}", results.scan_duration);
+        println!(
+            "  walk {:?}, hash {:?}, database {:?}",
+            results.walk_duration, results.hash_duration, results.db_duration
+        );
         println!("Errors: {}", results.errors);
+        if results.files_skipped > 0 {
+            println!("Skipped (excluded owner): {}", results.files_skipped);
+        }
+    }
+
+    if let Some(metrics_path) = metrics_out {
+        write_metrics(&engine, &metrics_path)?;
     }
 
     Ok(())
@@ -323,7 +614,7 @@ async fn handle_monitor(
     paths: Vec<PathBuf>,
     exclude: Vec<String>,
     interval: u64,
-    alerts_file: Option<PathBuf>,
+    output: MonitorOutput<std::io::Stdout>,
 ) -> Result<()> {
     info!("Starting real-time monitoring");
 
@@ -337,30 +628,11 @@ async fn handle_monitor(
     let mut engine = FimEngine::new(config)?;
 
     // Setup change handler for alerts
-    let alerts_file_clone = alerts_file.clone();
+    let output = Arc::new(output);
+    let handler_output = output.clone();
     engine.add_change_handler(move |change| {
-        let alert_msg = format!(
-            "[{}] {:?}: {}",
-            change.detected_at.format("%Y-%m-%d %H:%M:%S UTC"),
-            change.change_type,
-            change.path.display()
-        );
-
-        println!("{}", alert_msg);
-
-        // Write to alerts file if specified
-        if let Some(ref alerts_file) = alerts_file_clone {
-            if let Err(e) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(alerts_file)
-                .and_then(|mut f| {
-                    use std::io::Write;
-                    writeln!(f, "{}", alert_msg)
-                })
-            {
-                error!("Failed to write to alerts file: {}", e);
-            }
+        if let Err(e) = handler_output.handle(change) {
+            error!("{:#}", e);
         }
     });
 
@@ -398,41 +670,99 @@ async fn handle_monitor(
         }
     });
 
+    // Deliver digests and rolling reports even when no further changes arrive
+    if output.digest().is_some() || output.report().is_some() {
+        let digest_output = output.clone();
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(Duration::from_secs(10));
+            loop {
+                tick.tick().await;
+                if let Some(Err(e)) = digest_output.digest().map(|digest| digest.flush_if_due()) {
+                    error!("Failed to write digest: {:#}", e);
+                }
+                if let Some(Err(e)) = digest_output.report().map(|report| report.flush_if_due()) {
+                    error!("Failed to write rolling report: {:#}", e);
+                }
+            }
+        });
+    }
+
     // Wait for shutdown signal
     println!("FIM monitoring active. Press Ctrl+C to stop.");
     signal::ctrl_c().await?;
     println!("Shutting down...");
 
+    if let Some(digest) = output.digest() {
+        digest.flush()?;
+    }
+    if let Some(report) = output.report() {
+        report.flush()?;
+    }
+
     Ok(())
 }
 
+/// Verify a file or the database; false when a file no longer matches its
+/// recorded or expected hash
 async fn handle_verify(
     config: FimConfig,
     path: Option<PathBuf>,
     hash: Option<String>,
     detailed: bool,
-) -> Result<()> {
+) -> Result<bool> {
     let engine = FimEngine::new(config)?;
 
     if let Some(path) = path {
         if let Some(expected_hash) = hash {
             // Verify specific file against hash
-            let hasher = crate::hasher::FileHasher::blake3_only();
+            let hasher = rusty_fim::hasher::FileHasher::blake3_only();
             match hasher.verify_file(&path, &expected_hash) {
                 Ok(true) => {
                     println!("✓ {} - VERIFIED", path.display());
                 }
                 Ok(false) => {
                     println!("✗ {} - VERIFICATION FAILED", path.display());
+                    return Ok(false);
                 }
                 Err(e) => {
                     error!("Verification error for {}: {}", path.display(), e);
+                    return Ok(false);
                 }
             }
         } else {
             // Verify against database
             println!("Verifying {} against database", path.display());
-            // Implementation would check against stored hash
+            let outcome = engine.verify_path(&path)?;
+            match &outcome {
+                VerifyOutcome::Match => println!("✓ {} - VERIFIED", path.display()),
+                VerifyOutcome::Mismatch { fields } => {
+                    println!("✗ {} - VERIFICATION FAILED", path.display());
+                    if detailed {
+                        for field in fields {
+                            println!("  {}: {} -> {}", field.field, field.old, field.new);
+                        }
+                    }
+                }
+                VerifyOutcome::Missing => println!("✗ {} - MISSING", path.display()),
+                VerifyOutcome::AccessLost => println!("✗ {} - ACCESS DENIED", path.display()),
+                VerifyOutcome::NotInBaseline => {
+                    println!("{} is not in the database", path.display());
+                }
+                VerifyOutcome::ExcludedOwner => {
+                    println!("{} is owned by an excluded user or group", path.display());
+                }
+            }
+            if detailed {
+                if let Some(entry) = engine.get_entry(&path)? {
+                    print_entry_timestamps(&entry);
+                }
+            }
+            if matches!(
+                outcome,
+                VerifyOutcome::Mismatch { .. } | VerifyOutcome::Missing | VerifyOutcome::AccessLost
+            ) {
+                return Ok(false);
+            }
         }
     } else {
         // Verify entire database integrity
@@ -447,13 +777,165 @@ async fn handle_verify(
         }
     }
 
+    Ok(true)
+}
+
+/// Check the files of a hash list; false on any mismatch or unreadable file
+fn handle_verify_hashes(list: &std::path::Path, detailed: bool) -> Result<bool> {
+    let hash_list = rusty_fim::manifest::HashList::load(list)?;
+    let report = hash_list.verify(&rusty_fim::hasher::FileHasher::blake3_only());
+
+    for check in &report.checks {
+        match &check.status {
+            HashCheckStatus::Passed => println!("PASS  {}", check.path.display()),
+            HashCheckStatus::Mismatched { actual } => {
+                println!("FAIL  {}", check.path.display());
+                if detailed {
+                    println!("      expected {}", check.expected);
+                    println!("      actual   {}", actual);
+                }
+            }
+            HashCheckStatus::Failed { error } => {
+                println!("ERROR {} ({})", check.path.display(), error);
+            }
+        }
+    }
+
+    let failures = report.failures();
+    println!("\n{} passed, {} failed", report.checks.len() - failures, failures);
+    Ok(report.is_clean())
+}
+
+fn handle_history(config: FimConfig, path: &std::path::Path, limit: usize) -> Result<()> {
+    let engine = FimEngine::new(config)?;
+    let history = engine.get_history(path, limit)?;
+    let current = engine.get_entry(path)?;
+    if history.is_empty() {
+        match current {
+            Some(_) => println!("{} has not changed since it was recorded", path.display()),
+            None => println!("{} is not in the database", path.display()),
+        }
+        return Ok(());
+    }
+
+    println!("History of {} ({} changes)", path.display(), history.len());
+    // Each change runs from one recorded state to the next, ending at the current entry
+    let next_states = history.iter()
+        .skip(1)
+        .map(|(_, data)| Some(data))
+        .chain(std::iter::once(current.as_ref().map(|entry| &entry.data)));
+    for ((changed_at, old), new) in history.iter().zip(next_states) {
+        println!("\n{}", changed_at.to_rfc3339());
+        match new {
+            Some(new) => {
+                for field in old.diff(new) {
+                    println!("  {}: {} -> {}", field.field, field.old, field.new);
+                }
+            }
+            None => println!("  (no longer in the database)"),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_list(mut config: FimConfig, paths: Vec<PathBuf>, exclude: Vec<String>) -> Result<()> {
+    if !paths.is_empty() {
+        config.monitor_paths = paths;
+    }
+    config.exclude_patterns.extend(exclude);
+    // Listing never touches the database or the watcher
+    config.memory_database = true;
+    config.enable_realtime = false;
+
+    let engine = FimEngine::new(config.clone())?;
+    let files = engine.list_scannable()?;
+
+    let mut total_size = 0u64;
+    let mut oversized = 0usize;
+    for file in &files {
+        let size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        total_size += size;
+        if config.max_file_size.is_some_and(|max| size > max) {
+            oversized += 1;
+        }
+        println!("{}", file.display());
+    }
+
+    println!("\n{} files, {}", files.len(), rusty_fim::utils::format_size(total_size));
+    if oversized > 0 {
+        println!("{} files exceed max_file_size and would be reported as errors", oversized);
+    }
+
     Ok(())
 }
 
-async fn handle_db_commands(config: FimConfig, action: DbCommands) -> Result<()> {
+async fn handle_report(
+    config: FimConfig,
+    since: Option<String>,
+    until: Option<String>,
+    format: String,
+    output: Option<PathBuf>,
+    full_snapshot: bool,
+) -> Result<()> {
+    let format: OutputFormat = format.parse()?;
+    if full_snapshot && !matches!(format, OutputFormat::Json) {
+        anyhow::bail!("--full-snapshot requires --format json");
+    }
+    let now = chrono::Utc::now();
+    let since = since.map(|spec| rusty_fim::utils::parse_time_spec(&spec, now)).transpose()?;
+    let until = until.map(|spec| rusty_fim::utils::parse_time_spec(&spec, now)).transpose()?;
+
+    let mut builder = ReportGenerator::builder();
+    if let Some(since) = since {
+        builder = builder.since(since);
+    }
+    if let Some(until) = until {
+        builder = builder.until(until);
+    }
+    if let Some(output_path) = &output {
+        builder = builder.file(format.clone(), output_path);
+    }
+    let generator = builder.build();
+
     let engine = FimEngine::new(config)?;
+    let mut report = generator.report_from_store(engine.store())?;
+    if full_snapshot {
+        report.snapshot = Some(engine.snapshot()?);
+    }
+
+    match output {
+        Some(output_path) => {
+            generator.export_all(&report)?;
+            println!("Report written to: {}", output_path.display());
+        }
+        None => print!("{}", generator.render_report(&report, format)?),
+    }
+
+    Ok(())
+}
+
+/// Snapshot format from `--format`, or from the file extension when not given
+fn snapshot_format(format: Option<&str>, path: &std::path::Path) -> Result<SnapshotFormat> {
+    match format {
+        Some(format) => format.parse(),
+        None => Ok(SnapshotFormat::from_path(path)),
+    }
+}
+
+/// Run a database command; false when `db verify` finds the database damaged
+async fn handle_db_commands(config: FimConfig, action: DbCommands) -> Result<bool> {
+    let db_file = config.database_file().to_path_buf();
+    let syslog = config.syslog.clone();
+    let mut engine = FimEngine::new(config)?;
 
     match action {
+        DbCommands::Profiles => {
+            let active = engine.store().profile();
+            for profile in engine.store().list_profiles()? {
+                let marker = if profile == active { "*" } else { " " };
+                println!("{} {}", marker, profile);
+            }
+        }
         DbCommands::Stats => {
             let stats = engine.get_stats()?;
             println!("=== Database Statistics ===");
@@ -461,28 +943,74 @@ async fn handle_db_commands(config: FimConfig, action: DbCommands) -> Result<()>
             println!("Scanned files: {}", stats.scanned_files);
             println!("Unscanned files: {}", stats.unscanned_files);
         }
-        DbCommands::Export { output } => {
-            engine.export_database(&output)?;
+        DbCommands::Export { output, format } => {
+            let format = snapshot_format(format.as_deref(), &output)?;
+            engine.export_snapshot(&output, format)?;
             println!("Database exported to: {}", output.display());
         }
-        DbCommands::Import { input: _ } => {
-            // Implementation would import from JSON
-            println!("Database import functionality not yet implemented");
+        DbCommands::Import { input, format } => {
+            let snapshot = match snapshot_format(format.as_deref(), &input)? {
+                SnapshotFormat::Binary => FullSnapshot::load(&input, SnapshotFormat::Binary)?,
+                SnapshotFormat::Json => {
+                    let content = std::fs::read_to_string(&input)
+                        .with_context(|| format!("Failed to read {}", input.display()))?;
+                    match serde_json::from_str::<FullSnapshot>(&content) {
+                        Ok(snapshot) => snapshot,
+                        Err(_) => {
+                            let report: FimReport = serde_json::from_str(&content)
+                                .with_context(|| format!("Failed to parse report {}", input.display()))?;
+                            report.snapshot.context(
+                                "Report has no snapshot; generate it with `report --full-snapshot`",
+                            )?
+                        }
+                    }
+                }
+            };
+            let imported = engine.import_snapshot(&snapshot)?;
+            println!("Imported {} entries from {}", imported, input.display());
+            println!("Checksum: {}", snapshot.checksum);
         }
         DbCommands::Clean { force } => {
             if force || confirm_action("This will delete all FIM data. Continue?")? {
-                crate::database::FimDb::clean()?;
+                // Close the database first so SQLite doesn't recreate its files
+                drop(engine);
+                rusty_fim::database::FimDb::clean_at(&db_file)?;
                 println!("Database cleaned successfully");
             }
         }
         DbCommands::Verify => {
-            let checksum = engine.verify_integrity()?;
+            let report = engine.check_database_integrity()?;
+            if let Some(alert) = AlertGenerator::new().generate_integrity_alert(&report, &db_file) {
+                // The verdict below matters more than reaching syslog
+                let syslog = AlertGenerator::with_syslog(syslog);
+                if let Err(e) = syslog.send_alert(&alert) {
+                    error!("{:#}", e);
+                }
+                println!("✗ CRITICAL: {}", alert.message);
+                return Ok(false);
+            }
+            let change_log = engine.verify_change_log()?;
+            if !change_log.is_intact() {
+                match change_log.first_break {
+                    Some(id) => println!("✗ CRITICAL: Change log tampered at entry {}", id),
+                    None => println!("✗ CRITICAL: Change log entries removed from the end"),
+                }
+                return Ok(false);
+            }
+            let history = engine.verify_history()?;
+            if !history.is_intact() {
+                match history.first_break {
+                    Some(id) => println!("✗ CRITICAL: File history tampered at entry {}", id),
+                    None => println!("✗ CRITICAL: File history entries removed from the end"),
+                }
+                return Ok(false);
+            }
             println!("Database integrity verified");
-            println!("Checksum: {}", checksum);
+            println!("Checksum: {}", engine.verify_integrity()?);
         }
     }
 
-    Ok(())
+    Ok(true)
 }
 
 async fn handle_config_commands(action: ConfigCommands) -> Result<()> {
@@ -494,8 +1022,7 @@ async fn handle_config_commands(action: ConfigCommands) -> Result<()> {
             println!("Default configuration written to: {}", output.display());
         }
         ConfigCommands::Validate { file } => {
-            let content = std::fs::read_to_string(&file)?;
-            let _config: FimConfig = toml::from_str(&content)
+            let _config = FimConfig::load(&file)
                 .context("Invalid configuration file")?;
             println!("Configuration file is valid");
         }
@@ -508,11 +1035,18 @@ async fn handle_config_commands(action: ConfigCommands) -> Result<()> {
     Ok(())
 }
 
-async fn handle_status(config: FimConfig, detailed: bool) -> Result<()> {
+async fn handle_status(config: FimConfig, detailed: bool, under: Option<PathBuf>) -> Result<()> {
     let engine = FimEngine::new(config)?;
+    if let Some(prefix) = under {
+        return print_subtree_status(&engine, &prefix);
+    }
     let stats = engine.get_stats()?;
 
     println!("=== FIM Status ===");
+    match engine.database_path() {
+        Some(path) => println!("Database: {}", path.display()),
+        None => println!("Database: in memory"),
+    }
     println!("Total files monitored: {}", stats.total_files);
     println!("Files up to date: {}", stats.scanned_files);
     
@@ -524,24 +1058,71 @@ async fn handle_status(config: FimConfig, detailed: bool) -> Result<()> {
         println!("\n=== Detailed Information ===");
         // Additional detailed stats would go here
         println!("Database integrity: {}", engine.verify_integrity()?);
+
+        let entries = engine.snapshot()?.entries;
+        if let Some(first_seen) = entries.iter().filter_map(|entry| entry.created_at).min() {
+            println!("First file seen: {}", first_seen.to_rfc3339());
+        }
+        let last_changed = entries
+            .iter()
+            .filter_map(|entry| entry.updated_at.map(|time| (time, &entry.path)))
+            .max();
+        if let Some((time, path)) = last_changed {
+            println!("Last change: {} ({})", time.to_rfc3339(), path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarize the recorded entries at or below `prefix`
+fn print_subtree_status(engine: &FimEngine, prefix: &Path) -> Result<()> {
+    let entries = engine.list_entries(prefix)?;
+
+    println!("=== FIM Status: {} ===", prefix.display());
+    println!("Entries: {}", entries.len());
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let files: Vec<_> = entries.iter().filter(|entry| !entry.data.is_dir).collect();
+    println!("Files: {}", files.len());
+    println!("Directories: {}", entries.len() - files.len());
+    println!("Total size: {} bytes", files.iter().map(|entry| entry.data.size).sum::<u64>());
+
+    let unscanned = entries.iter().filter(|entry| !entry.data.scanned).count();
+    if unscanned > 0 {
+        println!("Entries requiring scan: {}", unscanned);
+    }
+    let last_changed = entries
+        .iter()
+        .filter_map(|entry| entry.updated_at.map(|time| (time, &entry.path)))
+        .max();
+    if let Some((time, path)) = last_changed {
+        println!("Last change: {} ({})", time.to_rfc3339(), path.display());
     }
 
     Ok(())
 }
 
+/// Print when FIM first recorded an entry and when its data last changed
+fn print_entry_timestamps(entry: &rusty_fim::FimEntry) {
+    let format = |time: Option<chrono::DateTime<chrono::Utc>>| {
+        time.map_or_else(|| "unknown".to_string(), |time| time.to_rfc3339())
+    };
+    println!("  First seen:   {}", format(entry.created_at));
+    println!("  Last changed: {}", format(entry.updated_at));
+}
+
 fn load_config(cli: &Cli) -> Result<FimConfig> {
     if let Some(config_path) = &cli.config {
-        let content = std::fs::read_to_string(config_path)
-            .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
-        
-        toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", config_path.display()))
+        FimConfig::load(config_path)
     } else {
         Ok(FimConfig::default())
     }
 }
 
-fn init_logging(level: &str, verbose: bool) -> Result<()> {
+fn init_logging(level: &str, verbose: bool, log_format: LogFormat, to_stderr: bool) -> Result<()> {
     let log_level = if verbose {
         Level::DEBUG
     } else {
@@ -555,15 +1136,14 @@ fn init_logging(level: &str, verbose: bool) -> Result<()> {
         }
     };
 
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
-        .with_thread_ids(false)
-        .with_file(false)
-        .with_line_number(false)
-        .init();
-
-    Ok(())
+    let subscriber = if to_stderr {
+        rusty_fim::log_subscriber(log_level, log_format, std::io::stderr)
+    } else {
+        rusty_fim::log_subscriber(log_level, log_format, std::io::stdout)
+    };
+    subscriber
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))
 }
 
 fn confirm_action(message: &str) -> Result<bool> {
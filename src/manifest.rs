@@ -0,0 +1,358 @@
+//! Signed tree manifests for offline supply-chain verification
+//!
+//! A manifest lists every file of a directory tree with its BLAKE3 hash and
+//! carries an Ed25519 signature over that list, so a released artifact
+//! directory can be checked against the manifest shipped alongside it without
+//! any persistent FIM database.
+
+use crate::hasher::FileHasher;
+
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File list of a directory tree with an Ed25519 signature
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeManifest {
+    /// Entry value keyed by `/`-separated path relative to the tree root:
+    /// the BLAKE3 hash of a regular file, else what [`entry_value`] records
+    /// for a symlink or special file
+    pub files: BTreeMap<String, String>,
+    /// Hex-encoded signature over the serialized `files` map
+    pub signature: Option<String>,
+}
+
+impl TreeManifest {
+    /// Build an unsigned manifest of every file, symlink and special file
+    /// under `root`
+    pub fn from_tree(root: &Path, hasher: &FileHasher) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for path in collect_tree_files(root)? {
+            files.insert(manifest_key(root, &path)?, entry_value(&path, hasher)?);
+        }
+
+        Ok(Self {
+            files,
+            signature: None,
+        })
+    }
+
+    /// Load a manifest from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    /// Save the manifest as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest {}", path.display()))
+    }
+
+    /// Sign the file list with an Ed25519 secret key
+    pub fn sign(&mut self, secret_key: &[u8; 32]) -> Result<()> {
+        self.signature = Some(sign_payload(secret_key, &self.signed_payload()?));
+        Ok(())
+    }
+
+    /// Check the signature against an Ed25519 public key
+    pub fn verify_signature(&self, public_key: &[u8; 32]) -> Result<()> {
+        let signature = self.signature.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Manifest is not signed"))?;
+        verify_payload(public_key, &self.signed_payload()?, signature)
+            .context("Manifest signature verification failed")
+    }
+
+    /// Canonical bytes covered by the signature
+    fn signed_payload(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&self.files)?)
+    }
+}
+
+/// Hex-encoded Ed25519 signature over `payload`
+pub(crate) fn sign_payload(secret_key: &[u8; 32], payload: &[u8]) -> String {
+    hex::encode(SigningKey::from_bytes(secret_key).sign(payload).to_bytes())
+}
+
+/// Check a hex-encoded Ed25519 signature over `payload`
+pub(crate) fn verify_payload(public_key: &[u8; 32], payload: &[u8], signature_hex: &str) -> Result<()> {
+    let verifying_key = VerifyingKey::from_bytes(public_key)
+        .context("Invalid public key")?;
+    let signature_bytes: [u8; 64] = hex::decode(signature_hex)
+        .context("Malformed signature")?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Malformed signature"))?;
+
+    verifying_key
+        .verify(payload, &Signature::from_bytes(&signature_bytes))
+        .context("Signature does not match")
+}
+
+/// Outcome of verifying a directory tree against a manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeVerifyReport {
+    /// Files whose hash, or link target or file kind, matches the manifest
+    pub verified: Vec<PathBuf>,
+    /// Files whose hash, or link target or file kind, differs from the manifest
+    pub mismatched: Vec<PathBuf>,
+    /// Files listed in the manifest but absent from the tree
+    pub missing: Vec<PathBuf>,
+    /// Files present in the tree but not listed in the manifest
+    pub extra: Vec<PathBuf>,
+}
+
+impl TreeVerifyReport {
+    /// True when the tree matches the manifest exactly
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Expected BLAKE3 hashes of individual files, in the `<hash>  <path>` line
+/// format written by `b3sum`
+///
+/// Blank lines and lines starting with `#` are skipped; a `*` before the
+/// path (binary mode marker) is ignored. Relative paths are used as given,
+/// so they resolve against the working directory, as with `b3sum --check`.
+#[derive(Debug, Clone, Default)]
+pub struct HashList {
+    pub entries: Vec<(PathBuf, String)>,
+}
+
+impl HashList {
+    /// Parse a hash list, failing on the first malformed line
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (hash, path) = line.split_once(char::is_whitespace)
+                .map(|(hash, path)| (hash, path.trim_start().trim_start_matches('*')))
+                .filter(|(_, path)| !path.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("Line {}: expected '<hash>  <path>'", number + 1))?;
+            entries.push((PathBuf::from(path), hash.to_ascii_lowercase()));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Load a hash list from a file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read hash list {}", path.display()))?;
+        Self::parse(&content)
+            .with_context(|| format!("Invalid hash list {}", path.display()))
+    }
+
+    /// Hash every listed file and compare it with its expected hash
+    pub fn verify(&self, hasher: &FileHasher) -> HashListReport {
+        let checks = self.entries
+            .iter()
+            .map(|(path, expected)| {
+                let status = match hasher.hash_file(path) {
+                    Ok(hashes) if hashes.blake3 == *expected => HashCheckStatus::Passed,
+                    Ok(hashes) => HashCheckStatus::Mismatched { actual: hashes.blake3 },
+                    Err(e) => HashCheckStatus::Failed { error: format!("{:#}", e) },
+                };
+                HashCheck {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    status,
+                }
+            })
+            .collect();
+        HashListReport { checks }
+    }
+}
+
+/// Result of checking one [`HashList`] entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashCheck {
+    pub path: PathBuf,
+    pub expected: String,
+    pub status: HashCheckStatus,
+}
+
+/// Whether a listed file matched its expected hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashCheckStatus {
+    Passed,
+    Mismatched { actual: String },
+    /// The file could not be hashed, e.g. because it is missing
+    Failed { error: String },
+}
+
+/// Outcome of verifying files against a [`HashList`], in list order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HashListReport {
+    pub checks: Vec<HashCheck>,
+}
+
+impl HashListReport {
+    /// True when every listed file matched
+    pub fn is_clean(&self) -> bool {
+        self.checks.iter().all(|check| check.status == HashCheckStatus::Passed)
+    }
+
+    /// Number of files that did not match or could not be hashed
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|check| check.status != HashCheckStatus::Passed).count()
+    }
+}
+
+/// Recursively collect everything but directories under `root`: regular
+/// files, symlinks (not followed), FIFOs, sockets and devices
+pub(crate) fn collect_tree_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Manifest value of a tree entry: the BLAKE3 hash of a regular file, else
+/// its kind and what it points at, e.g. `symlink:../lib/libfoo.so`, `fifo`
+/// or `char-device:<hex rdev>`; special files are never opened
+pub(crate) fn entry_value(path: &Path, hasher: &FileHasher) -> Result<String> {
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
+    let file_type = metadata.file_type();
+    if file_type.is_file() {
+        return Ok(hasher.hash_file(path)?.blake3);
+    }
+    if file_type.is_symlink() {
+        let target = fs::read_link(path)
+            .with_context(|| format!("Failed to read link {}", path.display()))?;
+        return Ok(format!("symlink:{}", target.to_string_lossy()));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{FileTypeExt, MetadataExt};
+        if file_type.is_fifo() {
+            return Ok("fifo".to_string());
+        }
+        if file_type.is_socket() {
+            return Ok("socket".to_string());
+        }
+        if file_type.is_char_device() {
+            return Ok(format!("char-device:{:x}", metadata.rdev()));
+        }
+        if file_type.is_block_device() {
+            return Ok(format!("block-device:{:x}", metadata.rdev()));
+        }
+    }
+    Ok("special".to_string())
+}
+
+/// Manifest key for a file: its path relative to `root`, `/`-separated
+pub(crate) fn manifest_key(root: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(root)
+        .with_context(|| format!("{} is outside {}", path.display(), root.display()))?;
+    Ok(relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_manifest_signature() -> Result<()> {
+        let temp_dir = tempdir()?;
+        fs::create_dir(temp_dir.path().join("bin"))?;
+        fs::write(temp_dir.path().join("bin").join("tool"), b"binary")?;
+
+        let secret = [7u8; 32];
+        let public = SigningKey::from_bytes(&secret).verifying_key().to_bytes();
+
+        let mut manifest = TreeManifest::from_tree(temp_dir.path(), &FileHasher::blake3_only())?;
+        assert!(manifest.files.contains_key("bin/tool"));
+        assert!(manifest.verify_signature(&public).is_err());
+
+        manifest.sign(&secret)?;
+        manifest.verify_signature(&public)?;
+
+        manifest.files.insert("bin/tool".to_string(), "forged".to_string());
+        assert!(manifest.verify_signature(&public).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_manifest_records_links_and_special_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        fs::write(root.join("file.txt"), b"content")?;
+        std::os::unix::fs::symlink("file.txt", root.join("link"))?;
+        let fifo = std::ffi::CString::new(root.join("pipe").to_string_lossy().as_bytes())?;
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+
+        let manifest = TreeManifest::from_tree(root, &FileHasher::blake3_only())?;
+        assert_eq!(manifest.files.len(), 3);
+        assert_eq!(manifest.files["link"], "symlink:file.txt");
+        assert_eq!(manifest.files["pipe"], "fifo");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_list_verification() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let good = temp_dir.path().join("good.conf");
+        let tampered = temp_dir.path().join("tampered.conf");
+        fs::write(&good, b"trusted")?;
+        fs::write(&tampered, b"changed")?;
+        let expected = |content: &[u8]| blake3::hash(content).to_hex().to_string();
+
+        let list_path = temp_dir.path().join("hashes.b3");
+        fs::write(&list_path, format!(
+            "# critical files\n{}  {}\n{} *{}\n\n{}  {}\n",
+            expected(b"trusted"), good.display(),
+            expected(b"original"), tampered.display(),
+            expected(b"gone"), temp_dir.path().join("missing.conf").display(),
+        ))?;
+
+        let report = HashList::load(&list_path)?.verify(&FileHasher::blake3_only());
+        assert_eq!(report.checks.len(), 3);
+        assert_eq!(report.checks[0].path, good);
+        assert_eq!(report.checks[0].status, HashCheckStatus::Passed);
+        assert_eq!(report.checks[1].path, tampered);
+        assert_eq!(
+            report.checks[1].status,
+            HashCheckStatus::Mismatched { actual: expected(b"changed") }
+        );
+        assert!(matches!(report.checks[2].status, HashCheckStatus::Failed { .. }));
+        assert_eq!(report.failures(), 2);
+        assert!(!report.is_clean());
+
+        assert!(HashList::parse("deadbeef\n").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! Prometheus exposition of engine metrics
+//!
+//! Renders a [`MetricsSnapshot`] in the Prometheus text format (version 0.0.4)
+//! so a long-running service can serve [`FimEngine::metrics_text`] from a
+//! scrape endpoint. Counters accumulate from engine creation, so they only
+//! reset when the process restarts.
+//!
+//! [`FimEngine::metrics_text`]: crate::FimEngine::metrics_text
+
+use crate::fim::MetricsSnapshot;
+use std::fmt::{Display, Write as _};
+
+/// Content type of the rendered text, for the HTTP response
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Render `snapshot` in the Prometheus text exposition format
+pub fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let mut out = Exposition::default();
+
+    out.family("fim_scans_total", "counter", "Completed scans");
+    out.sample("fim_scans_total", &[], snapshot.scans_completed);
+    out.family("fim_files_scanned_total", "counter", "Files hashed and compared by scans");
+    out.sample("fim_files_scanned_total", &[], snapshot.files_scanned);
+    out.family("fim_bytes_scanned_total", "counter", "Bytes of file content scanned");
+    out.sample("fim_bytes_scanned_total", &[], snapshot.bytes_scanned);
+    out.family("fim_scan_errors_total", "counter", "Files that could not be scanned");
+    out.sample("fim_scan_errors_total", &[], snapshot.errors);
+
+    out.family("fim_changes_detected_total", "counter", "Detected changes by change type");
+    for (change_type, count) in &snapshot.changes_by_type {
+        out.sample("fim_changes_detected_total", &[("change_type", change_type)], count);
+    }
+
+    out.family("fim_scan_duration_seconds", "summary", "Time spent in scans");
+    out.sample("fim_scan_duration_seconds_sum", &[], snapshot.scan_duration_total.as_secs_f64());
+    out.sample("fim_scan_duration_seconds_count", &[], snapshot.scans_completed);
+    out.family("fim_last_scan_duration_seconds", "gauge", "Duration of the most recent scan");
+    out.sample("fim_last_scan_duration_seconds", &[], snapshot.last_scan_duration.as_secs_f64());
+
+    out.family("fim_events_throttled_total", "counter", "Real-time events dropped by the rate limit");
+    out.sample("fim_events_throttled_total", &[], snapshot.events_throttled);
+    out.family("fim_handler_errors_total", "counter", "Change handler failures");
+    out.sample("fim_handler_errors_total", &[], snapshot.handler_errors);
+    out.family("fim_dropped_changes_total", "counter", "Changes a lagging subscriber missed");
+    out.sample("fim_dropped_changes_total", &[], snapshot.dropped_changes);
+
+    out.family("fim_watcher_queue_depth", "gauge", "Real-time events waiting to be processed");
+    out.sample("fim_watcher_queue_depth", &[], snapshot.watcher_queue_depth);
+    out.family("fim_pending_coalesced_events", "gauge", "Paths waiting out their coalescing window");
+    out.sample("fim_pending_coalesced_events", &[], snapshot.pending_coalesced_events);
+
+    out.text
+}
+
+/// Builder for exposition text
+#[derive(Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    /// `# HELP` and `# TYPE` lines introducing a metric family
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+}
+
+/// Escape a label value as the exposition format requires
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_prometheus() {
+        let mut snapshot = MetricsSnapshot {
+            scans_completed: 2,
+            files_scanned: 40,
+            scan_duration_total: Duration::from_millis(1500),
+            events_throttled: 7,
+            ..Default::default()
+        };
+        snapshot.changes_by_type.insert("HashChanged".to_string(), 3);
+        snapshot.changes_by_type.insert("Odd\"type".to_string(), 1);
+
+        let text = render_prometheus(&snapshot);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"# TYPE fim_files_scanned_total counter"));
+        assert!(lines.contains(&"fim_files_scanned_total 40"));
+        assert!(lines.contains(&"fim_changes_detected_total{change_type=\"HashChanged\"} 3"));
+        assert!(lines.contains(&"fim_changes_detected_total{change_type=\"Odd\\\"type\"} 1"));
+        assert!(lines.contains(&"fim_scan_duration_seconds_sum 1.5"));
+        assert!(lines.contains(&"fim_scan_duration_seconds_count 2"));
+        assert!(lines.contains(&"fim_events_throttled_total 7"));
+
+        // Every sample belongs to a declared family
+        for line in lines.iter().filter(|line| !line.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            let family = name.trim_end_matches("_sum").trim_end_matches("_count");
+            assert!(text.contains(&format!("# TYPE {} ", family)), "{}", line);
+        }
+    }
+}
@@ -3,16 +3,23 @@
 //! Provides various output formats for FIM results including JSON, CSV, HTML reports,
 //! and integration with external alerting systems.
 
-use crate::fim::{ChangeType, FileChange, ScanResults};
-use crate::database::FimStats;
+use crate::fim::{ChangeType, FileChange, RetryConfig, ScanResults};
+use crate::database::{DbIntegrityReport, FimEntryData, FimStats, FullSnapshot};
+use crate::store::FimStore;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
 use std::fs;
-// use std::io::Write; // unused
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use tracing::{debug, info};
 
 /// Report generation configuration
@@ -26,6 +33,28 @@ pub struct ReportConfig {
     pub max_changes_displayed: Option<usize>,
     pub group_by_type: bool,
     pub sort_by: SortOrder,
+    /// Line ending for CSV and text output
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    /// Prefix CSV and text output with a UTF-8 byte order mark
+    #[serde(default)]
+    pub utf8_bom: bool,
+    /// Show change times relative to report generation (e.g. "3 minutes ago") in text/HTML
+    #[serde(default)]
+    pub relative_timestamps: bool,
+    /// Only report changes detected at or after this time
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only report changes detected at or before this time
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Render CSV/HTML/text rows in parallel for reports with at least this many changes
+    #[serde(default = "default_parallel_render_threshold")]
+    pub parallel_render_threshold: Option<usize>,
+}
+
+fn default_parallel_render_threshold() -> Option<usize> {
+    Some(10_000)
 }
 
 impl Default for ReportConfig {
@@ -39,10 +68,25 @@ impl Default for ReportConfig {
             max_changes_displayed: Some(1000),
             group_by_type: true,
             sort_by: SortOrder::Timestamp,
+            line_ending: LineEnding::Lf,
+            utf8_bom: false,
+            relative_timestamps: false,
+            since: None,
+            until: None,
+            parallel_render_threshold: default_parallel_render_threshold(),
         }
     }
 }
 
+/// Line ending used in text-based report output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// Windows-style `\r\n`, expected by some Windows tooling
+    CrLf,
+}
+
 /// Sort order for changes in reports
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SortOrder {
@@ -60,6 +104,9 @@ pub struct FimReport {
     pub statistics: Option<FimStats>,
     pub changes: Vec<FileChange>,
     pub scan_results: Option<ScanResults>,
+    /// Complete baseline entries, only present when explicitly requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<FullSnapshot>,
 }
 
 /// Report metadata
@@ -99,6 +146,9 @@ pub struct ReportSummary {
     pub files_affected: usize,
     pub total_size_changed: u64,
     pub risk_level: RiskLevel,
+    /// Reasons behind `risk_level`, e.g. "12 critical deletions"
+    #[serde(default)]
+    pub risk_factors: Vec<String>,
 }
 
 /// Risk assessment levels
@@ -118,17 +168,278 @@ pub enum OutputFormat {
     Html,
     Text,
     Xml,
+    /// ArcSight Common Event Format, one event per line
+    Cef,
+    /// IBM QRadar Log Event Extended Format, one event per line
+    Leef,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "html" => Ok(OutputFormat::Html),
+            "text" | "txt" => Ok(OutputFormat::Text),
+            "xml" => Ok(OutputFormat::Xml),
+            "cef" => Ok(OutputFormat::Cef),
+            "leef" => Ok(OutputFormat::Leef),
+            _ => Err(anyhow::anyhow!(
+                "Unknown output format '{}' (expected json, csv, html, text, xml, cef or leef)", s
+            )),
+        }
+    }
 }
 
 /// Alert severity levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AlertSeverity {
+    #[default]
     Info,
     Warning,
     Error,
     Critical,
 }
 
+impl std::str::FromStr for AlertSeverity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "info" => Ok(AlertSeverity::Info),
+            "warning" => Ok(AlertSeverity::Warning),
+            "error" => Ok(AlertSeverity::Error),
+            "critical" => Ok(AlertSeverity::Critical),
+            other => Err(anyhow::anyhow!("Unknown alert severity: {}", other)),
+        }
+    }
+}
+
+/// Severity and criticality the engine assigns to each change it detects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityRules {
+    /// Severity per change type, replacing the defaults as a whole; unlisted
+    /// types are Info. Moves carry their paths, so they can't be listed and
+    /// take `moved_severity` instead.
+    #[serde(default = "default_severities")]
+    pub severities: HashMap<ChangeType, AlertSeverity>,
+    /// Severity of renames followed by real-time monitoring
+    #[serde(default = "default_moved_severity")]
+    pub moved_severity: AlertSeverity,
+    /// Paths where changes are critical, each a glob pattern such as
+    /// `**/*.sudoers` or `C:\Windows\System32\**` with the change types it
+    /// applies to; replaces the defaults (new files in executable
+    /// directories, permission changes in `/etc` and `/usr/[s]bin`) as a whole
+    #[serde(default = "default_critical_paths")]
+    pub critical_paths: Vec<CriticalPath>,
+    /// Change types that are critical at any path; replaces the defaults as
+    /// a whole
+    #[serde(default = "default_critical_change_types")]
+    pub critical_change_types: Vec<ChangeType>,
+    /// Severity overrides by path, checked in order before `severities`; the
+    /// first matching pattern decides
+    #[serde(default)]
+    pub path_severities: Vec<PathSeverity>,
+}
+
+/// Severity for every change at paths matching a glob pattern
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathSeverity {
+    pub path_pattern: String,
+    pub severity: AlertSeverity,
+}
+
+/// Changes at paths matching a glob pattern that are critical
+///
+/// Configured either as a bare pattern, covering every change type, or as a
+/// table listing the change types.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "CriticalPathConfig")]
+pub struct CriticalPath {
+    pub path_pattern: String,
+    /// Change types that are critical at matching paths (empty = all)
+    pub change_types: Vec<ChangeType>,
+}
+
+impl CriticalPath {
+    fn new(path_pattern: &str, change_types: &[ChangeType]) -> Self {
+        Self { path_pattern: path_pattern.to_string(), change_types: change_types.to_vec() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CriticalPathConfig {
+    Pattern(String),
+    Rule {
+        path_pattern: String,
+        #[serde(default)]
+        change_types: Vec<ChangeType>,
+    },
+}
+
+impl From<CriticalPathConfig> for CriticalPath {
+    fn from(config: CriticalPathConfig) -> Self {
+        match config {
+            CriticalPathConfig::Pattern(path_pattern) => Self { path_pattern, change_types: Vec::new() },
+            CriticalPathConfig::Rule { path_pattern, change_types } => Self { path_pattern, change_types },
+        }
+    }
+}
+
+fn default_moved_severity() -> AlertSeverity {
+    AlertSeverity::Warning
+}
+
+fn default_critical_paths() -> Vec<CriticalPath> {
+    vec![
+        // New executables
+        CriticalPath::new("**/bin/**", &[ChangeType::Added]),
+        CriticalPath::new("**/sbin/**", &[ChangeType::Added]),
+        // Permission changes on system files
+        CriticalPath::new("/etc/**", &[ChangeType::PermissionChanged]),
+        CriticalPath::new("/usr/bin/**", &[ChangeType::PermissionChanged]),
+        CriticalPath::new("/usr/sbin/**", &[ChangeType::PermissionChanged]),
+    ]
+}
+
+fn default_critical_change_types() -> Vec<ChangeType> {
+    vec![
+        ChangeType::Deleted,
+        ChangeType::HashChanged,
+        ChangeType::AttributeChanged,
+        ChangeType::ContentClassChanged,
+    ]
+}
+
+fn default_severities() -> HashMap<ChangeType, AlertSeverity> {
+    let mut severities = HashMap::new();
+    severities.insert(ChangeType::Deleted, AlertSeverity::Critical);
+    severities.insert(ChangeType::AttributeChanged, AlertSeverity::Critical);
+    severities.insert(ChangeType::HashChanged, AlertSeverity::Error);
+    severities.insert(ChangeType::ContentClassChanged, AlertSeverity::Error);
+    severities.insert(ChangeType::Added, AlertSeverity::Warning);
+    severities.insert(ChangeType::PermissionChanged, AlertSeverity::Warning);
+    severities.insert(ChangeType::MountChanged, AlertSeverity::Warning);
+    severities.insert(ChangeType::AccessLost, AlertSeverity::Error);
+    severities.insert(ChangeType::Modified, AlertSeverity::Info);
+    severities.insert(ChangeType::SizeChanged, AlertSeverity::Info);
+    severities.insert(ChangeType::TimestampChanged, AlertSeverity::Info);
+    severities
+}
+
+impl Default for SeverityRules {
+    fn default() -> Self {
+        Self {
+            severities: default_severities(),
+            moved_severity: default_moved_severity(),
+            critical_paths: default_critical_paths(),
+            critical_change_types: default_critical_change_types(),
+            path_severities: Vec::new(),
+        }
+    }
+}
+
+impl SeverityRules {
+    /// Severity of a change type
+    pub fn severity(&self, change_type: &ChangeType) -> AlertSeverity {
+        if let ChangeType::Moved { .. } = change_type {
+            return self.moved_severity;
+        }
+        self.severities.get(change_type).copied().unwrap_or_default()
+    }
+
+    /// Set the severity of a change type
+    pub fn set_severity(&mut self, change_type: ChangeType, severity: AlertSeverity) {
+        self.severities.insert(change_type, severity);
+    }
+
+    /// Fail on a critical path or severity override pattern that is not a
+    /// valid glob
+    pub fn validate(&self) -> Result<()> {
+        self.compile().map(drop)
+    }
+
+    /// Compile the path patterns once for classifying changes
+    pub fn compile(&self) -> Result<CompiledSeverityRules> {
+        let critical_paths = self.critical_paths
+            .iter()
+            .map(|rule| {
+                let pattern = glob::Pattern::new(&rule.path_pattern)
+                    .with_context(|| format!("Invalid critical path pattern: {}", rule.path_pattern))?;
+                Ok((pattern, rule.change_types.clone()))
+            })
+            .collect::<Result<_>>()?;
+        let path_severities = self.path_severities
+            .iter()
+            .map(|rule| {
+                let pattern = glob::Pattern::new(&rule.path_pattern)
+                    .with_context(|| format!("Invalid severity path pattern: {}", rule.path_pattern))?;
+                Ok((pattern, rule.severity))
+            })
+            .collect::<Result<_>>()?;
+        Ok(CompiledSeverityRules {
+            rules: self.clone(),
+            critical_paths,
+            path_severities,
+        })
+    }
+}
+
+/// [`SeverityRules`] with their path patterns compiled
+#[derive(Debug, Clone)]
+pub struct CompiledSeverityRules {
+    rules: SeverityRules,
+    critical_paths: Vec<(glob::Pattern, Vec<ChangeType>)>,
+    path_severities: Vec<(glob::Pattern, AlertSeverity)>,
+}
+
+impl Default for CompiledSeverityRules {
+    fn default() -> Self {
+        SeverityRules::default().compile().expect("default severity rules are valid")
+    }
+}
+
+impl CompiledSeverityRules {
+    /// The rules as configured
+    pub fn rules(&self) -> &SeverityRules {
+        &self.rules
+    }
+
+    /// Set the severity of a change type
+    pub fn set_severity(&mut self, change_type: ChangeType, severity: AlertSeverity) {
+        self.rules.set_severity(change_type, severity);
+    }
+
+    /// Severity of a change of this type at `path`: the first matching path
+    /// override, else the severity of the type
+    pub fn severity_at(&self, path: &Path, change_type: &ChangeType) -> AlertSeverity {
+        self.path_severities
+            .iter()
+            .find(|(pattern, _)| pattern.matches_path(path))
+            .map(|(_, severity)| *severity)
+            .unwrap_or_else(|| self.rules.severity(change_type))
+    }
+
+    /// Whether a change of this type at `path` is critical: its type is one
+    /// of `critical_change_types`, or `path` matches a critical path covering
+    /// the type
+    pub fn is_critical(&self, path: &Path, change_type: &ChangeType) -> bool {
+        self.rules.critical_change_types.contains(change_type)
+            || self.critical_paths.iter().any(|(pattern, change_types)| {
+                (change_types.is_empty() || change_types.contains(change_type)) && pattern.matches_path(path)
+            })
+    }
+
+    /// Set the severity and criticality of a change
+    pub fn classify(&self, change: &mut FileChange) {
+        change.severity = self.severity_at(&change.path, &change.change_type);
+        change.is_critical = self.is_critical(&change.path, &change.change_type);
+    }
+}
+
 /// Alert structure for external systems
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Alert {
@@ -139,23 +450,195 @@ pub struct Alert {
     pub timestamp: DateTime<Utc>,
     pub file_path: PathBuf,
     pub change_type: ChangeType,
+    /// Details of the change: `size`, `permissions`, `hash`, `uid`, `gid` and
+    /// `capabilities` of the new state, `changed_fields`, and `old_hash`, the
+    /// recorded hash, whenever there was a previous state. Every key appears
+    /// in JSON alerts and syslog structured data.
     pub metadata: HashMap<String, String>,
 }
 
 /// Main report generator
 pub struct ReportGenerator {
     config: ReportConfig,
+    pool: OnceLock<ThreadPool>,
+    outputs: Vec<(OutputFormat, ReportOutput)>,
+}
+
+/// Callback receiving a report and its rendered text
+pub type ReportHandler = Box<dyn Fn(&FimReport, &str) -> Result<()> + Send + Sync>;
+
+/// Where a rendered report goes, from [`ReportGenerator::generate_and_export`]
+/// or a [`DigestSink`]
+pub enum ReportOutput {
+    /// Replace a file with the report, so it always holds the latest one
+    /// (e.g. an HTML page for a dashboard); written to a temporary file and
+    /// renamed into place so readers never see a partial report
+    File(PathBuf),
+    /// Append the report to a file, e.g. one digest after another
+    Append(PathBuf),
+    /// Hand the report to a callback (e.g. to upload or mail it)
+    Handler(ReportHandler),
+}
+
+impl ReportOutput {
+    fn deliver(&self, report: &FimReport, rendered: &str) -> Result<()> {
+        use std::io::Write as _;
+
+        match self {
+            ReportOutput::File(path) => {
+                let mut temp_path = path.as_os_str().to_owned();
+                temp_path.push(".tmp");
+                let temp_path = PathBuf::from(temp_path);
+                fs::write(&temp_path, rendered)
+                    .with_context(|| format!("Failed to write report {}", temp_path.display()))?;
+                fs::rename(&temp_path, path)
+                    .with_context(|| format!("Failed to replace report {}", path.display()))?;
+                Ok(())
+            }
+            ReportOutput::Append(path) => {
+                let mut file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open report file {}", path.display()))?;
+                file.write_all(rendered.as_bytes())?;
+                if !rendered.ends_with('\n') {
+                    file.write_all(b"\n")?;
+                }
+                Ok(())
+            }
+            ReportOutput::Handler(handler) => handler(report, rendered),
+        }
+    }
+}
+
+/// Builder for a [`ReportGenerator`] with its outputs
+///
+/// Settings not given keep their [`ReportConfig`] defaults.
+#[derive(Default)]
+pub struct ReportGeneratorBuilder {
+    config: ReportConfig,
+    outputs: Vec<(OutputFormat, ReportOutput)>,
+}
+
+impl ReportGeneratorBuilder {
+    /// Start from this configuration instead of the defaults
+    pub fn config(mut self, config: ReportConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.config.title = title.into();
+        self
+    }
+
+    /// Only report changes detected at or after this time
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.config.since = Some(since);
+        self
+    }
+
+    /// Only report changes detected at or before this time
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.config.until = Some(until);
+        self
+    }
+
+    pub fn group_by_type(mut self, group_by_type: bool) -> Self {
+        self.config.group_by_type = group_by_type;
+        self
+    }
+
+    /// Render the report in `format` and deliver it to `output`
+    pub fn output(mut self, format: OutputFormat, output: ReportOutput) -> Self {
+        self.outputs.push((format, output));
+        self
+    }
+
+    /// Write the report in `format` to the file at `path`
+    pub fn file(self, format: OutputFormat, path: impl Into<PathBuf>) -> Self {
+        self.output(format, ReportOutput::File(path.into()))
+    }
+
+    pub fn build(self) -> ReportGenerator {
+        ReportGenerator {
+            outputs: self.outputs,
+            ..ReportGenerator::new(self.config)
+        }
+    }
+}
+
+impl Default for ReportGenerator {
+    /// Create with default configuration
+    fn default() -> Self {
+        Self::new(ReportConfig::default())
+    }
 }
 
 impl ReportGenerator {
     /// Create new report generator
     pub fn new(config: ReportConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            pool: OnceLock::new(),
+            outputs: Vec::new(),
+        }
     }
 
-    /// Create with default configuration
-    pub fn default() -> Self {
-        Self::new(ReportConfig::default())
+    /// Builder for a generator with outputs for [`Self::generate_and_export`]
+    pub fn builder() -> ReportGeneratorBuilder {
+        ReportGeneratorBuilder::default()
+    }
+
+    /// Generate a report and deliver it to every configured output
+    ///
+    /// Each output gets the report rendered in its format; the first failing
+    /// output stops the export.
+    pub fn generate_and_export(
+        &self,
+        changes: Vec<FileChange>,
+        scan_results: Option<ScanResults>,
+        stats: Option<FimStats>,
+    ) -> Result<()> {
+        let report = self.generate_report(changes, scan_results, stats);
+        self.export_all(&report)
+    }
+
+    /// Deliver an already generated report to every configured output
+    pub fn export_all(&self, report: &FimReport) -> Result<()> {
+        for (format, output) in &self.outputs {
+            let rendered = self.render_report(report, format.clone())?;
+            output.deliver(report, &rendered)?;
+        }
+        Ok(())
+    }
+
+    /// Report on the changes recorded in a store's change log between `since`
+    /// and `until`, instead of live changes from a scan
+    ///
+    /// The window replaces `config.since`/`config.until`; the returned
+    /// generator renders the report in any [`OutputFormat`].
+    pub fn from_db<S: FimStore + ?Sized>(
+        db: &S,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        config: ReportConfig,
+    ) -> Result<(Self, FimReport)> {
+        let generator = Self::new(ReportConfig {
+            since,
+            until,
+            ..config
+        });
+        let report = generator.report_from_store(db)?;
+        Ok((generator, report))
+    }
+
+    /// Report on the changes recorded in a store's change log within
+    /// `config.since`/`config.until`
+    pub fn report_from_store<S: FimStore + ?Sized>(&self, db: &S) -> Result<FimReport> {
+        let changes = db.get_changes(self.config.since, self.config.until)?;
+        Ok(self.generate_report(changes, None, Some(db.get_stats()?)))
     }
 
     /// Generate comprehensive FIM report
@@ -165,6 +648,10 @@ impl ReportGenerator {
         scan_results: Option<ScanResults>,
         stats: Option<FimStats>,
     ) -> FimReport {
+        let changes: Vec<FileChange> = changes
+            .into_iter()
+            .filter(|change| self.in_window(change))
+            .collect();
         let summary = self.generate_summary(&changes);
         
         let metadata = ReportMetadata {
@@ -194,6 +681,7 @@ impl ReportGenerator {
             statistics: stats,
             changes: sorted_changes,
             scan_results,
+            snapshot: None,
         }
     }
 
@@ -204,16 +692,40 @@ impl ReportGenerator {
         output_path: P,
         format: OutputFormat,
     ) -> Result<()> {
-        let content = match format {
+        let content = self.render_report(report, format)?;
+        fs::write(output_path, content)?;
+        Ok(())
+    }
+
+    /// Render report in specified format
+    pub fn render_report(&self, report: &FimReport, format: OutputFormat) -> Result<String> {
+        Ok(match format {
             OutputFormat::Json => self.export_json(report)?,
-            OutputFormat::Csv => self.export_csv(report)?,
+            OutputFormat::Csv => self.apply_encoding(self.export_csv(report)?),
             OutputFormat::Html => self.export_html(report)?,
-            OutputFormat::Text => self.export_text(report)?,
+            OutputFormat::Text => self.apply_encoding(self.export_text(report)?),
             OutputFormat::Xml => self.export_xml(report)?,
-        };
+            OutputFormat::Cef => self.export_cef(report)?,
+            OutputFormat::Leef => self.export_leef(report)?,
+        })
+    }
 
-        fs::write(output_path, content)?;
-        Ok(())
+    /// Whether a change falls within the configured `since`/`until` window
+    fn in_window(&self, change: &FileChange) -> bool {
+        self.config.since.is_none_or(|since| change.detected_at >= since)
+            && self.config.until.is_none_or(|until| change.detected_at <= until)
+    }
+
+    /// Apply configured line ending and BOM to CSV/text output
+    fn apply_encoding(&self, content: String) -> String {
+        let mut content = match self.config.line_ending {
+            LineEnding::Lf => content,
+            LineEnding::CrLf => content.replace('\n', "\r\n"),
+        };
+        if self.config.utf8_bom {
+            content.insert(0, '\u{feff}');
+        }
+        content
     }
 
     /// Generate summary statistics
@@ -221,12 +733,11 @@ impl ReportGenerator {
         let mut changes_by_type = HashMap::new();
         let mut files_affected = std::collections::HashSet::new();
         let mut total_size_changed = 0u64;
-        let mut critical_changes = 0;
+        let mut critical_by_kind: BTreeMap<&'static str, usize> = BTreeMap::new();
 
         for change in changes {
             // Count by type
-            let type_name = format!("{:?}", change.change_type);
-            *changes_by_type.entry(type_name).or_insert(0) += 1;
+            *changes_by_type.entry(change.change_type.name().to_string()).or_insert(0) += 1;
 
             // Track affected files
             files_affected.insert(&change.path);
@@ -237,12 +748,13 @@ impl ReportGenerator {
             }
 
             // Count critical changes
-            if self.is_critical_change(change) {
-                critical_changes += 1;
+            if change.is_critical {
+                *critical_by_kind.entry(critical_kind(&change.change_type)).or_insert(0) += 1;
             }
         }
 
-        let risk_level = self.assess_risk_level(changes, critical_changes);
+        let critical_changes = critical_by_kind.values().sum();
+        let (risk_level, risk_factors) = self.assess_risk_level(changes, &critical_by_kind);
 
         ReportSummary {
             total_changes: changes.len(),
@@ -251,64 +763,66 @@ impl ReportGenerator {
             files_affected: files_affected.len(),
             total_size_changed,
             risk_level,
+            risk_factors,
         }
     }
 
-    /// Determine if a change is critical
-    fn is_critical_change(&self, change: &FileChange) -> bool {
-        match change.change_type {
-            ChangeType::Deleted => true,
-            ChangeType::Added => {
-                // New executable files might be critical
-                if let Some(path_str) = change.path.to_str() {
-                    path_str.contains("/bin/") || path_str.contains("/sbin/")
-                } else {
-                    false
-                }
-            }
-            ChangeType::HashChanged => true,
-            ChangeType::PermissionChanged => {
-                // Permission changes on system files are critical
-                if let Some(path_str) = change.path.to_str() {
-                    path_str.starts_with("/etc/") || 
-                    path_str.starts_with("/usr/bin/") ||
-                    path_str.starts_with("/usr/sbin/")
-                } else {
-                    false
+    /// Assess overall risk level, with the factors that explain it
+    fn assess_risk_level(
+        &self,
+        changes: &[FileChange],
+        critical_by_kind: &BTreeMap<&'static str, usize>,
+    ) -> (RiskLevel, Vec<String>) {
+        let total_changes = changes.len();
+        let critical_changes: usize = critical_by_kind.values().sum();
+
+        let mut factors: Vec<String> = critical_by_kind
+            .iter()
+            .map(|(kind, count)| {
+                let plural = if *count == 1 { "" } else { "s" };
+                format!("{} critical {}{}", count, kind, plural)
+            })
+            .collect();
+        let mut escalations = 0;
+        for change in changes {
+            let Some(new_entry) = &change.new_entry else { continue };
+            let old_bits = change.old_entry.as_ref().map_or(0, |old| special_bits(&old.perm));
+            let added = special_bits(&new_entry.perm) & !old_bits;
+            for (bit, name) in [(0o4000, "setuid"), (0o2000, "setgid")] {
+                if added & bit != 0 {
+                    escalations += 1;
+                    factors.push(format!("{} bit added on {}", name, change.path.display()));
                 }
             }
-            _ => false,
         }
-    }
-
-    /// Assess overall risk level
-    fn assess_risk_level(&self, changes: &[FileChange], critical_changes: usize) -> RiskLevel {
-        let total_changes = changes.len();
+        if let Some(threshold) = [1000, 100, 10].into_iter().find(|t| total_changes > *t) {
+            factors.push(format!(">{} total changes", threshold));
+        }
         
-        if critical_changes > 10 || total_changes > 1000 {
+        let level = if critical_changes > 10 || total_changes > 1000 {
             RiskLevel::Critical
-        } else if critical_changes > 5 || total_changes > 100 {
+        } else if critical_changes > 5 || total_changes > 100 || escalations > 0 {
             RiskLevel::High
         } else if critical_changes > 0 || total_changes > 10 {
             RiskLevel::Medium
         } else {
             RiskLevel::Low
-        }
+        };
+
+        (level, factors)
     }
 
     /// Sort changes according to configuration
     fn sort_changes(&self, changes: &mut [FileChange]) {
         match self.config.sort_by {
             SortOrder::Timestamp => {
-                changes.sort_by(|a, b| b.detected_at.cmp(&a.detected_at));
+                changes.sort_by_key(|c| std::cmp::Reverse(c.detected_at));
             }
             SortOrder::Path => {
                 changes.sort_by(|a, b| a.path.cmp(&b.path));
             }
             SortOrder::ChangeType => {
-                changes.sort_by(|a, b| {
-                    format!("{:?}", a.change_type).cmp(&format!("{:?}", b.change_type))
-                });
+                changes.sort_by_key(|c| c.change_type.name());
             }
             SortOrder::Size => {
                 changes.sort_by(|a, b| {
@@ -327,35 +841,49 @@ impl ReportGenerator {
 
     /// Export to CSV format
     fn export_csv(&self, report: &FimReport) -> Result<String> {
-        let mut output = String::new();
-        
-        // CSV header
-        output.push_str("timestamp,path,change_type,size,permissions,hash\n");
-        
+        type HashColumn = (&'static str, fn(&FimEntryData) -> Option<&String>);
+        const OPTIONAL_HASHES: [HashColumn; 3] = [
+            ("sha512", |e| e.sha512.as_ref()),
+            ("sha3_256", |e| e.sha3_256.as_ref()),
+            ("xxh3", |e| e.xxh3.as_ref()),
+        ];
+        // Optional hash columns appear only when some change carries them
+        let hash_columns: Vec<HashColumn> = OPTIONAL_HASHES
+            .into_iter()
+            .filter(|(_, get)| {
+                report.changes.iter().any(|change| change.new_entry.as_ref().and_then(get).is_some())
+            })
+            .collect();
+
         // CSV data
-        for change in &report.changes {
-            let size = change.new_entry.as_ref()
-                .map(|e| e.size.to_string())
-                .unwrap_or_else(|| "".to_string());
-            
-            let permissions = change.new_entry.as_ref()
-                .map(|e| e.perm.clone())
-                .unwrap_or_else(|| "".to_string());
-            
-            let hash = change.new_entry.as_ref()
-                .map(|e| e.blake3.clone())
-                .unwrap_or_else(|| "".to_string());
-            
-            output.push_str(&format!(
-                "{},{},{:?},{},{},{}\n",
+        let rows = self.render_rows(&report.changes, 160, |out, change| {
+            let entry = change.new_entry.as_ref();
+            write!(
+                out,
+                "{},{},{},{},{},{}",
                 change.detected_at.format("%Y-%m-%d %H:%M:%S UTC"),
                 change.path.display(),
-                change.change_type,
-                size,
-                permissions,
-                hash
-            ));
+                change.change_type.name(),
+                entry.map(|e| e.size.to_string()).unwrap_or_default(),
+                entry.map(|e| e.perm.as_str()).unwrap_or_default(),
+                entry.map(|e| e.blake3.as_str()).unwrap_or_default()
+            )?;
+            for (_, get) in &hash_columns {
+                write!(out, ",{}", entry.and_then(get).map(String::as_str).unwrap_or_default())?;
+            }
+            writeln!(out)
+        })?;
+
+        // CSV header
+        let mut header = "timestamp,path,change_type,size,permissions,hash".to_string();
+        for (name, _) in &hash_columns {
+            header.push(',');
+            header.push_str(name);
         }
+        header.push('\n');
+        let mut output = String::with_capacity(header.len() + rows.len());
+        output.push_str(&header);
+        output.push_str(&rows);
         
         Ok(output)
     }
@@ -412,7 +940,6 @@ impl ReportGenerator {
         <p><strong>Critical Changes:</strong> {}</p>
         <p><strong>Files Affected:</strong> {}</p>
         <p><strong>Risk Level:</strong> <span class="risk-level risk-{}">{:?}</span></p>
-    </div>
         "#, 
         report.summary.total_changes,
         report.summary.critical_changes,
@@ -420,6 +947,14 @@ impl ReportGenerator {
         format!("{:?}", report.summary.risk_level).to_lowercase(),
         report.summary.risk_level
     ));
+        if !report.summary.risk_factors.is_empty() {
+            html.push_str("<ul class=\"risk-factors\">");
+            for factor in &report.summary.risk_factors {
+                html.push_str(&format!("<li>{}</li>", escape_markup(factor)));
+            }
+            html.push_str("</ul>");
+        }
+        html.push_str("</div>");
 
         // Changes table
         if !report.changes.is_empty() {
@@ -438,7 +973,7 @@ impl ReportGenerator {
         <tbody>
             "#);
 
-            for change in &report.changes {
+            let rows = self.render_rows(&report.changes, 320, |out, change| {
                 let row_class = match change.change_type {
                     ChangeType::Added => "change-added",
                     ChangeType::Modified | ChangeType::HashChanged => "change-modified",
@@ -451,25 +986,28 @@ impl ReportGenerator {
                     .unwrap_or_else(|| "-".to_string());
 
                 let permissions = change.new_entry.as_ref()
-                    .map(|e| e.perm.clone())
-                    .unwrap_or_else(|| "-".to_string());
+                    .map(|e| e.perm.as_str())
+                    .unwrap_or("-");
 
-                html.push_str(&format!(
+                write!(
+                    out,
                     r#"<tr class="{}">
                         <td class="timestamp">{}</td>
                         <td>{}</td>
-                        <td>{:?}</td>
+                        <td>{}</td>
                         <td>{}</td>
                         <td>{}</td>
                     </tr>"#,
                     row_class,
-                    change.detected_at.format("%Y-%m-%d %H:%M:%S"),
+                    self.format_change_time(change, report),
                     change.path.display(),
-                    change.change_type,
+                    change.change_type.name(),
                     size,
                     permissions
-                ));
-            }
+                )
+            })?;
+            html.reserve(rows.len() + 64);
+            html.push_str(&rows);
 
             html.push_str("</tbody></table>");
         }
@@ -491,7 +1029,11 @@ impl ReportGenerator {
         output.push_str(&format!("Total Changes: {}\n", report.summary.total_changes));
         output.push_str(&format!("Critical Changes: {}\n", report.summary.critical_changes));
         output.push_str(&format!("Files Affected: {}\n", report.summary.files_affected));
-        output.push_str(&format!("Risk Level: {:?}\n\n", report.summary.risk_level));
+        output.push_str(&format!("Risk Level: {:?}\n", report.summary.risk_level));
+        for factor in &report.summary.risk_factors {
+            output.push_str(&format!("  - {}\n", factor));
+        }
+        output.push('\n');
 
         // Changes by type
         if !report.summary.changes_by_type.is_empty() {
@@ -505,19 +1047,89 @@ impl ReportGenerator {
         // Detailed changes
         if !report.changes.is_empty() {
             output.push_str("DETAILED CHANGES\n");
-            for change in &report.changes {
-                output.push_str(&format!(
-                    "[{}] {:?}: {}\n",
-                    change.detected_at.format("%Y-%m-%d %H:%M:%S"),
-                    change.change_type,
+            let rows = self.render_rows(&report.changes, 96, |out, change| {
+                write!(
+                    out,
+                    "[{}] {}: {}",
+                    self.format_change_time(change, report),
+                    change.change_type.name(),
                     change.path.display()
-                ));
-            }
+                )?;
+                let field_changes = change.field_changes();
+                if !field_changes.is_empty() {
+                    let fields: Vec<_> = field_changes.iter().map(|c| c.to_string()).collect();
+                    write!(out, " ({})", fields.join(", "))?;
+                }
+                writeln!(out)
+            })?;
+            output.push_str(&rows);
         }
 
         Ok(output)
     }
 
+    /// Render one output row per change, in parallel for large reports
+    ///
+    /// Parallel rendering fills per-chunk buffers that are joined in order, so
+    /// the output is identical to sequential rendering. `row_estimate` is the
+    /// expected bytes per row, used to pre-size buffers.
+    fn render_rows<F>(&self, changes: &[FileChange], row_estimate: usize, render: F) -> Result<String>
+    where
+        F: Fn(&mut String, &FileChange) -> std::fmt::Result + Sync,
+    {
+        let parallel = self.config.parallel_render_threshold
+            .is_some_and(|threshold| changes.len() >= threshold);
+
+        if !parallel {
+            let mut out = String::with_capacity(changes.len() * row_estimate);
+            for change in changes {
+                render(&mut out, change)?;
+            }
+            return Ok(out);
+        }
+
+        let pool = self.thread_pool();
+        let chunk_size = changes.len().div_ceil(pool.current_num_threads() * 4).max(1);
+        let chunks = pool.install(|| {
+            changes
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    let mut out = String::with_capacity(chunk.len() * row_estimate);
+                    for change in chunk {
+                        render(&mut out, change)?;
+                    }
+                    Ok(out)
+                })
+                .collect::<std::result::Result<Vec<String>, std::fmt::Error>>()
+        })?;
+
+        let mut out = String::with_capacity(chunks.iter().map(String::len).sum());
+        for chunk in &chunks {
+            out.push_str(chunk);
+        }
+        Ok(out)
+    }
+
+    /// Dedicated pool for parallel rendering, created on first use
+    fn thread_pool(&self) -> &ThreadPool {
+        self.pool.get_or_init(|| {
+            ThreadPoolBuilder::new()
+                .num_threads(num_cpus::get())
+                .build()
+                .expect("Failed to build report rendering thread pool")
+        })
+    }
+
+    /// Format a change time, with its age at report generation if configured
+    fn format_change_time(&self, change: &FileChange, report: &FimReport) -> String {
+        let absolute = change.detected_at.format("%Y-%m-%d %H:%M:%S");
+        if self.config.relative_timestamps {
+            format!("{} ({})", absolute, relative_time(change.detected_at, report.metadata.generated_at))
+        } else {
+            absolute.to_string()
+        }
+    }
+
     /// Export to XML format
     fn export_xml(&self, report: &FimReport) -> Result<String> {
         let mut xml = String::new();
@@ -544,13 +1156,17 @@ impl ReportGenerator {
     <critical_changes>{}</critical_changes>
     <files_affected>{}</files_affected>
     <risk_level>{:?}</risk_level>
-  </summary>
 "#,
         report.summary.total_changes,
         report.summary.critical_changes,
         report.summary.files_affected,
         report.summary.risk_level
     ));
+        xml.push_str("    <risk_factors>\n");
+        for factor in &report.summary.risk_factors {
+            xml.push_str(&format!("      <factor>{}</factor>\n", escape_markup(factor)));
+        }
+        xml.push_str("    </risk_factors>\n  </summary>\n");
 
         // Changes
         xml.push_str("  <changes>\n");
@@ -558,12 +1174,12 @@ impl ReportGenerator {
             xml.push_str(&format!(r#"    <change>
       <timestamp>{}</timestamp>
       <path>{}</path>
-      <type>{:?}</type>
+      <type>{}</type>
     </change>
 "#,
             change.detected_at.to_rfc3339(),
             change.path.display(),
-            change.change_type
+            change.change_type.name()
         ));
         }
         xml.push_str("  </changes>\n");
@@ -571,35 +1187,259 @@ impl ReportGenerator {
         xml.push_str("</fim_report>\n");
         Ok(xml)
     }
+
+    /// Export changes as CEF event lines
+    pub fn export_cef(&self, report: &FimReport) -> Result<String> {
+        self.render_rows(&report.changes, 256, |out, change| {
+            writeln!(out, "{}", SiemEvent::from_change(change).to_cef())
+        })
+    }
+
+    /// Export changes as LEEF event lines
+    pub fn export_leef(&self, report: &FimReport) -> Result<String> {
+        self.render_rows(&report.changes, 256, |out, change| {
+            writeln!(out, "{}", SiemEvent::from_change(change).to_leef())
+        })
+    }
+}
+
+/// Describe `then` relative to `now`, e.g. "5 minutes ago"
+fn relative_time(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = now.signed_duration_since(then);
+    let seconds = delta.num_seconds().abs();
+    if seconds < 1 {
+        return "just now".to_string();
+    }
+
+    let (amount, unit) = if seconds < 60 {
+        (seconds, "second")
+    } else if seconds < 3600 {
+        (seconds / 60, "minute")
+    } else if seconds < 86400 {
+        (seconds / 3600, "hour")
+    } else {
+        (seconds / 86400, "day")
+    };
+
+    let plural = if amount == 1 { "" } else { "s" };
+    if delta.num_seconds() >= 0 {
+        format!("{} {}{} ago", amount, unit, plural)
+    } else {
+        format!("in {} {}{}", amount, unit, plural)
+    }
+}
+
+/// Vendor and product in CEF and LEEF headers
+const SIEM_VENDOR: &str = "rusty-fim";
+const SIEM_PRODUCT: &str = "fim";
+
+/// A change as CEF and LEEF encode it
+struct SiemEvent<'a> {
+    timestamp: DateTime<Utc>,
+    severity: AlertSeverity,
+    change_type: &'a ChangeType,
+    name: String,
+    path: &'a Path,
+    size: Option<String>,
+    hash: Option<String>,
+    uid: Option<String>,
+    gid: Option<String>,
+    message: Option<&'a str>,
+}
+
+impl<'a> SiemEvent<'a> {
+    fn from_change(change: &'a FileChange) -> Self {
+        // Deletions only have the baseline entry
+        let entry = change.new_entry.as_ref().or(change.old_entry.as_ref());
+        Self {
+            timestamp: change.detected_at,
+            severity: change.severity,
+            change_type: &change.change_type,
+            name: format!("File {}", change.change_type.name()),
+            path: &change.path,
+            size: entry.map(|e| e.size.to_string()),
+            hash: entry.map(|e| e.blake3.clone()).filter(|hash| !hash.is_empty()),
+            uid: entry.map(|e| e.uid.to_string()),
+            gid: entry.map(|e| e.gid.to_string()),
+            message: None,
+        }
+    }
+
+    fn from_alert(alert: &'a Alert) -> Self {
+        Self {
+            timestamp: alert.timestamp,
+            severity: alert.severity,
+            change_type: &alert.change_type,
+            name: format!("File {}", alert.change_type.name()),
+            path: &alert.file_path,
+            size: alert.metadata.get("size").cloned(),
+            hash: alert.metadata.get("hash").cloned(),
+            uid: alert.metadata.get("uid").cloned(),
+            gid: alert.metadata.get("gid").cloned(),
+            message: Some(&alert.message),
+        }
+    }
+
+    /// Severity on the 0-10 scale both formats use
+    fn level(&self) -> u8 {
+        match self.severity {
+            AlertSeverity::Info => 3,
+            AlertSeverity::Warning => 5,
+            AlertSeverity::Error => 8,
+            AlertSeverity::Critical => 10,
+        }
+    }
+
+    /// Key-value fields, named with CEF keys where one exists
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("act", self.change_type.name().to_string()),
+            ("filePath", self.path.display().to_string()),
+        ];
+        if let Some(name) = self.path.file_name() {
+            fields.push(("fname", name.to_string_lossy().into_owned()));
+        }
+        if let ChangeType::Moved { from, .. } = self.change_type {
+            fields.push(("oldFilePath", from.display().to_string()));
+        }
+        fields.extend(
+            [("fsize", &self.size), ("fileHash", &self.hash), ("uid", &self.uid), ("gid", &self.gid)]
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value.clone()?))),
+        );
+        if let Some(message) = self.message {
+            fields.push(("msg", message.to_string()));
+        }
+        fields
+    }
+
+    /// `CEF:0|vendor|product|version|signature|name|severity|extensions`
+    fn to_cef(&self) -> String {
+        fn header(value: &str) -> String {
+            value.replace('\\', "\\\\").replace('|', "\\|")
+        }
+        fn extension(value: &str) -> String {
+            value
+                .replace('\\', "\\\\")
+                .replace('=', "\\=")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        }
+
+        let mut line = format!(
+            "CEF:0|{}|{}|{}|{}|{}|{}|rt={}",
+            SIEM_VENDOR,
+            SIEM_PRODUCT,
+            crate::VERSION,
+            header(self.change_type.name()),
+            header(&self.name),
+            self.level(),
+            self.timestamp.timestamp_millis()
+        );
+        for (key, value) in self.fields() {
+            // CEF has no uid/gid keys; use the labelled custom numbers
+            let (key, label) = match key {
+                "uid" => ("cn1", Some("cn1Label=uid")),
+                "gid" => ("cn2", Some("cn2Label=gid")),
+                key => (key, None),
+            };
+            let _ = write!(line, " {}={}", key, extension(&value));
+            if let Some(label) = label {
+                line.push(' ');
+                line.push_str(label);
+            }
+        }
+        line
+    }
+
+    /// `LEEF:1.0|vendor|product|version|event id|` then tab-separated attributes
+    fn to_leef(&self) -> String {
+        fn header(value: &str) -> String {
+            value.replace('\\', "\\\\").replace('|', "\\|")
+        }
+        fn attribute(value: &str) -> String {
+            value
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        }
+
+        let mut line = format!(
+            "LEEF:1.0|{}|{}|{}|{}|devTime={}\tdevTimeFormat=yyyy-MM-dd'T'HH:mm:ss.SSSZ\tsev={}\tcat={}",
+            SIEM_VENDOR,
+            SIEM_PRODUCT,
+            crate::VERSION,
+            header(self.change_type.name()),
+            self.timestamp.format("%Y-%m-%dT%H:%M:%S%.3f%z"),
+            self.level(),
+            attribute(self.change_type.name())
+        );
+        for (key, value) in self.fields() {
+            let _ = write!(line, "\t{}={}", key, attribute(&value));
+        }
+        line
+    }
 }
 
 /// Alert generator for external system integration
+///
+/// Alerts take the severity the engine assigned to each change (see
+/// [`SeverityRules`]) unless the generator has rules of its own.
 pub struct AlertGenerator {
-    severity_rules: HashMap<ChangeType, AlertSeverity>,
+    syslog: Option<SyslogConfig>,
+    /// Rules reclassifying changes, replacing the engine's severity
+    rules: Option<CompiledSeverityRules>,
+    /// Open syslog connection, made on the first alert and after a failed send
+    syslog_logger: Mutex<Option<SyslogLogger>>,
+}
+
+impl Default for AlertGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AlertGenerator {
-    /// Create new alert generator with default severity rules
+    /// Create new alert generator that only logs alerts
+    ///
+    /// Use [`AlertGenerator::with_syslog`] to also send them to syslog.
     pub fn new() -> Self {
-        let mut severity_rules = HashMap::new();
-        severity_rules.insert(ChangeType::Deleted, AlertSeverity::Critical);
-        severity_rules.insert(ChangeType::HashChanged, AlertSeverity::Error);
-        severity_rules.insert(ChangeType::Added, AlertSeverity::Warning);
-        severity_rules.insert(ChangeType::PermissionChanged, AlertSeverity::Warning);
-        severity_rules.insert(ChangeType::Modified, AlertSeverity::Info);
-        severity_rules.insert(ChangeType::SizeChanged, AlertSeverity::Info);
-        severity_rules.insert(ChangeType::TimestampChanged, AlertSeverity::Info);
+        Self::with_syslog(None)
+    }
+
+    /// Create an alert generator with a syslog destination (None = no syslog)
+    pub fn with_syslog(syslog: Option<SyslogConfig>) -> Self {
+        Self {
+            syslog,
+            rules: None,
+            syslog_logger: Mutex::new(None),
+        }
+    }
+
+    /// Derive alert severities from `rules` instead of the severity the
+    /// engine assigned
+    pub fn with_rules(mut self, rules: CompiledSeverityRules) -> Self {
+        self.rules = Some(rules);
+        self
+    }
 
-        Self { severity_rules }
+    /// Set the alert severity of a change type, starting from the default
+    /// rules if the generator has none
+    pub fn set_severity(&mut self, change_type: ChangeType, severity: AlertSeverity) {
+        self.rules
+            .get_or_insert_with(CompiledSeverityRules::default)
+            .set_severity(change_type, severity);
     }
 
     /// Generate alert from file change
     pub fn generate_alert(&self, change: &FileChange) -> Alert {
-        let severity = self.severity_rules.get(&change.change_type)
-            .cloned()
-            .unwrap_or(AlertSeverity::Info);
+        let severity = match self.rules {
+            Some(ref rules) => rules.severity_at(&change.path, &change.change_type),
+            None => change.severity,
+        };
 
-        let title = format!("File {:?}: {}", change.change_type, change.path.display());
+        let title = format!("File {}: {}", change.change_type.name(), change.path.display());
         let message = self.format_alert_message(change);
 
         let mut metadata = HashMap::new();
@@ -607,13 +1447,33 @@ impl AlertGenerator {
             metadata.insert("size".to_string(), new_entry.size.to_string());
             metadata.insert("permissions".to_string(), new_entry.perm.clone());
             metadata.insert("hash".to_string(), new_entry.blake3.clone());
+            metadata.insert("uid".to_string(), new_entry.uid.to_string());
+            metadata.insert("gid".to_string(), new_entry.gid.to_string());
+            if let Some(ref capabilities) = new_entry.capabilities {
+                metadata.insert("capabilities".to_string(), capabilities.clone());
+            }
+        }
+        if let Some(ref old_entry) = change.old_entry {
+            metadata.insert("old_hash".to_string(), old_entry.blake3.clone());
+        }
+        let field_changes = change.field_changes();
+        if !field_changes.is_empty() {
+            let fields: Vec<_> = field_changes.iter().map(|c| c.field.as_str()).collect();
+            metadata.insert("changed_fields".to_string(), fields.join(","));
         }
 
-        Alert {
-            id: format!("fim_{}_{}", 
+        // Changes from before correlation ids get the legacy time/path id
+        let id = if change.change_id.is_empty() {
+            format!("fim_{}_{}",
                 change.detected_at.timestamp(),
-                blake3::hash(change.path.to_string_lossy().as_bytes()).to_hex().to_string()[..8].to_string()
-            ),
+                &blake3::hash(change.path.to_string_lossy().as_bytes()).to_hex()[..8]
+            )
+        } else {
+            change.change_id.clone()
+        };
+
+        Alert {
+            id,
             severity,
             title,
             message,
@@ -624,8 +1484,44 @@ impl AlertGenerator {
         }
     }
 
-    /// Format detailed alert message
-    fn format_alert_message(&self, change: &FileChange) -> String {
+    /// Generate a critical alert for a failed database integrity check
+    ///
+    /// Returns `None` when the check passed.
+    pub fn generate_integrity_alert(&self, report: &DbIntegrityReport, db_path: &Path) -> Option<Alert> {
+        if report.is_ok() {
+            return None;
+        }
+
+        let mut problems = report.sqlite_errors.clone();
+        if !report.checksum_matches() {
+            problems.push(format!(
+                "running checksum mismatch (stored {}, computed {})",
+                report.stored_checksum.as_deref().unwrap_or("none"),
+                report.computed_checksum
+            ));
+        }
+
+        let mut metadata = HashMap::new();
+        metadata.insert("computed_checksum".to_string(), report.computed_checksum.clone());
+        if let Some(ref stored) = report.stored_checksum {
+            metadata.insert("stored_checksum".to_string(), stored.clone());
+        }
+
+        let timestamp = Utc::now();
+        Some(Alert {
+            id: format!("fim_db_{}", timestamp.timestamp()),
+            severity: AlertSeverity::Critical,
+            title: format!("FIM database tampering suspected: {}", db_path.display()),
+            message: format!("Database integrity check failed: {}", problems.join("; ")),
+            timestamp,
+            file_path: db_path.to_path_buf(),
+            change_type: ChangeType::Modified,
+            metadata,
+        })
+    }
+
+    /// Format detailed alert message
+    fn format_alert_message(&self, change: &FileChange) -> String {
         match change.change_type {
             ChangeType::Added => {
                 format!("New file created: {}", change.path.display())
@@ -637,14 +1533,28 @@ impl AlertGenerator {
                 format!("File content modified: {}", change.path.display())
             }
             ChangeType::PermissionChanged => {
-                let old_perm = change.old_entry.as_ref()
-                    .map(|e| e.perm.as_str())
-                    .unwrap_or("unknown");
-                let new_perm = change.new_entry.as_ref()
-                    .map(|e| e.perm.as_str())
-                    .unwrap_or("unknown");
-                format!("Permissions changed: {} ({} -> {})", 
-                    change.path.display(), old_perm, new_perm)
+                format!("Permissions changed: {} ({})",
+                    change.path.display(),
+                    describe_fields(change, &["perm", "uid", "gid", "owner_sid", "dacl"]))
+            }
+            ChangeType::AttributeChanged => {
+                format!("File attributes changed: {} ({})",
+                    change.path.display(),
+                    describe_fields(change, &["capabilities", "xattr_hash"]))
+            }
+            ChangeType::ContentClassChanged => {
+                format!("File content type changed: {} ({})",
+                    change.path.display(),
+                    describe_fields(change, &["content_type"]))
+            }
+            ChangeType::MountChanged => {
+                format!("Filesystem unmounted or replaced: {}", change.path.display())
+            }
+            ChangeType::AccessLost => {
+                format!("File no longer readable by the monitor: {}", change.path.display())
+            }
+            ChangeType::Moved { ref from, ref to } => {
+                format!("File moved: {} -> {}", from.display(), to.display())
             }
             _ => {
                 format!("File modified: {}", change.path.display())
@@ -652,63 +1562,828 @@ impl AlertGenerator {
         }
     }
 
-    /// Send alert to external system (placeholder implementation)
+    /// Render `alert` as a CEF event line
+    pub fn to_cef(&self, alert: &Alert) -> String {
+        SiemEvent::from_alert(alert).to_cef()
+    }
+
+    /// Render `alert` as a LEEF event line
+    pub fn to_leef(&self, alert: &Alert) -> String {
+        SiemEvent::from_alert(alert).to_leef()
+    }
+
+    /// Log an alert and send it to the configured syslog destination
+    ///
+    /// HTTP endpoints are served by [`AlertGenerator::send_alert_webhook`].
     pub fn send_alert(&self, alert: &Alert) -> Result<()> {
-        // This would integrate with external alerting systems like:
-        // - Syslog
-        // - SIEM systems
-        // - Slack/Teams webhooks
-        // - Email notifications
-        // - HTTP endpoints
-        
         info!("Alert generated: {:?} - {}", alert.severity, alert.title);
         debug!("Alert details: {:?}", alert);
-        
-        // Example: Send to syslog
-        #[cfg(unix)]
-        {
-            use std::process::Command;
-            let _ = Command::new("logger")
-                .arg("-t")
-                .arg("fim")
-                .arg(&format!("{:?}: {}", alert.severity, alert.message))
-                .output();
+
+        match &self.syslog {
+            Some(config) => self.send_syslog(alert, config),
+            None => Ok(()),
         }
-        
+    }
+
+    /// Send `alert` as an RFC 5424 message carrying its path, change type
+    /// and metadata as structured data
+    fn send_syslog(&self, alert: &Alert, config: &SyslogConfig) -> Result<()> {
+        let mut guard = self.syslog_logger.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(config.connect()?);
+        }
+        let logger = guard.as_mut().expect("syslog connected above");
+
+        let mut params: BTreeMap<String, String> = alert.metadata
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        params.insert("id".to_string(), alert.id.clone());
+        params.insert("path".to_string(), alert.file_path.display().to_string());
+        params.insert("change_type".to_string(), alert.change_type.name().to_string());
+        let data = BTreeMap::from([(SYSLOG_SD_ID.to_string(), params)]);
+
+        let message = (0, data, &alert.message);
+        let sent = match alert.severity {
+            AlertSeverity::Critical => logger.crit(message),
+            AlertSeverity::Error => logger.err(message),
+            AlertSeverity::Warning => logger.warning(message),
+            AlertSeverity::Info => logger.info(message),
+        };
+        if sent.is_err() {
+            // Reconnect on the next alert, e.g. after the daemon restarted
+            *guard = None;
+        }
+        sent.with_context(|| format!("Failed to send alert {} to syslog", alert.id))
+    }
+
+    /// POST `alert` to an HTTP webhook
+    ///
+    /// Timeouts, connection failures, 429 and 5xx responses are retried per
+    /// `cfg.retry`; any other failure, or the last retry's, is returned. The
+    /// client is blocking, so call this from a worker thread rather than
+    /// directly inside an async task.
+    pub fn send_alert_webhook(&self, alert: &Alert, cfg: &WebhookConfig) -> Result<()> {
+        let body = match cfg.format {
+            WebhookFormat::Json => serde_json::to_value(alert)?,
+            WebhookFormat::Slack => serde_json::json!({
+                "text": format!("[{:?}] {}\n{}", alert.severity, alert.title, alert.message),
+            }),
+        };
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(cfg.timeout_secs))
+            .build()
+            .context("Failed to build webhook client")?;
+
+        cfg.retry
+            .run_while(is_retryable_webhook_error, || {
+                let mut request = client.post(&cfg.url).json(&body);
+                for (name, value) in &cfg.headers {
+                    request = request.header(name, value);
+                }
+                request.send()?.error_for_status()?;
+                Ok(())
+            })
+            .with_context(|| format!("Failed to deliver alert {} to {}", alert.id, cfg.url))?;
+
+        debug!("Alert {} delivered to {}", alert.id, cfg.url);
+        Ok(())
+    }
+
+    /// Email `alert` through an SMTP server
+    ///
+    /// The subject carries the severity and change summary, the body the
+    /// path, old and new hash and details. Like webhooks, delivery blocks.
+    pub fn send_email(&self, alert: &Alert, cfg: &EmailConfig) -> Result<()> {
+        let subject = format!("[FIM {:?}] {}", alert.severity, alert.title);
+        cfg.send(subject, email_section(alert))
+            .with_context(|| format!("Failed to email alert {} via {}", alert.id, cfg.smtp_host))?;
+        debug!("Alert {} emailed via {}", alert.id, cfg.smtp_host);
+        Ok(())
+    }
+
+    /// Email `alerts` as one digest message, most severe first, rather than
+    /// one message each; nothing is sent for an empty batch
+    pub fn send_email_digest(&self, alerts: &[Alert], cfg: &EmailConfig) -> Result<()> {
+        let Some(highest) = alerts.iter().map(|alert| alert.severity).max() else {
+            return Ok(());
+        };
+        let subject = format!("[FIM {:?}] {} file integrity alerts", highest, alerts.len());
+
+        let mut sorted: Vec<&Alert> = alerts.iter().collect();
+        sorted.sort_by_key(|alert| std::cmp::Reverse(alert.severity));
+        let mut body = String::new();
+        for severity in [AlertSeverity::Critical, AlertSeverity::Error, AlertSeverity::Warning, AlertSeverity::Info] {
+            let count = alerts.iter().filter(|alert| alert.severity == severity).count();
+            if count > 0 {
+                let _ = writeln!(body, "{:?}: {}", severity, count);
+            }
+        }
+        for alert in sorted {
+            let _ = write!(body, "\n{}\n{}", alert.title, email_section(alert));
+        }
+
+        cfg.send(subject, body)
+            .with_context(|| format!("Failed to email digest of {} alerts via {}", alerts.len(), cfg.smtp_host))?;
+        debug!("Digest of {} alerts emailed via {}", alerts.len(), cfg.smtp_host);
+        Ok(())
+    }
+}
+
+/// Plain text details of one alert for an email body
+fn email_section(alert: &Alert) -> String {
+    let hash = |key: &str| alert.metadata.get(key).map(String::as_str).unwrap_or("-").to_string();
+    format!(
+        "Path: {}\nChange: {}\nSeverity: {:?}\nDetected: {}\nOld hash: {}\nNew hash: {}\n{}\n",
+        alert.file_path.display(),
+        alert.change_type.name(),
+        alert.severity,
+        alert.timestamp.to_rfc3339(),
+        hash("old_hash"),
+        hash("hash"),
+        alert.message
+    )
+}
+
+/// Whether a failed webhook delivery may succeed when retried
+fn is_retryable_webhook_error(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+        e.is_timeout()
+            || e.is_connect()
+            || e.status().is_some_and(|status| {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            })
+    })
+}
+
+/// RFC 5424 structured data id for alert fields (32473 is the enterprise
+/// number reserved for documentation and examples)
+const SYSLOG_SD_ID: &str = "fim@32473";
+
+type SyslogLogger = syslog::Logger<Box<dyn Write + Send>, syslog::Formatter5424>;
+
+/// Syslog destination for [`AlertGenerator::send_alert`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogConfig {
+    /// Facility name, e.g. `user`, `auth` or `local0`
+    #[serde(default = "default_syslog_facility")]
+    pub facility: String,
+    #[serde(default)]
+    pub transport: SyslogTransport,
+    /// Application name in each message
+    #[serde(default = "default_syslog_process")]
+    pub process: String,
+    /// Hostname in each message (None = this machine's)
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+fn default_syslog_facility() -> String {
+    "user".to_string()
+}
+
+fn default_syslog_process() -> String {
+    "fim".to_string()
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            facility: default_syslog_facility(),
+            transport: SyslogTransport::default(),
+            process: default_syslog_process(),
+            hostname: None,
+        }
+    }
+}
+
+/// How syslog messages reach the collector
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyslogTransport {
+    /// The local daemon's socket (`/dev/log` or `/var/run/syslog`)
+    #[default]
+    Local,
+    /// A remote collector over UDP, e.g. `logs.example.com:514`
+    Udp { server: String },
+    /// A remote collector over TCP, with octet-counted framing (RFC 6587)
+    Tcp { server: String },
+}
+
+impl SyslogConfig {
+    fn connect(&self) -> Result<SyslogLogger> {
+        let facility = self.facility.parse::<syslog::Facility>()
+            .map_err(|_| anyhow::anyhow!("Unknown syslog facility: {}", self.facility))?;
+        let formatter = syslog::Formatter5424 {
+            facility,
+            hostname: self.hostname.clone().or_else(|| syslog::Formatter5424::default().hostname),
+            process: self.process.clone(),
+            pid: std::process::id(),
+        };
+
+        let backend: Box<dyn Write + Send> = match &self.transport {
+            SyslogTransport::Local => Box::new(
+                syslog::unix(())
+                    .context("Failed to connect to the local syslog socket")?
+                    .backend,
+            ),
+            SyslogTransport::Udp { server } => {
+                let addr = server.to_socket_addrs()
+                    .with_context(|| format!("Failed to resolve syslog server {}", server))?
+                    .next()
+                    .with_context(|| format!("No address for syslog server {}", server))?;
+                let local = if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+                Box::new(
+                    syslog::udp((), local, addr)
+                        .with_context(|| format!("Failed to open UDP socket for {}", server))?
+                        .backend,
+                )
+            }
+            SyslogTransport::Tcp { server } => Box::new(OctetCounted(
+                TcpStream::connect(server)
+                    .with_context(|| format!("Failed to connect to syslog server {}", server))?,
+            )),
+        };
+        Ok(syslog::Logger::new(backend, formatter))
+    }
+}
+
+/// Stream writer prefixing each formatted message with its length, so a
+/// TCP collector can split messages that contain newlines
+struct OctetCounted<W: Write>(W);
+
+impl<W: Write> Write for OctetCounted<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        write!(self.0, "{} ", buf.len())?;
+        self.0.write_all(buf)?;
+        self.0.flush()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+
+    // The formatter writes each message as a single `write!`
+    fn write_fmt(&mut self, args: std::fmt::Arguments<'_>) -> std::io::Result<()> {
+        self.write(std::fmt::format(args).as_bytes()).map(|_| ())
+    }
+}
+
+/// Payload sent by [`AlertGenerator::send_alert_webhook`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The [`Alert`] serialized as JSON, for generic endpoints
+    #[default]
+    Json,
+    /// A `{"text": ...}` message, as Slack and Teams incoming webhooks expect
+    Slack,
+}
+
+/// HTTP endpoint receiving alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Timeout for each delivery attempt, in seconds
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_secs: u64,
+    /// Retries for timeouts, connection failures, 429 and 5xx responses
+    #[serde(default = "default_webhook_retry")]
+    pub retry: RetryConfig,
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
+}
+
+fn default_webhook_retry() -> RetryConfig {
+    RetryConfig {
+        max_retries: 3,
+        initial_backoff_ms: 500,
+    }
+}
+
+impl WebhookConfig {
+    /// JSON delivery to `url` with the default timeout and retries
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: BTreeMap::new(),
+            format: WebhookFormat::default(),
+            timeout_secs: default_webhook_timeout(),
+            retry: default_webhook_retry(),
+        }
+    }
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTls {
+    /// Plain connection upgraded with STARTTLS, usually on port 587
+    #[default]
+    StartTls,
+    /// TLS from the first byte, usually on port 465
+    Implicit,
+    /// No encryption, only for a relay on a trusted network
+    None,
+}
+
+/// SMTP server and recipients for alert emails
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    /// Sender address, e.g. `FIM <fim@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+    /// Login for servers requiring authentication
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub tls: EmailTls,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+// By hand so the password never reaches logs
+impl std::fmt::Debug for EmailConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailConfig")
+            .field("smtp_host", &self.smtp_host)
+            .field("port", &self.port)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+impl EmailConfig {
+    /// Send a plain text message to every recipient
+    fn send(&self, subject: String, body: String) -> Result<()> {
+        use lettre::message::header::ContentType;
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{Message, SmtpTransport, Transport};
+
+        if self.to.is_empty() {
+            anyhow::bail!("No email recipients configured");
+        }
+        let mut message = Message::builder()
+            .from(self.from.parse().with_context(|| format!("Invalid sender address: {}", self.from))?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            message = message.to(to.parse().with_context(|| format!("Invalid recipient address: {}", to))?);
+        }
+        let message = message.body(body).context("Failed to build email")?;
+
+        let mut transport = match self.tls {
+            EmailTls::StartTls => SmtpTransport::starttls_relay(&self.smtp_host)?,
+            EmailTls::Implicit => SmtpTransport::relay(&self.smtp_host)?,
+            EmailTls::None => SmtpTransport::builder_dangerous(&self.smtp_host),
+        }
+        .port(self.port);
+        if let Some(ref username) = self.username {
+            let password = self.password.clone().unwrap_or_default();
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        transport.build().send(&message)?;
+        Ok(())
+    }
+}
+
+/// Rotation limits for an alert log file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RotationConfig {
+    /// Rotate once the active file reaches this many bytes
+    pub max_size: Option<u64>,
+    /// Rotate once the active file has been written for this long (seconds),
+    /// counted from the previous rotation across restarts
+    pub max_age: Option<u64>,
+    /// Rotated files to keep (`alerts.log.1` is the newest)
+    pub max_files: usize,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            max_size: Some(10 * 1024 * 1024),
+            max_age: None,
+            max_files: 5,
+        }
+    }
+}
+
+/// Append-only alert log with size- and time-based rotation
+///
+/// Lines are written whole under a lock, so handlers sharing one sink never
+/// interleave partial lines.
+pub struct AlertFileSink {
+    path: PathBuf,
+    rotation: RotationConfig,
+    state: Mutex<SinkState>,
+}
+
+struct SinkState {
+    file: fs::File,
+    size: u64,
+    /// When the active file was started
+    started_at: SystemTime,
+}
+
+impl AlertFileSink {
+    /// Open (or create) the alert log at `path`
+    pub fn open(path: impl Into<PathBuf>, rotation: RotationConfig) -> Result<Self> {
+        let path = path.into();
+        let state = SinkState::open(&path)?;
+        Ok(Self {
+            path,
+            rotation,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Append one line, rotating first when a limit has been reached
+    pub fn write_line(&self, line: &str) -> Result<()> {
+        use std::io::Write as _;
+
+        let mut state = self.state.lock().unwrap();
+        let line_len = line.len() as u64 + 1;
+        let too_big = self.rotation.max_size
+            .is_some_and(|max| state.size > 0 && state.size + line_len > max);
+        let too_old = self.rotation.max_age.is_some_and(|max| {
+            SystemTime::now().duration_since(state.started_at).unwrap_or_default() >= Duration::from_secs(max)
+        });
+        if too_big || too_old {
+            self.rotate()?;
+            *state = SinkState::open(&self.path)?;
+        }
+
+        let mut buffer = String::with_capacity(line.len() + 1);
+        buffer.push_str(line);
+        buffer.push('\n');
+        state.file.write_all(buffer.as_bytes())
+            .with_context(|| format!("Failed to write alert log {}", self.path.display()))?;
+        state.size += line_len;
+        Ok(())
+    }
+
+    /// Shift `path.N` to `path.N+1`, dropping files beyond `max_files`
+    fn rotate(&self) -> Result<()> {
+        let numbered = |n: usize| rotated_path(&self.path, n);
+
+        if self.rotation.max_files == 0 {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+
+        let oldest = numbered(self.rotation.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.rotation.max_files).rev() {
+            let from = numbered(n);
+            if from.exists() {
+                fs::rename(&from, numbered(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, numbered(1))
+            .with_context(|| format!("Failed to rotate alert log {}", self.path.display()))?;
+
+        debug!("Rotated alert log {}", self.path.display());
         Ok(())
     }
 }
 
+impl SinkState {
+    fn open(path: &Path) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open alert log {}", path.display()))?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+
+        // A file left by an earlier run started when the previous one was
+        // rotated out, or when it was created if it never was
+        let started_at = if size == 0 {
+            SystemTime::now()
+        } else {
+            fs::metadata(rotated_path(path, 1))
+                .and_then(|rotated| rotated.modified())
+                .or_else(|_| metadata.created())
+                .or_else(|_| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now())
+        };
+        Ok(Self {
+            file,
+            size,
+            started_at,
+        })
+    }
+}
+
+/// `path.N`, the Nth newest rotated alert log
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Change output for `fim monitor`
+///
+/// Every change is appended to the alerts file, but only changes at or above
+/// `min_severity` reach the console.
+pub struct MonitorOutput<W: std::io::Write> {
+    console: Mutex<W>,
+    min_severity: AlertSeverity,
+    alerts: Option<AlertFileSink>,
+    digest: Option<DigestSink>,
+    report: Option<DigestSink>,
+}
+
+impl<W: std::io::Write> MonitorOutput<W> {
+    pub fn new(console: W, min_severity: AlertSeverity, alerts: Option<AlertFileSink>) -> Self {
+        Self {
+            console: Mutex::new(console),
+            min_severity,
+            alerts,
+            digest: None,
+            report: None,
+        }
+    }
+
+    /// Also batch every change into periodic digests
+    pub fn with_digest(mut self, digest: DigestSink) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Digest sink, if one is attached
+    pub fn digest(&self) -> Option<&DigestSink> {
+        self.digest.as_ref()
+    }
+
+    /// Also keep a rolling report of every change, typically a
+    /// [`DigestSink`] replacing a file on every interval
+    pub fn with_report(mut self, report: DigestSink) -> Self {
+        self.report = Some(report);
+        self
+    }
+
+    /// Rolling report sink, if one is attached
+    pub fn report(&self) -> Option<&DigestSink> {
+        self.report.as_ref()
+    }
+
+    /// Print and log one change
+    pub fn handle(&self, change: &FileChange) -> Result<()> {
+        let alert_msg = format!(
+            "[{}] {}: {}",
+            change.detected_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            change.change_type.name(),
+            change.path.display()
+        );
+
+        if change.severity >= self.min_severity {
+            let mut console = self.console.lock().unwrap();
+            writeln!(console, "{}", alert_msg)?;
+        }
+        if let Some(ref sink) = self.alerts {
+            sink.write_line(&alert_msg)
+                .context("Failed to write to alerts file")?;
+        }
+        if let Some(ref digest) = self.digest {
+            digest.push(change)?;
+        }
+        if let Some(ref report) = self.report {
+            report.push(change)?;
+        }
+        Ok(())
+    }
+
+    /// Console writer, consuming the output
+    pub fn into_console(self) -> W {
+        self.console.into_inner().unwrap()
+    }
+}
+
+/// Batches changes into one report per interval instead of one alert per change
+///
+/// Changes accumulate until `interval` has passed since the first one, or
+/// until `max_changes` are pending, and are then rendered together by a
+/// [`ReportGenerator`] and delivered to the destination. Call
+/// [`DigestSink::flush_if_due`] periodically so a quiet interval still
+/// delivers what it collected, and [`DigestSink::flush`] on shutdown.
+///
+/// With [`DigestSink::with_every_interval`] a digest is instead delivered on
+/// a fixed schedule, empty or not, for a rolling report.
+pub struct DigestSink {
+    generator: ReportGenerator,
+    format: OutputFormat,
+    destination: ReportOutput,
+    interval: Duration,
+    max_changes: Option<usize>,
+    every_interval: bool,
+    state: Mutex<DigestState>,
+}
+
+#[derive(Default)]
+struct DigestState {
+    pending: Vec<FileChange>,
+    window_start: Option<Instant>,
+}
+
+impl DigestSink {
+    pub fn new(destination: ReportOutput, format: OutputFormat, interval: Duration) -> Self {
+        Self {
+            generator: ReportGenerator::new(ReportConfig {
+                title: "FIM Change Digest".to_string(),
+                max_changes_displayed: None,
+                ..Default::default()
+            }),
+            format,
+            destination,
+            interval,
+            max_changes: None,
+            every_interval: false,
+            state: Mutex::new(DigestState::default()),
+        }
+    }
+
+    /// Deliver a digest every interval from now on, even one without
+    /// changes, so the destination always shows how recent its report is
+    pub fn with_every_interval(mut self) -> Self {
+        self.every_interval = true;
+        self.state.get_mut().unwrap().window_start = Some(Instant::now());
+        self
+    }
+
+    /// Deliver early once this many changes are pending
+    pub fn with_max_changes(mut self, max_changes: usize) -> Self {
+        self.max_changes = Some(max_changes);
+        self
+    }
+
+    /// Render digests with this report configuration
+    ///
+    /// Its display limit is ignored: a digest always holds every pending
+    /// change, since delivered changes are not kept for a later one.
+    pub fn with_report_config(mut self, config: ReportConfig) -> Self {
+        self.generator = ReportGenerator::new(ReportConfig {
+            max_changes_displayed: None,
+            ..config
+        });
+        self
+    }
+
+    /// Add a change, delivering the digest if the interval or threshold is reached
+    ///
+    /// Returns the delivered report, if any.
+    pub fn push(&self, change: &FileChange) -> Result<Option<FimReport>> {
+        let mut state = self.state.lock().unwrap();
+        state.window_start.get_or_insert_with(Instant::now);
+        state.pending.push(change.clone());
+
+        let full = self.max_changes.is_some_and(|max| state.pending.len() >= max);
+        if full || self.is_due(&state) {
+            self.deliver(&mut state)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Deliver the pending changes if the interval has passed
+    pub fn flush_if_due(&self) -> Result<Option<FimReport>> {
+        let mut state = self.state.lock().unwrap();
+        if self.is_due(&state) {
+            self.deliver(&mut state)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Deliver the pending changes now
+    pub fn flush(&self) -> Result<Option<FimReport>> {
+        let mut state = self.state.lock().unwrap();
+        self.deliver(&mut state)
+    }
+
+    /// Number of changes waiting for the next digest
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    fn is_due(&self, state: &DigestState) -> bool {
+        state.window_start.is_some_and(|start| start.elapsed() >= self.interval)
+    }
+
+    /// Deliver the pending changes; on failure they stay pending, due
+    /// again, for the next attempt
+    fn deliver(&self, state: &mut DigestState) -> Result<Option<FimReport>> {
+        let window_start = state.window_start;
+        state.window_start = self.every_interval.then(Instant::now);
+        if state.pending.is_empty() && !self.every_interval {
+            return Ok(None);
+        }
+
+        let count = state.pending.len();
+        let report = self.generator.generate_report(state.pending.clone(), None, None);
+        let delivered = self.generator
+            .render_report(&report, self.format.clone())
+            .and_then(|rendered| self.destination.deliver(&report, &rendered));
+        if let Err(e) = delivered {
+            state.window_start = window_start;
+            return Err(e);
+        }
+        state.pending.clear();
+
+        info!("Delivered digest of {} changes", count);
+        Ok(Some(report))
+    }
+}
+
+/// Noun describing a critical change of this type in risk factors
+fn critical_kind(change_type: &ChangeType) -> &'static str {
+    match change_type {
+        ChangeType::Added => "new executable",
+        ChangeType::Deleted => "deletion",
+        ChangeType::HashChanged => "content change",
+        ChangeType::PermissionChanged => "system permission change",
+        ChangeType::AttributeChanged => "capability change",
+        ChangeType::ContentClassChanged => "content type change",
+        _ => "change",
+    }
+}
+
+/// setuid and setgid bits of an octal permission string such as `4755`
+fn special_bits(perm: &str) -> u32 {
+    u32::from_str_radix(perm, 8).unwrap_or(0) & 0o6000
+}
+
+/// Escape text for HTML and XML element content
+fn escape_markup(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the listed fields that differ, e.g. `perm 644 -> 600, uid 0 -> 1000`
+fn describe_fields(change: &FileChange, fields: &[&str]) -> String {
+    let described: Vec<_> = change.field_changes()
+        .iter()
+        .filter(|c| fields.contains(&c.field.as_str()))
+        .map(|c| c.to_string())
+        .collect();
+
+    if described.is_empty() {
+        "unchanged".to_string()
+    } else {
+        described.join(", ")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::tests::test_entry;
     use tempfile::tempdir;
     use std::path::PathBuf;
 
     fn create_test_change() -> FileChange {
-        use crate::database::FimEntryData;
-        
         FileChange {
             path: PathBuf::from("/test/file.txt"),
             change_type: ChangeType::HashChanged,
             old_entry: None,
-            new_entry: Some(FimEntryData {
-                size: 1024,
-                perm: "644".to_string(),
-                uid: 1000,
-                gid: 1000,
-                md5: None,
-                sha1: None,
-                sha256: None,
-                blake3: "test_hash".to_string(),
-                mtime: Utc::now(),
-                ctime: Utc::now(),
-                atime: Utc::now(),
-                inode: 12345,
-                dev: 2049,
-                scanned: true,
-            }),
+            new_entry: Some(test_entry()),
             detected_at: Utc::now(),
+            severity: AlertSeverity::Error,
+            is_critical: true,
+            change_id: FileChange::new_id(),
         }
     }
 
@@ -723,6 +2398,31 @@ mod tests {
         assert!(report.summary.changes_by_type.contains_key("HashChanged"));
     }
 
+    #[test]
+    fn test_builder_exports_every_output() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let json_path = temp_dir.path().join("report.json");
+        let html_path = temp_dir.path().join("report.html");
+
+        let generator = ReportGenerator::builder()
+            .title("Nightly FIM Report")
+            .since(Utc::now() - chrono::Duration::hours(1))
+            .group_by_type(false)
+            .file(OutputFormat::Json, &json_path)
+            .file(OutputFormat::Html, &html_path)
+            .build();
+        generator.generate_and_export(vec![create_test_change()], None, None)?;
+
+        let report: FimReport = serde_json::from_str(&fs::read_to_string(&json_path)?)?;
+        assert_eq!(report.metadata.title, "Nightly FIM Report");
+        assert_eq!(report.summary.total_changes, 1);
+        let html = fs::read_to_string(&html_path)?;
+        assert!(html.contains("Nightly FIM Report"));
+        assert!(html.contains("/test/file.txt"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_risk_assessment() {
         let generator = ReportGenerator::default();
@@ -777,4 +2477,934 @@ mod tests {
         assert!(!alert.id.is_empty());
         assert!(alert.title.contains("HashChanged"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_optional_hash_columns() -> Result<()> {
+        let generator = ReportGenerator::default();
+        let plain = generator.generate_report(vec![create_test_change()], None, None);
+        let csv = generator.export_csv(&plain)?;
+        assert!(csv.starts_with("timestamp,path,change_type,size,permissions,hash\n"));
+
+        let mut change = create_test_change();
+        if let Some(entry) = change.new_entry.as_mut() {
+            entry.sha512 = Some("sha512_hash".to_string());
+        }
+        let report = generator.generate_report(vec![change, create_test_change()], None, None);
+        let csv = generator.export_csv(&report)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,path,change_type,size,permissions,hash,sha512");
+        assert!(lines[1..].iter().any(|line| line.ends_with(",test_hash,sha512_hash")));
+        assert!(lines[1..].iter().any(|line| line.ends_with(",test_hash,")));
+
+        let json: serde_json::Value = serde_json::from_str(&generator.export_json(&report)?)?;
+        let hashes: Vec<&serde_json::Value> = json["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| &change["new_entry"]["sha512"])
+            .collect();
+        assert!(hashes.contains(&&serde_json::json!("sha512_hash")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_line_endings() -> Result<()> {
+        let generator = ReportGenerator::new(ReportConfig {
+            line_ending: LineEnding::CrLf,
+            utf8_bom: true,
+            ..Default::default()
+        });
+        let report = generator.generate_report(vec![create_test_change()], None, None);
+
+        let temp_dir = tempdir()?;
+        let csv_path = temp_dir.path().join("report.csv");
+        generator.export_report(&report, &csv_path, OutputFormat::Csv)?;
+
+        let content = fs::read(&csv_path)?;
+        assert!(content.starts_with(b"\xef\xbb\xbftimestamp,"));
+        let text = String::from_utf8(content)?;
+        assert_eq!(text.matches("\r\n").count(), 2);
+        assert_eq!(text.matches('\n').count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_timestamps() -> Result<()> {
+        let generator = ReportGenerator::new(ReportConfig {
+            relative_timestamps: true,
+            ..Default::default()
+        });
+        let now = DateTime::parse_from_rfc3339("2024-03-01T12:00:00Z")?.with_timezone(&Utc);
+
+        let mut change = create_test_change();
+        change.detected_at = now - chrono::Duration::minutes(5);
+        let mut report = generator.generate_report(vec![change], None, None);
+        report.metadata.generated_at = now;
+
+        let text = generator.export_text(&report)?;
+        assert!(text.contains("[2024-03-01 11:55:00 (5 minutes ago)]"));
+        let html = generator.export_html(&report)?;
+        assert!(html.contains("2024-03-01 11:55:00 (5 minutes ago)"));
+
+        assert_eq!(relative_time(now - chrono::Duration::hours(1), now), "1 hour ago");
+        assert_eq!(relative_time(now, now), "just now");
+
+        // Absolute-only by default
+        let text = ReportGenerator::default().export_text(&report)?;
+        assert!(!text.contains("ago"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_time_window() {
+        let now = Utc::now();
+        let generator = ReportGenerator::new(ReportConfig {
+            since: Some(now - chrono::Duration::hours(24)),
+            until: Some(now),
+            ..Default::default()
+        });
+
+        let mut recent = create_test_change();
+        recent.detected_at = now - chrono::Duration::hours(1);
+        let mut stale = create_test_change();
+        stale.path = PathBuf::from("/test/stale.txt");
+        stale.change_type = ChangeType::Deleted;
+        stale.detected_at = now - chrono::Duration::hours(48);
+        let mut future = create_test_change();
+        future.path = PathBuf::from("/test/future.txt");
+        future.detected_at = now + chrono::Duration::hours(1);
+
+        let report = generator.generate_report(vec![recent, stale, future], None, None);
+
+        assert_eq!(report.changes.len(), 1);
+        assert_eq!(report.changes[0].path, PathBuf::from("/test/file.txt"));
+        assert_eq!(report.summary.total_changes, 1);
+        assert_eq!(report.summary.files_affected, 1);
+        assert!(!report.summary.changes_by_type.contains_key("Deleted"));
+    }
+
+    #[test]
+    fn test_integrity_alert() {
+        let generator = AlertGenerator::new();
+        let mut report = DbIntegrityReport {
+            sqlite_errors: vec![],
+            stored_checksum: Some("aa".to_string()),
+            computed_checksum: "aa".to_string(),
+        };
+        assert!(generator.generate_integrity_alert(&report, Path::new("fim.db")).is_none());
+
+        report.computed_checksum = "bb".to_string();
+        let alert = generator.generate_integrity_alert(&report, Path::new("fim.db")).unwrap();
+        assert_eq!(alert.severity, AlertSeverity::Critical);
+        assert!(alert.message.contains("running checksum mismatch"));
+    }
+
+    #[test]
+    fn test_parallel_rendering_matches_sequential() -> Result<()> {
+        let changes: Vec<_> = (0..500)
+            .map(|i| {
+                let mut change = create_test_change();
+                change.path = PathBuf::from(format!("/test/file{}.txt", i));
+                change.change_type = if i % 3 == 0 { ChangeType::Added } else { ChangeType::HashChanged };
+                change
+            })
+            .collect();
+
+        let sequential = ReportGenerator::new(ReportConfig {
+            parallel_render_threshold: None,
+            max_changes_displayed: None,
+            relative_timestamps: true,
+            ..Default::default()
+        });
+        let parallel = ReportGenerator::new(ReportConfig {
+            parallel_render_threshold: Some(1),
+            ..sequential.config.clone()
+        });
+        let report = sequential.generate_report(changes, None, None);
+
+        for format in [OutputFormat::Csv, OutputFormat::Html, OutputFormat::Text] {
+            let expected = sequential.render_report(&report, format.clone())?;
+            assert_eq!(parallel.render_report(&report, format)?, expected);
+        }
+        assert_eq!(sequential.export_csv(&report)?.lines().count(), 501);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_risk_factors_explain_rating() -> Result<()> {
+        let generator = ReportGenerator::new(ReportConfig::default());
+
+        let mut changes = Vec::new();
+        for i in 0..12 {
+            let mut change = create_test_change();
+            change.path = PathBuf::from(format!("/srv/app/file{}", i));
+            change.change_type = ChangeType::Deleted;
+            changes.push(change);
+        }
+        changes.push(create_test_change());
+
+        let report = generator.generate_report(changes, None, None);
+        assert_eq!(report.summary.risk_level, RiskLevel::Critical);
+        assert_eq!(report.summary.risk_factors, vec![
+            "1 critical content change".to_string(),
+            "12 critical deletions".to_string(),
+            ">10 total changes".to_string(),
+        ]);
+
+        let text = generator.export_text(&report)?;
+        assert!(text.contains("Risk Level: Critical\n  - 1 critical content change\n"));
+        assert!(generator.export_html(&report)?.contains("<li>12 critical deletions</li>"));
+        assert!(generator.export_xml(&report)?.contains("<factor>&gt;10 total changes</factor>"));
+        assert!(generator.export_html(&report)?.contains("<li>&gt;10 total changes</li>"));
+
+        // Privilege escalation through setuid or setgid is called out by path
+        let mut old_entry = test_entry();
+        old_entry.perm = "755".to_string();
+        let mut escalated = create_test_change();
+        escalated.path = PathBuf::from("/usr/bin/<foo>");
+        escalated.change_type = ChangeType::PermissionChanged;
+        escalated.is_critical = false;
+        escalated.new_entry = Some(FimEntryData { perm: "4755".to_string(), ..old_entry.clone() });
+        escalated.old_entry = Some(old_entry);
+        let report = generator.generate_report(vec![escalated], None, None);
+        assert_eq!(report.summary.risk_level, RiskLevel::High);
+        assert_eq!(report.summary.risk_factors, vec!["setuid bit added on /usr/bin/<foo>".to_string()]);
+        assert!(generator.export_xml(&report)?.contains("<factor>setuid bit added on /usr/bin/&lt;foo&gt;</factor>"));
+
+        let quiet = generator.generate_report(Vec::new(), None, None);
+        assert_eq!(quiet.summary.risk_level, RiskLevel::Low);
+        assert!(quiet.summary.risk_factors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_from_change_log() -> Result<()> {
+        use crate::database::FimDb;
+
+        let db = FimDb::init(true)?;
+        let now = Utc::now();
+        for (name, days_ago, change_type) in [
+            ("ancient", 30, ChangeType::Added),
+            ("week_old", 6, ChangeType::Deleted),
+            ("yesterday", 1, ChangeType::HashChanged),
+            ("today", 0, ChangeType::PermissionChanged),
+        ] {
+            let mut change = create_test_change();
+            change.path = PathBuf::from(format!("/etc/{}", name));
+            change.change_type = change_type;
+            change.detected_at = now - chrono::Duration::days(days_ago) - chrono::Duration::minutes(1);
+            db.record_change(&change)?;
+        }
+
+        let (generator, report) = ReportGenerator::from_db(
+            &db,
+            Some(now - chrono::Duration::days(7)),
+            Some(now - chrono::Duration::hours(12)),
+            ReportConfig::default(),
+        )?;
+        let paths: Vec<_> = report.changes.iter().map(|change| change.path.clone()).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&PathBuf::from("/etc/week_old")));
+        assert!(paths.contains(&PathBuf::from("/etc/yesterday")));
+        assert_eq!(report.summary.total_changes, 2);
+        assert_eq!(report.summary.changes_by_type.get("Deleted"), Some(&1));
+
+        let csv = generator.render_report(&report, OutputFormat::Csv)?;
+        assert!(csv.contains("/etc/yesterday"));
+        assert!(!csv.contains("/etc/today"));
+        assert!(!csv.contains("/etc/ancient"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alert_carries_change_id() -> Result<()> {
+        use crate::database::FimDb;
+
+        let db = FimDb::init(true)?;
+        let change = create_test_change();
+        let other = create_test_change();
+        assert_eq!(change.change_id.len(), 32);
+        assert_ne!(change.change_id, other.change_id);
+        db.record_change(&change)?;
+        db.record_change(&other)?;
+
+        let alert = AlertGenerator::new().generate_alert(&change);
+        assert_eq!(alert.id, change.change_id);
+
+        // The alert leads back to the exact change log row
+        let recorded = db.get_change(&alert.id)?.unwrap();
+        assert_eq!(recorded.change_id, change.change_id);
+        assert_eq!(recorded.detected_at, change.detected_at);
+        assert!(db.get_change("unknown")?.is_none());
+
+        // Changes recorded before ids keep the legacy alert id
+        let legacy = FileChange {
+            change_id: String::new(),
+            ..change
+        };
+        assert!(AlertGenerator::new().generate_alert(&legacy).id.starts_with("fim_"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_critical_rules() -> Result<()> {
+        let rules = CompiledSeverityRules::default();
+        assert!(rules.is_critical(Path::new("/usr/local/bin/tool"), &ChangeType::Added));
+        assert!(rules.is_critical(Path::new("/bin/tool"), &ChangeType::Added));
+        assert!(rules.is_critical(Path::new("/etc/passwd"), &ChangeType::PermissionChanged));
+        assert!(rules.is_critical(Path::new("/home/user/notes.txt"), &ChangeType::Deleted));
+        assert!(!rules.is_critical(Path::new("/home/user/notes.txt"), &ChangeType::PermissionChanged));
+        // System paths only make the listed change types critical
+        assert!(!rules.is_critical(Path::new("/etc/passwd"), &ChangeType::TimestampChanged));
+        assert!(!rules.is_critical(Path::new("/usr/bin/ls"), &ChangeType::SizeChanged));
+        assert!(!rules.is_critical(Path::new("/etc/new.conf"), &ChangeType::Added));
+
+        // Each configured list replaces its defaults; severities keep theirs
+        let rules = toml::from_str::<SeverityRules>(
+            "critical_paths = [\"**/*.sudoers\", { path_pattern = \"/srv/app/**\", change_types = [\"Added\"] }]\n\
+             critical_change_types = [\"Deleted\"]\n",
+        )?.compile()?;
+        assert!(rules.is_critical(Path::new("/etc/sudoers.d/ops.sudoers"), &ChangeType::TimestampChanged));
+        assert!(rules.is_critical(Path::new("/srv/app/config.yml"), &ChangeType::Added));
+        assert!(!rules.is_critical(Path::new("/srv/app/config.yml"), &ChangeType::PermissionChanged));
+        assert!(!rules.is_critical(Path::new("/usr/bin/ls"), &ChangeType::HashChanged));
+        assert!(rules.is_critical(Path::new("/usr/bin/ls"), &ChangeType::Deleted));
+        assert_eq!(rules.rules().severity(&ChangeType::HashChanged), AlertSeverity::Error);
+
+        let invalid = SeverityRules {
+            critical_paths: vec![CriticalPath::new("/srv/[app", &[])],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_alert_severities() -> Result<()> {
+        let change = |path: &str, change_type: ChangeType| {
+            let mut change = create_test_change();
+            change.path = PathBuf::from(path);
+            change.change_type = change_type;
+            CompiledSeverityRules::default().classify(&mut change);
+            change
+        };
+        let added = change("/home/user/notes.txt", ChangeType::Added);
+        let ssh_touch = change("/etc/ssh/sshd_config", ChangeType::TimestampChanged);
+
+        // Without rules of its own the generator keeps the engine's severity
+        let mut generator = AlertGenerator::with_syslog(None);
+        assert_eq!(generator.generate_alert(&added).severity, AlertSeverity::Warning);
+
+        generator.set_severity(ChangeType::Added, AlertSeverity::Critical);
+        assert_eq!(generator.generate_alert(&added).severity, AlertSeverity::Critical);
+        assert_eq!(generator.generate_alert(&ssh_touch).severity, AlertSeverity::Info);
+
+        // Path overrides win over the type, first match first
+        let rules = toml::from_str::<SeverityRules>(
+            "[[path_severities]]\n\
+             path_pattern = \"/etc/ssh/**\"\n\
+             severity = \"Critical\"\n\
+             [[path_severities]]\n\
+             path_pattern = \"/etc/**\"\n\
+             severity = \"Warning\"\n",
+        )?.compile()?;
+        let generator = AlertGenerator::with_syslog(None).with_rules(rules.clone());
+        assert_eq!(generator.generate_alert(&ssh_touch).severity, AlertSeverity::Critical);
+        let hosts = change("/etc/hosts", ChangeType::Deleted);
+        assert_eq!(generator.generate_alert(&hosts).severity, AlertSeverity::Warning);
+        assert_eq!(generator.generate_alert(&added).severity, AlertSeverity::Warning);
+
+        // The engine applies the same overrides when classifying
+        let mut classified = ssh_touch.clone();
+        rules.classify(&mut classified);
+        assert_eq!(classified.severity, AlertSeverity::Critical);
+
+        let invalid = SeverityRules {
+            path_severities: vec![PathSeverity {
+                path_pattern: "/etc/[ssh".to_string(),
+                severity: AlertSeverity::Critical,
+            }],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        // Moves can't be listed by type, so they have a setting of their own
+        let moved = ChangeType::Moved { from: PathBuf::from("/srv/a"), to: PathBuf::from("/srv/b") };
+        assert_eq!(SeverityRules::default().severity(&moved), AlertSeverity::Warning);
+        let rules: SeverityRules = toml::from_str("moved_severity = \"Info\"\n")?;
+        assert_eq!(rules.severity(&moved), AlertSeverity::Info);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_monitor_output_min_severity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let log_path = temp_dir.path().join("alerts.log");
+        let sink = AlertFileSink::open(&log_path, RotationConfig::default())?;
+        let output = MonitorOutput::new(Vec::new(), "error".parse()?, Some(sink));
+
+        let mut info = create_test_change();
+        info.path = PathBuf::from("/test/touched.txt");
+        info.change_type = ChangeType::TimestampChanged;
+        info.severity = AlertSeverity::Info;
+        output.handle(&info)?;
+        output.handle(&create_test_change())?;
+
+        let console = String::from_utf8(output.into_console())?;
+        assert_eq!(console.lines().count(), 1);
+        assert!(console.contains("HashChanged: /test/file.txt"));
+        assert!(!console.contains("touched.txt"));
+
+        // The alerts file still records both
+        let logged = fs::read_to_string(&log_path)?;
+        assert!(logged.contains("TimestampChanged: /test/touched.txt"));
+        assert!(logged.contains("HashChanged: /test/file.txt"));
+
+        assert_eq!("Critical".parse::<AlertSeverity>()?, AlertSeverity::Critical);
+        assert!("loud".parse::<AlertSeverity>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digest_batches_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let digest_path = temp_dir.path().join("digest.json");
+        let output = MonitorOutput::new(Vec::new(), AlertSeverity::Info, None).with_digest(
+            DigestSink::new(
+                ReportOutput::Append(digest_path.clone()),
+                OutputFormat::Json,
+                Duration::from_secs(3600),
+            ),
+        );
+
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            let mut change = create_test_change();
+            change.path = PathBuf::from("/test").join(name);
+            output.handle(&change)?;
+        }
+        let digest = output.digest().unwrap();
+        assert_eq!(digest.pending(), 3);
+        assert!(digest.flush_if_due()?.is_none());
+        assert!(!digest_path.exists());
+
+        let report = digest.flush()?.expect("pending changes are delivered");
+        assert_eq!(report.summary.total_changes, 3);
+        assert!(digest.flush()?.is_none());
+
+        // One digest holding all three changes
+        let written = fs::read_to_string(&digest_path)?;
+        let reports: Vec<FimReport> = serde_json::Deserializer::from_str(&written)
+            .into_iter()
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].changes.len(), 3);
+        assert!(reports[0].changes.iter().any(|c| c.path == Path::new("/test/b.txt")));
+
+        // The threshold delivers without waiting for the interval
+        let delivered = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let seen = delivered.clone();
+        let digest = DigestSink::new(
+            ReportOutput::Handler(Box::new(move |report: &FimReport, _: &str| {
+                seen.lock().unwrap().push(report.changes.len());
+                Ok(())
+            })),
+            OutputFormat::Text,
+            Duration::from_secs(3600),
+        ).with_max_changes(2);
+        assert!(digest.push(&create_test_change())?.is_none());
+        assert!(digest.push(&create_test_change())?.is_some());
+        assert_eq!(*delivered.lock().unwrap(), vec![2]);
+
+        // A failed delivery keeps the batch for the next one
+        let attempts = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let seen = attempts.clone();
+        let digest = DigestSink::new(
+            ReportOutput::Handler(Box::new(move |report: &FimReport, _: &str| {
+                let mut attempts = seen.lock().unwrap();
+                attempts.push(report.changes.len());
+                anyhow::ensure!(attempts.len() > 1, "destination unavailable");
+                Ok(())
+            })),
+            OutputFormat::Text,
+            Duration::from_secs(3600),
+        );
+        digest.push(&create_test_change())?;
+        digest.push(&create_test_change())?;
+        assert!(digest.flush().is_err());
+        assert_eq!(digest.pending(), 2);
+        digest.push(&create_test_change())?;
+        assert_eq!(digest.flush()?.expect("retried").changes.len(), 3);
+        assert_eq!(digest.pending(), 0);
+        assert_eq!(*attempts.lock().unwrap(), vec![2, 3]);
+
+        // Past the report display limit, no change is dropped
+        let digest = DigestSink::new(
+            ReportOutput::Handler(Box::new(|_: &FimReport, _: &str| Ok(()))),
+            OutputFormat::Json,
+            Duration::from_secs(3600),
+        );
+        let limit = ReportConfig::default().max_changes_displayed.unwrap();
+        for i in 0..limit + 5 {
+            let mut change = create_test_change();
+            change.path = PathBuf::from(format!("/test/{}.txt", i));
+            digest.push(&change)?;
+        }
+        let report = digest.flush()?.expect("pending changes are delivered");
+        assert_eq!(report.changes.len(), limit + 5);
+        assert_eq!(digest.pending(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rolling_report_replaces_file() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let report_path = temp_dir.path().join("report.json");
+        let output = MonitorOutput::new(Vec::new(), AlertSeverity::Info, None).with_report(
+            DigestSink::new(
+                ReportOutput::File(report_path.clone()),
+                OutputFormat::Json,
+                Duration::from_secs(3600),
+            )
+            .with_every_interval(),
+        );
+        let read_report = || -> Result<FimReport> {
+            Ok(serde_json::from_str(&fs::read_to_string(&report_path)?)?)
+        };
+
+        output.handle(&create_test_change())?;
+        output.handle(&create_test_change())?;
+        let report = output.report().unwrap();
+        assert!(report.flush_if_due()?.is_none());
+        report.flush()?;
+        assert_eq!(read_report()?.changes.len(), 2);
+
+        // The next report holds only what came since, even nothing
+        report.flush()?;
+        assert_eq!(read_report()?.changes.len(), 0);
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+        // Quiet intervals still deliver
+        let report = DigestSink::new(
+            ReportOutput::File(report_path.clone()),
+            OutputFormat::Json,
+            Duration::ZERO,
+        )
+        .with_every_interval();
+        assert!(report.flush_if_due()?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alert_file_rotation() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let log_path = temp_dir.path().join("alerts.log");
+        let sink = std::sync::Arc::new(AlertFileSink::open(&log_path, RotationConfig {
+            max_size: Some(100),
+            max_age: None,
+            max_files: 2,
+        })?);
+
+        // Concurrent writers, 20 lines of 28 bytes each
+        let handles: Vec<_> = (0..4)
+            .map(|writer| {
+                let sink = std::sync::Arc::clone(&sink);
+                std::thread::spawn(move || {
+                    for i in 0..5 {
+                        sink.write_line(&format!("writer {} alert {:012}", writer, i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let rotated = |n: usize| temp_dir.path().join(format!("alerts.log.{}", n));
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+
+        for path in [log_path.clone(), rotated(1), rotated(2)] {
+            let content = fs::read_to_string(&path)?;
+            assert!(content.len() <= 100);
+            // Every line arrived whole
+            assert!(content.lines().all(|line| line.len() == 27 && line.starts_with("writer ")));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alert_file_age_survives_reopen() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let log_path = temp_dir.path().join("alerts.log");
+        let rotated = temp_dir.path().join("alerts.log.1");
+        let rotation = RotationConfig {
+            max_size: None,
+            max_age: Some(3600),
+            max_files: 2,
+        };
+
+        // The active file was started two hours ago, when alerts.log.1 was rotated out
+        fs::write(&rotated, "old alert\n")?;
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 3600);
+        fs::File::options().write(true).open(&rotated)?.set_modified(two_hours_ago)?;
+        fs::write(&log_path, "earlier alert\n")?;
+
+        AlertFileSink::open(&log_path, rotation.clone())?.write_line("new alert")?;
+        assert_eq!(fs::read_to_string(&log_path)?, "new alert\n");
+        assert_eq!(fs::read_to_string(&rotated)?, "earlier alert\n");
+
+        // The fresh file is young, so reopening it does not rotate again
+        AlertFileSink::open(&log_path, rotation)?.write_line("another alert")?;
+        assert_eq!(fs::read_to_string(&log_path)?, "new alert\nanother alert\n");
+
+        Ok(())
+    }
+
+    /// Answer one request per status with that status, returning each raw request
+    fn serve_statuses(statuses: Vec<u16>) -> Result<(String, std::thread::JoinHandle<Vec<String>>)> {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/hook", listener.local_addr()?);
+        let handle = std::thread::spawn(move || {
+            statuses
+                .into_iter()
+                .map(|status| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut request = String::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                        request.push_str(&line);
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    request.push_str(&String::from_utf8(body).unwrap());
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                    .unwrap();
+                    request
+                })
+                .collect()
+        });
+        Ok((url, handle))
+    }
+
+    #[test]
+    fn test_send_alert_webhook() -> Result<()> {
+        let generator = AlertGenerator::new();
+        let alert = generator.generate_alert(&create_test_change());
+        let retry = RetryConfig {
+            max_retries: 2,
+            initial_backoff_ms: 1,
+        };
+
+        // A server error is retried, and the JSON body carries the alert
+        let (url, server) = serve_statuses(vec![503, 200])?;
+        let mut cfg = WebhookConfig::new(url);
+        cfg.headers.insert("X-Token".to_string(), "secret".to_string());
+        cfg.retry = retry.clone();
+        generator.send_alert_webhook(&alert, &cfg)?;
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("POST /hook "));
+        assert!(requests[1].to_lowercase().contains("x-token: secret"));
+        let body: serde_json::Value = serde_json::from_str(requests[1].split("\r\n\r\n").nth(1).unwrap())?;
+        assert_eq!(body["severity"], "Error");
+        assert_eq!(body["file_path"], "/test/file.txt");
+        assert_eq!(body["change_type"], "HashChanged");
+
+        // A client error fails at once with the Slack payload
+        let (url, server) = serve_statuses(vec![400])?;
+        let mut cfg = WebhookConfig::new(url);
+        cfg.format = WebhookFormat::Slack;
+        cfg.retry = retry;
+        assert!(generator.send_alert_webhook(&alert, &cfg).is_err());
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_str(requests[0].split("\r\n\r\n").nth(1).unwrap())?;
+        assert!(body["text"].as_str().unwrap().contains("[Error] File HashChanged: /test/file.txt"));
+
+        Ok(())
+    }
+
+    /// Recipients and data of a message received by [`serve_smtp`]
+    type SmtpMessage = (Vec<String>, String);
+
+    /// Accept one SMTP session per message, returning each message
+    fn serve_smtp(messages: usize) -> Result<(u16, std::thread::JoinHandle<Vec<SmtpMessage>>)> {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let handle = std::thread::spawn(move || {
+            (0..messages)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let reply = |reader: &mut BufReader<TcpStream>, line: &str| {
+                        write!(reader.get_mut(), "{}\r\n", line).unwrap();
+                    };
+                    reply(&mut reader, "220 localhost");
+                    let mut recipients = Vec::new();
+                    let mut data = String::new();
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap() == 0 {
+                            break;
+                        }
+                        let command = line.to_uppercase();
+                        if command.starts_with("RCPT TO:") {
+                            recipients.push(line[8..].trim().to_string());
+                            reply(&mut reader, "250 OK");
+                        } else if command.starts_with("DATA") {
+                            reply(&mut reader, "354 Go ahead");
+                            while !data.ends_with("\r\n.\r\n") {
+                                reader.read_line(&mut data).unwrap();
+                            }
+                            reply(&mut reader, "250 Queued");
+                        } else if command.starts_with("QUIT") {
+                            reply(&mut reader, "221 Bye");
+                            break;
+                        } else {
+                            reply(&mut reader, "250 OK");
+                        }
+                    }
+                    (recipients, data)
+                })
+                .collect()
+        });
+        Ok((port, handle))
+    }
+
+    #[test]
+    fn test_send_email() -> Result<()> {
+        let generator = AlertGenerator::with_syslog(None);
+        let mut change = create_test_change();
+        change.old_entry = change.new_entry.clone();
+        change.old_entry.as_mut().unwrap().blake3 = "old_test_hash".to_string();
+        let alert = generator.generate_alert(&change);
+
+        let (port, server) = serve_smtp(2)?;
+        let cfg = EmailConfig {
+            smtp_host: "127.0.0.1".to_string(),
+            port,
+            from: "FIM <fim@example.com>".to_string(),
+            to: vec!["ops@example.com".to_string(), "sec@example.com".to_string()],
+            username: None,
+            password: None,
+            tls: EmailTls::None,
+        };
+        generator.send_email(&alert, &cfg)?;
+
+        // A digest is one message, most severe alert first
+        let mut deleted = create_test_change();
+        deleted.path = PathBuf::from("/etc/hosts");
+        deleted.change_type = ChangeType::Deleted;
+        deleted.severity = AlertSeverity::Critical;
+        let alerts = vec![alert.clone(), generator.generate_alert(&deleted)];
+        generator.send_email_digest(&alerts, &cfg)?;
+        generator.send_email_digest(&[], &cfg)?;
+
+        let sessions = server.join().unwrap();
+        let (recipients, data) = &sessions[0];
+        assert_eq!(recipients, &["<ops@example.com>", "<sec@example.com>"]);
+        assert!(data.contains("Subject: [FIM Error] File HashChanged: /test/file.txt"), "{}", data);
+        assert!(data.contains("Path: /test/file.txt"));
+        assert!(data.contains("Old hash: old_test_hash"));
+        assert!(data.contains("New hash: test_hash"));
+        assert!(data.contains("Severity: Error"));
+
+        let (_, digest) = &sessions[1];
+        assert!(digest.contains("Subject: [FIM Critical] 2 file integrity alerts"), "{}", digest);
+        assert!(digest.contains("Critical: 1"));
+        assert!(digest.contains("Error: 1"));
+        let hosts = digest.find("Path: /etc/hosts").unwrap();
+        assert!(hosts < digest.find("Path: /test/file.txt").unwrap());
+
+        // Bad addresses fail before connecting
+        let invalid = EmailConfig { to: vec!["not an address".to_string()], ..cfg };
+        assert!(generator.send_email(&alert, &invalid).is_err());
+
+        // Debug output never carries the password
+        let authenticated = EmailConfig {
+            username: Some("fim".to_string()),
+            password: Some("hunter2".to_string()),
+            ..invalid
+        };
+        let debug = format!("{:?}", authenticated);
+        assert!(debug.contains("<redacted>") && !debug.contains("hunter2"), "{}", debug);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_alert_syslog() -> Result<()> {
+        use std::io::Read;
+
+        let change = create_test_change();
+
+        // Without a syslog destination alerts are only logged
+        let generator = AlertGenerator::new();
+        generator.send_alert(&generator.generate_alert(&change))?;
+
+        // UDP: one datagram per alert, priority from facility and severity
+        let collector = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        collector.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let generator = AlertGenerator::with_syslog(Some(SyslogConfig {
+            facility: "local0".to_string(),
+            transport: SyslogTransport::Udp { server: collector.local_addr()?.to_string() },
+            hostname: Some("host1".to_string()),
+            ..Default::default()
+        }));
+        let alert = generator.generate_alert(&change);
+        generator.send_alert(&alert)?;
+        let mut buf = [0; 4096];
+        let len = collector.recv(&mut buf)?;
+        let message = std::str::from_utf8(&buf[..len])?;
+        // local0 (16 << 3) + err (3)
+        assert!(message.starts_with("<131>1 "), "{}", message);
+        assert!(message.contains(" host1 fim "));
+        assert!(message.contains("[fim@32473 "));
+        assert!(message.contains(r#"path="/test/file.txt""#));
+        assert!(message.contains(r#"change_type="HashChanged""#));
+        assert!(message.ends_with(&alert.message));
+
+        // TCP: messages are framed with their length
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let generator = AlertGenerator::with_syslog(Some(SyslogConfig {
+            transport: SyslogTransport::Tcp { server: listener.local_addr()?.to_string() },
+            ..Default::default()
+        }));
+        generator.send_alert(&alert)?;
+        generator.send_alert(&alert)?;
+        drop(generator);
+        let mut received = String::new();
+        listener.accept()?.0.read_to_string(&mut received)?;
+        let mut rest = received.as_str();
+        for _ in 0..2 {
+            let (len, tail) = rest.split_once(' ').unwrap();
+            let (message, tail) = tail.split_at(len.parse()?);
+            assert!(message.starts_with("<11>1 "), "{}", message);
+            rest = tail;
+        }
+        assert!(rest.is_empty());
+
+        // An unknown facility is reported rather than ignored
+        let generator = AlertGenerator::with_syslog(Some(SyslogConfig {
+            facility: "nonsense".to_string(),
+            ..Default::default()
+        }));
+        assert!(generator.send_alert(&alert).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cef_and_leef_export() -> Result<()> {
+        let generator = ReportGenerator::default();
+        let mut change = create_test_change();
+        change.path = PathBuf::from("/srv/a=b|c\\d.txt");
+        let report = generator.generate_report(vec![change.clone()], None, None);
+
+        let cef = generator.render_report(&report, OutputFormat::Cef)?;
+        assert_eq!(cef.lines().count(), 1);
+        let prefix = format!("CEF:0|rusty-fim|fim|{}|HashChanged|File HashChanged|8|rt=", crate::VERSION);
+        assert!(cef.starts_with(&prefix), "{}", cef);
+        // `=` and `\` are escaped in extensions, `|` is not
+        assert!(cef.contains(r" filePath=/srv/a\=b|c\\d.txt "), "{}", cef);
+        assert!(cef.contains(" fsize=1024 fileHash=test_hash cn1=1000 cn1Label=uid cn2=1000 cn2Label=gid"));
+
+        let leef = generator.render_report(&report, OutputFormat::Leef)?;
+        let prefix = format!("LEEF:1.0|rusty-fim|fim|{}|HashChanged|devTime=", crate::VERSION);
+        assert!(leef.starts_with(&prefix), "{}", leef);
+        let attributes: HashMap<&str, &str> = leef.trim_end()
+            .splitn(6, '|')
+            .nth(5)
+            .unwrap()
+            .split('\t')
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+        assert_eq!(attributes["sev"], "8");
+        assert_eq!(attributes["fileHash"], "test_hash");
+        assert_eq!(attributes["uid"], "1000");
+        assert_eq!(attributes["gid"], "1000");
+        assert_eq!(attributes["filePath"], "/srv/a=b|c\\\\d.txt");
+
+        // Alerts add their message; newlines can't split the event
+        let alerts = AlertGenerator::with_syslog(None);
+        let mut alert = alerts.generate_alert(&change);
+        alert.message = "line one\nline two".to_string();
+        let cef = alerts.to_cef(&alert);
+        assert!(!cef.contains('\n'));
+        assert!(cef.ends_with(r" msg=line one\nline two"), "{}", cef);
+        assert!(cef.contains(" cn1=1000 "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_snapshot_round_trip() -> Result<()> {
+        use crate::database::{FimDb, FimEntryData};
+
+        let mut source = FimDb::init(true)?;
+        let entry = create_test_change().new_entry.unwrap();
+        for (path, hash) in [("/etc/hosts", "aaa"), ("/etc/passwd", "bbb"), ("/bin/sh", "ccc")] {
+            let data = FimEntryData { blake3: hash.to_string(), ..entry.clone() };
+            source.insert_data(Path::new(path), &data)?;
+        }
+
+        let generator = ReportGenerator::default();
+        let mut report = generator.generate_report(vec![create_test_change()], None, None);
+        assert!(!generator.render_report(&report, OutputFormat::Json)?.contains("\"snapshot\""));
+
+        report.snapshot = Some(source.snapshot()?);
+        let json = generator.render_report(&report, OutputFormat::Json)?;
+        let imported: FimReport = serde_json::from_str(&json)?;
+        let snapshot = imported.snapshot.expect("snapshot serialized");
+        assert_eq!(snapshot.entries.len(), 3);
+
+        // A target with unrelated data is fully replaced
+        let mut target = FimDb::init(true)?;
+        target.insert_data(Path::new("/stale"), &entry)?;
+        assert_eq!(target.restore_snapshot(&snapshot)?, 3);
+        assert_eq!(target.get_data_checksum()?, source.get_data_checksum()?);
+        assert!(target.get_path(Path::new("/stale"))?.is_none());
+        assert!(target.integrity_check()?.is_ok());
+
+        // Tampered snapshots are rejected before anything is written
+        let mut tampered = snapshot.clone();
+        tampered.entries[0].data.blake3 = "evil".to_string();
+        assert!(target.restore_snapshot(&tampered).is_err());
+        assert_eq!(target.get_data_checksum()?, source.get_data_checksum()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,540 @@
+//! Storage backend abstraction
+//!
+//! [`FimEngine`](crate::fim::FimEngine) keeps its baseline in a [`FimStore`].
+//! [`FimDb`] is the SQLite implementation; other backends (Postgres, a remote
+//! service, a plain map) only need the required methods, while transactions,
+//! the change log and integrity checks have conservative defaults.
+
+use crate::database::{
+    data_checksum, entries_digest, ChangeLogVerification, DbIntegrityReport, FimDb, FimEntry, FimEntryData,
+    FimStats, FullSnapshot,
+};
+use crate::fim::FileChange;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Storage for file entries used by the FIM engine
+pub trait FimStore: Send {
+    /// Insert or replace the entry for a path
+    fn insert_data(&mut self, file_path: &Path, entry: &FimEntryData) -> Result<()>;
+
+    /// Entry for a path, if recorded
+    fn get_path(&self, file_path: &Path) -> Result<Option<FimEntry>>;
+
+    /// Remove the entry for a path; removing an unknown path is not an error
+    fn remove_path(&mut self, file_path: &Path) -> Result<()>;
+
+    /// Remove the entries for several paths, returning how many were recorded
+    fn remove_paths(&mut self, paths: &[PathBuf]) -> Result<u64> {
+        let mut removed = 0;
+        for path in paths {
+            if self.get_path(path)?.is_some() {
+                self.remove_path(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Clear the scanned flag of every entry before a full scan
+    fn set_all_unscanned(&mut self) -> Result<()>;
+
+    /// Remove entries whose scanned flag is clear, returning how many
+    fn delete_not_scanned(&mut self) -> Result<u64>;
+
+    /// All entries, ordered by path
+    fn get_all_entries(&self) -> Result<Vec<FimEntry>>;
+
+    /// Entry counts
+    fn get_stats(&self) -> Result<FimStats> {
+        let entries = self.get_all_entries()?;
+        let scanned = entries.iter().filter(|entry| entry.data.scanned).count() as i32;
+        Ok(FimStats {
+            total_files: entries.len() as i32,
+            scanned_files: scanned,
+            unscanned_files: entries.len() as i32 - scanned,
+        })
+    }
+
+    /// Checksum over all entry hashes, as computed by [`FimDb::get_data_checksum`]
+    fn get_data_checksum(&self) -> Result<String> {
+        Ok(data_checksum(&self.get_all_entries()?))
+    }
+
+    /// Set the scanned flag of an existing entry without changing its data
+    fn mark_scanned(&mut self, file_path: &Path) -> Result<()> {
+        if let Some(mut entry) = self.get_path(file_path)? {
+            entry.data.scanned = true;
+            self.insert_data(file_path, &entry.data)?;
+        }
+        Ok(())
+    }
+
+    /// Clear the scanned flag of `root` and every entry below it
+    fn set_unscanned_under(&mut self, root: &Path) -> Result<()> {
+        for mut entry in self.get_all_entries()? {
+            if entry.path.starts_with(root) && entry.data.scanned {
+                entry.data.scanned = false;
+                self.insert_data(&entry.path, &entry.data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries not visited by the current scan, optionally limited to `root` and below
+    fn get_not_scanned(&self, root: Option<&Path>) -> Result<Vec<FimEntry>> {
+        Ok(self.get_all_entries()?
+            .into_iter()
+            .filter(|entry| !entry.data.scanned)
+            .filter(|entry| root.is_none_or(|root| entry.path.starts_with(root)))
+            .collect())
+    }
+
+    /// Entries at or below `prefix`, ordered by path, at most `limit` of them
+    ///
+    /// Paths sort as strings, the order [`FimDb`] keeps them in, so
+    /// `/srv/sub-x` comes before `/srv/sub/b` although it does not by
+    /// component.
+    fn get_entries_under(&self, prefix: &Path, limit: Option<usize>) -> Result<Vec<FimEntry>> {
+        let mut entries: Vec<FimEntry> = self.get_all_entries()?
+            .into_iter()
+            .filter(|entry| entry.path.starts_with(prefix))
+            .collect();
+        entries.sort_by(|a, b| a.path.to_string_lossy().cmp(&b.path.to_string_lossy()));
+        entries.truncate(limit.unwrap_or(usize::MAX));
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries whose path sorts after `after` as a string, in
+    /// that order, for reading the baseline a page at a time
+    fn get_entries_after(&self, after: Option<&Path>, limit: usize) -> Result<Vec<FimEntry>> {
+        let mut entries: Vec<FimEntry> = self.get_all_entries()?
+            .into_iter()
+            .filter(|entry| after.is_none_or(|after| entry.path.to_string_lossy() > after.to_string_lossy()))
+            .collect();
+        entries.sort_by(|a, b| a.path.to_string_lossy().cmp(&b.path.to_string_lossy()));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    /// Start a batch of writes
+    fn begin_transaction(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish a batch started with [`FimStore::begin_transaction`]
+    fn commit_transaction(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Persist pending writes during a long scan
+    fn force_commit(&mut self) {}
+
+    /// [`FimStore::force_commit`], also compacting any write-ahead log
+    fn force_commit_with_checkpoint(&mut self) {
+        self.force_commit();
+    }
+
+    /// Append a detected change to the audit log (no-op without one)
+    fn record_change(&mut self, _change: &FileChange) -> Result<()> {
+        Ok(())
+    }
+
+    /// Recorded changes in a time window
+    fn get_changes(
+        &self,
+        _since: Option<DateTime<Utc>>,
+        _until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileChange>> {
+        Ok(Vec::new())
+    }
+
+    /// Up to `limit` most recent previous states of a path, oldest first,
+    /// each with when it was overwritten
+    fn get_history(&self, _path: &Path, _limit: usize) -> Result<Vec<(DateTime<Utc>, FimEntryData)>> {
+        Ok(Vec::new())
+    }
+
+    /// Recorded paths of the file with this inode on this device
+    fn get_paths_from_inode(&self, inode: u64, dev: u64) -> Result<Vec<PathBuf>> {
+        Ok(self.get_all_entries()?
+            .into_iter()
+            .filter(|entry| entry.data.inode == inode && entry.data.dev == dev)
+            .map(|entry| entry.path)
+            .collect())
+    }
+
+    /// Move the entries at and below `from` to the same place under `to`,
+    /// returning each moved `(old, new)` path pair
+    fn rename_path(&mut self, from: &Path, to: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut moved = Vec::new();
+        for entry in self.get_all_entries()? {
+            let Ok(rest) = entry.path.strip_prefix(from) else { continue };
+            let new = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+            self.remove_path(&entry.path)?;
+            self.insert_data(&new, &entry.data)?;
+            moved.push((entry.path, new));
+        }
+        Ok(moved)
+    }
+
+    /// Check the audit log for tampering
+    fn verify_change_log(&self) -> Result<ChangeLogVerification> {
+        Ok(ChangeLogVerification {
+            rows_checked: 0,
+            first_break: None,
+            head_matches: true,
+        })
+    }
+
+    /// Check the file history for tampering
+    fn verify_history(&self) -> Result<ChangeLogVerification> {
+        Ok(ChangeLogVerification {
+            rows_checked: 0,
+            first_break: None,
+            head_matches: true,
+        })
+    }
+
+    /// Check the store for corruption and tampering
+    ///
+    /// A store without an independently maintained checksum has nothing to
+    /// compare its entries against, so this fails rather than report them
+    /// intact.
+    fn integrity_check(&self) -> Result<DbIntegrityReport> {
+        anyhow::bail!("This store does not support integrity checks")
+    }
+
+    /// Capture every entry along with the data checksum and digest
+    fn snapshot(&self) -> Result<FullSnapshot> {
+        let entries = self.get_all_entries()?;
+        Ok(FullSnapshot {
+            checksum: data_checksum(&entries),
+            digest: entries_digest(&entries),
+            entries,
+        })
+    }
+
+    /// Write every entry as a JSON [`FullSnapshot`], returning the entry count
+    fn export_json(&self, writer: &mut dyn Write) -> Result<usize> {
+        let snapshot = self.snapshot()?;
+        serde_json::to_writer(&mut *writer, &snapshot)?;
+        writer.flush()?;
+        Ok(snapshot.entries.len())
+    }
+
+    /// Replace all entries with the contents of a snapshot
+    fn restore_snapshot(&mut self, snapshot: &FullSnapshot) -> Result<usize> {
+        snapshot.validate()?;
+
+        for entry in self.get_all_entries()? {
+            self.remove_path(&entry.path)?;
+        }
+        for entry in &snapshot.entries {
+            self.insert_data(&entry.path, &entry.data)?;
+        }
+        Ok(snapshot.entries.len())
+    }
+
+    /// Files backing the store, which scans must never report on
+    fn own_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Location of the store's main file, if it lives on local disk
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+impl FimStore for FimDb {
+    fn insert_data(&mut self, file_path: &Path, entry: &FimEntryData) -> Result<()> {
+        FimDb::insert_data(self, file_path, entry).map(drop)
+    }
+
+    fn get_path(&self, file_path: &Path) -> Result<Option<FimEntry>> {
+        FimDb::get_path(self, file_path)
+    }
+
+    fn remove_path(&mut self, file_path: &Path) -> Result<()> {
+        FimDb::remove_path(self, file_path).map(drop)
+    }
+
+    fn set_all_unscanned(&mut self) -> Result<()> {
+        FimDb::set_all_unscanned(self).map(drop)
+    }
+
+    fn delete_not_scanned(&mut self) -> Result<u64> {
+        Ok(FimDb::delete_not_scanned(self)? as u64)
+    }
+
+    fn remove_paths(&mut self, paths: &[PathBuf]) -> Result<u64> {
+        Ok(FimDb::remove_paths(self, paths)? as u64)
+    }
+
+    fn get_all_entries(&self) -> Result<Vec<FimEntry>> {
+        FimDb::get_all_entries(self)
+    }
+
+    fn get_stats(&self) -> Result<FimStats> {
+        FimDb::get_stats(self)
+    }
+
+    fn get_data_checksum(&self) -> Result<String> {
+        FimDb::get_data_checksum(self)
+    }
+
+    fn mark_scanned(&mut self, file_path: &Path) -> Result<()> {
+        FimDb::mark_scanned(self, file_path).map(drop)
+    }
+
+    fn set_unscanned_under(&mut self, root: &Path) -> Result<()> {
+        FimDb::set_unscanned_under(self, root).map(drop)
+    }
+
+    fn get_not_scanned(&self, root: Option<&Path>) -> Result<Vec<FimEntry>> {
+        FimDb::get_not_scanned(self, root)
+    }
+
+    fn get_entries_under(&self, prefix: &Path, limit: Option<usize>) -> Result<Vec<FimEntry>> {
+        FimDb::get_entries_under(self, prefix, limit)
+    }
+
+    fn get_entries_after(&self, after: Option<&Path>, limit: usize) -> Result<Vec<FimEntry>> {
+        FimDb::get_entries_after(self, after, limit)
+    }
+
+    fn begin_transaction(&mut self) -> Result<()> {
+        FimDb::begin_transaction(self)
+    }
+
+    fn commit_transaction(&mut self) -> Result<()> {
+        FimDb::commit_transaction(self)
+    }
+
+    fn force_commit(&mut self) {
+        FimDb::force_commit(self)
+    }
+
+    fn force_commit_with_checkpoint(&mut self) {
+        FimDb::force_commit_with_checkpoint(self)
+    }
+
+    fn record_change(&mut self, change: &FileChange) -> Result<()> {
+        FimDb::record_change(self, change).map(drop)
+    }
+
+    fn get_changes(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<Vec<FileChange>> {
+        FimDb::get_changes(self, since, until)
+    }
+
+    fn get_paths_from_inode(&self, inode: u64, dev: u64) -> Result<Vec<PathBuf>> {
+        FimDb::get_paths_from_inode(self, inode, dev)
+    }
+
+    fn rename_path(&mut self, from: &Path, to: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+        FimDb::rename_path(self, from, to)
+    }
+
+    fn get_history(&self, path: &Path, limit: usize) -> Result<Vec<(DateTime<Utc>, FimEntryData)>> {
+        FimDb::get_history(self, path, limit)
+    }
+
+    fn verify_change_log(&self) -> Result<ChangeLogVerification> {
+        FimDb::verify_change_log(self)
+    }
+
+    fn verify_history(&self) -> Result<ChangeLogVerification> {
+        FimDb::verify_history(self)
+    }
+
+    fn integrity_check(&self) -> Result<DbIntegrityReport> {
+        FimDb::integrity_check(self)
+    }
+
+    fn snapshot(&self) -> Result<FullSnapshot> {
+        FimDb::snapshot(self)
+    }
+
+    fn export_json(&self, writer: &mut dyn Write) -> Result<usize> {
+        FimDb::export_json(self, writer)
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &FullSnapshot) -> Result<usize> {
+        FimDb::restore_snapshot(self, snapshot)
+    }
+
+    fn own_files(&self) -> Vec<PathBuf> {
+        FimDb::own_files(self)
+    }
+
+    fn path(&self) -> Option<&Path> {
+        FimDb::path(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fim::{ChangeType, FimConfig, FimEngine};
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// Store keeping entries in a map, implementing only the required methods
+    #[derive(Default)]
+    struct MapStore {
+        entries: HashMap<PathBuf, FimEntryData>,
+    }
+
+    impl FimStore for MapStore {
+        fn insert_data(&mut self, file_path: &Path, entry: &FimEntryData) -> Result<()> {
+            self.entries.insert(file_path.to_path_buf(), entry.clone());
+            Ok(())
+        }
+
+        fn get_path(&self, file_path: &Path) -> Result<Option<FimEntry>> {
+            Ok(self.entries.get(file_path).map(|data| FimEntry {
+                path: file_path.to_path_buf(),
+                data: data.clone(),
+                created_at: None,
+                updated_at: None,
+            }))
+        }
+
+        fn remove_path(&mut self, file_path: &Path) -> Result<()> {
+            self.entries.remove(file_path);
+            Ok(())
+        }
+
+        fn set_all_unscanned(&mut self) -> Result<()> {
+            for data in self.entries.values_mut() {
+                data.scanned = false;
+            }
+            Ok(())
+        }
+
+        fn delete_not_scanned(&mut self) -> Result<u64> {
+            let before = self.entries.len();
+            self.entries.retain(|_, data| data.scanned);
+            Ok((before - self.entries.len()) as u64)
+        }
+
+        fn get_all_entries(&self) -> Result<Vec<FimEntry>> {
+            let mut entries: Vec<_> = self.entries
+                .iter()
+                .map(|(path, data)| FimEntry {
+                    path: path.clone(),
+                    data: data.clone(),
+                    created_at: None,
+                    updated_at: None,
+                })
+                .collect();
+            entries.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(entries)
+        }
+    }
+
+    #[test]
+    fn test_custom_store_drives_scan() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let root = temp_dir.path();
+        fs::write(root.join("keep.txt"), b"unchanged")?;
+        fs::write(root.join("edit.txt"), b"before")?;
+        fs::write(root.join("gone.txt"), b"deleted soon")?;
+
+        let config = FimConfig {
+            monitor_paths: vec![root.to_path_buf()],
+            enable_realtime: false,
+            ..Default::default()
+        };
+        let mut engine = FimEngine::with_store(config, MapStore::default())?;
+
+        let results = engine.baseline_scan()?;
+        assert_eq!(results.files_added, 3);
+        assert_eq!(engine.get_stats()?.total_files, 3);
+
+        fs::write(root.join("edit.txt"), b"after, and longer")?;
+        fs::remove_file(root.join("gone.txt"))?;
+        fs::write(root.join("new.txt"), b"added")?;
+
+        let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        engine.add_change_handler(move |change| {
+            seen.lock().unwrap().push((change.path.clone(), change.change_type.clone()));
+        });
+
+        let results = engine.incremental_scan()?;
+        assert_eq!(results.files_added, 1);
+        assert_eq!(results.files_modified, 1);
+        assert_eq!(results.files_deleted, 1);
+
+        let changes = changes.lock().unwrap();
+        assert!(changes.contains(&(root.join("new.txt"), ChangeType::Added)));
+        assert!(changes.contains(&(root.join("gone.txt"), ChangeType::Deleted)));
+        assert!(changes.iter().any(|(path, _)| path == &root.join("edit.txt")));
+
+        let stats = engine.get_stats()?;
+        assert_eq!(stats.total_files, 3);
+        assert_eq!(stats.unscanned_files, 0);
+
+        // The default checksum matches what SQLite computes for the same entries
+        let mut db = FimDb::init(true)?;
+        for entry in engine.snapshot()?.entries {
+            FimDb::insert_data(&mut db, &entry.path, &entry.data)?;
+        }
+        assert_eq!(engine.verify_integrity()?, db.get_data_checksum()?);
+        assert!(engine.check_database_integrity().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_entries_under_sorted_and_limited() -> Result<()> {
+        let mut store = MapStore::default();
+        let entry = crate::database::tests::test_entry();
+        for name in ["c.txt", "a.txt", "sub/b.txt", "sub-x.txt", "d.txt"] {
+            store.insert_data(&Path::new("/srv").join(name), &entry)?;
+        }
+        store.insert_data(Path::new("/other/a.txt"), &entry)?;
+
+        let paths = |entries: Vec<FimEntry>| -> Vec<PathBuf> {
+            entries.into_iter().map(|entry| entry.path).collect()
+        };
+        assert_eq!(
+            paths(store.get_entries_under(Path::new("/srv"), Some(3))?),
+            vec![PathBuf::from("/srv/a.txt"), PathBuf::from("/srv/c.txt"), PathBuf::from("/srv/d.txt")]
+        );
+        assert_eq!(store.get_entries_under(Path::new("/srv"), None)?.len(), 5);
+
+        // Pages follow path string order, as FimDb keeps it
+        let mut db = FimDb::init(true)?;
+        for entry in store.get_all_entries()? {
+            FimDb::insert_data(&mut db, &entry.path, &entry.data)?;
+        }
+        let first = store.get_entries_after(None, 3)?;
+        assert_eq!(
+            paths(first.clone()),
+            vec![PathBuf::from("/other/a.txt"), PathBuf::from("/srv/a.txt"), PathBuf::from("/srv/c.txt")]
+        );
+        let rest = store.get_entries_after(Some(&first[2].path), 3)?;
+        assert_eq!(
+            paths(rest),
+            vec![PathBuf::from("/srv/d.txt"), PathBuf::from("/srv/sub-x.txt"), PathBuf::from("/srv/sub/b.txt")]
+        );
+        assert_eq!(paths(db.get_entries_after(None, 10)?), paths(store.get_entries_after(None, 10)?));
+        assert_eq!(
+            paths(db.get_entries_under(Path::new("/srv"), None)?),
+            paths(store.get_entries_under(Path::new("/srv"), None)?)
+        );
+
+        Ok(())
+    }
+}
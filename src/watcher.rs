@@ -5,6 +5,7 @@
 
 use anyhow::{Context, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use notify::event::{ModifyKind, RenameMode};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use notify_debouncer_full::{
     new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, FileIdMap,
@@ -26,7 +27,8 @@ mod duration_serde {
     where
         S: Serializer,
     {
-        duration.as_millis().serialize(serializer)
+        // u64 rather than u128, which TOML cannot represent
+        u64::try_from(duration.as_millis()).unwrap_or(u64::MAX).serialize(serializer)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
@@ -44,8 +46,12 @@ pub enum FimEventKind {
     Created,
     Modified,
     Deleted,
-    MovedFrom(PathBuf),
-    MovedTo(PathBuf),
+    /// A file arrived at the event path by a rename, from the given path when
+    /// that half of the rename was observed
+    MovedFrom(Option<PathBuf>),
+    /// The file at the event path was renamed away, to the given path when
+    /// that half of the rename was observed
+    MovedTo(Option<PathBuf>),
     AttributeChanged,
     Unknown,
 }
@@ -125,6 +131,8 @@ pub struct FimWatcher {
 struct EventCounter {
     count: u32,
     last_reset: std::time::Instant,
+    /// Events dropped by throttling since the watcher was created
+    throttled: u64,
 }
 
 impl EventCounter {
@@ -132,6 +140,7 @@ impl EventCounter {
         Self {
             count: 0,
             last_reset: std::time::Instant::now(),
+            throttled: 0,
         }
     }
 
@@ -143,7 +152,11 @@ impl EventCounter {
         }
         
         self.count += 1;
-        self.count > max_per_second
+        let throttle = self.count > max_per_second;
+        if throttle {
+            self.throttled += 1;
+        }
+        throttle
     }
 }
 
@@ -240,12 +253,30 @@ impl FimWatcher {
     }
 
     /// Stop the watcher
+    ///
+    /// Iterators from [`FimWatcher::events_iter`] end once they have drained
+    /// the events already queued.
     pub fn stop(&mut self) {
         *self.is_running.lock().unwrap() = false;
         self._debouncer = None;
+
+        // Disconnect existing receivers; the processing thread drops its
+        // sender when it exits
+        let (event_sender, event_receiver) = unbounded();
+        self.event_sender = event_sender;
+        self.event_receiver = event_receiver;
+
         info!("FIM watcher stopped");
     }
 
+    /// Blocking iterator over events that ends when the watcher stops
+    ///
+    /// The iterator does not borrow the watcher, so it can be consumed on
+    /// another thread while this one calls [`FimWatcher::stop`].
+    pub fn events_iter(&self) -> impl Iterator<Item = FimEvent> {
+        self.event_receiver.clone().into_iter()
+    }
+
     /// Get next FIM event (blocking)
     pub fn _next_event(&self) -> Result<FimEvent> {
         self.event_receiver
@@ -258,6 +289,11 @@ impl FimWatcher {
         self.event_receiver.try_recv().ok()
     }
 
+    /// Wait up to `timeout` for the next FIM event
+    pub fn next_event_timeout(&self, timeout: Duration) -> Option<FimEvent> {
+        self.event_receiver.recv_timeout(timeout).ok()
+    }
+
     /// Get event receiver for custom processing
     pub fn _event_receiver(&self) -> &Receiver<FimEvent> {
         &self.event_receiver
@@ -300,14 +336,33 @@ impl FimWatcher {
 
     /// Convert notify event to FIM event
     fn convert_event(event: DebouncedEvent, config: &WatchConfig) -> Option<FimEvent> {
-        // Get the first path from the event
-        let path = event.event.paths.first()?;
-        
-        // Apply ignore filters
-        if Self::should_ignore_path(path, config) {
-            debug!("Ignoring path: {}", path.display());
-            return None;
-        }
+        let (path, kind) = match event.event.kind {
+            EventKind::Modify(ModifyKind::Name(mode)) => {
+                Self::convert_rename(mode, &event.event.paths, config)?
+            }
+            kind => {
+                // Get the first path from the event
+                let path = event.event.paths.first()?;
+
+                // Apply ignore filters
+                if Self::should_ignore_path(path, config) {
+                    debug!("Ignoring path: {}", path.display());
+                    return None;
+                }
+
+                let kind = match kind {
+                    EventKind::Create(_) => FimEventKind::Created,
+                    EventKind::Modify(_) => FimEventKind::Modified,
+                    EventKind::Remove(_) => FimEventKind::Deleted,
+                    EventKind::Access(_) => {
+                        // Only track access if it's metadata change
+                        FimEventKind::AttributeChanged
+                    }
+                    _ => FimEventKind::Unknown,
+                };
+                (path, kind)
+            }
+        };
 
         let is_directory = path.is_dir();
         let size = if !is_directory {
@@ -316,17 +371,6 @@ impl FimWatcher {
             None
         };
 
-        let kind = match event.event.kind {
-            EventKind::Create(_) => FimEventKind::Created,
-            EventKind::Modify(_) => FimEventKind::Modified,
-            EventKind::Remove(_) => FimEventKind::Deleted,
-            EventKind::Access(_) => {
-                // Only track access if it's metadata change
-                FimEventKind::AttributeChanged
-            }
-            _ => FimEventKind::Unknown,
-        };
-
         Some(FimEvent {
             kind,
             path: path.clone(),
@@ -336,8 +380,44 @@ impl FimWatcher {
         })
     }
 
+    /// Event path and kind for a rename, keeping only the halves that pass the
+    /// ignore filters
+    ///
+    /// The debouncer joins both halves of a rename it saw into one event with
+    /// the source and destination paths; a lone half means the other end was
+    /// outside the watched tree or fell outside the debounce window.
+    fn convert_rename<'a>(
+        mode: RenameMode,
+        paths: &'a [PathBuf],
+        config: &WatchConfig,
+    ) -> Option<(&'a PathBuf, FimEventKind)> {
+        let kept = |path: &PathBuf| !Self::should_ignore_path(path, config);
+        let converted = match (mode, paths) {
+            (RenameMode::Both, [from, to]) => match (kept(from), kept(to)) {
+                (true, true) => (to, FimEventKind::MovedFrom(Some(from.clone()))),
+                (false, true) => (to, FimEventKind::MovedFrom(None)),
+                (true, false) => (from, FimEventKind::MovedTo(None)),
+                (false, false) => return None,
+            },
+            (RenameMode::To, [to, ..]) if kept(to) => (to, FimEventKind::MovedFrom(None)),
+            (RenameMode::From, [from, ..]) if kept(from) => (from, FimEventKind::MovedTo(None)),
+            // Backends that can't tell the halves apart: the side still present arrived
+            (RenameMode::Any | RenameMode::Other, [path, ..]) if kept(path) => {
+                if path.exists() {
+                    (path, FimEventKind::MovedFrom(None))
+                } else {
+                    (path, FimEventKind::MovedTo(None))
+                }
+            }
+            _ => return None,
+        };
+        Some(converted)
+    }
+
     /// Check if path should be ignored based on configuration
     fn should_ignore_path(path: &Path, config: &WatchConfig) -> bool {
+        // `..` would otherwise leave a directory it climbed out of in the components
+        let path = &crate::utils::normalize_path(path);
         let filename = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
         
         // Check ignore patterns
@@ -385,6 +465,16 @@ impl FimWatcher {
         name == pattern
     }
 
+    /// Number of events waiting to be processed
+    pub fn queued_events(&self) -> usize {
+        self.event_receiver.len()
+    }
+
+    /// Events dropped for exceeding `max_events_per_second`
+    pub fn throttled_events(&self) -> u64 {
+        self.event_counter.lock().unwrap().throttled
+    }
+
     /// Get statistics about the watcher
     pub fn _get_stats(&self) -> _WatcherStats {
         let counter = self.event_counter.lock().unwrap();
@@ -488,6 +578,40 @@ mod tests {
         assert!(FimWatcher::should_ignore_path(Path::new("file.log"), &config));
         assert!(FimWatcher::should_ignore_path(Path::new(".git/config"), &config));
         assert!(!FimWatcher::should_ignore_path(Path::new("important.txt"), &config));
+
+        // Decided on the normalized path
+        assert!(FimWatcher::should_ignore_path(Path::new("src//.git//config"), &config));
+        assert!(FimWatcher::should_ignore_path(Path::new("src/./.git/config/"), &config));
+        assert!(!FimWatcher::should_ignore_path(Path::new("src/.git/../important.txt"), &config));
+        assert!(!FimWatcher::should_ignore_path(Path::new("src/test.tmp/.."), &config));
+    }
+
+    #[test]
+    fn test_convert_rename() {
+        let config = WatchConfig::default();
+        let (from, to) = (PathBuf::from("/data/a.txt"), PathBuf::from("/data/b.txt"));
+        let tmp = PathBuf::from("/data/b.tmp");
+        let convert = |mode, paths: &[PathBuf]| {
+            FimWatcher::convert_rename(mode, paths, &config).map(|(path, kind)| (path.clone(), kind))
+        };
+
+        assert_eq!(
+            convert(RenameMode::Both, &[from.clone(), to.clone()]),
+            Some((to.clone(), FimEventKind::MovedFrom(Some(from.clone()))))
+        );
+        assert_eq!(convert(RenameMode::To, std::slice::from_ref(&to)), Some((to.clone(), FimEventKind::MovedFrom(None))));
+        assert_eq!(convert(RenameMode::From, std::slice::from_ref(&from)), Some((from.clone(), FimEventKind::MovedTo(None))));
+
+        // An ignored half is dropped, leaving the other unpaired
+        assert_eq!(
+            convert(RenameMode::Both, &[tmp.clone(), to.clone()]),
+            Some((to.clone(), FimEventKind::MovedFrom(None)))
+        );
+        assert_eq!(
+            convert(RenameMode::Both, &[from.clone(), tmp.clone()]),
+            Some((from, FimEventKind::MovedTo(None)))
+        );
+        assert_eq!(convert(RenameMode::From, &[tmp]), None);
     }
 
     #[test]
@@ -512,6 +636,49 @@ mod tests {
         assert_eq!(batch.unwrap().len(), 3);
     }
 
+    #[test]
+    fn test_events_iter_ends_on_stop() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let config = WatchConfig {
+            paths: vec![temp_dir.path().to_path_buf()],
+            debounce_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let mut watcher = FimWatcher::new(config)?;
+        watcher.start()?;
+
+        let (forward, received) = unbounded();
+        let events = watcher.events_iter();
+        let consumer = thread::spawn(move || {
+            for event in events {
+                let _ = forward.send(event);
+            }
+        });
+
+        let file_path = temp_dir.path().join("created.txt");
+        fs::write(&file_path, b"hello")?;
+        let event = loop {
+            let event = received.recv_timeout(Duration::from_secs(10))?;
+            if event.path == file_path {
+                break event;
+            }
+        };
+        assert!(matches!(event.kind, FimEventKind::Created | FimEventKind::Modified));
+
+        watcher.stop();
+        // The consumer thread's iterator ends, dropping the forwarding sender
+        loop {
+            match received.recv_timeout(Duration::from_secs(10)) {
+                Ok(_) => continue,
+                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+                Err(e) => panic!("events_iter did not end after stop: {}", e),
+            }
+        }
+        consumer.join().unwrap();
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_basic_watcher_creation() -> Result<()> {
         let temp_dir = tempdir()?;
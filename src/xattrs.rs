@@ -0,0 +1,97 @@
+//! Extended attribute capture
+//!
+//! Security-relevant metadata such as SELinux labels (`security.selinux`),
+//! file capabilities and macOS quarantine flags (`com.apple.quarantine`)
+//! lives in extended attributes, which content hashes and mode bits miss.
+//! FIM records one BLAKE3 digest over all of a file's attributes, so any
+//! added, removed or rewritten attribute changes it.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Read a file's extended attributes and digest them
+///
+/// Returns `None` when the platform or filesystem does not support extended
+/// attributes. A file without any has the digest of the empty map.
+#[cfg(all(unix, feature = "xattrs"))]
+pub fn read_xattr_hash(path: &Path) -> Result<Option<String>> {
+    use anyhow::Context;
+
+    let read = || -> std::io::Result<BTreeMap<OsString, Vec<u8>>> {
+        let mut attributes = BTreeMap::new();
+        for name in xattr::list_deref(path)? {
+            // An attribute removed since listing is simply left out
+            if let Some(value) = xattr::get_deref(path, &name)? {
+                attributes.insert(name, value);
+            }
+        }
+        Ok(attributes)
+    };
+
+    match read() {
+        Ok(attributes) => Ok(Some(hash_xattrs(&attributes))),
+        Err(e) if is_unsupported(&e) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read extended attributes of {}", path.display())),
+    }
+}
+
+/// Read a file's extended attributes and digest them
+#[cfg(not(all(unix, feature = "xattrs")))]
+pub fn read_xattr_hash(_path: &Path) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// Whether an error means the filesystem keeps no extended attributes
+#[cfg_attr(not(all(unix, feature = "xattrs")), allow(dead_code))]
+fn is_unsupported(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::ENOTSUP) {
+        return true;
+    }
+    error.kind() == std::io::ErrorKind::Unsupported
+}
+
+/// BLAKE3 digest of attributes in name order, each name and value length
+/// prefixed so no two maps encode alike
+pub fn hash_xattrs(attributes: &BTreeMap<OsString, Vec<u8>>) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for (name, value) in attributes {
+        let name = name.as_encoded_bytes();
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        hasher.update(&(value.len() as u64).to_le_bytes());
+        hasher.update(value);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_xattrs() {
+        let map = |entries: &[(&str, &[u8])]| -> BTreeMap<OsString, Vec<u8>> {
+            entries.iter().map(|(name, value)| (OsString::from(name), value.to_vec())).collect()
+        };
+
+        let labelled = map(&[("security.selinux", b"system_u:object_r:etc_t:s0"), ("user.note", b"x")]);
+        let reordered = map(&[("user.note", b"x"), ("security.selinux", b"system_u:object_r:etc_t:s0")]);
+        assert_eq!(hash_xattrs(&labelled), hash_xattrs(&reordered));
+
+        let relabelled = map(&[("security.selinux", b"system_u:object_r:shadow_t:s0"), ("user.note", b"x")]);
+        assert_ne!(hash_xattrs(&labelled), hash_xattrs(&relabelled));
+
+        // Moving bytes between name and value is a different map
+        assert_ne!(hash_xattrs(&map(&[("user.ab", b"c")])), hash_xattrs(&map(&[("user.a", b"bc")])));
+        assert_ne!(hash_xattrs(&map(&[])), hash_xattrs(&map(&[("user.empty", b"")])));
+
+        let unsupported = std::io::Error::from(std::io::ErrorKind::Unsupported);
+        assert!(is_unsupported(&unsupported));
+        #[cfg(unix)]
+        assert!(is_unsupported(&std::io::Error::from_raw_os_error(libc::ENOTSUP)));
+        assert!(!is_unsupported(&std::io::Error::from(std::io::ErrorKind::PermissionDenied)));
+    }
+}
@@ -0,0 +1,129 @@
+//! End-to-end checks of the `fim` command line
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn verify_hashes_exit_status() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let good = temp_dir.path().join("good.conf");
+    let bad = temp_dir.path().join("bad.conf");
+    fs::write(&good, b"expected content")?;
+    fs::write(&bad, b"tampered content")?;
+
+    let list = temp_dir.path().join("hashes.b3");
+    let hash_of = |content: &[u8]| blake3::hash(content).to_hex().to_string();
+    fs::write(
+        &list,
+        format!(
+            "{}  {}\n{}  {}\n",
+            hash_of(b"expected content"),
+            good.display(),
+            hash_of(b"original content"),
+            bad.display(),
+        ),
+    )?;
+
+    let run = || {
+        Command::new(env!("CARGO_BIN_EXE_fim"))
+            .current_dir(temp_dir.path())
+            .args(["verify", "--hashes"])
+            .arg(&list)
+            .output()
+    };
+
+    // A mismatch is reported and fails the run
+    let output = run()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains(&format!("PASS  {}", good.display())));
+    assert!(stdout.contains(&format!("FAIL  {}", bad.display())));
+    assert!(stdout.contains("1 passed, 1 failed"));
+
+    // Once every file matches the run succeeds
+    fs::write(&bad, b"original content")?;
+    let output = run()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("2 passed, 0 failed"));
+
+    Ok(())
+}
+
+#[test]
+fn verify_hash_exit_status() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let file = temp_dir.path().join("app.conf");
+    fs::write(&file, b"expected content")?;
+
+    let run = |path: &std::path::Path, hash: &str| {
+        Command::new(env!("CARGO_BIN_EXE_fim"))
+            .current_dir(temp_dir.path())
+            .arg("verify")
+            .arg(path)
+            .args(["--hash", hash])
+            .output()
+    };
+    let expected = blake3::hash(b"expected content").to_hex().to_string();
+
+    let output = run(&file, &expected)?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("VERIFIED"));
+
+    // A mismatch fails the run
+    let output = run(&file, &blake3::hash(b"other content").to_hex())?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("VERIFICATION FAILED"));
+
+    // So does a file that cannot be hashed
+    let output = run(&temp_dir.path().join("missing.conf"), &expected)?;
+    assert_eq!(output.status.code(), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn scan_output_formats() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let watched = temp_dir.path().join("etc");
+    fs::create_dir(&watched)?;
+    let file = watched.join("app.conf");
+    fs::write(&file, b"enabled = true")?;
+
+    let fim = || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_fim"));
+        command.current_dir(temp_dir.path());
+        command
+    };
+    let baseline = fim().arg("baseline").arg(&watched).output()?;
+    assert!(baseline.status.success(), "{}", String::from_utf8_lossy(&baseline.stderr));
+    let scan = |format: &str| fim().arg("scan").arg(&watched).args(["--format", format]).output();
+
+    // JSON holds the results and the changes
+    fs::write(&file, b"enabled = false")?;
+    let output = scan("json")?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(json["results"]["files_scanned"], 1);
+    let changes = json["changes"].as_array().expect("changes array");
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0]["path"], file.display().to_string());
+
+    // CSV starts with its header, one row per change
+    fs::write(&file, b"enabled = maybe")?;
+    let output = scan("csv")?;
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let csv = String::from_utf8(output.stdout)?;
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("timestamp,path,change_type,size,permissions,hash"));
+    assert_eq!(lines.filter(|line| line.contains(&*file.to_string_lossy())).count(), 1);
+
+    // An unknown format fails and names the accepted ones
+    let output = scan("yaml")?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Unknown scan output format 'yaml'"), "{}", stderr);
+    assert!(stderr.contains("expected text, json or csv"), "{}", stderr);
+
+    Ok(())
+}